@@ -0,0 +1,68 @@
+//! Pushing received frames into an rx queue one at a time versus a transfer's worth at once, as
+//! `RdxUsbHsHost::flush_rx_batches` does.
+//!
+//! This drives the `ringbuf` ring the rx queues' `AsyncHeapRb` wraps, so it measures publishing the write index
+//! per frame versus per transfer, but not waking an async reader. A reader thread drains the queue while it's
+//! filled. Run with `cargo bench --bench rx_batching --features unstable-hs`.
+
+use std::{hint::black_box, thread, time::Instant};
+
+use rdxusb::{host::HS_MAX_PACKET_SIZE, RdxUsbPacket};
+use ringbuf::{traits::{Consumer, Producer, Split}, HeapRb};
+
+const FRAMES: usize = 2_000_000;
+/// The rx queue size the examples open devices with.
+const QUEUE_SIZE: usize = 256;
+const PACKET: RdxUsbPacket = RdxUsbPacket { timestamp_ns: 0, arb_id: 0x205, dlc: 8, channel: 0, flags: 0, data: [0xa5; 64] };
+
+/// Pushes `FRAMES` frames in groups of `batch`, returning frames per second.
+fn run(batch: usize, batched: bool) -> f64 {
+    let frames = FRAMES / batch * batch;
+    let (mut prod, mut cons) = HeapRb::<RdxUsbPacket>::new(QUEUE_SIZE).split();
+    let reader = thread::spawn(move || {
+        let mut popped = 0;
+        while popped < frames {
+            match cons.try_pop() {
+                Some(packet) => {
+                    black_box(packet);
+                    popped += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+    });
+
+    let start = Instant::now();
+    let transfer = vec![PACKET; batch];
+    for _ in 0..frames / batch {
+        if batched {
+            let mut pushed = 0;
+            while pushed < batch {
+                pushed += prod.push_slice(&transfer[pushed..]);
+                if pushed < batch { thread::yield_now(); }
+            }
+        } else {
+            for &packet in &transfer {
+                while prod.try_push(packet).is_err() {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+    reader.join().unwrap();
+    frames as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    // a full high speed transfer, and a couple of frames in a mostly idle one
+    for batch in [HS_MAX_PACKET_SIZE / RdxUsbPacket::SIZE, 2] {
+        // warm up
+        run(batch, true);
+        let per_packet = run(batch, false);
+        let batched = run(batch, true);
+        println!(
+            "{batch} frames per transfer: per packet {:.2} Mframes/s, batched {:.2} Mframes/s ({:+.0}%)",
+            per_packet / 1e6, batched / 1e6, (batched / per_packet - 1.0) * 100.0,
+        );
+    }
+}
@@ -1,5 +1,6 @@
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(tokio_unstable)");
     let target = std::env::var("TARGET").unwrap();
     if target.contains("linux") {
         println!("cargo:rustc-link-arg=-Wl,-soname,librdxusb.so");
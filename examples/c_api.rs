@@ -0,0 +1,39 @@
+//! Uses the C API from Rust the way a C or C++ program would: open a device handle, wait for it, and read packets.
+//! Needs a device plugged in.
+//!
+//! `cargo run --example c_api`
+use rdxusb::c_api::{rdxusb_close_device, rdxusb_get_version, rdxusb_open_first_redux_device, rdxusb_read_packets, rdxusb_wait_connected};
+use rdxusb::event_loop::EventLoopError;
+
+mod common;
+
+fn main() {
+    let (mut major, mut minor, mut patch) = (0, 0, 0);
+    rdxusb_get_version(&mut major, &mut minor, &mut patch);
+    println!("rdxusb {major}.{minor}.{patch}");
+
+    let handle = rdxusb_open_first_redux_device(false, 256);
+    if handle < 0 {
+        eprintln!("rdxusb_open_first_redux_device failed with {handle}");
+        std::process::exit(1);
+    }
+    let result = rdxusb_wait_connected(handle, 1000);
+    if result == EventLoopError::ERR_TIMEOUT {
+        eprintln!("No device connected");
+        rdxusb_close_device(handle);
+        std::process::exit(1);
+    }
+
+    let mut packets = [common::EMPTY_PACKET; 64];
+    for _ in 0..20 {
+        let mut packets_read = 0u64;
+        let result = rdxusb_read_packets(handle, 0, packets.as_mut_ptr(), packets.len() as u64, &mut packets_read);
+        if result < 0 {
+            println!("rdxusb_read_packets failed with {result}");
+            break;
+        }
+        packets[..packets_read as usize].iter().for_each(common::print_packet);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    rdxusb_close_device(handle);
+}
@@ -0,0 +1,46 @@
+//! Shared helpers for the examples.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use rdxusb::{RdxUsbPacket, MESSAGE_ARB_ID_DEVICE, MESSAGE_ARB_ID_EXT};
+
+pub const EMPTY_PACKET: RdxUsbPacket = RdxUsbPacket { timestamp_ns: 0, arb_id: 0, dlc: 0, channel: 0, flags: 0, data: [0; 64] };
+
+/// Sample packets for the filtering and recording examples to work on when no device is plugged in: periodic status
+/// frames from three devices on channel 0, 10 ms apart. They never go through a host or the event loop.
+pub fn sample_packets(n: usize) -> Vec<RdxUsbPacket> {
+    (0..n).map(|i| {
+        let device_number = (i % 3) as u32;
+        let mut data = [0u8; 64];
+        data[..8].copy_from_slice(&(i as u64).to_le_bytes());
+        RdxUsbPacket {
+            timestamp_ns: i as u64 * Duration::from_millis(10).as_nanos() as u64,
+            // device type 7, manufacturer 0xe, api 0x240, device number
+            arb_id: (7 << 24 | 0xe << 16 | 0x240 << 6 | device_number) | MESSAGE_ARB_ID_EXT,
+            dlc: 8,
+            channel: 0,
+            flags: 0,
+            data,
+        }
+    }).collect()
+}
+
+/// A packet for a device-level message, which the device handles itself rather than putting on the bus.
+pub fn device_packet(arb_id: u32, payload: &[u8]) -> RdxUsbPacket {
+    let mut data = [0u8; 64];
+    data[..payload.len()].copy_from_slice(payload);
+    RdxUsbPacket {
+        timestamp_ns: 0,
+        arb_id: arb_id | MESSAGE_ARB_ID_EXT | MESSAGE_ARB_ID_DEVICE,
+        dlc: payload.len() as u8,
+        channel: 0,
+        flags: 0,
+        data,
+    }
+}
+
+pub fn print_packet(packet: &RdxUsbPacket) {
+    let data: Vec<String> = packet.payload().iter().map(|b| format!("{b:02x}")).collect();
+    println!("[{:>10.3} ms] ch{} {:08x} [{}] {}", packet.timestamp_ns as f64 / 1e6, packet.channel, packet.id(), packet.dlc, data.join(" "));
+}
@@ -0,0 +1,38 @@
+//! Filters traffic with a filter expression given on the command line. Without a device, it filters a fixed set of
+//! sample packets instead, which shows the filter syntax but not device traffic.
+//!
+//! `cargo run --example filtering -- "(id & 0x3f) == 1 && dlc == 8"`
+use std::time::Duration;
+
+use rdxusb::filter::Filter;
+use rdxusb::managed::ManagedDevice;
+
+mod common;
+
+fn main() {
+    let expr = std::env::args().nth(1).unwrap_or_else(|| "(id & 0x3f) == 1".to_string());
+    let filter = match Filter::parse(&expr) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("Bad filter {expr:?}: {e}");
+            std::process::exit(1);
+        }
+    };
+    println!("Filter: {expr}");
+
+    let packets = match ManagedDevice::open_first_redux_device(256) {
+        Ok(device) if device.wait_connected_blocking(Duration::from_secs(1)).is_ok() => {
+            std::thread::sleep(Duration::from_secs(1));
+            let mut packets = vec![common::EMPTY_PACKET; 256];
+            let n = device.read_packets(0, &mut packets).unwrap_or(0);
+            packets.truncate(n);
+            packets
+        }
+        _ => {
+            println!("No device connected, filtering sample packets instead");
+            common::sample_packets(30)
+        }
+    };
+    let matched = packets.iter().filter(|p| filter.matches(p)).inspect(|p| common::print_packet(p)).count();
+    println!("{matched} of {} packets matched", packets.len());
+}
@@ -0,0 +1,36 @@
+//! Reads traffic from the first Redux device with the managed API, which reconnects on its own if the device is
+//! unplugged. Needs a device plugged in.
+//!
+//! `cargo run --example managed`
+use std::time::Duration;
+
+use rdxusb::managed::ManagedDevice;
+
+mod common;
+
+fn main() {
+    let device = match ManagedDevice::open_first_redux_device(256) {
+        Ok(device) => device,
+        Err(e) => {
+            eprintln!("Could not open a device: {e:?}");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = device.wait_connected_blocking(Duration::from_secs(1)) {
+        eprintln!("No device connected: {e:?}");
+        std::process::exit(1);
+    }
+    println!("Connected: {:?}", device.describe());
+    if let Ok(rtt) = device.ping(Duration::from_millis(100)) {
+        println!("Ping round trip: {rtt:?}");
+    }
+
+    let mut packets = [common::EMPTY_PACKET; 64];
+    for _ in 0..20 {
+        match device.read_packets(0, &mut packets) {
+            Ok(n) => packets[..n].iter().for_each(common::print_packet),
+            Err(e) => println!("read failed: {e:?}"),
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
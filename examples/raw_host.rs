@@ -0,0 +1,56 @@
+//! Opens the first Redux device directly with the async host API, without the event loop, and prints what arrives
+//! on each channel. Needs a device plugged in.
+//!
+//! `cargo run --example raw_host`
+use std::time::Duration;
+
+use rdxusb::host::{RdxUsbHost, RdxUsbHostBuilder};
+use rdxusb::{vendor, RdxUsbPacket};
+
+mod common;
+
+#[tokio::main]
+async fn main() {
+    let Some(dev_info) = vendor::first_redux_device() else {
+        eprintln!("No Redux device plugged in");
+        std::process::exit(1);
+    };
+    let host = match RdxUsbHostBuilder::new().rx_queue_size(512).open(dev_info).await {
+        Ok(host) => host,
+        Err(e) => {
+            eprintln!("Could not open device: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // the host's poll loop moves packets between USB and the channels, so it runs alongside the readers.
+    match host {
+        RdxUsbHost::Fs(mut host, mut channels) => {
+            println!("Full speed device: {:?}", host.device_info());
+            let poll = tokio::spawn(async move { host.poll(32, false).await });
+            for _ in 0..20 {
+                for channel in channels.iter_mut() {
+                    while let Some(packet) = channel.try_read() {
+                        common::print_packet(&RdxUsbPacket::from(packet));
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            poll.abort();
+        }
+        #[cfg(feature = "unstable-hs")]
+        RdxUsbHost::Hs(mut host, mut channels) => {
+            println!("High speed device: {:?}", host.device_info());
+            let poll = tokio::spawn(async move { host.poll(32, false).await });
+            for _ in 0..20 {
+                for channel in channels.iter_mut() {
+                    while let Some(packet) = channel.try_read() {
+                        common::print_packet(&packet);
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            poll.abort();
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Records traffic into a native `.rdxcap` capture with an annotation, then replays it from the middle.
+//! Without a device, it writes sample packets with [`CaptureWriter`] directly instead of capturing through the
+//! event loop.
+//!
+//! `cargo run --example recording -- out.rdxcap`
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+
+use rdxusb::capture::{Annotation, CaptureWriter, PacketWrite, Replayer};
+use rdxusb::managed::ManagedDevice;
+
+mod common;
+
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or_else(|| std::env::temp_dir().join("rdxusb-example.rdxcap").display().to_string());
+
+    match ManagedDevice::open_first_redux_device(256) {
+        Ok(device) if device.wait_connected_blocking(Duration::from_secs(1)).is_ok() => {
+            // the event loop writes the capture in the background while the device is connected.
+            device.start_capture(&path).expect("could not start capture");
+            device.annotate("recording started").ok();
+            std::thread::sleep(Duration::from_secs(2));
+            device.stop_capture().expect("could not stop capture");
+        }
+        _ => {
+            println!("No device connected, recording sample packets instead");
+            let packets = common::sample_packets(100);
+            let mut writer = CaptureWriter::new(BufWriter::new(File::create(&path).expect("could not create capture"))).unwrap();
+            writer.write_annotation(&Annotation { timestamp_ns: 0, text: "recording started".to_string() }).unwrap();
+            for packet in &packets {
+                writer.write_packet(packet).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+    }
+    println!("Wrote {path}");
+
+    let mut replayer = Replayer::open(&path).expect("could not open capture");
+    let Some(last) = replayer.by_ref().filter_map(Result::ok).last() else {
+        println!("The capture is empty");
+        return;
+    };
+    let middle = { last.timestamp_ns } / 2;
+    replayer.seek_to(middle).unwrap();
+    println!("Packets from {:.3} ms on:", middle as f64 / 1e6);
+    replayer.take(5).filter_map(Result::ok).for_each(|p| common::print_packet(&p));
+}
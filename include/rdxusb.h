@@ -1,7 +1,28 @@
 #pragma once
 #include <stdint.h>
 
+/** Library version these headers were released with. */
+#define RDXUSB_VERSION_MAJOR 2025
+#define RDXUSB_VERSION_MINOR 0
+#define RDXUSB_VERSION_PATCH 1
+/** 
+ * C ABI version these headers describe. 
+ * 
+ * Compare against rdxusb_get_abi_version() when loading the library dynamically.
+ */
+#define RDXUSB_ABI_VERSION 1
 
+/** Optional features reported by rdxusb_get_build_features(). Bits 32 and up are experimental (unstable-*) features; without RDXUSB_FEATURE_UNSTABLE_HS, high speed devices fail to open. */
+#define RDXUSB_FEATURE_ZSTD (1ull << 0)
+#define RDXUSB_FEATURE_LZ4 (1ull << 1)
+#define RDXUSB_FEATURE_WEBSOCKET (1ull << 2)
+#define RDXUSB_FEATURE_BLOCKING (1ull << 3)
+#define RDXUSB_FEATURE_UDP_MIRROR (1ull << 4)
+#define RDXUSB_FEATURE_TOKIO_CONSOLE (1ull << 5)
+#define RDXUSB_FEATURE_DAEMON (1ull << 6)
+#define RDXUSB_FEATURE_MQTT (1ull << 7)
+#define RDXUSB_FEATURE_INTEGRITY (1ull << 8)
+#define RDXUSB_FEATURE_UNSTABLE_HS (1ull << 32)
 
 /** Extended (full 29-bit) frame. This is set on practically all FRC-related messages. */
 #define RDXUSB_ARB_ID_FLAG_EXT 0x80000000
@@ -18,6 +39,54 @@
  */
 #define RDXUSB_ARB_ID_FLAG_DEVICE 0x20000000
 
+/** CAN FD frame. USB-FS devices support FD payloads of up to 48 bytes. */
+#define RDXUSB_MESSAGE_FLAG_FDF 0x0001
+/** Bit rate switch: the data phase of the CAN FD frame is sent at the data bitrate. */
+#define RDXUSB_MESSAGE_FLAG_BRS 0x0002
+/** Error state indicator: the transmitter of the CAN FD frame was error passive. Ignored on transmit. */
+#define RDXUSB_MESSAGE_FLAG_ESI 0x0004
+/** Error frame reported by the device's CAN controller. The arbitration id holds RDXUSB_ERROR_* bits. */
+#define RDXUSB_MESSAGE_FLAG_ERR 0x0008
+/** 
+ * Set on sent frames to have the device echo them back once transmitted, see rdxusb_read_echoes.
+ * The echo carries the flag too, timestamped with the transmission time.
+ */
+#define RDXUSB_MESSAGE_FLAG_ECHO 0x0010
+/** 
+ * Sequence number of a frame sent with rdxusb_write_reliable. A device receiving a device-addressed frame whose
+ * nonzero sequence number matches the last one it acted on echoes it again without applying it twice.
+ */
+#define RDXUSB_MESSAGE_FLAG_SEQ_MASK 0x0f00
+
+/** The controller went bus-off and stopped participating on the bus. */
+#define RDXUSB_ERROR_BUS_OFF 0x0001
+/** The controller became error passive. */
+#define RDXUSB_ERROR_PASSIVE 0x0002
+/** An error counter reached the warning level. */
+#define RDXUSB_ERROR_WARNING 0x0004
+/** A bit stuffing error was detected. */
+#define RDXUSB_ERROR_STUFF 0x0008
+/** A form error was detected. */
+#define RDXUSB_ERROR_FORM 0x0010
+/** A transmitted frame wasn't acknowledged. */
+#define RDXUSB_ERROR_ACK 0x0020
+/** A transmitted bit didn't read back as sent. */
+#define RDXUSB_ERROR_BIT 0x0040
+/** A received frame failed its CRC check. */
+#define RDXUSB_ERROR_CRC 0x0080
+/** The controller's receive buffer overflowed and frames were lost. */
+#define RDXUSB_ERROR_RX_OVERFLOW 0x0100
+
+/** Channel value reserved for notifications originating from the device itself. */
+#define RDXUSB_NOTIFICATION_CHANNEL 0xff
+
+/** USB vendor ID used by Redux Robotics devices. */
+#define RDXUSB_REDUX_VID 0x16d0
+/** USB product ID of the Canandgyro. */
+#define RDXUSB_CANANDGYRO_PID 0x1278
+/** USB product ID of the Canandcolor. */
+#define RDXUSB_CANANDCOLOR_PID 0x1279
+
 /** The event loop has irrecoverably crashed. */
 #define RDXUSB_ERR_EVENT_LOOP_CRASHED -100
 /** The event loop cannot enumerate USB devices. */
@@ -28,12 +97,53 @@
 #define ERR_DEVICE_ITER_IDX_OUT_OF_RANGE -103
 /** A passed argument was null that should not be null. */
 #define RDXUSB_ERR_NULL_PTR -104
+/** No matching device is attached. */
+#define RDXUSB_ERR_NO_DEVICE_FOUND -105
+/** rdxusb_init was called while rdxusb is already initialized. */
+#define RDXUSB_ERR_ALREADY_INITIALIZED -106
+/** rdxusb_pump was called without rdxusb being initialized in manual pump mode. */
+#define RDXUSB_ERR_NOT_MANUAL_PUMP -107
+/** A caller-provided buffer is too small for the result. */
+#define RDXUSB_ERR_BUFFER_TOO_SMALL -108
+/** An argument has a value outside of its allowed set. */
+#define RDXUSB_ERR_INVALID_ARGUMENT -109
+/** rdxusb_stop_capture or rdxusb_capture_annotate was called without a capture running on the handle. */
+#define RDXUSB_ERR_CAPTURE_NOT_ACTIVE -110
+/** The capture file couldn't be created or written. */
+#define RDXUSB_ERR_CAPTURE_FAILED -111
+/** rdxusb hit an internal error (a Rust panic) during the call. It was contained, but the call did not complete. */
+#define RDXUSB_ERR_PANICKED -112
+/** The daemon this process forwards calls to (see rdxusb_connect_daemon) could not be reached. */
+#define RDXUSB_ERR_DAEMON_UNAVAILABLE -113
 /** The specified device handle is invalid. */
 #define RDXUSB_ERR_DEVICE_NOT_OPENED -200
 /** The specified device is not currently connected right now. */
 #define RDXUSB_ERR_DEVICE_NOT_CONNECTED -201
 /** The specified device channel is not valid for this device. */
 #define RDXUSB_ERR_CHANNEL_OUT_OF_RANGE -202
+/** A device operation did not complete within its timeout. */
+#define RDXUSB_ERR_TIMEOUT -203
+/** The packet's payload is longer than the device supports, or not a valid CAN FD length. */
+#define RDXUSB_ERR_INVALID_PACKET -204
+/** The device did not return the requested descriptor. */
+#define RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE -205
+/** Another thread is the reader of this channel, see rdxusb_set_exclusive_readers. */
+#define RDXUSB_ERR_CHANNEL_CLAIMED -206
+/** The device's firmware doesn't support the request, or the call isn't forwarded to the daemon (see rdxusb_connect_daemon). */
+#define RDXUSB_ERR_REQUEST_UNSUPPORTED -207
+
+/** Waiting for a matching device to show up. */
+#define RDXUSB_DEVICE_STATE_SEARCHING 0
+/** A matching device was found and is being opened. */
+#define RDXUSB_DEVICE_STATE_ATTACHING 1
+/** The device is open and exchanging packets. */
+#define RDXUSB_DEVICE_STATE_CONNECTED 2
+/** Opening failed or the connection was lost. The handle will go back to searching unless closed. */
+#define RDXUSB_DEVICE_STATE_FAULTED 3
+/** The handle is being closed and will not reconnect. */
+#define RDXUSB_DEVICE_STATE_CLOSING 4
+/** The handle went idle and released its device until it is read from or written to again, see rdxusb_set_idle_policy. */
+#define RDXUSB_DEVICE_STATE_PARKED 5
 
 #ifdef _MSC_VER
 #pragma pack(push, 4)
@@ -51,7 +161,7 @@ struct __attribute__((packed, aligned(4))) rdxusb_packet {
     uint8_t dlc;
     /** Channel associated with the packet. Zero most of the time. */
     uint8_t channel;
-    /** Misc flags (unused for now) */
+    /** Misc flags specified by the RDXUSB_MESSAGE_FLAG_* defines. */
     uint16_t flags;
     /** 
      * data (max size: 64 bytes) 
@@ -59,6 +169,36 @@ struct __attribute__((packed, aligned(4))) rdxusb_packet {
      */
     uint8_t data[64];
 };
+
+/**
+ * rdxusb_packet followed by metadata added by the host, see rdxusb_read_packets_ex.
+ * 
+ * The packet comes first, so a pointer to one can be read as a plain rdxusb_packet.
+ */
+#ifdef _MSC_VER
+struct rdxusb_packet_ex {
+#else
+struct __attribute__((packed, aligned(4))) rdxusb_packet_ex {
+#endif
+    struct rdxusb_packet packet;
+    /** Host wall clock time the packet was read from rdxusb (nanoseconds since the UNIX epoch) */
+    uint64_t host_timestamp_ns;
+    /** 
+     * Counts the connections of the handle the packet came from, starting at 1 for the first one.
+     * Device timestamps restart when the device does, so only compare them within one epoch.
+     */
+    uint32_t connection_epoch;
+    /** Handle the packet was read from */
+    int32_t origin_handle;
+    /** Position of the packet among everything read from its handle, starting at 0 */
+    uint64_t sequence;
+    /**
+     * The device timestamp mapped onto the host wall clock by the handle's timestamp smoothing filter
+     * (nanoseconds since the UNIX epoch), or 0 if smoothing is off or hasn't received anything yet.
+     * See rdxusb_set_timestamp_smoothing.
+     */
+    uint64_t smoothed_timestamp_ns;
+};
 #ifdef _MSC_VER
 #pragma pack(pop)
 #endif
@@ -83,10 +223,176 @@ struct rdxusb_device_entry {
 
 typedef uint64_t rdxusb_iter_id;
 
+/** Resources the application currently holds, filled in by rdxusb_get_leak_report. */
+struct rdxusb_leak_report {
+    /** Open device handles. */
+    uint32_t open_handles;
+    /** Open device handles nothing has ever read packets from. */
+    uint32_t unread_handles;
+    /** Age of the oldest never-read handle in milliseconds, or 0 if there is none. */
+    uint64_t oldest_unread_handle_ms;
+    /** Device iterators not yet freed with rdxusb_free_device_iterator. */
+    uint32_t live_iterators;
+    /** Age of the oldest live iterator in milliseconds, or 0 if there is none. */
+    uint64_t oldest_iterator_ms;
+};
+
+/** Traffic of a device handle over one sampling interval, passed to the rdxusb_set_stats_callback callback. */
+struct rdxusb_stats_snapshot {
+    /** Frames received from the bus per second, including ones dropped because their queue was full. */
+    double rx_frames_per_sec;
+    /** Frames sent per second. */
+    double tx_frames_per_sec;
+    /** Frames dropped during the interval because their channel's rx queue was full. */
+    uint64_t rx_drops;
+    /** Received packets waiting to be read, across all channels. */
+    uint64_t rx_queue_depth;
+    /** Estimated fraction of its bus the busiest channel's traffic occupies, at the bit rate set with rdxusb_set_stats_bit_rate. */
+    double bus_load;
+    /** Whether the device was connected when sampled. Everything else is 0 if not. */
+    bool connected;
+};
+
+/** Frame rates and bus traffic of one channel over a recent window, filled in by rdxusb_get_channel_meter. */
+struct rdxusb_channel_meter {
+    /** Frames received from the bus per second, including ones the channel's filters or a full queue dropped. */
+    double rx_frames_per_sec;
+    /** Frames sent from this host per second. */
+    double tx_frames_per_sec;
+    /** Nominal bits on the wire per second of the frames in both directions, not counting stuff bits. */
+    double bits_per_sec;
+    /** Estimated fraction of the bus the traffic occupies at the bit rate passed in. */
+    double bus_load;
+};
+
+/** Receive rate of one arbitration id, filled in by rdxusb_get_top_talkers. */
+struct rdxusb_talker_rate {
+    /** Arbitration id, with RDXUSB_ARB_ID_FLAG_EXT set for extended ids. */
+    uint32_t arb_id;
+    /** Frames per second, averaged over roughly the last second. */
+    double frames_per_sec;
+};
+
+/** Called with a device handle's traffic, see rdxusb_set_stats_callback. */
+typedef void (*rdxusb_stats_callback)(int32_t handle_id, const struct rdxusb_stats_snapshot* stats, void* user_data);
+
+/** Pass packets with unknown flag bits through silently. */
+#define RDXUSB_UNKNOWN_FLAGS_IGNORE 0
+/** Pass packets with unknown flag bits through, logging a warning once per device. */
+#define RDXUSB_UNKNOWN_FLAGS_WARN_ONCE 1
+/** Drop packets with unknown flag bits. */
+#define RDXUSB_UNKNOWN_FLAGS_REJECT 2
+
+/** 500 ms control and 2 s open timeouts, reconnecting immediately, with the rx watchdog off. */
+#define RDXUSB_TIMEOUT_PROFILE_DEFAULT 0
+/** 100 ms control and 500 ms open timeouts, with a 250 ms rx watchdog. */
+#define RDXUSB_TIMEOUT_PROFILE_REALTIME 1
+/** 2 s control and 10 s open timeouts, waiting 1 s between reconnect attempts, with the rx watchdog off. */
+#define RDXUSB_TIMEOUT_PROFILE_PATIENT 2
+
+/** Keep idle handles open and reconnecting. */
+#define RDXUSB_IDLE_ACTION_NONE 0
+/** Release the device of an idle handle until it is read from or written to again. */
+#define RDXUSB_IDLE_ACTION_PARK 1
+/** Close idle handles as with rdxusb_close_device. */
+#define RDXUSB_IDLE_ACTION_CLOSE 2
+
+/** Configuration passed to rdxusb_init. */
+struct rdxusb_config {
+    /** Number of worker threads the event loop uses. Zero picks one per core. */
+    uint32_t worker_threads;
+    /** Whether to watch for USB hotplug events to reconnect devices automatically. */
+    bool hotplug;
+    /** Maximum time a single control transfer may take, in milliseconds. Zero uses the default. */
+    uint32_t control_timeout_ms;
+    /** Maximum time opening a device may take, in milliseconds. Zero uses the default. */
+    uint32_t open_timeout_ms;
+    /**
+     * What to do with received packets carrying flag bits this version doesn't know.
+     * One of the RDXUSB_UNKNOWN_FLAGS_* values; anything else is treated as RDXUSB_UNKNOWN_FLAGS_IGNORE.
+     */
+    uint8_t unknown_flag_policy;
+    /** Run no background threads, and only process USB traffic inside rdxusb_pump calls. */
+    bool manual_pump;
+};
+
 #ifdef __cplusplus
 extern "C" {
 #endif 
 
+/**
+ * Explicitly initializes rdxusb.
+ * 
+ * Calling this is optional; without it, rdxusb initializes itself with defaults on first use.
+ * Hosts that load and unload the library repeatedly should pair this with rdxusb_finalize.
+ * 
+ * @param config pointer to the configuration to use. If NULL, defaults are used.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_init(const struct rdxusb_config* config);
+
+/**
+ * Processes USB traffic on the calling thread for the given duration.
+ * 
+ * Only valid when rdxusb was initialized with manual_pump set, in which case nothing is sent or received
+ * outside of this call. Call it periodically from the application's own loop.
+ * 
+ * @param max_duration_us how long to process for, in microseconds. 0 runs everything that is ready once.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_pump(uint64_t max_duration_us);
+
+/**
+ * Closes all devices and frees every resource held by rdxusb, joining its background threads.
+ * 
+ * All device handles and iterators are invalidated. rdxusb may be initialized again afterwards.
+ * Hosts that unload the library (e.g. JVM embedders) must call this first, as rdxusb's threads would otherwise
+ * keep running code from the unloaded library.
+ * 
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_finalize(void);
+
+/**
+ * Enables or disables audit mode.
+ * 
+ * While audit mode is on, every rdxusb C API call is logged with its arguments, return code, and duration
+ * under the `rdxusb::audit` log target.
+ * 
+ * @param enabled whether audit mode should be on
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_audit_mode(bool enabled);
+
+/**
+ * Gets the library version.
+ * 
+ * @param major pointer the major version is written to. Must not be NULL.
+ * @param minor pointer the minor version is written to. Must not be NULL.
+ * @param patch pointer the patch version is written to. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_get_version(uint32_t* major, uint32_t* minor, uint32_t* patch);
+
+/**
+ * Gets the C ABI version of the library.
+ * 
+ * Consumers that load rdxusb dynamically should compare this against the RDXUSB_ABI_VERSION
+ * they were built with before calling anything else.
+ * 
+ * @return the ABI version
+ */
+uint32_t rdxusb_get_abi_version(void);
+
+/**
+ * Gets the optional features the library was compiled with, so bindings can tell which subsystems
+ * the loaded library has.
+ * 
+ * @param features pointer updated with a bitmask of RDXUSB_FEATURE_* values. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_get_build_features(uint64_t* features);
+
 /**
  * Directs rdxusb to open a device with the associated vid/pid/serial number tuple.
  * 
@@ -98,11 +404,47 @@ extern "C" {
  * @param pid USB product ID to match
  * @param serial_number an optional serial number string. This MUST be utf-8 or NULL.
  * @param close_on_dc if true, closes the device handle on device disconnect
- * @param buf_size the maximum number of packets to buffer inbound/outbound
+ * @param buf_size the maximum number of packets to buffer inbound/outbound, at most 65536
  * @return a non-negative device handle on success, negative on error
  */
 int32_t rdxusb_open_device(uint16_t vid, uint16_t pid, const char* serial_number, bool close_on_dc, uint64_t buf_size);
 
+/**
+ * Directs rdxusb to open exactly the device at an index in an iterator, such as the row a user picked from a listing.
+ * 
+ * Devices with a serial number are matched by their vid/pid/serial number tuple from then on, as with
+ * rdxusb_open_device. Devices without one are matched only while they stay attached, so their handle
+ * does not reconnect after the device is replugged.
+ * 
+ * @param iter_id iterator handle to open from
+ * @param device_idx index of the device to open. Must be 0 <= device_idx < n_devices.
+ * @param close_on_dc if true, closes the device handle on device disconnect
+ * @param buf_size the maximum number of packets to buffer inbound/outbound, at most 65536
+ * @return a non-negative device handle on success, negative on error
+ */
+int32_t rdxusb_open_device_from_iterator(uint64_t iter_id, uint64_t device_idx, bool close_on_dc, uint64_t buf_size);
+
+/**
+ * Directs rdxusb to open the first attached Redux device it recognizes.
+ * 
+ * The device is matched by its vid/pid/serial number tuple from then on, exactly as if it had been
+ * passed to rdxusb_open_device.
+ * 
+ * @param close_on_dc if true, closes the device handle on device disconnect
+ * @param buf_size the maximum number of packets to buffer inbound/outbound, at most 65536
+ * @return a non-negative device handle on success, negative on error
+ */
+int32_t rdxusb_open_first_redux_device(bool close_on_dc, uint64_t buf_size);
+
+/**
+ * Checks whether a vid/pid pair belongs to a known Redux device.
+ * 
+ * @param vid USB vendor ID
+ * @param pid USB product ID
+ * @return true if the device is a known Redux device
+ */
+bool rdxusb_is_redux_device(uint16_t vid, uint16_t pid);
+
 /**
  * Forces the RdxUsb event loop to rescan USB devices.
  * 
@@ -116,6 +458,10 @@ int32_t rdxusb_force_scan_devices(void);
 /**
  * Reads packets into the specified buffer.
  * 
+ * Reading a channel from several threads at once splits its packets between them unpredictably.
+ * With rdxusb_set_exclusive_readers turned on, the first thread to read a channel becomes its only reader, and
+ * reads from other threads fail with RDXUSB_ERR_CHANNEL_CLAIMED until rdxusb_release_reader is called.
+ * 
  * @param handle_id a handle id returned from rdxusb_open_device
  * @param channel the USB channel to read from.
  *                The number of channels a device has is device dependent, but for now just pass in 0.
@@ -128,6 +474,131 @@ int32_t rdxusb_read_packets(int32_t handle_id, uint8_t channel,
                             struct rdxusb_packet* packets, 
                             uint64_t max_packets, uint64_t* packets_read);
 
+/**
+ * Reads packets from every channel into the specified buffer, merged in device timestamp order.
+ * 
+ * Packets come out in non-decreasing timestamp order as long as the device sends them in order;
+ * see rdxusb_set_reorder_depth for devices that don't. Notifications are not included.
+ * 
+ * With rdxusb_set_exclusive_readers turned on, the first thread to call this becomes the device's only reader,
+ * and it fails with RDXUSB_ERR_CHANNEL_CLAIMED if another thread reads any of the channels, until
+ * rdxusb_release_any_reader is called.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param packets a pointer to the packet buffer to read into. Must not be NULL.
+ * @param max_packets the maximum number of packets to read into the packet buffer.
+ * @param packets_read pointer updated with how many packets were actually read. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_read_packets_any(int32_t handle_id, struct rdxusb_packet* packets,
+                                uint64_t max_packets, uint64_t* packets_read);
+
+/**
+ * Like rdxusb_read_packets, but reads packets along with metadata added by the host.
+ * 
+ * Both share the channel's queue and reader claim, so mixing them on one channel is fine.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param channel the USB channel to read from.
+ * @param packets a pointer to the packet buffer to read into. Must not be NULL.
+ * @param max_packets the maximum number of packets to read into the packet buffer.
+ * @param packets_read pointer updated with how many packets were actually read. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_read_packets_ex(int32_t handle_id, uint8_t channel,
+                               struct rdxusb_packet_ex* packets,
+                               uint64_t max_packets, uint64_t* packets_read);
+
+/**
+ * Like rdxusb_read_packets_any, but reads packets along with metadata added by the host.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param packets a pointer to the packet buffer to read into. Must not be NULL.
+ * @param max_packets the maximum number of packets to read into the packet buffer.
+ * @param packets_read pointer updated with how many packets were actually read. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_read_packets_any_ex(int32_t handle_id, struct rdxusb_packet_ex* packets,
+                                   uint64_t max_packets, uint64_t* packets_read);
+
+/**
+ * Restricts each channel of a handle to a single reader thread.
+ * 
+ * Off by default. When on, the first thread to read a channel owns it and reads from other threads fail with
+ * RDXUSB_ERR_CHANNEL_CLAIMED, so concurrent callers can't silently split a channel's packets. Leave it off if
+ * reads come from a thread pool or JNI threads that aren't pinned. Turning it off drops every claim.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param exclusive true to enforce a single reader per channel
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_exclusive_readers(int32_t handle_id, bool exclusive);
+
+/**
+ * Releases the calling thread's claim on reading a channel, so another thread can read it.
+ * 
+ * Any thread may release a claim, including ones held by threads that have exited.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param channel the channel passed to rdxusb_read_packets
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_release_reader(int32_t handle_id, uint8_t channel);
+
+/**
+ * Releases the claim on rdxusb_read_packets_any, so another thread can read the device.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_release_any_reader(int32_t handle_id);
+
+/**
+ * Reads notifications sent by the device itself into the specified buffer.
+ * 
+ * Notifications carry device events such as fault codes, reset notices, or over-temperature warnings,
+ * and are kept separate from regular channel traffic. Their arbitration ids and payloads are device-specific.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param packets a pointer to the packet buffer to read into. Must not be NULL.
+ * @param max_packets the maximum number of notifications to read into the packet buffer.
+ * @param packets_read pointer updated with how many notifications were actually read. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_read_notifications(int32_t handle_id, struct rdxusb_packet* packets,
+                                  uint64_t max_packets, uint64_t* packets_read);
+
+/**
+ * Reads error frames reported by the device's CAN controllers into the specified buffer.
+ * 
+ * Error frames have RDXUSB_MESSAGE_FLAG_ERR set, and the RDXUSB_ERROR_* bits in their arbitration id describe
+ * the event, such as bus-off, error passive, or stuff errors. They are kept out of rdxusb_read_packets.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param packets a pointer to the packet buffer to read into. Must not be NULL.
+ * @param max_packets the maximum number of error frames to read into the packet buffer.
+ * @param packets_read pointer updated with how many error frames were actually read. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_read_error_frames(int32_t handle_id, struct rdxusb_packet* packets,
+                                 uint64_t max_packets, uint64_t* packets_read);
+
+/**
+ * Reads echoes of frames sent on a channel with RDXUSB_MESSAGE_FLAG_ECHO set.
+ * 
+ * Echoes are timestamped with when the device actually transmitted the frame, which measures bus latency
+ * and confirms the frame won arbitration. They are kept out of rdxusb_read_packets.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param channel the channel the frames were sent on
+ * @param packets a pointer to the packet buffer to read into. Must not be NULL.
+ * @param max_packets the maximum number of echoes to read into the packet buffer.
+ * @param packets_read pointer updated with how many echoes were actually read. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_read_echoes(int32_t handle_id, uint8_t channel, struct rdxusb_packet* packets,
+                           uint64_t max_packets, uint64_t* packets_read);
+
 /**
  * Writes packets from the specified buffer.
  * 
@@ -135,11 +606,342 @@ int32_t rdxusb_read_packets(int32_t handle_id, uint8_t channel,
  * @param packets a pointer to the packet buffer to write from. Must not be NULL.
  * @param packets_len the number of packets to write from the packet buffer.
  * @param packets_written pointer updated with how many packets were actually written. Can be NULL.
+ * 
+ * Writing stops at the first packet the device cannot carry (see RDXUSB_ERR_INVALID_PACKET),
+ * which is reported as an error once it is the first packet passed in.
  * @return 0 on success, negative on error
  */
 int32_t rdxusb_write_packets(int32_t handle_id, struct rdxusb_packet* packets, 
                             uint64_t packets_len, uint64_t* packets_written);
 
+/**
+ * Sends a device-addressed command until the device echoes it back, for configuration frames that must not be lost.
+ * 
+ * The frame is sent with RDXUSB_MESSAGE_FLAG_ECHO and a sequence number in RDXUSB_MESSAGE_FLAG_SEQ_MASK, and sent
+ * again with the same sequence number each time no echo arrives within the timeout. Devices apply a retried frame
+ * only once, but firmware predating sequence numbers applies every copy, so only send idempotent commands this way.
+ * 
+ * This blocks until the echo arrives. Other echoes read from the packet's channel in the meantime are discarded.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param packet the frame to send. Its arbitration id must have RDXUSB_ARB_ID_FLAG_DEVICE set. Must not be NULL.
+ * @param retries how many times to resend the frame before giving up
+ * @param timeout_ms how long to wait for the echo after each attempt, in milliseconds
+ * @param echo pointer updated with the echo. Can be NULL.
+ * @return 0 on success, RDXUSB_ERR_TIMEOUT if every attempt went unanswered, negative on other errors
+ */
+int32_t rdxusb_write_reliable(int32_t handle_id, const struct rdxusb_packet* packet, uint32_t retries,
+                              uint32_t timeout_ms, struct rdxusb_packet* echo);
+
+/**
+ * Checks that a connected device is responsive, measuring the round trip time of a ping control request.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param timeout_ms how long to wait for the device to answer, in milliseconds
+ * @param rtt_us pointer updated with the round trip time in microseconds. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_TIMEOUT if the device didn't answer,
+ *         RDXUSB_ERR_REQUEST_UNSUPPORTED if its firmware predates pings, negative on other errors
+ */
+int32_t rdxusb_ping(int32_t handle_id, uint64_t timeout_ms, uint64_t* rtt_us);
+
+/**
+ * Sets the rx watchdog timeout of a device handle.
+ * 
+ * If a connected device completes no inbound transfers for this long, it is pinged (see rdxusb_ping).
+ * If the ping also goes unanswered for this long, the device is considered wedged:
+ * rdxusb resets it, the handle moves to RDXUSB_DEVICE_STATE_FAULTED, and reconnection is attempted.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param timeout_ms the watchdog timeout in milliseconds, or 0 to disable the watchdog (the default)
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_rx_watchdog(int32_t handle_id, uint64_t timeout_ms);
+
+/**
+ * Sets the transfer watchdog timeout of a device handle.
+ * 
+ * If no inbound transfer completes for this long, the transfers in flight are cancelled and resubmitted, which
+ * recovers devices whose IN pipe silently wedges without resetting them. Devices on a quiet bus complete no
+ * transfers either, so pick a timeout well above their longest gap between frames. Trips are counted under
+ * "connection" in rdxusb_describe_device. Applies from the next time the device connects.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param timeout_ms the watchdog timeout in milliseconds, or 0 to disable the watchdog (the default)
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_transfer_watchdog(int32_t handle_id, uint64_t timeout_ms);
+
+/**
+ * Limits how many received packets per second rdxusb processes for a device handle.
+ * 
+ * Once the limit is reached, the handle's poller sleeps until it may continue, so a device flooding the bus
+ * can't take a whole core away from the robot program. Packets beyond the limit are dropped by the device.
+ * The limit carries over across reconnects, and the number of times it was hit is reported as `rx_throttled`
+ * by rdxusb_describe_device.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param max_packets_per_sec the limit in packets per second, or 0 for no limit (the default)
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_max_rx_rate(int32_t handle_id, uint32_t max_packets_per_sec);
+
+/**
+ * Turns on smoothing of a device handle's timestamps, for devices whose clocks jitter.
+ * 
+ * The handle's poller tracks the device clock with a PLL, and rdxusb_read_packets_ex fills in
+ * `smoothed_timestamp_ns`: the packet's timestamp on the host wall clock, without the jitter of the device clock
+ * and of USB delays. Lower bandwidths filter out more jitter but follow changes in the device clock's rate more
+ * slowly; 0.1 to 1 Hz suits most devices. The setting carries over across reconnects.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param bandwidth_hz the filter's loop bandwidth in Hz, or 0 to turn smoothing off (the default)
+ * @return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the bandwidth is negative or not finite, negative on other errors
+ */
+int32_t rdxusb_set_timestamp_smoothing(int32_t handle_id, double bandwidth_hz);
+
+/**
+ * Calls a function with a device handle's traffic at a fixed interval, for dashboards showing live meters.
+ * 
+ * The callback runs on an rdxusb thread (or inside rdxusb_pump in manual pump mode) and must return quickly.
+ * It keeps being called while the device is disconnected, until the handle is closed or the callback is removed.
+ * 
+ * Once this returns after replacing or removing a callback, or once rdxusb_close_device or rdxusb_close_all_devices
+ * returns, the previous callback is not running and won't be called again, so its user_data may be freed. A callback
+ * may remove itself, but must not wait on another thread that is removing or closing it.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param interval_ms how often to call the callback, in milliseconds
+ * @param callback the function to call, or NULL to remove the handle's callback
+ * @param user_data passed through to the callback
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_stats_callback(int32_t handle_id, uint32_t interval_ms, rdxusb_stats_callback callback, void* user_data);
+
+/**
+ * Sets the nominal bit rate of a device handle's buses, which the bus load in stats snapshots is estimated against.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param bit_rate the bit rate in bits per second (1000000 for FRC buses, the default), or 0 to report no bus load
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_stats_bit_rate(int32_t handle_id, uint32_t bit_rate);
+
+/**
+ * Measures the frame rates and estimated bus load of a channel over a recent window, to diagnose saturated buses.
+ * 
+ * Stuff bits typically add 10-20% to the bus load, so buses are effectively saturated somewhat below 1.0.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param channel the channel to measure
+ * @param window_ms how far back to look, in milliseconds. Rounded up to 100ms and capped at 10 seconds.
+ * @param bit_rate the bus's nominal bit rate in bits per second, e.g. 1000000 for FRC buses
+ * @param meter pointer updated with the measurement. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_get_channel_meter(int32_t handle_id, uint8_t channel, uint32_t window_ms, uint32_t bit_rate,
+                                 struct rdxusb_channel_meter* meter);
+
+/**
+ * Lists the arbitration ids received most often on a channel lately, busiest first, to find which messages
+ * load a bus.
+ * 
+ * Up to 512 ids are tracked per channel; beyond that the slowest is forgotten to make room for a new one.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param channel the channel to look at
+ * @param talkers a pointer to the buffer to fill. Must not be NULL.
+ * @param max_talkers how many ids the buffer holds
+ * @param talkers_len pointer updated with how many ids were filled in. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_get_top_talkers(int32_t handle_id, uint8_t channel, struct rdxusb_talker_rate* talkers,
+                               uint64_t max_talkers, uint64_t* talkers_len);
+
+/**
+ * Sets the order device handles are opened in when several devices attach at once, highest first.
+ * 
+ * After a hub power cycle every device reattaches together; rdxusb opens them one at a time, so giving critical
+ * devices (e.g. a gyro) a higher priority brings them back first. Devices of equal priority are opened in the
+ * order they were found.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param priority the handle's priority, 0 (the default) being the lowest
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_reconnect_priority(int32_t handle_id, uint8_t priority);
+
+/**
+ * Applies a named set of timeouts to a device handle.
+ * 
+ * A profile sets the open, control and write timeouts, the wait between reconnect attempts, and the rx watchdog
+ * (see rdxusb_set_rx_watchdog). The watchdog and reconnect wait apply right away; the others
+ * apply from the next time the device connects.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param profile one of the RDXUSB_TIMEOUT_PROFILE_* values
+ * @return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the profile is unknown, negative on other errors
+ */
+int32_t rdxusb_set_timeout_profile(int32_t handle_id, uint8_t profile);
+
+/**
+ * Starts recording every packet a device handle receives into a native capture file.
+ * 
+ * Recording continues across reconnects until rdxusb_stop_capture is called or the handle is closed.
+ * Starting a new capture finishes the previous one. The file is written on a background thread, so a slow disk
+ * drops packets from the capture instead of delaying the device.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param path null-terminated path of the .rdxcap file to create
+ * @return 0 on success, RDXUSB_ERR_CAPTURE_FAILED if the file couldn't be created, negative on other errors
+ */
+int32_t rdxusb_start_capture(int32_t handle_id, const char* path);
+
+/**
+ * Finishes a device handle's capture started with rdxusb_start_capture.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @return 0 on success, RDXUSB_ERR_CAPTURE_NOT_ACTIVE if no capture is running,
+ *         RDXUSB_ERR_CAPTURE_FAILED if writing the file failed, negative on other errors
+ */
+int32_t rdxusb_stop_capture(int32_t handle_id);
+
+/**
+ * Adds a text annotation, such as "auton started", to a device handle's capture.
+ * 
+ * The annotation is placed after the packets received so far, so it can be correlated with the traffic around it.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param text null-terminated UTF-8 annotation text
+ * @return 0 on success, RDXUSB_ERR_CAPTURE_NOT_ACTIVE if no capture is running, negative on other errors
+ */
+int32_t rdxusb_capture_annotate(int32_t handle_id, const char* text);
+
+/**
+ * Sets how many packets rdxusb_read_packets_any holds back to restore timestamp ordering across channels.
+ * 
+ * Only needed for devices that timestamp channels independently. Held back packets are released once
+ * that many newer packets have arrived, so this adds latency.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param depth the number of packets to hold back, or 0 to disable reordering (the default)
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_reorder_depth(int32_t handle_id, uint32_t depth);
+
+/**
+ * Gets the lifecycle state of a device handle.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param state pointer the state is written to, one of the RDXUSB_DEVICE_STATE_* values. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_get_device_state(int32_t handle_id, int32_t* state);
+
+/**
+ * Describes everything known about a device handle as a JSON object, for display in configuration tools.
+ * 
+ * The object holds the handle's state and match parameters, the USB descriptors of the matching device under "usb",
+ * and, while connected, the device info, channel count, max payload, and transfer counters under "connection".
+ * Anything not known is null.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param buf buffer the NUL-terminated JSON string is written to. Must not be NULL.
+ * @param buf_len size of buf in bytes
+ * @param json_len pointer the JSON length (excluding the NUL) is written to, even if buf is too small. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the string, negative on other errors
+ */
+int32_t rdxusb_describe_device(int32_t handle_id, char* buf, uint64_t buf_len, uint64_t* json_len);
+
+/**
+ * Reads a raw USB descriptor from the device a handle last matched, exactly as the device presents it.
+ * 
+ * The device must be attached, but the handle doesn't need to be connected.
+ * A configuration descriptor (type 2) is followed by all of its interface and endpoint descriptors.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param desc_type the descriptor type, e.g. 1 for the device descriptor or 2 for a configuration descriptor
+ * @param desc_index the descriptor index
+ * @param language_id the language of string descriptors, or 0 for other types
+ * @param buf buffer the descriptor is written to. Must not be NULL.
+ * @param buf_len size of buf in bytes
+ * @param desc_len pointer the descriptor length is written to, even if buf is too small. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the descriptor,
+ *         RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+ */
+int32_t rdxusb_get_raw_descriptor(int32_t handle_id, uint8_t desc_type, uint8_t desc_index, uint16_t language_id,
+                                  uint8_t* buf, uint64_t buf_len, uint64_t* desc_len);
+
+/**
+ * Reads a string descriptor in a chosen language from the device a handle last matched.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param desc_index the string index, as found in other descriptors. Must not be 0.
+ * @param language_id the language to read the string in, see rdxusb_get_string_languages
+ * @param buf buffer the NUL-terminated UTF-8 string is written to. Must not be NULL.
+ * @param buf_len size of buf in bytes
+ * @param str_len pointer the string length (excluding the NUL) is written to, even if buf is too small. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the string,
+ *         RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+ */
+int32_t rdxusb_get_string_descriptor(int32_t handle_id, uint8_t desc_index, uint16_t language_id,
+                                     char* buf, uint64_t buf_len, uint64_t* str_len);
+
+/**
+ * Lists the languages the device a handle last matched has string descriptors in.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param languages array the USB language ids are written to, e.g. 0x0409 for US English. Must not be NULL.
+ * @param max_languages length of the languages array
+ * @param n_languages pointer the number of languages is written to, even if the array is too small. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if the array can't hold every language,
+ *         RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return them, negative on other errors
+ */
+int32_t rdxusb_get_string_languages(int32_t handle_id, uint16_t* languages, uint64_t max_languages, uint64_t* n_languages);
+
+/**
+ * Blocks until a device handle is connected, so packets can be written right after opening it.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param timeout_ms how long to wait, in milliseconds
+ * @return 0 once connected, RDXUSB_ERR_TIMEOUT if the timeout elapsed first, negative on other errors
+ */
+int32_t rdxusb_wait_connected(int32_t handle_id, uint64_t timeout_ms);
+
+/**
+ * Blocks until every one of several device handles is connected at the same time.
+ * 
+ * Use rdxusb_get_device_state to find out which devices are missing after a timeout.
+ * 
+ * @param handle_ids array of handle ids returned from rdxusb_open_device. Must not be NULL.
+ * @param n_handles the number of handle ids in the array
+ * @param timeout_ms how long to wait, in milliseconds
+ * @return 0 once all are connected, RDXUSB_ERR_TIMEOUT if the timeout elapsed first, negative on other errors
+ */
+int32_t rdxusb_wait_all_connected(const int32_t* handle_ids, uint64_t n_handles, uint64_t timeout_ms);
+
+/**
+ * Adds a rule forwarding frames received on one channel of a device out onto another channel.
+ * 
+ * Forwarded frames are still delivered to rdxusb_read_packets as usual.
+ * Rules persist across reconnects until cleared with rdxusb_clear_bridge_rules.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param src_channel the channel to forward frames from
+ * @param dst_channel the channel to retransmit matching frames on
+ * @param id arbitration id to match, compared only on the bits set in mask
+ * @param mask arbitration id bits that must match id. A mask of 0 forwards every frame.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_add_bridge_rule(int32_t handle_id, uint8_t src_channel, uint8_t dst_channel, uint32_t id, uint32_t mask);
+
+/**
+ * Removes all bridge rules from a device.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_clear_bridge_rules(int32_t handle_id);
+
 /**
  * Closes the specified device, and stops reading from it.
  * 
@@ -155,11 +957,62 @@ int32_t rdxusb_close_device(int32_t handle_id);
  * Closes all device handles.
  * 
  * If the handle ID is already closed or invalid, this returns 0.
+ * When forwarding to a daemon, only the handles this process opened are closed.
  * 
  * @return 0 on success, negative on error.
  */
 int32_t rdxusb_close_all_devices();
 
+/**
+ * Serves this process's event loop to other processes on the same machine over a Unix socket.
+ * 
+ * Processes that share devices should let one of them call this, and connect the others with
+ * rdxusb_connect_daemon or by setting the RDXUSB_DAEMON environment variable to the socket path.
+ * Clients can only use the handles they opened, which are closed when they disconnect. The socket is created
+ * with mode 0660, so only processes of the same user or group can connect.
+ * 
+ * @param path path of the socket to create. This MUST be UTF-8 and not NULL.
+ * @return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the socket couldn't be created,
+ *         RDXUSB_ERR_REQUEST_UNSUPPORTED if the library was built without RDXUSB_FEATURE_DAEMON, negative on other errors
+ */
+int32_t rdxusb_serve_daemon(const char* path);
+
+/**
+ * Stops serving other processes, removing the socket created by rdxusb_serve_daemon.
+ * 
+ * Clients already connected are served until they disconnect.
+ * 
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_stop_daemon(void);
+
+/**
+ * Forwards calls to the event loop of another process serving a daemon socket, instead of running one here.
+ * 
+ * Only rdxusb_open_device, rdxusb_open_first_redux_device, rdxusb_read_packets, rdxusb_read_packets_any,
+ * rdxusb_write_packets, rdxusb_close_device, and rdxusb_close_all_devices are forwarded. Every other call that takes
+ * or creates a handle (rdxusb_read_packets_ex, rdxusb_start_capture, rdxusb_open_device_from_iterator, ...) returns
+ * RDXUSB_ERR_REQUEST_UNSUPPORTED while forwarding, since this process's own event loop doesn't know the daemon's
+ * handles. Device enumeration and library-wide settings still act locally. Setting the RDXUSB_DAEMON environment
+ * variable to the socket path connects on first use without calling this.
+ * 
+ * @param path path of the daemon's socket, or NULL to stop forwarding. This MUST be UTF-8.
+ * @return 0 on success, RDXUSB_ERR_DAEMON_UNAVAILABLE if the daemon couldn't be reached,
+ *         RDXUSB_ERR_REQUEST_UNSUPPORTED if the library was built without RDXUSB_FEATURE_DAEMON, negative on other errors
+ */
+int32_t rdxusb_connect_daemon(const char* path);
+
+/**
+ * Gets a counter that changes whenever a known Redux device (see rdxusb_is_redux_device) is attached or detached.
+ * 
+ * Polling this is a cheap way for a UI to decide when to list devices again. It is driven by hotplug events,
+ * so it never changes if rdxusb was initialized with hotplug off.
+ * 
+ * @param generation pointer the counter is written to. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_get_device_list_generation(uint64_t* generation);
+
 /**
  * Creates a new USB device iterator.
  * 
@@ -180,6 +1033,53 @@ int32_t rdxusb_new_device_iterator(rdxusb_iter_id* iter_id, uint64_t* n_devices)
 int32_t rdxusb_get_device_in_iterator(rdxusb_iter_id iter_id, uint64_t device_idx,
                                       struct rdxusb_device_entry* device_entry);
 
+/**
+ * Reads a raw USB descriptor from a device in an iterator, like rdxusb_get_raw_descriptor.
+ * 
+ * @param iter_id iterator handle to pull from
+ * @param device_idx index of the device. Must be 0 <= device_idx < n_devices.
+ * @param desc_type the descriptor type, e.g. 1 for the device descriptor or 2 for a configuration descriptor
+ * @param desc_index the descriptor index
+ * @param language_id the language of string descriptors, or 0 for other types
+ * @param buf buffer the descriptor is written to. Must not be NULL.
+ * @param buf_len size of buf in bytes
+ * @param desc_len pointer the descriptor length is written to, even if buf is too small. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the descriptor,
+ *         RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+ */
+int32_t rdxusb_iter_get_raw_descriptor(rdxusb_iter_id iter_id, uint64_t device_idx, uint8_t desc_type, uint8_t desc_index,
+                                       uint16_t language_id, uint8_t* buf, uint64_t buf_len, uint64_t* desc_len);
+
+/**
+ * Reads a string descriptor in a chosen language from a device in an iterator, like rdxusb_get_string_descriptor.
+ * 
+ * @param iter_id iterator handle to pull from
+ * @param device_idx index of the device. Must be 0 <= device_idx < n_devices.
+ * @param desc_index the string index, as found in other descriptors. Must not be 0.
+ * @param language_id the language to read the string in, see rdxusb_iter_get_string_languages
+ * @param buf buffer the NUL-terminated UTF-8 string is written to. Must not be NULL.
+ * @param buf_len size of buf in bytes
+ * @param str_len pointer the string length (excluding the NUL) is written to, even if buf is too small. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the string,
+ *         RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+ */
+int32_t rdxusb_iter_get_string_descriptor(rdxusb_iter_id iter_id, uint64_t device_idx, uint8_t desc_index,
+                                          uint16_t language_id, char* buf, uint64_t buf_len, uint64_t* str_len);
+
+/**
+ * Lists the languages a device in an iterator has string descriptors in, like rdxusb_get_string_languages.
+ * 
+ * @param iter_id iterator handle to pull from
+ * @param device_idx index of the device. Must be 0 <= device_idx < n_devices.
+ * @param languages array the USB language ids are written to, e.g. 0x0409 for US English. Must not be NULL.
+ * @param max_languages length of the languages array
+ * @param n_languages pointer the number of languages is written to, even if the array is too small. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if the array can't hold every language,
+ *         RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return them, negative on other errors
+ */
+int32_t rdxusb_iter_get_string_languages(rdxusb_iter_id iter_id, uint64_t device_idx, uint16_t* languages,
+                                         uint64_t max_languages, uint64_t* n_languages);
+
 /**
  * Frees a device iterator.
  * 
@@ -188,6 +1088,51 @@ int32_t rdxusb_get_device_in_iterator(rdxusb_iter_id iter_id, uint64_t device_id
  */
 int32_t rdxusb_free_device_iterator(rdxusb_iter_id iter_id);
 
+/**
+ * Sets how many open handles or live device iterators are considered a leak.
+ * 
+ * Once exceeded, every newly opened handle or iterator logs a warning.
+ * Warnings are off by default.
+ * 
+ * @param max_handles open handles allowed before warning, or 0 to never warn
+ * @param max_iterators live device iterators allowed before warning, or 0 to never warn
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_set_leak_warnings(uint32_t max_handles, uint32_t max_iterators);
+
+/**
+ * Reports the handles and device iterators currently held, to help find ones a binding layer never releases.
+ * 
+ * @param report pointer to write the report into. Must not be NULL.
+ * @return 0 on success, negative on error
+ */
+int32_t rdxusb_get_leak_report(struct rdxusb_leak_report* report);
+
+/**
+ * Parks or closes a device handle once nothing has read from or written to it for a while.
+ * 
+ * Handles opened with close_on_dc false otherwise keep reconnecting until closed. Parked handles move to
+ * RDXUSB_DEVICE_STATE_PARKED; the next read or write fails with RDXUSB_ERR_DEVICE_NOT_CONNECTED and reopens the device.
+ * 
+ * @param handle_id a handle id returned from rdxusb_open_device
+ * @param action one of the RDXUSB_IDLE_ACTION_* values
+ * @param idle_ms how long the handle may go unused before the action is taken
+ * @return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the action is unknown, negative on other errors
+ */
+int32_t rdxusb_set_idle_policy(int32_t handle_id, uint8_t action, uint64_t idle_ms);
+
+/**
+ * Parks or closes every device handle that went unused for at least a given time, regardless of its idle policy.
+ * 
+ * @param min_idle_ms how long a handle must have gone unused
+ * @param action RDXUSB_IDLE_ACTION_PARK or RDXUSB_IDLE_ACTION_CLOSE
+ * @param handles a pointer to a buffer the reaped handle ids are written to. May be NULL if max_handles is 0.
+ * @param max_handles how many handle ids the buffer holds
+ * @param handles_len pointer updated with how many handles were reaped, which may exceed max_handles. Must not be NULL.
+ * @return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the action is unknown, negative on other errors
+ */
+int32_t rdxusb_reap_idle_handles(uint64_t min_idle_ms, uint8_t action, int32_t* handles, uint64_t max_handles, uint64_t* handles_len);
+
 #ifdef __cplusplus
 }
 #endif
\ No newline at end of file
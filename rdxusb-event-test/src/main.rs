@@ -1,11 +1,11 @@
 use std::time::Duration;
 
-use rdxusb::{RdxUsbPacket, MESSAGE_ARB_ID_DEVICE, MESSAGE_ARB_ID_EXT};
+use rdxusb::{vendor, RdxUsbPacket, MESSAGE_ARB_ID_DEVICE, MESSAGE_ARB_ID_EXT};
 
 
 fn main() {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("trace"));
-    let handle = rdxusb::c_api::rdxusb_open_device(0x16d0, 0x1279, c"04-0-0000-000-E-1".as_ptr(), false, 48);
+    let handle = rdxusb::c_api::rdxusb_open_device(vendor::REDUX_VID, vendor::CANANDCOLOR_PID, c"04-0-0000-000-E-1".as_ptr(), false, 48);
     if handle < 0 {
         panic!("could not open device: {handle}");
     }
@@ -28,8 +28,11 @@ fn main() {
         data: data,
     };
 
-    // opening a handle isn't instantaneous. 
-    std::thread::sleep(Duration::from_millis(100));
+    // opening a handle isn't instantaneous.
+    let result = rdxusb::c_api::rdxusb_wait_connected(handle, 1000);
+    if result < 0 {
+        panic!("device did not connect: {result}");
+    }
     let mut packets_written = 0u64;
     let result = rdxusb::c_api::rdxusb_write_packets(handle, &packet, 1, &mut packets_written);
     println!("write packet: {result} for {packets_written}");
@@ -2,9 +2,10 @@
 
 use bytemuck::{Pod, Zeroable};
 
-/// In bulk xfer endpoint (has top bit set)
+/// In bulk xfer endpoint (has top bit set).
+/// Hosts use the endpoints listed in the interface descriptors, and only fall back to these.
 pub const ENDPOINT_IN: u8 = 0x81;
-/// Out bulk xfer endpoint
+/// Out bulk xfer endpoint, see [`ENDPOINT_IN`].
 pub const ENDPOINT_OUT: u8 = 0x02;
 
 /// this bit is true on arbitration IDs [`RdxUsbFsPacket::arb_id`] that are extended (29-bit).
@@ -20,6 +21,65 @@ pub const MESSAGE_ARB_ID_RTR: u32 = 0x40000000;
 /// regardless of any configured device id bits.
 pub const MESSAGE_ARB_ID_DEVICE: u32 = 0x20000000;
 
+/// Channel value reserved for notifications originating from the device itself 
+/// (fault codes, reset notices, over-temperature warnings, etc.) rather than from a bus.
+///
+/// The notification kind and payload are carried in the packet's arbitration id and data, and are device-specific.
+pub const NOTIFICATION_CHANNEL: u8 = 0xff;
+
+/// Set in [`RdxUsbPacket::flags`] on CAN FD frames.
+///
+/// FD payloads may be 0-8, 12, 16, 20, 24, 32, 48, or 64 bytes long; full speed devices carry at most 48.
+pub const MESSAGE_FLAG_FDF: u16 = 0x0001;
+/// Bit rate switch: the data phase of this CAN FD frame is sent at the data bitrate.
+pub const MESSAGE_FLAG_BRS: u16 = 0x0002;
+/// Error state indicator: the transmitter of this CAN FD frame was error passive. Ignored on transmit.
+pub const MESSAGE_FLAG_ESI: u16 = 0x0004;
+/// Set in [`RdxUsbPacket::flags`] on error frames reported by the device's CAN controller.
+///
+/// Error frames aren't bus traffic: their arbitration id holds `MESSAGE_ERROR_*` bits describing the event,
+/// and their payload may carry device-specific detail such as error counters.
+pub const MESSAGE_FLAG_ERR: u16 = 0x0008;
+/// Set in [`RdxUsbPacket::flags`] on packets sent to the device to have it echo the frame back once it's been
+/// transmitted. The echo carries this flag too, with [`RdxUsbPacket::timestamp_ns`] set to the transmission time.
+pub const MESSAGE_FLAG_ECHO: u16 = 0x0010;
+/// Sequence number of a reliably delivered command, in [`RdxUsbPacket::flags`] bits 8-11.
+///
+/// A device receiving a [`MESSAGE_ARB_ID_DEVICE`] frame whose nonzero sequence number matches the last one it
+/// acted on treats it as a retry of that frame: it echoes it again without applying it twice. Zero never matches.
+pub const MESSAGE_FLAG_SEQ_MASK: u16 = 0x0f00;
+/// Shift of the sequence number in [`MESSAGE_FLAG_SEQ_MASK`].
+pub const MESSAGE_FLAG_SEQ_SHIFT: u32 = 8;
+
+/// The controller went bus-off and stopped participating on the bus.
+pub const MESSAGE_ERROR_BUS_OFF: u32 = 0x0001;
+/// The controller became error passive.
+pub const MESSAGE_ERROR_PASSIVE: u32 = 0x0002;
+/// An error counter reached the warning level.
+pub const MESSAGE_ERROR_WARNING: u32 = 0x0004;
+/// A bit stuffing error was detected.
+pub const MESSAGE_ERROR_STUFF: u32 = 0x0008;
+/// A form error was detected.
+pub const MESSAGE_ERROR_FORM: u32 = 0x0010;
+/// A transmitted frame wasn't acknowledged.
+pub const MESSAGE_ERROR_ACK: u32 = 0x0020;
+/// A transmitted bit didn't read back as sent.
+pub const MESSAGE_ERROR_BIT: u32 = 0x0040;
+/// A received frame failed its CRC check.
+pub const MESSAGE_ERROR_CRC: u32 = 0x0080;
+/// The controller's receive buffer overflowed and frames were lost.
+pub const MESSAGE_ERROR_RX_OVERFLOW: u32 = 0x0100;
+
+/// Is `len` a payload length that a CAN FD frame can have?
+pub const fn is_valid_fd_len(len: u8) -> bool {
+    matches!(len, 0..=8 | 12 | 16 | 20 | 24 | 32 | 48 | 64)
+}
+
+/// Every [`RdxUsbFsPacket::flags`] bit this version of the protocol defines.
+///
+/// Bits outside this mask may be set by newer firmware; hosts should pass them through untouched.
+pub const KNOWN_FLAGS: u16 = MESSAGE_FLAG_FDF | MESSAGE_FLAG_BRS | MESSAGE_FLAG_ESI | MESSAGE_FLAG_ERR | MESSAGE_FLAG_ECHO | MESSAGE_FLAG_SEQ_MASK;
+
 
 /// Data packet passed to USB-full-speed devices which have a max packet size of 64.
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Pod, Zeroable)]
@@ -33,9 +93,9 @@ pub struct RdxUsbFsPacket {
     pub dlc: u8,
     /// Relevant channel. Zero most of the time.
     pub channel: u8,
-    /// Misc flags (unused for now)
+    /// Misc flags, see the `MESSAGE_FLAG_*` constants.
     pub flags: u16,
-    /// data (max size: 48 bytes)
+    /// data (max size: 48 bytes, including CAN FD frames)
     pub data: [u8; 48]
 }
 
@@ -51,7 +111,7 @@ pub struct RdxUsbPacket {
     pub dlc: u8,
     /// Relevant channel. Zero most of the time.
     pub channel: u8,
-    /// Misc flags (unused for now)
+    /// Misc flags, see the `MESSAGE_FLAG_*` constants.
     pub flags: u16,
     /// data (max size: 64 bytes)
     pub data: [u8; 64]
@@ -72,6 +132,41 @@ impl From<RdxUsbFsPacket> for RdxUsbPacket {
     }
 }
 
+/// [`RdxUsbPacket`] followed by metadata added by the host.
+///
+/// The packet comes first, so a pointer to one can be read as a plain [`RdxUsbPacket`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct RdxUsbPacketEx {
+    pub packet: RdxUsbPacket,
+    /// Host wall clock time the packet was read from rdxusb (nanoseconds since the UNIX epoch)
+    pub host_timestamp_ns: u64,
+    /// Counts the connections of the handle the packet came from, starting at 1 for the first one.
+    ///
+    /// Device timestamps restart when the device does, so only compare them within one epoch.
+    pub connection_epoch: u32,
+    /// Handle the packet was read from
+    pub origin_handle: i32,
+    /// Position of the packet among everything read from its handle, starting at 0
+    pub sequence: u64,
+    /// The device timestamp mapped onto the host wall clock by the handle's timestamp smoothing filter
+    /// (nanoseconds since the UNIX epoch), or 0 if smoothing is off or hasn't received anything yet
+    pub smoothed_timestamp_ns: u64,
+}
+
+impl From<RdxUsbPacket> for RdxUsbPacketEx {
+    /// Wraps a packet with all metadata zeroed.
+    fn from(packet: RdxUsbPacket) -> Self {
+        Self { packet, host_timestamp_ns: 0, connection_epoch: 0, origin_handle: 0, sequence: 0, smoothed_timestamp_ns: 0 }
+    }
+}
+
+impl From<RdxUsbPacketEx> for RdxUsbPacket {
+    fn from(value: RdxUsbPacketEx) -> Self {
+        value.packet
+    }
+}
+
 impl TryFrom<RdxUsbPacket> for RdxUsbFsPacket {
     type Error = RdxUsbPacket;
 
@@ -113,6 +208,48 @@ impl RdxUsbFsPacket {
         self.arb_id & MESSAGE_ARB_ID_DEVICE != 0
     }
 
+    /// Is the packet a CAN FD frame?
+    pub const fn fd(&self) -> bool {
+        self.flags & MESSAGE_FLAG_FDF != 0
+    }
+
+    /// Does the CAN FD frame use bit rate switching?
+    pub const fn brs(&self) -> bool {
+        self.flags & MESSAGE_FLAG_BRS != 0
+    }
+
+    /// Was the CAN FD frame's transmitter error passive?
+    pub const fn esi(&self) -> bool {
+        self.flags & MESSAGE_FLAG_ESI != 0
+    }
+
+    /// Is the packet an error frame? See [`MESSAGE_FLAG_ERR`].
+    pub const fn error_frame(&self) -> bool {
+        self.flags & MESSAGE_FLAG_ERR != 0
+    }
+
+    /// Is (or, when sending, should) the packet be echoed? See [`MESSAGE_FLAG_ECHO`].
+    pub const fn echo(&self) -> bool {
+        self.flags & MESSAGE_FLAG_ECHO != 0
+    }
+
+    /// Clamps [`Self::dlc`] to the size of [`Self::data`], returning true if it was out of range.
+    ///
+    /// Hosts sanitize every packet received from a device, so `&data[..dlc as usize]` cannot panic on them.
+    pub fn sanitize(&mut self) -> bool {
+        if self.dlc as usize > self.data.len() {
+            self.dlc = self.data.len() as u8;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The payload bytes, i.e. the first [`Self::dlc`] bytes of [`Self::data`], clamped to the buffer.
+    pub fn payload(&self) -> &[u8] {
+        &self.data[..(self.dlc as usize).min(self.data.len())]
+    }
+
     /// Should always be 64.
     pub const SIZE: usize = core::mem::size_of::<Self>();
 
@@ -146,6 +283,48 @@ impl RdxUsbPacket {
         self.arb_id & MESSAGE_ARB_ID_DEVICE != 0
     }
 
+    /// Is the packet a CAN FD frame?
+    pub const fn fd(&self) -> bool {
+        self.flags & MESSAGE_FLAG_FDF != 0
+    }
+
+    /// Does the CAN FD frame use bit rate switching?
+    pub const fn brs(&self) -> bool {
+        self.flags & MESSAGE_FLAG_BRS != 0
+    }
+
+    /// Was the CAN FD frame's transmitter error passive?
+    pub const fn esi(&self) -> bool {
+        self.flags & MESSAGE_FLAG_ESI != 0
+    }
+
+    /// Is the packet an error frame? See [`MESSAGE_FLAG_ERR`].
+    pub const fn error_frame(&self) -> bool {
+        self.flags & MESSAGE_FLAG_ERR != 0
+    }
+
+    /// Is (or, when sending, should) the packet be echoed? See [`MESSAGE_FLAG_ECHO`].
+    pub const fn echo(&self) -> bool {
+        self.flags & MESSAGE_FLAG_ECHO != 0
+    }
+
+    /// Clamps [`Self::dlc`] to the size of [`Self::data`], returning true if it was out of range.
+    ///
+    /// Hosts sanitize every packet received from a device, so `&data[..dlc as usize]` cannot panic on them.
+    pub fn sanitize(&mut self) -> bool {
+        if self.dlc as usize > self.data.len() {
+            self.dlc = self.data.len() as u8;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The payload bytes, i.e. the first [`Self::dlc`] bytes of [`Self::data`], clamped to the buffer.
+    pub fn payload(&self) -> &[u8] {
+        &self.data[..(self.dlc as usize).min(self.data.len())]
+    }
+
     /// Should always be 64.
     pub const SIZE: usize = core::mem::size_of::<Self>();
 
@@ -172,8 +351,11 @@ pub struct RdxUsbDeviceInfo {
     pub protocol_version_major: u16,
     /// The minor protocol version
     pub protocol_version_minor: u16,
+    /// Units of [`RdxUsbPacket::timestamp_ns`] as sent by the device, an [`RdxUsbTimestampUnits`].
+    /// Firmware predating this field leaves it 0 (nanoseconds).
+    pub timestamp_units: u8,
     /// Reserved bits
-    pub reserved: [u8; 24]
+    pub reserved: [u8; 23]
 }
 
 impl RdxUsbDeviceInfo {
@@ -187,6 +369,45 @@ impl RdxUsbDeviceInfo {
     pub fn from_buf(buf: [u8; Self::SIZE]) -> Self {
         bytemuck::cast(buf)
     }
+
+    /// The units the device sends timestamps in, or `None` if it reported units this version doesn't know.
+    pub fn timestamp_units(&self) -> Option<RdxUsbTimestampUnits> {
+        self.timestamp_units.try_into().ok()
+    }
+}
+
+/// Units a device counts packet timestamps in, reported in [`RdxUsbDeviceInfo::timestamp_units`].
+///
+/// Hosts normalize timestamps to nanoseconds as packets arrive, so this only matters for diagnostics.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[repr(u8)]
+pub enum RdxUsbTimestampUnits {
+    /// (default)
+    #[default]
+    Nanoseconds = 0,
+    Microseconds = 1,
+}
+
+impl RdxUsbTimestampUnits {
+    /// Converts a timestamp in these units to nanoseconds.
+    pub const fn to_ns(self, timestamp: u64) -> u64 {
+        match self {
+            RdxUsbTimestampUnits::Nanoseconds => timestamp,
+            RdxUsbTimestampUnits::Microseconds => timestamp.saturating_mul(1000),
+        }
+    }
+}
+
+impl TryFrom<u8> for RdxUsbTimestampUnits {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => RdxUsbTimestampUnits::Nanoseconds,
+            1 => RdxUsbTimestampUnits::Microseconds,
+            _ => return Err(value),
+        })
+    }
 }
 
 /// Control requests supported
@@ -194,7 +415,313 @@ impl RdxUsbDeviceInfo {
 #[repr(u8)]
 pub enum RdxUsbCtrl {
     DeviceInfo = 0,
+    /// Replaces a channel's acceptance filters with the [`RdxUsbIdMaskFilter`] array in the data stage.
+    /// An empty array accepts every frame.
+    SetFilters = 1,
+    /// Reads the device clock as an [`RdxUsbDeviceTime`], in the same timebase as [`RdxUsbPacket::timestamp_ns`].
+    GetTime = 2,
+    /// Sets a channel's nominal (arbitration phase) bit timing to the [`RdxUsbBitTiming`] in the data stage.
+    SetBitrate = 3,
+    /// Sets a channel's CAN FD data phase bit timing to the [`RdxUsbBitTiming`] in the data stage.
+    SetDataBitrate = 4,
+    /// Sets a channel's [`RdxUsbChannelMode`], sent as a single byte in the data stage.
+    SetMode = 5,
+    /// Reads a channel's CAN controller state as an [`RdxUsbBusState`].
+    GetBusState = 6,
+    /// Reboots the device's firmware. The device disconnects and re-enumerates.
+    Reset = 7,
+    /// Reboots the device into its bootloader for a firmware update. The device disconnects and re-enumerates,
+    /// usually with a different product id.
+    EnterBootloader = 8,
+    /// Echoes the 16-bit nonce sent in `wValue` back as 2 little-endian data bytes, to check the device is responsive.
+    Ping = 9,
+    /// Blinks the device's status LED for `wValue` seconds so it can be told apart from identical devices.
+    /// A `wValue` of 0 stops blinking.
+    Identify = 10,
+}
+
+/// Fault confinement state of a CAN controller.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum RdxUsbControllerState {
+    /// Both error counters are below 128; the controller takes part in the bus normally.
+    ErrorActive = 0,
+    /// An error counter reached 128; the controller only signals errors passively.
+    ErrorPassive = 1,
+    /// The transmit error counter passed 255 and the controller is off the bus until it recovers.
+    BusOff = 2,
+}
+
+impl TryFrom<u8> for RdxUsbControllerState {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::ErrorActive,
+            1 => Self::ErrorPassive,
+            2 => Self::BusOff,
+            _ => return Err(value),
+        })
+    }
+}
+
+/// Struct returned by the [`RdxUsbCtrl::GetBusState`] control request
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct RdxUsbBusState {
+    /// One of the [`RdxUsbControllerState`] values
+    pub state: u8,
+    pub reserved: u8,
+    /// Transmit error counter
+    pub tec: u16,
+    /// Receive error counter
+    pub rec: u16,
+}
+
+impl RdxUsbBusState {
+    /// The controller state, or `None` if the device reported one this version doesn't know.
+    pub fn controller_state(&self) -> Option<RdxUsbControllerState> {
+        self.state.try_into().ok()
+    }
+
+    pub fn bus_off(&self) -> bool {
+        self.state == RdxUsbControllerState::BusOff as u8
+    }
+}
+
+/// Operating mode of a channel's CAN controller, set with [`RdxUsbCtrl::SetMode`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[repr(u8)]
+pub enum RdxUsbChannelMode {
+    /// Receives, transmits, and acknowledges frames. (default)
+    #[default]
+    Normal = 0,
+    /// Receives without ever driving the bus, not even ACK bits, so the channel can snoop a bus unnoticed.
+    /// Frames written to the channel are not sent.
+    ListenOnly = 1,
+    /// Transmitted frames are received back internally without reaching the bus.
+    Loopback = 2,
+}
+
+impl TryFrom<u8> for RdxUsbChannelMode {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Normal,
+            1 => Self::ListenOnly,
+            2 => Self::Loopback,
+            _ => return Err(value),
+        })
+    }
+}
+
+/// Bit timing sent with the [`RdxUsbCtrl::SetBitrate`] and [`RdxUsbCtrl::SetDataBitrate`] control requests.
+///
+/// The device derives its time quanta from these; zero fields leave the choice to the device.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct RdxUsbBitTiming {
+    /// Bits per second, e.g. 1_000_000
+    pub bitrate: u32,
+    /// Sample point in tenths of a percent, e.g. 875 for 87.5%
+    pub sample_point: u16,
+    /// Synchronization jump width in time quanta
+    pub sjw: u8,
+    pub reserved: u8,
+}
+
+impl RdxUsbBitTiming {
+    /// Timing for `bitrate` with the device's default sample point and jump width.
+    pub const fn new(bitrate: u32) -> Self {
+        Self { bitrate, sample_point: 0, sjw: 0, reserved: 0 }
+    }
+
+    pub const fn with_sample_point(mut self, sample_point: u16) -> Self {
+        self.sample_point = sample_point;
+        self
+    }
+
+    pub const fn with_sjw(mut self, sjw: u8) -> Self {
+        self.sjw = sjw;
+        self
+    }
+}
+
+impl From<u32> for RdxUsbBitTiming {
+    fn from(bitrate: u32) -> Self {
+        Self::new(bitrate)
+    }
+}
+
+/// Struct returned by the [`RdxUsbCtrl::GetTime`] control request
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct RdxUsbDeviceTime {
+    /// Device time in nanoseconds since boot
+    pub timestamp_ns: u64,
+}
+
+/// Acceptance filter entry: frames are accepted if their arbitration id matches `id` on every bit set in `mask`.
+///
+/// Both fields use the [`RdxUsbPacket::arb_id`] layout, so the `MESSAGE_ARB_ID_*` flag bits can be matched too.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct RdxUsbIdMaskFilter {
+    pub id: u32,
+    pub mask: u32,
+}
+
+impl RdxUsbIdMaskFilter {
+    /// Does a frame with `arb_id` pass this filter?
+    pub const fn matches(&self, arb_id: u32) -> bool {
+        (arb_id & self.mask) == (self.id & self.mask)
+    }
 }
 
 /// USB-Full Speed protocol version
-pub const PROTOCOL_VERSION_MAJOR_FS: u16 = 1;
\ No newline at end of file
+pub const PROTOCOL_VERSION_MAJOR_FS: u16 = 1;
+/// USB-High Speed protocol version.
+///
+/// High speed devices exchange full [`RdxUsbPacket`]s, packing as many as fit into each 512-byte bulk transfer.
+pub const PROTOCOL_VERSION_MAJOR_HS: u16 = 2;
+/// High speed devices reporting at least this minor version start every bulk IN transfer with an [`RdxUsbHsTransferHeader`].
+pub const PROTOCOL_VERSION_MINOR_HS_FRAMED: u16 = 1;
+
+/// Header in front of the packets of a framed high speed bulk IN transfer, see [`PROTOCOL_VERSION_MINOR_HS_FRAMED`].
+///
+/// A transfer whose header doesn't check out is dropped as a whole; since every transfer starts with its own header,
+/// the next one is parsed from scratch and packet alignment is never lost.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct RdxUsbHsTransferHeader {
+    /// Always [`Self::MAGIC`].
+    pub magic: u16,
+    /// Number of [`RdxUsbPacket`]s following the header.
+    pub count: u8,
+    /// Incremented (wrapping) on every transfer, so the host can tell when transfers were lost.
+    pub seq: u8,
+    /// [`crc32`] of the packets following the header.
+    pub crc: u32,
+}
+
+impl RdxUsbHsTransferHeader {
+    pub const MAGIC: u16 = 0x5852;
+    /// Should always be 8.
+    pub const SIZE: usize = core::mem::size_of::<Self>();
+    /// Most packets that fit behind the header in one 512-byte transfer.
+    pub const MAX_PACKETS: usize = (512 - Self::SIZE) / RdxUsbPacket::SIZE;
+
+    /// Builds the header for a transfer carrying `packets`.
+    pub fn new(seq: u8, packets: &[RdxUsbPacket]) -> Self {
+        let data: &[u8] = bytemuck::cast_slice(packets);
+        Self { magic: Self::MAGIC, count: packets.len() as u8, seq, crc: crc32(data) }
+    }
+
+    /// Splits a received transfer into its header and packets, or `None` if the header or checksum don't match.
+    pub fn parse(transfer: &[u8]) -> Option<(Self, &[RdxUsbPacket])> {
+        let header: Self = *bytemuck::try_from_bytes(transfer.get(..Self::SIZE)?).ok()?;
+        let (magic, count, crc) = (header.magic, header.count as usize, header.crc);
+        if magic != Self::MAGIC || count > Self::MAX_PACKETS { return None; }
+        let data = transfer.get(Self::SIZE..Self::SIZE + count * RdxUsbPacket::SIZE)?;
+        if crc32(data) != crc { return None; }
+        Some((header, bytemuck::try_cast_slice(data).ok()?))
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE 802.3, as used by zlib) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc = CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(arb_id: u32, fill: u8) -> RdxUsbPacket {
+        RdxUsbPacket { timestamp_ns: arb_id as u64 * 1000, arb_id, dlc: 8, channel: 0, flags: 0, data: [fill; 64] }
+    }
+
+    /// Writes the header and packets into `buf` the way a device would, returning the transfer length.
+    fn encode(buf: &mut [u8], seq: u8, packets: &[RdxUsbPacket]) -> usize {
+        let header = RdxUsbHsTransferHeader::new(seq, packets);
+        let data: &[u8] = bytemuck::cast_slice(packets);
+        buf[..RdxUsbHsTransferHeader::SIZE].copy_from_slice(bytemuck::bytes_of(&header));
+        buf[RdxUsbHsTransferHeader::SIZE..RdxUsbHsTransferHeader::SIZE + data.len()].copy_from_slice(data);
+        RdxUsbHsTransferHeader::SIZE + data.len()
+    }
+
+    #[test]
+    fn crc32_known_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"a"), 0xe8b7_be43);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414f_a339);
+        assert_eq!(crc32(&[0u8; 32]), 0x190a_55ad);
+    }
+
+    #[test]
+    fn hs_transfer_round_trip() {
+        let packets = [packet(0x123, 0xaa), packet(0x8000_0456, 0x55), packet(0x7ff, 0)];
+        let mut buf = [0u8; 512];
+        let len = encode(&mut buf, 42, &packets);
+
+        let (header, parsed) = RdxUsbHsTransferHeader::parse(&buf[..len]).unwrap();
+        let (magic, count, seq) = (header.magic, header.count, header.seq);
+        assert_eq!((magic, count, seq), (RdxUsbHsTransferHeader::MAGIC, 3, 42));
+        assert_eq!(parsed, &packets);
+
+        // trailing bytes past the counted packets are ignored
+        assert_eq!(RdxUsbHsTransferHeader::parse(&buf).unwrap().1, &packets);
+
+        let full = [packet(1, 1); RdxUsbHsTransferHeader::MAX_PACKETS];
+        let len = encode(&mut buf, 0, &full);
+        assert_eq!(RdxUsbHsTransferHeader::parse(&buf[..len]).unwrap().1, &full);
+
+        let len = encode(&mut buf, 7, &[]);
+        assert_eq!(len, RdxUsbHsTransferHeader::SIZE);
+        assert!(RdxUsbHsTransferHeader::parse(&buf[..len]).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn hs_transfer_rejects_corruption() {
+        let packets = [packet(0x123, 0xaa), packet(0x456, 0x55)];
+        let mut buf = [0u8; 512];
+        let len = encode(&mut buf, 1, &packets);
+
+        let mut flipped = buf;
+        flipped[RdxUsbHsTransferHeader::SIZE + 20] ^= 0x01;
+        assert!(RdxUsbHsTransferHeader::parse(&flipped[..len]).is_none());
+
+        let mut bad_magic = buf;
+        bad_magic[0] ^= 0xff;
+        assert!(RdxUsbHsTransferHeader::parse(&bad_magic[..len]).is_none());
+
+        let mut bad_count = buf;
+        bad_count[2] = RdxUsbHsTransferHeader::MAX_PACKETS as u8 + 1;
+        assert!(RdxUsbHsTransferHeader::parse(&bad_count).is_none());
+
+        assert!(RdxUsbHsTransferHeader::parse(&buf[..len - 1]).is_none());
+        assert!(RdxUsbHsTransferHeader::parse(&buf[..RdxUsbHsTransferHeader::SIZE - 1]).is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use nusb::DeviceInfo;
+use rdxusb_protocol::{RdxUsbDeviceInfo, RdxUsbFsPacket, RdxUsbIdMaskFilter};
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+use crate::host::{RdxUsbFsChannel, RdxUsbFsHost, RdxUsbHostError, RdxUsbHostResult, RdxUsbPollExit, RdxUsbPollReport, RdxUsbTimeouts};
+
+/// Bulk IN transfers the background poll task keeps in flight.
+const N_TRANSFERS: usize = 32;
+
+/// A full speed device opened without async code.
+///
+/// The host runs its poll loop on an internal single-worker runtime, which is shared with the device's
+/// [`RdxUsbBlockingFsChannel`]s and shut down once the host and all of its channels are dropped.
+pub struct RdxUsbBlockingFsHost {
+    rt: Arc<Runtime>,
+    poll_task: JoinHandle<RdxUsbPollReport>,
+    device_info: RdxUsbDeviceInfo,
+}
+
+impl RdxUsbBlockingFsHost {
+    /// Opens the device, blocking until it is ready. See [`RdxUsbFsHost::open_device`].
+    pub fn open_device(dev_info: DeviceInfo, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbBlockingFsChannel>)> {
+        Self::open_device_with_timeouts(dev_info, rx_q_size, RdxUsbTimeouts::default())
+    }
+
+    /// Opens the device like [`Self::open_device`], but with non-default timeouts.
+    pub fn open_device_with_timeouts(dev_info: DeviceInfo, rx_q_size: usize, timeouts: RdxUsbTimeouts) -> RdxUsbHostResult<(Self, Vec<RdxUsbBlockingFsChannel>)> {
+        let rt = Arc::new(tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?);
+        let (mut host, channels) = rt.block_on(RdxUsbFsHost::open_device_with_timeouts(dev_info, rx_q_size, timeouts))?;
+        let device_info = host.device_info();
+        let poll_task = crate::spawn_named(rt.handle(), "blocking-poller", async move { host.poll_with_report(N_TRANSFERS, false).await });
+        let channels = channels.into_iter().map(|inner| RdxUsbBlockingFsChannel { rt: rt.clone(), inner }).collect();
+        Ok((Self { rt, poll_task, device_info }, channels))
+    }
+
+    /// The configuration the device reported when it was opened.
+    pub fn device_info(&self) -> RdxUsbDeviceInfo {
+        self.device_info
+    }
+
+    /// Whether the poll loop is still receiving from the device. It stops once the device is disconnected.
+    pub fn is_running(&self) -> bool {
+        !self.poll_task.is_finished()
+    }
+
+    /// Blocks until the poll loop stops, returning why it did.
+    pub fn join(self) -> RdxUsbHostResult<()> {
+        Err(self.join_with_report().into_error())
+    }
+
+    /// Like [`Self::join`], but returns the poll loop's full [`RdxUsbPollReport`].
+    pub fn join_with_report(mut self) -> RdxUsbPollReport {
+        let rt = self.rt.clone();
+        rt.block_on(async move {
+            match (&mut self.poll_task).await {
+                Ok(report) => report,
+                Err(_) => RdxUsbPollReport { exit: RdxUsbPollExit::Disconnected, packets: 0, transfers: 0 },
+            }
+        })
+    }
+}
+
+impl Drop for RdxUsbBlockingFsHost {
+    fn drop(&mut self) {
+        self.poll_task.abort();
+    }
+}
+
+/// One channel of a [`RdxUsbBlockingFsHost`], with blocking counterparts of the [`RdxUsbFsChannel`] methods.
+pub struct RdxUsbBlockingFsChannel {
+    rt: Arc<Runtime>,
+    inner: RdxUsbFsChannel,
+}
+
+impl RdxUsbBlockingFsChannel {
+    /// Blocks until a packet is received.
+    ///
+    /// Fails with [`RdxUsbHostError::DeviceDisconnected`] once the host is gone and the queue is empty.
+    pub fn read(&mut self) -> RdxUsbHostResult<RdxUsbFsPacket> {
+        self.rt.block_on(self.inner.read())
+    }
+
+    /// Blocks until a packet is received or `timeout` elapses, in which case this fails with [`RdxUsbHostError::Timeout`].
+    pub fn read_timeout(&mut self, timeout: Duration) -> RdxUsbHostResult<RdxUsbFsPacket> {
+        self.rt.block_on(async {
+            tokio::time::timeout(timeout, self.inner.read()).await.map_err(|_| RdxUsbHostError::Timeout)?
+        })
+    }
+
+    pub fn try_read(&mut self) -> Option<RdxUsbFsPacket> {
+        self.inner.try_read()
+    }
+
+    /// Sends a packet on this channel, blocking until the transfer completes.
+    pub fn write(&mut self, pkt: RdxUsbFsPacket) -> RdxUsbHostResult<()> {
+        self.rt.block_on(self.inner.write(pkt))
+    }
+
+    /// Sets the channel's host-side acceptance filters, see [`RdxUsbFsChannel::set_filters`].
+    pub fn set_filters(&self, filters: &[RdxUsbIdMaskFilter]) {
+        self.inner.set_filters(filters);
+    }
+
+    /// The underlying async channel, for anything not wrapped here.
+    pub fn get_mut(&mut self) -> &mut RdxUsbFsChannel {
+        &mut self.inner
+    }
+}
+
+/// Blocks for each received packet, ending once the host is dropped.
+impl Iterator for RdxUsbBlockingFsChannel {
+    type Item = RdxUsbFsPacket;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().ok()
+    }
+}
@@ -1,8 +1,88 @@
-use std::{collections::HashMap, ffi::{c_char, CStr, CString}, sync::{Mutex, OnceLock}};
+use std::{collections::HashMap, ffi::{c_char, c_void, CStr, CString}, fmt::Debug, sync::{atomic::{AtomicBool, AtomicU32, Ordering}, Mutex, OnceLock}, time::{Duration, Instant}};
 
-use rdxusb_protocol::RdxUsbPacket;
+use rdxusb_protocol::{RdxUsbPacket, RdxUsbPacketEx};
 
-use crate::event_loop::{self, EventLoopError};
+use crate::{event_loop::{self, EventLoopError, IdleAction, IdlePolicy}, host::{RdxUsbBridgeRule, RdxUsbDescriptorReader, RdxUsbTimeoutProfile, RdxUsbTimeouts}};
+
+/// Version of the C ABI exposed by this library.
+///
+/// This is bumped whenever an existing exported function or struct changes incompatibly.
+/// Adding new functions does not change it.
+pub const RDXUSB_ABI_VERSION: u32 = 1;
+
+/// Bits reported by rdxusb_get_build_features, one per optional cargo feature.
+/// Bits 32 and up are `unstable-*` features; without `unstable-hs`, high speed devices fail to open.
+pub const RDXUSB_FEATURE_ZSTD: u64 = 1 << 0;
+pub const RDXUSB_FEATURE_LZ4: u64 = 1 << 1;
+pub const RDXUSB_FEATURE_WEBSOCKET: u64 = 1 << 2;
+pub const RDXUSB_FEATURE_BLOCKING: u64 = 1 << 3;
+pub const RDXUSB_FEATURE_UDP_MIRROR: u64 = 1 << 4;
+pub const RDXUSB_FEATURE_TOKIO_CONSOLE: u64 = 1 << 5;
+pub const RDXUSB_FEATURE_DAEMON: u64 = 1 << 6;
+pub const RDXUSB_FEATURE_MQTT: u64 = 1 << 7;
+pub const RDXUSB_FEATURE_INTEGRITY: u64 = 1 << 8;
+pub const RDXUSB_FEATURE_UNSTABLE_HS: u64 = 1 << 32;
+
+/// The RDXUSB_FEATURE_* bits of every feature this build was compiled with.
+const BUILD_FEATURES: u64 = (cfg!(feature = "zstd") as u64 * RDXUSB_FEATURE_ZSTD)
+    | (cfg!(feature = "lz4") as u64 * RDXUSB_FEATURE_LZ4)
+    | (cfg!(feature = "websocket") as u64 * RDXUSB_FEATURE_WEBSOCKET)
+    | (cfg!(feature = "blocking") as u64 * RDXUSB_FEATURE_BLOCKING)
+    | (cfg!(feature = "udp-mirror") as u64 * RDXUSB_FEATURE_UDP_MIRROR)
+    | (cfg!(feature = "tokio-console") as u64 * RDXUSB_FEATURE_TOKIO_CONSOLE)
+    | (cfg!(all(feature = "daemon", unix)) as u64 * RDXUSB_FEATURE_DAEMON)
+    | (cfg!(feature = "mqtt") as u64 * RDXUSB_FEATURE_MQTT)
+    | (cfg!(feature = "integrity") as u64 * RDXUSB_FEATURE_INTEGRITY)
+    | (cfg!(feature = "unstable-hs") as u64 * RDXUSB_FEATURE_UNSTABLE_HS);
+
+static AUDIT_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Runs the body of a C API call, logging the call to the `rdxusb::audit` target if audit mode is on.
+///
+/// `args` is only evaluated when audit mode is on. A panic in `f` never unwinds into the caller; it is logged and
+/// the call returns [`FfiReturn::panicked`] instead. Builds with `panic = "abort"` abort the process instead,
+/// which is just as safe for the host runtime.
+fn audit<R: Debug + FfiReturn>(name: &str, args: impl FnOnce() -> String, f: impl FnOnce() -> R) -> R {
+    let audit_mode = AUDIT_MODE.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let ret = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        log::error!(target: "rdxusb", "{name} panicked: {}", event_loop::panic_message(panic.as_ref()));
+        R::panicked()
+    });
+    if audit_mode {
+        log::info!(target: "rdxusb::audit", "{name}({}) -> {ret:?} [{:?}]", args(), start.elapsed());
+    }
+    ret
+}
+
+/// [`audit`] for calls that aren't forwarded to a daemon, which fail with RDXUSB_ERR_REQUEST_UNSUPPORTED while
+/// this process is a daemon client rather than acting on handle ids the local event loop never issued.
+fn audit_local(name: &str, args: impl FnOnce() -> String, f: impl FnOnce() -> i32) -> i32 {
+    audit(name, args, || forward::local_only().map_or_else(|e| e as i32, |_| f()))
+}
+
+/// Return types of C API calls, with the value returned when a call panics.
+trait FfiReturn {
+    fn panicked() -> Self;
+}
+
+impl FfiReturn for i32 {
+    fn panicked() -> Self {
+        EventLoopError::ERR_PANICKED
+    }
+}
+
+impl FfiReturn for u32 {
+    fn panicked() -> Self {
+        0
+    }
+}
+
+impl FfiReturn for bool {
+    fn panicked() -> Self {
+        false
+    }
+}
 
 fn to_optional_string(cs: *const c_char) -> Option<String> {
     if cs == core::ptr::null() {
@@ -11,91 +91,1110 @@ fn to_optional_string(cs: *const c_char) -> Option<String> {
         unsafe { Some(CStr::from_ptr(cs).to_string_lossy().to_string()) } 
     }
 }
- 
-/// Directs rdxusb to open an RdxUsb-compatible device with the associated vid/pid/serial number tuple.
+ 
+/// Configuration passed to rdxusb_init.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RdxUsbConfig {
+    /// Number of worker threads the event loop uses. Zero picks one per core.
+    pub worker_threads: u32,
+    /// Whether to watch for USB hotplug events to reconnect devices automatically.
+    pub hotplug: bool,
+    /// Maximum time a single control transfer may take, in milliseconds. Zero uses the default.
+    pub control_timeout_ms: u32,
+    /// Maximum time opening a device may take, in milliseconds. Zero uses the default.
+    pub open_timeout_ms: u32,
+    /// What to do with received packets carrying flag bits this version doesn't know.
+    /// One of the RDXUSB_UNKNOWN_FLAGS_* values; anything else is treated as RDXUSB_UNKNOWN_FLAGS_IGNORE.
+    pub unknown_flag_policy: u8,
+    /// Run no background threads, and only process USB traffic inside rdxusb_pump calls.
+    pub manual_pump: bool,
+}
+
+impl From<RdxUsbConfig> for event_loop::EventLoopConfig {
+    fn from(value: RdxUsbConfig) -> Self {
+        let mut timeouts = RdxUsbTimeouts::default();
+        if value.control_timeout_ms > 0 {
+            timeouts.control = Duration::from_millis(value.control_timeout_ms as u64);
+        }
+        if value.open_timeout_ms > 0 {
+            timeouts.open = Duration::from_millis(value.open_timeout_ms as u64);
+        }
+        Self {
+            worker_threads: value.worker_threads as usize,
+            hotplug: value.hotplug,
+            timeouts,
+            unknown_flag_policy: value.unknown_flag_policy.try_into().unwrap_or_default(),
+            manual_pump: value.manual_pump,
+            ..Self::default()
+        }
+    }
+}
+
+/// Explicitly initializes rdxusb.
+///
+/// Calling this is optional; without it, rdxusb initializes itself with defaults on first use.
+/// Hosts that load and unload the library repeatedly should pair this with rdxusb_finalize.
+///
+/// * **config** - pointer to the configuration to use. If NULL, defaults are used.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_init(config: *const RdxUsbConfig) -> i32 {
+    audit("rdxusb_init", || format!("config={config:?}"), || {
+        let config = match unsafe { config.as_ref() } {
+            Some(c) => (*c).into(),
+            None => event_loop::EventLoopConfig::default(),
+        };
+        event_loop::init(config).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Processes USB traffic on the calling thread for the given duration.
+///
+/// Only valid when rdxusb was initialized with `manual_pump` set, in which case nothing is sent or received
+/// outside of this call. Call it periodically from the application's own loop.
+///
+/// * **max_duration_us** - how long to process for, in microseconds. 0 runs everything that is ready once.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_pump(max_duration_us: u64) -> i32 {
+    audit("rdxusb_pump", || format!("max_duration_us={max_duration_us}"), || {
+        event_loop::pump(Duration::from_micros(max_duration_us)).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Closes all devices and frees every resource held by rdxusb, joining its background threads.
+///
+/// All device handles and iterators are invalidated. rdxusb may be initialized again afterwards.
+/// Hosts that unload the library (e.g. JVM embedders) must call this first, as rdxusb's threads would otherwise
+/// keep running code from the unloaded library.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_finalize() -> i32 {
+    audit("rdxusb_finalize", String::new, || {
+        // devices and threads go first, then the iterators, which hold no resources besides memory.
+        #[cfg(all(feature = "daemon", unix))]
+        if let Ok(mut server) = DAEMON_SERVER.lock() { server.take(); }
+        let result = event_loop::finalize();
+        let mut info_lock = DEVICE_INFOS.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        DEVICE_INFOS.clear_poison();
+        info_lock.take();
+        result.map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Enables or disables audit mode.
+///
+/// While audit mode is on, every rdxusb C API call is logged with its arguments, return code, and duration
+/// under the `rdxusb::audit` log target.
+///
+/// * **enabled** - whether audit mode should be on
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_audit_mode(enabled: bool) -> i32 {
+    AUDIT_MODE.store(enabled, Ordering::Relaxed);
+    audit("rdxusb_set_audit_mode", || format!("enabled={enabled}"), || 0)
+}
+
+/// Gets the library version.
+///
+/// * **major** - pointer the major version is written to. Must not be NULL.
+/// * **minor** - pointer the minor version is written to. Must not be NULL.
+/// * **patch** - pointer the patch version is written to. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_get_version(major: *mut u32, minor: *mut u32, patch: *mut u32) -> i32 {
+    audit("rdxusb_get_version", || format!("major={major:?}, minor={minor:?}, patch={patch:?}"), || {
+        if major.is_null() || minor.is_null() || patch.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        unsafe {
+            *major = env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0);
+            *minor = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0);
+            *patch = env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0);
+        }
+        0
+    })
+}
+
+/// Gets the C ABI version of the library.
+///
+/// Consumers that load rdxusb dynamically should compare this against the RDXUSB_ABI_VERSION
+/// they were built with before calling anything else.
+///
+/// Returns the ABI version
+#[no_mangle]
+pub extern "C" fn rdxusb_get_abi_version() -> u32 {
+    audit("rdxusb_get_abi_version", String::new, || RDXUSB_ABI_VERSION)
+}
+
+/// Gets the optional features the library was compiled with, so bindings can tell which subsystems
+/// the loaded library has.
+///
+/// * **features** - pointer updated with a bitmask of RDXUSB_FEATURE_* values. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_get_build_features(features: *mut u64) -> i32 {
+    audit("rdxusb_get_build_features", || format!("features={features:?}"), || {
+        if features.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        unsafe { *features = BUILD_FEATURES; }
+        0
+    })
+}
+
+/// Directs rdxusb to open an RdxUsb-compatible device with the associated vid/pid/serial number tuple.
+///
+/// rdxusb will spawn an event loop that will continually attempt to open a matching device and
+/// send/receive messages from it. If connection with the matching device is lost, reconnection is 
+/// continually attempted.
+///
+/// * **vid** - USB vendor ID to match
+/// * **pid** - USB product ID to match
+/// * **serial_number** - an optional serial number string. This MUST be UTF-8 or NULL.
+/// * **close_on_dc** - if true, closes the device handle on device disconnect
+/// * **buf_size** - the maximum number of packets to buffer inbound/outbound, at most 65536
+/// 
+/// Returns a non-negative device handle on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_open_device(vid: u16, pid: u16, serial_number: *const c_char, close_on_dc: bool, buf_size: u64) -> i32 {
+    audit("rdxusb_open_device", || format!("vid={vid:#06x}, pid={pid:#06x}, serial_number={serial_number:?}, close_on_dc={close_on_dc}, buf_size={buf_size}"), || {
+        let serial_number = to_optional_string(serial_number);
+        let handle = forward::open_device(vid, pid, serial_number, close_on_dc, buf_size as usize).unwrap_or_else(|e| e as i32);
+        if handle >= 0 { warn_on_handle_leaks(); }
+        handle
+    })
+}
+
+/// Directs rdxusb to open exactly the device at an index in an iterator, such as the row a user picked from a listing.
+///
+/// Devices with a serial number are matched by their vid/pid/serial number tuple from then on, as with
+/// rdxusb_open_device. Devices without one are matched only while they stay attached, so their handle
+/// does not reconnect after the device is replugged.
+///
+/// * **iter_id** - iterator handle to open from
+/// * **device_idx** - index of the device to open. Must be 0 <= device_idx < n_devices.
+/// * **close_on_dc** - if true, closes the device handle on device disconnect
+/// * **buf_size** - the maximum number of packets to buffer inbound/outbound, at most 65536
+///
+/// Returns a non-negative device handle on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_open_device_from_iterator(iter_id: u64, device_idx: u64, close_on_dc: bool, buf_size: u64) -> i32 {
+    audit_local("rdxusb_open_device_from_iterator", || format!("iter_id={iter_id}, device_idx={device_idx}, close_on_dc={close_on_dc}, buf_size={buf_size}"), || {
+        let info = {
+            let Ok(info_lock) = DEVICE_INFOS.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
+            let Some(device_infos) = info_lock.get().and_then(|infos| infos.info_map.get(&iter_id)) else { return EventLoopError::ERR_DEVICE_ITER_INVALID; };
+            let Some(info) = device_infos.get(device_idx as usize) else { return EventLoopError::ERR_DEVICE_ITER_IDX_OUT_OF_RANGE; };
+            info.clone()
+        };
+        let handle = event_loop::open_device_info(&info, close_on_dc, buf_size as usize).unwrap_or_else(|e| e as i32);
+        if handle >= 0 { warn_on_handle_leaks(); }
+        handle
+    })
+}
+
+/// Directs rdxusb to open the first attached Redux device it recognizes.
+///
+/// The device is matched by its vid/pid/serial number tuple from then on, exactly as if it had been
+/// passed to rdxusb_open_device.
+///
+/// * **close_on_dc** - if true, closes the device handle on device disconnect
+/// * **buf_size** - the maximum number of packets to buffer inbound/outbound, at most 65536
+///
+/// Returns a non-negative device handle on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_open_first_redux_device(close_on_dc: bool, buf_size: u64) -> i32 {
+    audit("rdxusb_open_first_redux_device", || format!("close_on_dc={close_on_dc}, buf_size={buf_size}"), || {
+        let handle = forward::open_first_redux_device(close_on_dc, buf_size as usize).unwrap_or_else(|e| e as i32);
+        if handle >= 0 { warn_on_handle_leaks(); }
+        handle
+    })
+}
+
+/// Checks whether a vid/pid pair belongs to a known Redux device.
+///
+/// * **vid** - USB vendor ID
+/// * **pid** - USB product ID
+///
+/// Returns true if the device is a known Redux device
+#[no_mangle]
+pub extern "C" fn rdxusb_is_redux_device(vid: u16, pid: u16) -> bool {
+    audit("rdxusb_is_redux_device", || format!("vid={vid:#06x}, pid={pid:#06x}"), || crate::vendor::is_redux_device(vid, pid))
+}
+
+/// Forces the RdxUsb event loop to rescan USB devices.
+/// 
+/// By default, the RdxUsb event loop will automatically reconnect devices via hotplug, 
+/// but if hotplug does not work, manually calling this function will rescan and potentially reconnect devices.
+/// 
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_force_scan_devices() -> i32 {
+    audit("rdxusb_force_scan_devices", String::new, || {
+        let Ok(event_loop) = event_loop::try_acquire_event_loop() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
+        match event_loop::force_scan_devices(event_loop) {
+            Ok(_) => 0,
+            Err(e) => e as i32,
+        }
+    })
+}
+
+/// Reads packets into the specified buffer.
+///
+/// Reading a channel from several threads at once splits its packets between them unpredictably.
+/// With rdxusb_set_exclusive_readers turned on, the first thread to read a channel becomes its only reader, and
+/// reads from other threads fail with RDXUSB_ERR_CHANNEL_CLAIMED until rdxusb_release_reader is called.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **channel** - the USB channel to read from.
+///                 The number of channels a device has is device dependent, but for now just pass in 0.
+/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
+/// * **max_packets** - the maximum number of packets to read into the packet buffer.
+/// * **packets_read** - pointer updated with how many packets were actually read. Must not be NULL.
+/// 
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_read_packets(handle_id: i32, channel: u8, packets: *mut RdxUsbPacket, max_packets: u64, packets_read: *mut u64) -> i32 {
+    audit("rdxusb_read_packets", || format!("handle_id={handle_id}, channel={channel}, packets={packets:?}, max_packets={max_packets}, packets_read={packets_read:?}"), || {
+        if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
+        match forward::read_packets(handle_id, channel, packets) {
+            Ok(w) => {
+                unsafe { *packets_read = w as u64; }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Reads packets from every channel into the specified buffer, merged in device timestamp order.
+///
+/// Packets come out in non-decreasing timestamp order as long as the device sends them in order;
+/// see rdxusb_set_reorder_depth for devices that don't. Notifications are not included.
+///
+/// With rdxusb_set_exclusive_readers turned on, the first thread to call this becomes the device's only reader,
+/// and it fails with RDXUSB_ERR_CHANNEL_CLAIMED if another thread reads any of the channels, until
+/// rdxusb_release_any_reader is called.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
+/// * **max_packets** - the maximum number of packets to read into the packet buffer.
+/// * **packets_read** - pointer updated with how many packets were actually read. Must not be NULL.
+/// 
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_read_packets_any(handle_id: i32, packets: *mut RdxUsbPacket, max_packets: u64, packets_read: *mut u64) -> i32 {
+    audit("rdxusb_read_packets_any", || format!("handle_id={handle_id}, packets={packets:?}, max_packets={max_packets}, packets_read={packets_read:?}"), || {
+        if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
+        match forward::read_packets_any(handle_id, packets) {
+            Ok(w) => {
+                unsafe { *packets_read = w as u64; }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Like rdxusb_read_packets, but reads packets along with metadata added by the host.
+///
+/// Both share the channel's queue and reader claim, so mixing them on one channel is fine.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **channel** - the USB channel to read from.
+/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
+/// * **max_packets** - the maximum number of packets to read into the packet buffer.
+/// * **packets_read** - pointer updated with how many packets were actually read. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_read_packets_ex(handle_id: i32, channel: u8, packets: *mut RdxUsbPacketEx, max_packets: u64, packets_read: *mut u64) -> i32 {
+    audit_local("rdxusb_read_packets_ex", || format!("handle_id={handle_id}, channel={channel}, packets={packets:?}, max_packets={max_packets}, packets_read={packets_read:?}"), || {
+        if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
+        match event_loop::read_packets_ex(handle_id, channel, packets) {
+            Ok(w) => {
+                unsafe { *packets_read = w as u64; }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Like rdxusb_read_packets_any, but reads packets along with metadata added by the host.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
+/// * **max_packets** - the maximum number of packets to read into the packet buffer.
+/// * **packets_read** - pointer updated with how many packets were actually read. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_read_packets_any_ex(handle_id: i32, packets: *mut RdxUsbPacketEx, max_packets: u64, packets_read: *mut u64) -> i32 {
+    audit_local("rdxusb_read_packets_any_ex", || format!("handle_id={handle_id}, packets={packets:?}, max_packets={max_packets}, packets_read={packets_read:?}"), || {
+        if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
+        match event_loop::read_packets_any_ex(handle_id, packets) {
+            Ok(w) => {
+                unsafe { *packets_read = w as u64; }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Restricts each channel of a handle to a single reader thread.
+///
+/// Off by default. When on, the first thread to read a channel owns it and reads from other threads fail with
+/// RDXUSB_ERR_CHANNEL_CLAIMED, so concurrent callers can't silently split a channel's packets. Leave it off if
+/// reads come from a thread pool or JNI threads that aren't pinned. Turning it off drops every claim.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **exclusive** - true to enforce a single reader per channel
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_exclusive_readers(handle_id: i32, exclusive: bool) -> i32 {
+    audit_local("rdxusb_set_exclusive_readers", || format!("handle_id={handle_id}, exclusive={exclusive}"), || {
+        event_loop::set_exclusive_readers(handle_id, exclusive).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Releases the calling thread's claim on reading a channel, so another thread can read it.
+///
+/// Any thread may release a claim, including ones held by threads that have exited.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **channel** - the channel passed to rdxusb_read_packets
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_release_reader(handle_id: i32, channel: u8) -> i32 {
+    audit_local("rdxusb_release_reader", || format!("handle_id={handle_id}, channel={channel}"), || {
+        event_loop::release_reader(handle_id, Some(channel)).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Releases the claim on rdxusb_read_packets_any, so another thread can read the device.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_release_any_reader(handle_id: i32) -> i32 {
+    audit_local("rdxusb_release_any_reader", || format!("handle_id={handle_id}"), || {
+        event_loop::release_reader(handle_id, None).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Reads notifications sent by the device itself into the specified buffer.
+///
+/// Notifications carry device events such as fault codes, reset notices, or over-temperature warnings,
+/// and are kept separate from regular channel traffic. Their arbitration ids and payloads are device-specific.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
+/// * **max_packets** - the maximum number of notifications to read into the packet buffer.
+/// * **packets_read** - pointer updated with how many notifications were actually read. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_read_notifications(handle_id: i32, packets: *mut RdxUsbPacket, max_packets: u64, packets_read: *mut u64) -> i32 {
+    audit_local("rdxusb_read_notifications", || format!("handle_id={handle_id}, packets={packets:?}, max_packets={max_packets}, packets_read={packets_read:?}"), || {
+        if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
+        match event_loop::read_notifications(handle_id, packets) {
+            Ok(w) => {
+                unsafe { *packets_read = w as u64; }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Reads error frames reported by the device's CAN controllers into the specified buffer.
+///
+/// Error frames have RDXUSB_MESSAGE_FLAG_ERR set, and the RDXUSB_ERROR_* bits in their arbitration id describe
+/// the event, such as bus-off, error passive, or stuff errors. They are kept out of rdxusb_read_packets.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
+/// * **max_packets** - the maximum number of error frames to read into the packet buffer.
+/// * **packets_read** - pointer updated with how many error frames were actually read. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_read_error_frames(handle_id: i32, packets: *mut RdxUsbPacket, max_packets: u64, packets_read: *mut u64) -> i32 {
+    audit_local("rdxusb_read_error_frames", || format!("handle_id={handle_id}, packets={packets:?}, max_packets={max_packets}, packets_read={packets_read:?}"), || {
+        if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
+        match event_loop::read_error_frames(handle_id, packets) {
+            Ok(w) => {
+                unsafe { *packets_read = w as u64; }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Reads echoes of frames sent on a channel with RDXUSB_MESSAGE_FLAG_ECHO set.
+///
+/// Echoes are timestamped with when the device actually transmitted the frame, which measures bus latency
+/// and confirms the frame won arbitration. They are kept out of rdxusb_read_packets.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **channel** - the channel the frames were sent on
+/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
+/// * **max_packets** - the maximum number of echoes to read into the packet buffer.
+/// * **packets_read** - pointer updated with how many echoes were actually read. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_read_echoes(handle_id: i32, channel: u8, packets: *mut RdxUsbPacket, max_packets: u64, packets_read: *mut u64) -> i32 {
+    audit_local("rdxusb_read_echoes", || format!("handle_id={handle_id}, channel={channel}, packets={packets:?}, max_packets={max_packets}, packets_read={packets_read:?}"), || {
+        if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
+        match event_loop::read_echoes(handle_id, channel, packets) {
+            Ok(w) => {
+                unsafe { *packets_read = w as u64; }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Writes packets from the specified buffer.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **packets** - a pointer to the packet buffer to write from. Must not be NULL.
+/// * **packets_len** - the number of packets to write from the packet buffer.
+/// * **packets_written** - pointer updated with how many packets were actually written. Can be NULL.
+/// 
+/// Writing stops at the first packet the device cannot carry, which is reported as
+/// `ERR_INVALID_PACKET` once it is the first packet passed in.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_write_packets(handle_id: i32, packets: *const RdxUsbPacket, packets_len: u64, packets_written: *mut u64) -> i32 {
+    audit("rdxusb_write_packets", || format!("handle_id={handle_id}, packets={packets:?}, packets_len={packets_len}, packets_written={packets_written:?}"), || {
+        if packets.is_null() { return EventLoopError::ERR_NULL_PTR; }
+
+        let packets = unsafe { core::slice::from_raw_parts(packets, packets_len as usize) };
+        match forward::write_packets(handle_id, packets) {
+            Ok(w) => {
+                unsafe { 
+                    match packets_written.as_mut() {
+                        Some(p) => *p = w as u64,
+                        None => {}
+                    }
+                }
+                0
+            }
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Sends a device-addressed command until the device echoes it back, for configuration frames that must not be lost.
+///
+/// The frame is sent with RDXUSB_MESSAGE_FLAG_ECHO and a sequence number in RDXUSB_MESSAGE_FLAG_SEQ_MASK, and sent
+/// again with the same sequence number each time no echo arrives within the timeout. Devices apply a retried frame
+/// only once, but firmware predating sequence numbers applies every copy, so only send idempotent commands this way.
+///
+/// This blocks until the echo arrives. Other echoes read from the packet's channel in the meantime are discarded.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **packet** - the frame to send. Its arbitration id must have RDXUSB_ARB_ID_FLAG_DEVICE set. Must not be NULL.
+/// * **retries** - how many times to resend the frame before giving up
+/// * **timeout_ms** - how long to wait for the echo after each attempt, in milliseconds
+/// * **echo** - pointer updated with the echo. Can be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_TIMEOUT if every attempt went unanswered, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_write_reliable(handle_id: i32, packet: *const RdxUsbPacket, retries: u32, timeout_ms: u32, echo: *mut RdxUsbPacket) -> i32 {
+    audit_local("rdxusb_write_reliable", || format!("handle_id={handle_id}, packet={packet:?}, retries={retries}, timeout_ms={timeout_ms}, echo={echo:?}"), || {
+        let Some(packet) = (unsafe { packet.as_ref() }) else { return EventLoopError::ERR_NULL_PTR; };
+        match event_loop::write_reliable(handle_id, packet, retries, Duration::from_millis(timeout_ms as u64)) {
+            Ok(p) => {
+                if let Some(echo) = unsafe { echo.as_mut() } { *echo = p; }
+                0
+            }
+            Err(e) => e as i32,
+        }
+    })
+}
+
+/// Checks that a connected device is responsive, measuring the round trip time of a ping control request.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **timeout_ms** - how long to wait for the device to answer, in milliseconds
+/// * **rtt_us** - pointer updated with the round trip time in microseconds. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_TIMEOUT if the device didn't answer, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_ping(handle_id: i32, timeout_ms: u64, rtt_us: *mut u64) -> i32 {
+    audit_local("rdxusb_ping", || format!("handle_id={handle_id}, timeout_ms={timeout_ms}, rtt_us={rtt_us:?}"), || {
+        if rtt_us.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        match event_loop::ping(handle_id, Duration::from_millis(timeout_ms)) {
+            Ok(rtt) => {
+                unsafe { *rtt_us = rtt.as_micros() as u64; }
+                0
+            }
+            Err(e) => e as i32,
+        }
+    })
+}
+
+/// Sets the rx watchdog timeout of a device handle.
+///
+/// If a connected device completes no inbound transfers for this long, it is pinged (see rdxusb_ping).
+/// If the ping also goes unanswered for this long, the device is considered wedged:
+/// rdxusb resets it, the handle moves to RDXUSB_DEVICE_STATE_FAULTED, and reconnection is attempted.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **timeout_ms** - the watchdog timeout in milliseconds, or 0 to disable the watchdog (the default)
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_rx_watchdog(handle_id: i32, timeout_ms: u64) -> i32 {
+    audit_local("rdxusb_set_rx_watchdog", || format!("handle_id={handle_id}, timeout_ms={timeout_ms}"), || {
+        event_loop::set_rx_watchdog(handle_id, timeout_ms).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Sets the transfer watchdog timeout of a device handle.
+///
+/// If no inbound transfer completes for this long, the transfers in flight are cancelled and resubmitted, which
+/// recovers devices whose IN pipe silently wedges without resetting them. Devices on a quiet bus complete no
+/// transfers either, so pick a timeout well above their longest gap between frames. Trips are counted under
+/// "connection" in rdxusb_describe_device. Applies from the next time the device connects.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **timeout_ms** - the watchdog timeout in milliseconds, or 0 to disable the watchdog (the default)
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_transfer_watchdog(handle_id: i32, timeout_ms: u64) -> i32 {
+    audit_local("rdxusb_set_transfer_watchdog", || format!("handle_id={handle_id}, timeout_ms={timeout_ms}"), || {
+        event_loop::set_transfer_watchdog(handle_id, timeout_ms).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Limits how many received packets per second rdxusb processes for a device handle.
+///
+/// Once the limit is reached, the handle's poller sleeps until it may continue, so a device flooding the bus
+/// can't take a whole core away from the robot program. Packets beyond the limit are dropped by the device.
+/// The limit carries over across reconnects, and the number of times it was hit is reported as `rx_throttled`
+/// by rdxusb_describe_device.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **max_packets_per_sec** - the limit in packets per second, or 0 for no limit (the default)
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_max_rx_rate(handle_id: i32, max_packets_per_sec: u32) -> i32 {
+    audit_local("rdxusb_set_max_rx_rate", || format!("handle_id={handle_id}, max_packets_per_sec={max_packets_per_sec}"), || {
+        event_loop::set_max_rx_rate(handle_id, max_packets_per_sec).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Turns on smoothing of a device handle's timestamps, for devices whose clocks jitter.
+///
+/// The handle's poller tracks the device clock with a PLL, and rdxusb_read_packets_ex fills in
+/// `smoothed_timestamp_ns`: the packet's timestamp on the host wall clock, without the jitter of the device clock
+/// and of USB delays. Lower bandwidths filter out more jitter but follow changes in the device clock's rate more
+/// slowly; 0.1 to 1 Hz suits most devices. The setting carries over across reconnects.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **bandwidth_hz** - the filter's loop bandwidth in Hz, or 0 to turn smoothing off (the default)
+///
+/// Return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the bandwidth is negative or not finite, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_set_timestamp_smoothing(handle_id: i32, bandwidth_hz: f64) -> i32 {
+    audit_local("rdxusb_set_timestamp_smoothing", || format!("handle_id={handle_id}, bandwidth_hz={bandwidth_hz}"), || {
+        if !bandwidth_hz.is_finite() || bandwidth_hz < 0.0 { return EventLoopError::ERR_INVALID_ARGUMENT; }
+        event_loop::set_timestamp_smoothing(handle_id, bandwidth_hz).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Traffic of a device handle over one sampling interval, passed to the rdxusb_set_stats_callback callback.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RdxUsbStatsSnapshot {
+    /// Frames received from the bus per second, including ones dropped because their queue was full.
+    pub rx_frames_per_sec: f64,
+    /// Frames sent per second.
+    pub tx_frames_per_sec: f64,
+    /// Frames dropped during the interval because their channel's rx queue was full.
+    pub rx_drops: u64,
+    /// Received packets waiting to be read, across all channels.
+    pub rx_queue_depth: u64,
+    /// Estimated fraction of its bus the busiest channel's traffic occupies, at the bit rate set with rdxusb_set_stats_bit_rate.
+    pub bus_load: f64,
+    /// Whether the device was connected when sampled. Everything else is 0 if not.
+    pub connected: bool,
+}
+
+impl From<&event_loop::HandleStats> for RdxUsbStatsSnapshot {
+    fn from(value: &event_loop::HandleStats) -> Self {
+        Self {
+            rx_frames_per_sec: value.rx_frames_per_sec,
+            tx_frames_per_sec: value.tx_frames_per_sec,
+            rx_drops: value.rx_drops,
+            rx_queue_depth: value.rx_queue_depth as u64,
+            bus_load: value.bus_load,
+            connected: value.connected,
+        }
+    }
+}
+
+pub type RdxUsbStatsCallback = extern "C" fn(handle_id: i32, stats: *const RdxUsbStatsSnapshot, user_data: *mut c_void);
+
+/// The user data pointer handed back to a stats callback, which the caller vouches can be used from rdxusb's threads.
+struct CallbackUserData(*mut c_void);
+unsafe impl Send for CallbackUserData {}
+
+/// Calls a function with a device handle's traffic at a fixed interval, for dashboards showing live meters.
+///
+/// The callback runs on an rdxusb thread (or inside rdxusb_pump in manual pump mode) and must return quickly.
+/// It keeps being called while the device is disconnected, until the handle is closed or the callback is removed.
+///
+/// Once this returns after replacing or removing a callback, or once rdxusb_close_device or rdxusb_close_all_devices
+/// returns, the previous callback is not running and won't be called again, so its user_data may be freed. A callback
+/// may remove itself, but must not wait on another thread that is removing or closing it.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **interval_ms** - how often to call the callback, in milliseconds
+/// * **callback** - the function to call, or NULL to remove the handle's callback
+/// * **user_data** - passed through to the callback
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_stats_callback(handle_id: i32, interval_ms: u32, callback: Option<RdxUsbStatsCallback>, user_data: *mut c_void) -> i32 {
+    audit_local("rdxusb_set_stats_callback", || format!("handle_id={handle_id}, interval_ms={interval_ms}, callback={:?}, user_data={user_data:?}", callback.map(|f| f as *const c_void)), || {
+        let user_data = CallbackUserData(user_data);
+        let callback = callback.map(|callback| -> event_loop::StatsCallback {
+            Box::new(move |stats| {
+                let user_data = &user_data;
+                let snapshot = RdxUsbStatsSnapshot::from(stats);
+                callback(handle_id, &snapshot, user_data.0);
+            })
+        });
+        event_loop::set_stats_callback(handle_id, Duration::from_millis(interval_ms as u64), callback).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Sets the nominal bit rate of a device handle's buses, which the bus load in stats snapshots is estimated against.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **bit_rate** - the bit rate in bits per second (1000000 for FRC buses, the default), or 0 to report no bus load
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_stats_bit_rate(handle_id: i32, bit_rate: u32) -> i32 {
+    audit_local("rdxusb_set_stats_bit_rate", || format!("handle_id={handle_id}, bit_rate={bit_rate}"), || {
+        event_loop::set_stats_bit_rate(handle_id, bit_rate).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Frame rates and bus traffic of one channel over a recent window, filled in by rdxusb_get_channel_meter.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RdxUsbChannelMeterReading {
+    /// Frames received from the bus per second, including ones the channel's filters or a full queue dropped.
+    pub rx_frames_per_sec: f64,
+    /// Frames sent from this host per second.
+    pub tx_frames_per_sec: f64,
+    /// Nominal bits on the wire per second of the frames in both directions, not counting stuff bits.
+    pub bits_per_sec: f64,
+    /// Estimated fraction of the bus the traffic occupies at the bit rate passed in.
+    pub bus_load: f64,
+}
+
+/// Measures the frame rates and estimated bus load of a channel over a recent window, to diagnose saturated buses.
+///
+/// Stuff bits typically add 10-20% to the bus load, so buses are effectively saturated somewhat below 1.0.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **channel** - the channel to measure
+/// * **window_ms** - how far back to look, in milliseconds. Rounded up to 100ms and capped at 10 seconds.
+/// * **bit_rate** - the bus's nominal bit rate in bits per second, e.g. 1000000 for FRC buses
+/// * **meter** - pointer updated with the measurement. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_get_channel_meter(handle_id: i32, channel: u8, window_ms: u32, bit_rate: u32, meter: *mut RdxUsbChannelMeterReading) -> i32 {
+    audit_local("rdxusb_get_channel_meter", || format!("handle_id={handle_id}, channel={channel}, window_ms={window_ms}, bit_rate={bit_rate}, meter={meter:?}"), || {
+        let Some(meter) = (unsafe { meter.as_mut() }) else { return EventLoopError::ERR_NULL_PTR; };
+        match event_loop::channel_meter(handle_id, channel, Duration::from_millis(window_ms as u64)) {
+            Ok(reading) => {
+                *meter = RdxUsbChannelMeterReading {
+                    rx_frames_per_sec: reading.rx_frames_per_sec,
+                    tx_frames_per_sec: reading.tx_frames_per_sec,
+                    bits_per_sec: reading.bits_per_sec,
+                    bus_load: reading.bus_load(bit_rate),
+                };
+                0
+            }
+            Err(e) => e as i32,
+        }
+    })
+}
+
+/// Receive rate of one arbitration id, filled in by rdxusb_get_top_talkers.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RdxUsbTalkerRate {
+    /// Arbitration id, with RDXUSB_ARB_ID_FLAG_EXT set for extended ids.
+    pub arb_id: u32,
+    /// Frames per second, averaged over roughly the last second.
+    pub frames_per_sec: f64,
+}
+
+/// Lists the arbitration ids received most often on a channel lately, busiest first, to find which messages
+/// load a bus.
+///
+/// Up to 512 ids are tracked per channel; beyond that the slowest is forgotten to make room for a new one.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **channel** - the channel to look at
+/// * **talkers** - a pointer to the buffer to fill. Must not be NULL.
+/// * **max_talkers** - how many ids the buffer holds
+/// * **talkers_len** - pointer updated with how many ids were filled in. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_get_top_talkers(handle_id: i32, channel: u8, talkers: *mut RdxUsbTalkerRate, max_talkers: u64, talkers_len: *mut u64) -> i32 {
+    audit_local("rdxusb_get_top_talkers", || format!("handle_id={handle_id}, channel={channel}, talkers={talkers:?}, max_talkers={max_talkers}, talkers_len={talkers_len:?}"), || {
+        if talkers.is_null() || talkers_len.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let talkers = unsafe { core::slice::from_raw_parts_mut(talkers, max_talkers as usize) };
+        match event_loop::top_talkers(handle_id, channel, talkers.len()) {
+            Ok(top) => {
+                for (out, talker) in talkers.iter_mut().zip(&top) {
+                    *out = RdxUsbTalkerRate { arb_id: talker.arb_id, frames_per_sec: talker.frames_per_sec };
+                }
+                unsafe { *talkers_len = top.len() as u64; }
+                0
+            }
+            Err(e) => e as i32,
+        }
+    })
+}
+
+/// Sets the order device handles are opened in when several devices attach at once, highest first.
+///
+/// After a hub power cycle every device reattaches together; rdxusb opens them one at a time, so giving critical
+/// devices (e.g. a gyro) a higher priority brings them back first. Devices of equal priority are opened in the
+/// order they were found.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **priority** - the handle's priority, 0 (the default) being the lowest
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_reconnect_priority(handle_id: i32, priority: u8) -> i32 {
+    audit_local("rdxusb_set_reconnect_priority", || format!("handle_id={handle_id}, priority={priority}"), || {
+        event_loop::set_reconnect_priority(handle_id, priority).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Applies a named set of timeouts to a device handle.
 ///
-/// rdxusb will spawn an event loop that will continually attempt to open a matching device and
-/// send/receive messages from it. If connection with the matching device is lost, reconnection is 
-/// continually attempted.
+/// A profile sets the open, control and write timeouts, the wait between reconnect attempts, and the rx watchdog
+/// (see rdxusb_set_rx_watchdog). The watchdog and reconnect wait apply right away; the others
+/// apply from the next time the device connects.
 ///
-/// * **vid** - USB vendor ID to match
-/// * **pid** - USB product ID to match
-/// * **serial_number** - an optional serial number string. This MUST be UTF-8 or NULL.
-/// * **close_on_dc** - if true, closes the device handle on device disconnect
-/// * **buf_size** - the maximum number of packets to buffer inbound/outbound
-/// 
-/// Returns a non-negative device handle on success, negative on error
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **profile** - one of the RDXUSB_TIMEOUT_PROFILE_* values
+///
+/// Return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the profile is unknown, negative on other errors
 #[no_mangle]
-pub extern "C" fn rdxusb_open_device(vid: u16, pid: u16, serial_number: *const c_char, close_on_dc: bool, buf_size: u64) -> i32 {
-    let serial_number = to_optional_string(serial_number);
-    event_loop::open_device(vid, pid, serial_number, close_on_dc, buf_size as usize).unwrap_or_else(|e| e as i32)
+pub extern "C" fn rdxusb_set_timeout_profile(handle_id: i32, profile: u8) -> i32 {
+    audit_local("rdxusb_set_timeout_profile", || format!("handle_id={handle_id}, profile={profile}"), || {
+        let Ok(profile) = RdxUsbTimeoutProfile::try_from(profile) else { return EventLoopError::ERR_INVALID_ARGUMENT; };
+        event_loop::set_timeout_profile(handle_id, profile).map_or_else(|e| e as i32, |_| 0)
+    })
 }
 
-/// Forces the RdxUsb event loop to rescan USB devices.
-/// 
-/// By default, the RdxUsb event loop will automatically reconnect devices via hotplug, 
-/// but if hotplug does not work, manually calling this function will rescan and potentially reconnect devices.
-/// 
+/// Starts recording every packet a device handle receives into a native capture file.
+///
+/// Recording continues across reconnects until rdxusb_stop_capture is called or the handle is closed.
+/// Starting a new capture finishes the previous one. The file is written on a background thread, so a slow disk
+/// drops packets from the capture instead of delaying the device.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **path** - null-terminated path of the `.rdxcap` file to create
+///
 /// Return 0 on success, negative on error
 #[no_mangle]
-pub extern "C" fn rdxusb_force_scan_devices() -> i32 {
-    let Ok(event_loop) = event_loop::try_acquire_event_loop() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
-    match event_loop::force_scan_devices(event_loop) {
-        Ok(_) => 0,
-        Err(e) => e as i32,
-    }
+pub extern "C" fn rdxusb_start_capture(handle_id: i32, path: *const c_char) -> i32 {
+    audit_local("rdxusb_start_capture", || format!("handle_id={handle_id}, path={path:?}"), || {
+        let Some(path) = to_optional_string(path) else { return EventLoopError::ERR_INVALID_ARGUMENT; };
+        event_loop::start_capture(handle_id, &path).map_or_else(|e| e as i32, |_| 0)
+    })
 }
 
-/// Reads packets into the specified buffer.
+/// Finishes a device handle's capture started with rdxusb_start_capture.
 ///
 /// * **handle_id** - a handle id returned from rdxusb_open_device
-/// * **channel** - the USB channel to read from.
-///                 The number of channels a device has is device dependent, but for now just pass in 0.
-/// * **packets** - a pointer to the packet buffer to read into. Must not be NULL.
-/// * **max_packets** - the maximum number of packets to read into the packet buffer.
-/// * **packets_read** - pointer updated with how many packets were actually read. Must not be NULL.
-/// 
+///
 /// Return 0 on success, negative on error
 #[no_mangle]
-pub extern "C" fn rdxusb_read_packets(handle_id: i32, channel: u8, packets: *mut RdxUsbPacket, max_packets: u64, packets_read: *mut u64) -> i32 {
-    if packets.is_null() || packets_read.is_null() { return EventLoopError::ERR_NULL_PTR; }
-    let packets = unsafe { core::slice::from_raw_parts_mut(packets, max_packets as usize) };
-    match event_loop::read_packets(handle_id, channel, packets) {
-        Ok(w) => {
-            unsafe { *packets_read = w as u64; }
-            0
-        }
-        Err(e) => { e as i32 }
-    }
+pub extern "C" fn rdxusb_stop_capture(handle_id: i32) -> i32 {
+    audit_local("rdxusb_stop_capture", || format!("handle_id={handle_id}"), || {
+        event_loop::stop_capture(handle_id).map_or_else(|e| e as i32, |_| 0)
+    })
 }
 
-/// Writes packets from the specified buffer.
+/// Adds a text annotation, such as "auton started", to a device handle's capture.
+///
+/// The annotation is placed after the packets received so far, so it can be correlated with the traffic around it.
 ///
 /// * **handle_id** - a handle id returned from rdxusb_open_device
-/// * **packets** - a pointer to the packet buffer to write from. Must not be NULL.
-/// * **packets_len** - the number of packets to write from the packet buffer.
-/// * **packets_written** - pointer updated with how many packets were actually written. Can be NULL.
-/// 
+/// * **text** - null-terminated UTF-8 annotation text
+///
 /// Return 0 on success, negative on error
 #[no_mangle]
-pub extern "C" fn rdxusb_write_packets(handle_id: i32, packets: *const RdxUsbPacket, packets_len: u64, packets_written: *mut u64) -> i32 {
-    if packets.is_null() { return EventLoopError::ERR_NULL_PTR; }
-
-    let packets = unsafe { core::slice::from_raw_parts(packets, packets_len as usize) };
-    match event_loop::write_packets(handle_id, packets) {
-        Ok(w) => {
-            unsafe { 
-                match packets_written.as_mut() {
-                    Some(p) => *p = w as u64,
-                    None => {}
-                }
+pub extern "C" fn rdxusb_capture_annotate(handle_id: i32, text: *const c_char) -> i32 {
+    audit_local("rdxusb_capture_annotate", || format!("handle_id={handle_id}, text={text:?}"), || {
+        let Some(text) = to_optional_string(text) else { return EventLoopError::ERR_INVALID_ARGUMENT; };
+        event_loop::annotate_capture(handle_id, &text).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Sets how many packets rdxusb_read_packets_any holds back to restore timestamp ordering across channels.
+///
+/// Only needed for devices that timestamp channels independently. Held back packets are released once
+/// that many newer packets have arrived, so this adds latency.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **depth** - the number of packets to hold back, or 0 to disable reordering (the default)
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_reorder_depth(handle_id: i32, depth: u32) -> i32 {
+    audit_local("rdxusb_set_reorder_depth", || format!("handle_id={handle_id}, depth={depth}"), || {
+        event_loop::set_reorder_depth(handle_id, depth as usize).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Gets the lifecycle state of a device handle.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **state** - pointer the state is written to, one of the RDXUSB_DEVICE_STATE_* values. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_get_device_state(handle_id: i32, state: *mut i32) -> i32 {
+    audit_local("rdxusb_get_device_state", || format!("handle_id={handle_id}, state={state:?}"), || {
+        if state.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        match event_loop::device_state(handle_id) {
+            Ok(s) => {
+                unsafe { *state = s as i32; }
+                0
             }
-            0
+            Err(e) => { e as i32 }
+        }
+    })
+}
+
+/// Describes everything known about a device handle as a JSON object, for display in configuration tools.
+///
+/// The object holds the handle's state and match parameters, the USB descriptors of the matching device under "usb",
+/// and, while connected, the device info, channel count, max payload, and transfer counters under "connection".
+/// Anything not known is null.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **buf** - buffer the NUL-terminated JSON string is written to. Must not be NULL.
+/// * **buf_len** - size of buf in bytes
+/// * **json_len** - pointer the JSON length (excluding the NUL) is written to, even if buf is too small. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the string, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_describe_device(handle_id: i32, buf: *mut c_char, buf_len: u64, json_len: *mut u64) -> i32 {
+    audit_local("rdxusb_describe_device", || format!("handle_id={handle_id}, buf={buf:?}, buf_len={buf_len}, json_len={json_len:?}"), || {
+        if buf.is_null() || json_len.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        match event_loop::describe_device(handle_id) {
+            Ok(description) => copy_out_str(&description.to_json(), buf, buf_len, json_len),
+            Err(e) => e as i32,
         }
-        Err(e) => { e as i32 }
+    })
+}
+
+/// Copies `s` into a caller's buffer as a NUL-terminated string, reporting its length even if it doesn't fit.
+fn copy_out_str(s: &str, buf: *mut c_char, buf_len: u64, str_len: *mut u64) -> i32 {
+    unsafe { *str_len = s.len() as u64; }
+    if s.len() as u64 >= buf_len { return EventLoopError::ERR_BUFFER_TOO_SMALL; }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, buf_len as usize) };
+    buf[..s.len()].copy_from_slice(s.as_bytes());
+    buf[s.len()] = 0;
+    0
+}
+
+/// Copies `data` into a caller's buffer, reporting its length even if it doesn't fit.
+fn copy_out<T: Copy>(data: &[T], buf: *mut T, buf_len: u64, data_len: *mut u64) -> i32 {
+    unsafe { *data_len = data.len() as u64; }
+    if data.len() as u64 > buf_len { return EventLoopError::ERR_BUFFER_TOO_SMALL; }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf, buf_len as usize) };
+    buf[..data.len()].copy_from_slice(data);
+    0
+}
+
+fn get_raw_descriptor(reader: Result<RdxUsbDescriptorReader, i32>, desc_type: u8, desc_index: u8, language_id: u16, buf: *mut u8, buf_len: u64, desc_len: *mut u64) -> i32 {
+    if buf.is_null() || desc_len.is_null() { return EventLoopError::ERR_NULL_PTR; }
+    let reader = match reader { Ok(r) => r, Err(e) => { return e; } };
+    match reader.raw_descriptor(desc_type, desc_index, language_id) {
+        Ok(desc) => copy_out(&desc, buf, buf_len, desc_len),
+        Err(_) => EventLoopError::ERR_DESCRIPTOR_UNAVAILABLE,
+    }
+}
+
+fn get_string_descriptor(reader: Result<RdxUsbDescriptorReader, i32>, desc_index: u8, language_id: u16, buf: *mut c_char, buf_len: u64, str_len: *mut u64) -> i32 {
+    if buf.is_null() || str_len.is_null() { return EventLoopError::ERR_NULL_PTR; }
+    let Some(desc_index) = std::num::NonZeroU8::new(desc_index) else { return EventLoopError::ERR_INVALID_ARGUMENT; };
+    let reader = match reader { Ok(r) => r, Err(e) => { return e; } };
+    match reader.string_descriptor(desc_index, language_id) {
+        Ok(s) => copy_out_str(&s, buf, buf_len, str_len),
+        Err(_) => EventLoopError::ERR_DESCRIPTOR_UNAVAILABLE,
+    }
+}
+
+fn get_string_languages(reader: Result<RdxUsbDescriptorReader, i32>, languages: *mut u16, max_languages: u64, n_languages: *mut u64) -> i32 {
+    if languages.is_null() || n_languages.is_null() { return EventLoopError::ERR_NULL_PTR; }
+    let reader = match reader { Ok(r) => r, Err(e) => { return e; } };
+    match reader.string_languages() {
+        Ok(langs) => copy_out(&langs, languages, max_languages, n_languages),
+        Err(_) => EventLoopError::ERR_DESCRIPTOR_UNAVAILABLE,
     }
 }
 
+/// Reads a raw USB descriptor from the device a handle last matched, exactly as the device presents it.
+///
+/// The device must be attached, but the handle doesn't need to be connected.
+/// A configuration descriptor (type 2) is followed by all of its interface and endpoint descriptors.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **desc_type** - the descriptor type, e.g. 1 for the device descriptor or 2 for a configuration descriptor
+/// * **desc_index** - the descriptor index
+/// * **language_id** - the language of string descriptors, or 0 for other types
+/// * **buf** - buffer the descriptor is written to. Must not be NULL.
+/// * **buf_len** - size of buf in bytes
+/// * **desc_len** - pointer the descriptor length is written to, even if buf is too small. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the descriptor,
+/// RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_get_raw_descriptor(handle_id: i32, desc_type: u8, desc_index: u8, language_id: u16, buf: *mut u8, buf_len: u64, desc_len: *mut u64) -> i32 {
+    audit_local("rdxusb_get_raw_descriptor", || format!("handle_id={handle_id}, desc_type={desc_type}, desc_index={desc_index}, language_id={language_id}, buf={buf:?}, buf_len={buf_len}, desc_len={desc_len:?}"), || {
+        get_raw_descriptor(event_loop::descriptor_reader(handle_id).map_err(i32::from), desc_type, desc_index, language_id, buf, buf_len, desc_len)
+    })
+}
+
+/// Reads a string descriptor in a chosen language from the device a handle last matched.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **desc_index** - the string index, as found in other descriptors. Must not be 0.
+/// * **language_id** - the language to read the string in, see rdxusb_get_string_languages
+/// * **buf** - buffer the NUL-terminated UTF-8 string is written to. Must not be NULL.
+/// * **buf_len** - size of buf in bytes
+/// * **str_len** - pointer the string length (excluding the NUL) is written to, even if buf is too small. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the string,
+/// RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_get_string_descriptor(handle_id: i32, desc_index: u8, language_id: u16, buf: *mut c_char, buf_len: u64, str_len: *mut u64) -> i32 {
+    audit_local("rdxusb_get_string_descriptor", || format!("handle_id={handle_id}, desc_index={desc_index}, language_id={language_id}, buf={buf:?}, buf_len={buf_len}, str_len={str_len:?}"), || {
+        get_string_descriptor(event_loop::descriptor_reader(handle_id).map_err(i32::from), desc_index, language_id, buf, buf_len, str_len)
+    })
+}
+
+/// Lists the languages the device a handle last matched has string descriptors in.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **languages** - array the USB language ids are written to, e.g. 0x0409 for US English. Must not be NULL.
+/// * **max_languages** - length of the languages array
+/// * **n_languages** - pointer the number of languages is written to, even if the array is too small. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if the array can't hold every language,
+/// RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return them, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_get_string_languages(handle_id: i32, languages: *mut u16, max_languages: u64, n_languages: *mut u64) -> i32 {
+    audit_local("rdxusb_get_string_languages", || format!("handle_id={handle_id}, languages={languages:?}, max_languages={max_languages}, n_languages={n_languages:?}"), || {
+        get_string_languages(event_loop::descriptor_reader(handle_id).map_err(i32::from), languages, max_languages, n_languages)
+    })
+}
+
+/// Blocks until a device handle is connected, so packets can be written right after opening it.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **timeout_ms** - how long to wait, in milliseconds
+///
+/// Return 0 once connected, RDXUSB_ERR_TIMEOUT if the timeout elapsed first, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_wait_connected(handle_id: i32, timeout_ms: u64) -> i32 {
+    audit_local("rdxusb_wait_connected", || format!("handle_id={handle_id}, timeout_ms={timeout_ms}"), || {
+        event_loop::wait_connected_blocking(handle_id, Duration::from_millis(timeout_ms)).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Blocks until every one of several device handles is connected at the same time.
+///
+/// Use rdxusb_get_device_state to find out which devices are missing after a timeout.
+///
+/// * **handle_ids** - array of handle ids returned from rdxusb_open_device. Must not be NULL.
+/// * **n_handles** - the number of handle ids in the array
+/// * **timeout_ms** - how long to wait, in milliseconds
+///
+/// Return 0 once all are connected, RDXUSB_ERR_TIMEOUT if the timeout elapsed first, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_wait_all_connected(handle_ids: *const i32, n_handles: u64, timeout_ms: u64) -> i32 {
+    audit_local("rdxusb_wait_all_connected", || format!("handle_ids={handle_ids:?}, n_handles={n_handles}, timeout_ms={timeout_ms}"), || {
+        if handle_ids.is_null() { return EventLoopError::ERR_NULL_PTR; }
+        let handle_ids = unsafe { core::slice::from_raw_parts(handle_ids, n_handles as usize) };
+        event_loop::wait_all_connected_blocking(handle_ids, Duration::from_millis(timeout_ms)).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Adds a rule forwarding frames received on one channel of a device out onto another channel.
+///
+/// Forwarded frames are still delivered to rdxusb_read_packets as usual.
+/// Rules persist across reconnects until cleared with rdxusb_clear_bridge_rules.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **src_channel** - the channel to forward frames from
+/// * **dst_channel** - the channel to retransmit matching frames on
+/// * **id** - arbitration id to match, compared only on the bits set in mask
+/// * **mask** - arbitration id bits that must match id. A mask of 0 forwards every frame.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_add_bridge_rule(handle_id: i32, src_channel: u8, dst_channel: u8, id: u32, mask: u32) -> i32 {
+    audit_local("rdxusb_add_bridge_rule", || format!("handle_id={handle_id}, src_channel={src_channel}, dst_channel={dst_channel}, id={id:#x}, mask={mask:#x}"), || {
+        let rule = RdxUsbBridgeRule { src_channel, dst_channel, id, mask };
+        event_loop::add_bridge_rule(handle_id, rule).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Removes all bridge rules from a device.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_clear_bridge_rules(handle_id: i32) -> i32 {
+    audit_local("rdxusb_clear_bridge_rules", || format!("handle_id={handle_id}"), || event_loop::clear_bridge_rules(handle_id).map_or_else(|e| e as i32, |_| 0))
+}
+
 /// Closes the specified device, and stops reading from it.
 ///
 /// If the handle ID is already closed or invalid, this returns 0.
@@ -105,38 +1204,319 @@ pub extern "C" fn rdxusb_write_packets(handle_id: i32, packets: *const RdxUsbPac
 /// Return 0 on success, negative on error.
 #[no_mangle]
 pub extern "C" fn rdxusb_close_device(handle_id: i32) -> i32 {
-    event_loop::close_device(handle_id).map_or_else(|e| e as i32, |_| 0)
+    audit("rdxusb_close_device", || format!("handle_id={handle_id}"), || forward::close_device(handle_id).map_or_else(|e| e as i32, |_| 0))
 }
 
 /// Closes all device handles.
 ///
 /// If the handle ID is already closed or invalid, this returns 0.
+/// When forwarding to a daemon, only the handles this process opened are closed.
 ///
 /// Return 0 on success, negative on error.
 #[no_mangle]
 pub extern "C" fn rdxusb_close_all_devices() -> i32 {
-    event_loop::close_all_devices().map_or_else(|e| e as i32, |_| 0)
+    audit("rdxusb_close_all_devices", String::new, || forward::close_all_devices().map_or_else(|e| e as i32, |_| 0))
+}
+
+// Daemon mode --------
+
+/// The event loop calls that are forwarded to a daemon when this process is a daemon client.
+mod forward {
+    use rdxusb_protocol::RdxUsbPacket;
+
+    use crate::event_loop::{self, EventLoopError};
+
+    /// Fails calls that have no daemon counterpart while this process is a daemon client.
+    pub fn local_only() -> Result<(), EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if crate::daemon::client().is_some() { return Err(EventLoopError::RequestUnsupported); }
+        Ok(())
+    }
+
+    pub fn open_device(vid: u16, pid: u16, serial_number: Option<String>, close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Some(client) = crate::daemon::client() { return client.open_device(vid, pid, serial_number.as_deref(), close_on_dc, capacity); }
+        event_loop::open_device(vid, pid, serial_number, close_on_dc, capacity)
+    }
+
+    pub fn open_first_redux_device(close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Some(client) = crate::daemon::client() { return client.open_first_redux_device(close_on_dc, capacity); }
+        event_loop::open_first_redux_device(close_on_dc, capacity)
+    }
+
+    pub fn read_packets(handle_id: i32, channel: u8, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Some(client) = crate::daemon::client() { return client.read_packets(handle_id, channel, packets); }
+        event_loop::read_packets(handle_id, channel, packets)
+    }
+
+    pub fn read_packets_any(handle_id: i32, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Some(client) = crate::daemon::client() { return client.read_packets_any(handle_id, packets); }
+        event_loop::read_packets_any(handle_id, packets)
+    }
+
+    pub fn write_packets(handle_id: i32, packets: &[RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Some(client) = crate::daemon::client() { return client.write_packets(handle_id, packets); }
+        event_loop::write_packets(handle_id, packets)
+    }
+
+    pub fn close_device(handle_id: i32) -> Result<(), EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Some(client) = crate::daemon::client() { return client.close_device(handle_id); }
+        event_loop::close_device(handle_id)
+    }
+
+    pub fn close_all_devices() -> Result<(), EventLoopError> {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Some(client) = crate::daemon::client() { return client.close_all_devices(); }
+        event_loop::close_all_devices()
+    }
+}
+
+#[cfg(all(feature = "daemon", unix))]
+static DAEMON_SERVER: Mutex<Option<crate::daemon::DaemonServer>> = Mutex::new(None);
+
+/// Serves this process's event loop to other processes on the same machine over a Unix socket.
+///
+/// Processes that share devices should let one of them call this, and connect the others with
+/// rdxusb_connect_daemon or by setting the RDXUSB_DAEMON environment variable to the socket path.
+/// Clients can only use the handles they opened, which are closed when they disconnect. The socket is created
+/// with mode 0660, so only processes of the same user or group can connect.
+///
+/// * **path** - path of the socket to create. This MUST be UTF-8 and not NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_serve_daemon(path: *const c_char) -> i32 {
+    audit("rdxusb_serve_daemon", || format!("path={path:?}"), || {
+        let Some(path) = to_optional_string(path) else { return EventLoopError::ERR_NULL_PTR; };
+        #[cfg(all(feature = "daemon", unix))]
+        {
+            let Ok(mut server) = DAEMON_SERVER.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
+            // the old socket has to go first in case it's the same path
+            server.take();
+            match crate::daemon::DaemonServer::bind(&path) {
+                Ok(s) => { server.replace(s); 0 }
+                Err(e) => {
+                    log::error!(target: "rdxusb", "daemon: could not bind {path}: {e}");
+                    EventLoopError::ERR_INVALID_ARGUMENT
+                }
+            }
+        }
+        #[cfg(not(all(feature = "daemon", unix)))]
+        {
+            let _ = path;
+            EventLoopError::ERR_REQUEST_UNSUPPORTED
+        }
+    })
+}
+
+/// Stops serving other processes, removing the socket created by rdxusb_serve_daemon.
+///
+/// Clients already connected are served until they disconnect.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_stop_daemon() -> i32 {
+    audit("rdxusb_stop_daemon", String::new, || {
+        #[cfg(all(feature = "daemon", unix))]
+        if let Ok(mut server) = DAEMON_SERVER.lock() { server.take(); }
+        0
+    })
+}
+
+/// Forwards calls to the event loop of another process serving a daemon socket, instead of running one here.
+///
+/// Only opening, reading, writing, and closing are forwarded; other calls that take or create a handle return
+/// RDXUSB_ERR_REQUEST_UNSUPPORTED while forwarding, and enumeration and library-wide settings act locally.
+///
+/// * **path** - path of the daemon's socket, or NULL to stop forwarding. This MUST be UTF-8.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_connect_daemon(path: *const c_char) -> i32 {
+    audit("rdxusb_connect_daemon", || format!("path={path:?}"), || {
+        let path = to_optional_string(path);
+        #[cfg(all(feature = "daemon", unix))]
+        match crate::daemon::set_client(path.as_deref().map(std::path::Path::new)) {
+            Ok(_) => 0,
+            Err(e) => {
+                log::error!(target: "rdxusb", "daemon: could not connect to {path:?}: {e}");
+                EventLoopError::ERR_DAEMON_UNAVAILABLE
+            }
+        }
+        #[cfg(not(all(feature = "daemon", unix)))]
+        {
+            let _ = path;
+            EventLoopError::ERR_REQUEST_UNSUPPORTED
+        }
+    })
+}
+
+// Leak diagnostics --------
+
+/// Open handles and live iterators past which a warning is logged, or 0 for no warnings.
+static HANDLE_WARN_THRESHOLD: AtomicU32 = AtomicU32::new(0);
+static ITERATOR_WARN_THRESHOLD: AtomicU32 = AtomicU32::new(0);
+
+/// Resources the application currently holds, filled in by rdxusb_get_leak_report.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RdxUsbLeakReport {
+    /// Open device handles.
+    pub open_handles: u32,
+    /// Open device handles nothing has ever read packets from.
+    pub unread_handles: u32,
+    /// Age of the oldest never-read handle in milliseconds, or 0 if there is none.
+    pub oldest_unread_handle_ms: u64,
+    /// Device iterators not yet freed with rdxusb_free_device_iterator.
+    pub live_iterators: u32,
+    /// Age of the oldest live iterator in milliseconds, or 0 if there is none.
+    pub oldest_iterator_ms: u64,
+}
+
+fn warn_on_handle_leaks() {
+    let threshold = HANDLE_WARN_THRESHOLD.load(Ordering::Relaxed);
+    if threshold == 0 { return; }
+    let Ok(handles) = event_loop::handle_diagnostics() else { return; };
+    if handles.len() <= threshold as usize { return; }
+    let unread: Vec<i32> = handles.iter().filter(|h| h.packets_read == 0).map(|h| h.handle_id).collect();
+    log::warn!(target: "rdxusb", "{} device handles are open, more than the leak warning threshold of {threshold}; never read: {unread:?}", handles.len());
+}
+
+fn warn_on_iterator_leaks(live: usize) {
+    let threshold = ITERATOR_WARN_THRESHOLD.load(Ordering::Relaxed);
+    if threshold == 0 || live <= threshold as usize { return; }
+    log::warn!(target: "rdxusb", "{live} device iterators are live, more than the leak warning threshold of {threshold}; are they freed with rdxusb_free_device_iterator?");
+}
+
+/// Sets how many open handles or live device iterators are considered a leak.
+///
+/// Once exceeded, every newly opened handle or iterator logs a warning.
+/// Warnings are off by default.
+///
+/// * **max_handles** - open handles allowed before warning, or 0 to never warn
+/// * **max_iterators** - live device iterators allowed before warning, or 0 to never warn
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_set_leak_warnings(max_handles: u32, max_iterators: u32) -> i32 {
+    audit("rdxusb_set_leak_warnings", || format!("max_handles={max_handles}, max_iterators={max_iterators}"), || {
+        HANDLE_WARN_THRESHOLD.store(max_handles, Ordering::Relaxed);
+        ITERATOR_WARN_THRESHOLD.store(max_iterators, Ordering::Relaxed);
+        0
+    })
+}
+
+/// Reports the handles and device iterators currently held, to help find ones a binding layer never releases.
+///
+/// * **report** - pointer to write the report into. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_get_leak_report(report: *mut RdxUsbLeakReport) -> i32 {
+    audit("rdxusb_get_leak_report", || format!("report={report:?}"), || {
+        let Some(report) = (unsafe { report.as_mut() }) else { return EventLoopError::ERR_NULL_PTR; };
+        let handles = match event_loop::handle_diagnostics() {
+            Ok(handles) => handles,
+            Err(e) => return e as i32,
+        };
+        let unread = handles.iter().filter(|h| h.packets_read == 0);
+        let mut out = RdxUsbLeakReport {
+            open_handles: handles.len() as u32,
+            unread_handles: unread.clone().count() as u32,
+            oldest_unread_handle_ms: unread.map(|h| h.age.as_millis() as u64).max().unwrap_or(0),
+            ..Default::default()
+        };
+        if let Some(infos) = DEVICE_INFOS.lock().unwrap().get() {
+            out.live_iterators = infos.info_map.len() as u32;
+            out.oldest_iterator_ms = infos.created.values().map(|t| t.elapsed().as_millis() as u64).max().unwrap_or(0);
+        }
+        *report = out;
+        0
+    })
+}
+
+/// Parks or closes a device handle once nothing has read from or written to it for a while.
+///
+/// Handles opened with close_on_dc false otherwise keep reconnecting until closed. Parked handles move to
+/// RDXUSB_DEVICE_STATE_PARKED; the next read or write fails with RDXUSB_ERR_DEVICE_NOT_CONNECTED and reopens the device.
+///
+/// * **handle_id** - a handle id returned from rdxusb_open_device
+/// * **action** - one of the RDXUSB_IDLE_ACTION_* values
+/// * **idle_ms** - how long the handle may go unused before the action is taken
+///
+/// Return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the action is unknown, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_set_idle_policy(handle_id: i32, action: u8, idle_ms: u64) -> i32 {
+    audit_local("rdxusb_set_idle_policy", || format!("handle_id={handle_id}, action={action}, idle_ms={idle_ms}"), || {
+        let policy = match action {
+            0 => None,
+            action => {
+                let Ok(action) = IdleAction::try_from(action) else { return EventLoopError::ERR_INVALID_ARGUMENT; };
+                Some(IdlePolicy { after: Duration::from_millis(idle_ms), action })
+            }
+        };
+        event_loop::set_idle_policy(handle_id, policy).map_or_else(|e| e as i32, |_| 0)
+    })
+}
+
+/// Parks or closes every device handle that went unused for at least a given time, regardless of its idle policy.
+///
+/// * **min_idle_ms** - how long a handle must have gone unused
+/// * **action** - RDXUSB_IDLE_ACTION_PARK or RDXUSB_IDLE_ACTION_CLOSE
+/// * **handles** - a pointer to a buffer the reaped handle ids are written to. May be NULL if max_handles is 0.
+/// * **max_handles** - how many handle ids the buffer holds
+/// * **handles_len** - pointer updated with how many handles were reaped, which may exceed max_handles. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_INVALID_ARGUMENT if the action is unknown, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_reap_idle_handles(min_idle_ms: u64, action: u8, handles: *mut i32, max_handles: u64, handles_len: *mut u64) -> i32 {
+    audit_local("rdxusb_reap_idle_handles", || format!("min_idle_ms={min_idle_ms}, action={action}, handles={handles:?}, max_handles={max_handles}, handles_len={handles_len:?}"), || {
+        if handles_len.is_null() || (handles.is_null() && max_handles > 0) { return EventLoopError::ERR_NULL_PTR; }
+        let Ok(action) = IdleAction::try_from(action) else { return EventLoopError::ERR_INVALID_ARGUMENT; };
+        match event_loop::reap_idle_handles(Duration::from_millis(min_idle_ms), action) {
+            Ok(reaped) => {
+                if max_handles > 0 {
+                    let handles = unsafe { core::slice::from_raw_parts_mut(handles, max_handles as usize) };
+                    for (out, handle_id) in handles.iter_mut().zip(&reaped) {
+                        *out = *handle_id;
+                    }
+                }
+                unsafe { *handles_len = reaped.len() as u64; }
+                0
+            }
+            Err(e) => e as i32,
+        }
+    })
 }
 
 // Device Iterators --------
 
 struct DeviceInfos {
     info_map: HashMap<u64, Vec<nusb::DeviceInfo>>,
+    /// when each iterator was created, for rdxusb_get_leak_report
+    created: HashMap<u64, Instant>,
     next_idx: u64,
 }
 impl DeviceInfos {
     pub fn new() -> Self {
-        Self { info_map: HashMap::new(), next_idx: 0 }
+        Self { info_map: HashMap::new(), created: HashMap::new(), next_idx: 0 }
     }
     pub fn allocate_idx_and_insert(&mut self, devices: Vec<nusb::DeviceInfo>) -> u64 {
         let idx = self.next_idx;
         self.info_map.insert(idx, devices);
+        self.created.insert(idx, Instant::now());
         self.next_idx += 1;
         idx
     }
 
     pub fn free_idx(&mut self, idx: u64) {
         self.info_map.remove(&idx);
+        self.created.remove(&idx);
     }
 }
 
@@ -161,6 +1541,28 @@ fn strncpy_into_buf(s: &CStr, dest: &mut [u8]) {
     dest[max_len] = 0;
 }
 
+/// Gets a counter that changes whenever a known Redux device (see rdxusb_is_redux_device) is attached or detached.
+///
+/// Polling this is a cheap way for a UI to decide when to list devices again. It is driven by hotplug events,
+/// so it never changes if rdxusb was initialized with hotplug off.
+///
+/// * **generation** - pointer the counter is written to. Must not be NULL.
+///
+/// Return 0 on success, negative on error
+#[no_mangle]
+pub extern "C" fn rdxusb_get_device_list_generation(generation: *mut u64) -> i32 {
+    audit("rdxusb_get_device_list_generation", || format!("generation={generation:?}"), || {
+        let Some(generation) = (unsafe { generation.as_mut() }) else { return EventLoopError::ERR_NULL_PTR; };
+        match event_loop::device_list_generation() {
+            Ok(g) => {
+                *generation = g;
+                0
+            }
+            Err(e) => e as i32,
+        }
+    })
+}
+
 /// Creates a new USB device iterator.
 /// 
 /// * **iter_id** - pointer where the iterator handle will be written
@@ -169,22 +1571,25 @@ fn strncpy_into_buf(s: &CStr, dest: &mut [u8]) {
 /// Return 0 on success, negative on error
 #[no_mangle]
 pub extern "C" fn rdxusb_new_device_iterator(iter_id: *mut u64, n_devices: *mut u64) -> i32 {
-    if iter_id.is_null() || n_devices.is_null() {
-        return EventLoopError::ERR_NULL_PTR;
-    }
+    audit("rdxusb_new_device_iterator", || format!("iter_id={iter_id:?}, n_devices={n_devices:?}"), || {
+        if iter_id.is_null() || n_devices.is_null() {
+            return EventLoopError::ERR_NULL_PTR;
+        }
 
-    DEVICE_INFOS.lock().unwrap().get_or_init(DeviceInfos::new);
-    let Ok(mut info_lock) = DEVICE_INFOS.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
-    let infos = info_lock.get_mut().unwrap();
-    let Ok(device_iter) = nusb::list_devices() else { return EventLoopError::ERR_CANNOT_LIST_DEVICES; };
-    let devices: Vec<nusb::DeviceInfo> = device_iter.collect();
-    let devices_count = devices.len() as u64;
-    let idx = infos.allocate_idx_and_insert(devices);
-    unsafe {
-        *iter_id = idx;
-        *n_devices = devices_count;
-    }
-    0
+        DEVICE_INFOS.lock().unwrap().get_or_init(DeviceInfos::new);
+        let Ok(mut info_lock) = DEVICE_INFOS.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
+        let infos = info_lock.get_mut().unwrap();
+        let Ok(device_iter) = nusb::list_devices() else { return EventLoopError::ERR_CANNOT_LIST_DEVICES; };
+        let devices: Vec<nusb::DeviceInfo> = device_iter.collect();
+        let devices_count = devices.len() as u64;
+        let idx = infos.allocate_idx_and_insert(devices);
+        warn_on_iterator_leaks(infos.info_map.len());
+        unsafe {
+            *iter_id = idx;
+            *n_devices = devices_count;
+        }
+        0
+    })
 }
 
 /// Gets a device by index in an iterator.
@@ -196,34 +1601,102 @@ pub extern "C" fn rdxusb_new_device_iterator(iter_id: *mut u64, n_devices: *mut
 /// Return 0 on success, negative on error
 #[no_mangle]
 pub extern "C" fn rdxusb_get_device_in_iterator(iter_id: u64, device_idx: u64, device_entry: *mut RdxUsbDeviceEntry) -> i32 {
-    if device_entry.is_null() {
-        return EventLoopError::ERR_NULL_PTR;
-    }
+    audit("rdxusb_get_device_in_iterator", || format!("iter_id={iter_id}, device_idx={device_idx}, device_entry={device_entry:?}"), || {
+        if device_entry.is_null() {
+            return EventLoopError::ERR_NULL_PTR;
+        }
 
 
-    DEVICE_INFOS.lock().unwrap().get_or_init(DeviceInfos::new);
-    let Ok(mut info_lock) = DEVICE_INFOS.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
-    let infos = info_lock.get_mut().unwrap();
+        DEVICE_INFOS.lock().unwrap().get_or_init(DeviceInfos::new);
+        let Ok(mut info_lock) = DEVICE_INFOS.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
+        let infos = info_lock.get_mut().unwrap();
 
-    let Some(device_infos) = infos.info_map.get(&iter_id) else { return EventLoopError::ERR_DEVICE_ITER_INVALID; };
-    let device_idx = device_idx as usize;
-    if device_idx >= device_infos.len() { return EventLoopError::ERR_DEVICE_ITER_IDX_OUT_OF_RANGE; }
-    let device_ent = &device_infos[device_idx];
+        let Some(device_infos) = infos.info_map.get(&iter_id) else { return EventLoopError::ERR_DEVICE_ITER_INVALID; };
+        let device_idx = device_idx as usize;
+        if device_idx >= device_infos.len() { return EventLoopError::ERR_DEVICE_ITER_IDX_OUT_OF_RANGE; }
+        let device_ent = &device_infos[device_idx];
 
-    let device_entry = unsafe { &mut *device_entry };
+        let device_entry = unsafe { &mut *device_entry };
 
-    let serial_str = CString::new(device_ent.serial_number().unwrap_or("")).unwrap_or(c"".into());
-    let mfg_str = CString::new(device_ent.manufacturer_string().unwrap_or("")).unwrap_or(c"".into());
-    let prod_str = CString::new(device_ent.product_string().unwrap_or("")).unwrap_or(c"".into());
-    strncpy_into_buf(serial_str.as_c_str(), &mut device_entry.serial);
-    strncpy_into_buf(mfg_str.as_c_str(), &mut device_entry.manufacturer);
-    strncpy_into_buf(prod_str.as_c_str(), &mut device_entry.product_str);
+        let serial_str = CString::new(device_ent.serial_number().unwrap_or("")).unwrap_or(c"".into());
+        let mfg_str = CString::new(device_ent.manufacturer_string().unwrap_or("")).unwrap_or(c"".into());
+        let prod_str = CString::new(device_ent.product_string().unwrap_or("")).unwrap_or(c"".into());
+        strncpy_into_buf(serial_str.as_c_str(), &mut device_entry.serial);
+        strncpy_into_buf(mfg_str.as_c_str(), &mut device_entry.manufacturer);
+        strncpy_into_buf(prod_str.as_c_str(), &mut device_entry.product_str);
 
-    device_entry.vid = device_ent.vendor_id();
-    device_entry.pid = device_ent.product_id();
-    device_entry.bus_number = 0; //device_ent.bus_number();
-    device_entry.device_address = device_ent.device_address();
-    0
+        device_entry.vid = device_ent.vendor_id();
+        device_entry.pid = device_ent.product_id();
+        device_entry.bus_number = 0; //device_ent.bus_number();
+        device_entry.device_address = device_ent.device_address();
+        0
+    })
+}
+
+fn iter_descriptor_reader(iter_id: u64, device_idx: u64) -> Result<RdxUsbDescriptorReader, i32> {
+    let info = {
+        let Ok(info_lock) = DEVICE_INFOS.lock() else { return Err(EventLoopError::ERR_EVENT_LOOP_CRASHED); };
+        let Some(device_infos) = info_lock.get().and_then(|infos| infos.info_map.get(&iter_id)) else { return Err(EventLoopError::ERR_DEVICE_ITER_INVALID); };
+        let Some(info) = device_infos.get(device_idx as usize) else { return Err(EventLoopError::ERR_DEVICE_ITER_IDX_OUT_OF_RANGE); };
+        info.clone()
+    };
+    RdxUsbDescriptorReader::open(&info, RdxUsbTimeouts::default().control).map_err(|_| EventLoopError::ERR_DEVICE_NOT_CONNECTED)
+}
+
+/// Reads a raw USB descriptor from a device in an iterator, like rdxusb_get_raw_descriptor.
+///
+/// * **iter_id** - iterator handle to pull from
+/// * **device_idx** - index of the device. Must be 0 <= device_idx < n_devices.
+/// * **desc_type** - the descriptor type, e.g. 1 for the device descriptor or 2 for a configuration descriptor
+/// * **desc_index** - the descriptor index
+/// * **language_id** - the language of string descriptors, or 0 for other types
+/// * **buf** - buffer the descriptor is written to. Must not be NULL.
+/// * **buf_len** - size of buf in bytes
+/// * **desc_len** - pointer the descriptor length is written to, even if buf is too small. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the descriptor,
+/// RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_iter_get_raw_descriptor(iter_id: u64, device_idx: u64, desc_type: u8, desc_index: u8, language_id: u16, buf: *mut u8, buf_len: u64, desc_len: *mut u64) -> i32 {
+    audit("rdxusb_iter_get_raw_descriptor", || format!("iter_id={iter_id}, device_idx={device_idx}, desc_type={desc_type}, desc_index={desc_index}, language_id={language_id}, buf={buf:?}, buf_len={buf_len}, desc_len={desc_len:?}"), || {
+        get_raw_descriptor(iter_descriptor_reader(iter_id, device_idx), desc_type, desc_index, language_id, buf, buf_len, desc_len)
+    })
+}
+
+/// Reads a string descriptor in a chosen language from a device in an iterator, like rdxusb_get_string_descriptor.
+///
+/// * **iter_id** - iterator handle to pull from
+/// * **device_idx** - index of the device. Must be 0 <= device_idx < n_devices.
+/// * **desc_index** - the string index, as found in other descriptors. Must not be 0.
+/// * **language_id** - the language to read the string in, see rdxusb_iter_get_string_languages
+/// * **buf** - buffer the NUL-terminated UTF-8 string is written to. Must not be NULL.
+/// * **buf_len** - size of buf in bytes
+/// * **str_len** - pointer the string length (excluding the NUL) is written to, even if buf is too small. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if buf can't hold the string,
+/// RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return it, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_iter_get_string_descriptor(iter_id: u64, device_idx: u64, desc_index: u8, language_id: u16, buf: *mut c_char, buf_len: u64, str_len: *mut u64) -> i32 {
+    audit("rdxusb_iter_get_string_descriptor", || format!("iter_id={iter_id}, device_idx={device_idx}, desc_index={desc_index}, language_id={language_id}, buf={buf:?}, buf_len={buf_len}, str_len={str_len:?}"), || {
+        get_string_descriptor(iter_descriptor_reader(iter_id, device_idx), desc_index, language_id, buf, buf_len, str_len)
+    })
+}
+
+/// Lists the languages a device in an iterator has string descriptors in, like rdxusb_get_string_languages.
+///
+/// * **iter_id** - iterator handle to pull from
+/// * **device_idx** - index of the device. Must be 0 <= device_idx < n_devices.
+/// * **languages** - array the USB language ids are written to, e.g. 0x0409 for US English. Must not be NULL.
+/// * **max_languages** - length of the languages array
+/// * **n_languages** - pointer the number of languages is written to, even if the array is too small. Must not be NULL.
+///
+/// Return 0 on success, RDXUSB_ERR_BUFFER_TOO_SMALL if the array can't hold every language,
+/// RDXUSB_ERR_DESCRIPTOR_UNAVAILABLE if the device didn't return them, negative on other errors
+#[no_mangle]
+pub extern "C" fn rdxusb_iter_get_string_languages(iter_id: u64, device_idx: u64, languages: *mut u16, max_languages: u64, n_languages: *mut u64) -> i32 {
+    audit("rdxusb_iter_get_string_languages", || format!("iter_id={iter_id}, device_idx={device_idx}, languages={languages:?}, max_languages={max_languages}, n_languages={n_languages:?}"), || {
+        get_string_languages(iter_descriptor_reader(iter_id, device_idx), languages, max_languages, n_languages)
+    })
 }
 
 /// Frees a device iterator.
@@ -233,9 +1706,11 @@ pub extern "C" fn rdxusb_get_device_in_iterator(iter_id: u64, device_idx: u64, d
 /// Return 0 on success, negative on error
 #[no_mangle]
 pub extern "C" fn rdxusb_free_device_iterator(iter_id: u64) -> i32 {
-    DEVICE_INFOS.lock().unwrap().get_or_init(DeviceInfos::new);
-    let Ok(mut info_lock) = DEVICE_INFOS.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
-    let infos = info_lock.get_mut().unwrap();
-    infos.free_idx(iter_id);
-    0
+    audit("rdxusb_free_device_iterator", || format!("iter_id={iter_id}"), || {
+        DEVICE_INFOS.lock().unwrap().get_or_init(DeviceInfos::new);
+        let Ok(mut info_lock) = DEVICE_INFOS.lock() else { return EventLoopError::ERR_EVENT_LOOP_CRASHED; };
+        let infos = info_lock.get_mut().unwrap();
+        infos.free_idx(iter_id);
+        0
+    })
 }
\ No newline at end of file
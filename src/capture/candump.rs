@@ -0,0 +1,178 @@
+//! Lines look like `(1436509052.249713) can0 123#11223344`.
+//!
+//! The channel maps to the interface name (`can<channel>`). FD-flagged packets and payloads longer
+//! than 8 bytes use the CAN FD `##` syntax, which carries the BRS and ESI flags. The
+//! [`MESSAGE_ARB_ID_DEVICE`] bit and other packet flags have no candump representation and are dropped.
+//!
+//! Annotations are written as comment lines, `# (1436509052.249713) auton started`, which candump tools ignore.
+//!
+//! [`MESSAGE_ARB_ID_DEVICE`]: rdxusb_protocol::MESSAGE_ARB_ID_DEVICE
+use std::io::{BufRead, BufReader, Read, Write};
+
+use rdxusb_protocol::{RdxUsbPacket, MESSAGE_ARB_ID_EXT, MESSAGE_ARB_ID_RTR, MESSAGE_FLAG_BRS, MESSAGE_FLAG_ESI, MESSAGE_FLAG_FDF};
+
+use super::{hex_decode, hex_encode, Annotation, CaptureEntry, CaptureError, CaptureResult, PacketWrite};
+
+/// Writes `candump -l` style logs.
+pub struct CandumpWriter<W: Write> {
+    inner: W,
+    line: String,
+}
+
+impl<W: Write> CandumpWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, line: String::new() }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> PacketWrite for CandumpWriter<W> {
+    fn write_packet(&mut self, packet: &RdxUsbPacket) -> CaptureResult<()> {
+        use std::fmt::Write;
+        let ts = packet.timestamp_ns;
+        self.line.clear();
+        write!(self.line, "({}.{:06}) can{} ", ts / 1_000_000_000, ts % 1_000_000_000 / 1000, packet.channel).ok();
+        if packet.extended() {
+            write!(self.line, "{:08X}", packet.id()).ok();
+        } else {
+            write!(self.line, "{:03X}", packet.id() & 0x7ff).ok();
+        }
+        if packet.rtr() {
+            self.line.push_str("#R");
+        } else if packet.fd() || packet.dlc > 8 {
+            let fd_flags = packet.brs() as u8 | (packet.esi() as u8) << 1;
+            write!(self.line, "##{fd_flags:X}").ok();
+            hex_encode(packet.payload(), &mut self.line);
+        } else {
+            self.line.push('#');
+            hex_encode(packet.payload(), &mut self.line);
+        }
+        self.line.push('\n');
+        self.inner.write_all(self.line.as_bytes())?;
+        Ok(())
+    }
+
+    fn write_annotation(&mut self, annotation: &Annotation) -> CaptureResult<()> {
+        let ts = annotation.timestamp_ns;
+        writeln!(self.inner, "# ({}.{:06}) {}", ts / 1_000_000_000, ts % 1_000_000_000 / 1000, super::single_line(&annotation.text))?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> CaptureResult<()> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// Reads `candump -l` style logs.
+pub struct CandumpReader<R: Read> {
+    lines: std::io::Lines<BufReader<R>>,
+    line_no: usize,
+}
+
+impl<R: Read> CandumpReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { lines: BufReader::new(inner).lines(), line_no: 0 }
+    }
+
+    /// Reads the next packet or annotation, or `None` at end of file.
+    pub fn read_entry(&mut self) -> CaptureResult<Option<CaptureEntry>> {
+        loop {
+            let Some(line) = self.lines.next() else { return Ok(None); };
+            let line = line?;
+            self.line_no += 1;
+            let line = line.trim();
+            if line.is_empty() { continue; }
+            if let Some(comment) = line.strip_prefix('#') {
+                match parse_annotation(comment) {
+                    Some(annotation) => return Ok(Some(CaptureEntry::Annotation(annotation))),
+                    None => continue,
+                }
+            }
+            return parse_line(line)
+                .map(|p| Some(CaptureEntry::Packet(p)))
+                .map_err(|msg| CaptureError::Parse { line: self.line_no, msg: msg.to_string() });
+        }
+    }
+
+    /// Turns the reader into an iterator over packets and annotations.
+    pub fn into_entries(mut self) -> impl Iterator<Item = CaptureResult<CaptureEntry>> {
+        std::iter::from_fn(move || self.read_entry().transpose())
+    }
+}
+
+impl<R: Read> Iterator for CandumpReader<R> {
+    type Item = CaptureResult<RdxUsbPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.read_entry().transpose()? {
+                Ok(CaptureEntry::Packet(packet)) => return Some(Ok(packet)),
+                Ok(CaptureEntry::Annotation(_)) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Parses a `(secs.frac)` timestamp into nanoseconds.
+fn parse_timestamp(ts: &str) -> Option<u64> {
+    let ts = ts.strip_prefix('(')?.strip_suffix(')')?;
+    let (secs, frac) = ts.split_once('.').unwrap_or((ts, "0"));
+    let secs: u64 = secs.parse().ok()?;
+    // pad/truncate the fractional part to nanoseconds
+    let frac_ns: u64 = format!("{frac:0<9}").get(..9)?.parse().ok()?;
+    secs.checked_mul(1_000_000_000)?.checked_add(frac_ns)
+}
+
+fn parse_annotation(comment: &str) -> Option<Annotation> {
+    let (ts, text) = comment.trim_start().split_once(' ').unwrap_or((comment.trim(), ""));
+    Some(Annotation { timestamp_ns: parse_timestamp(ts)?, text: text.to_string() })
+}
+
+fn parse_line(line: &str) -> Result<RdxUsbPacket, &'static str> {
+    let mut parts = line.split_whitespace();
+    let ts = parts.next().ok_or("missing timestamp")?;
+    let iface = parts.next().ok_or("missing interface")?;
+    let frame = parts.next().ok_or("missing frame")?;
+    let timestamp_ns = parse_timestamp(ts).ok_or("bad timestamp")?;
+
+    let channel_digits = iface.len() - iface.bytes().rev().take_while(u8::is_ascii_digit).count();
+    let channel = iface[channel_digits..].parse().unwrap_or(0);
+
+    let mut packet = parse_frame(frame)?;
+    packet.timestamp_ns = timestamp_ns;
+    packet.channel = channel;
+    Ok(packet)
+}
+
+/// Parses a `cansend` style frame like `123#DEADBEEF`, `1F334455#R8` or `123##1AABB`.
+pub(crate) fn parse_frame(frame: &str) -> Result<RdxUsbPacket, &'static str> {
+    let (id, rest) = frame.split_once('#').ok_or("missing '#'")?;
+    // from_str_radix would also take a sign, so check the digits first
+    if id.is_empty() || id.len() > 8 || !id.bytes().all(|b| b.is_ascii_hexdigit()) { return Err("bad id"); }
+    let mut arb_id = u32::from_str_radix(id, 16).map_err(|_| "bad id")?;
+    if arb_id > 0x1fff_ffff { return Err("bad id"); }
+    if id.len() > 3 { arb_id |= MESSAGE_ARB_ID_EXT; }
+
+    let mut packet: RdxUsbPacket = bytemuck::Zeroable::zeroed();
+    if let Some(fd) = rest.strip_prefix('#') {
+        let fd_flags = fd.get(..1).and_then(|f| u8::from_str_radix(f, 16).ok()).ok_or("bad fd frame")?;
+        let data = fd.get(1..).ok_or("bad fd frame")?;
+        packet.flags = MESSAGE_FLAG_FDF;
+        if fd_flags & 1 != 0 { packet.flags |= MESSAGE_FLAG_BRS; }
+        if fd_flags & 2 != 0 { packet.flags |= MESSAGE_FLAG_ESI; }
+        packet.dlc = hex_decode(data, &mut packet.data).ok_or("bad data")? as u8;
+    } else if let Some(rtr) = rest.strip_prefix('R') {
+        arb_id |= MESSAGE_ARB_ID_RTR;
+        packet.arb_id = arb_id;
+        packet.dlc = rtr.parse().unwrap_or(0);
+        packet.sanitize();
+    } else {
+        packet.dlc = hex_decode(rest, &mut packet.data).ok_or("bad data")? as u8;
+    }
+    packet.arb_id = arb_id;
+    Ok(packet)
+}
@@ -0,0 +1,133 @@
+//! Columns are `timestamp_ns,channel,arb_id,dlc,flags,data`, with a header row.
+//!
+//! `arb_id` is the raw arbitration id in hex, including the ext/rtr/device flag bits,
+//! and `data` is the payload in hex. This format round-trips every packet field.
+//!
+//! Annotations are written as comment lines, `# <timestamp_ns> <text>`. Other lines starting with `#` are ignored.
+use std::io::{BufRead, BufReader, Read, Write};
+
+use rdxusb_protocol::RdxUsbPacket;
+
+use super::{hex_decode, hex_encode, Annotation, CaptureEntry, CaptureError, CaptureResult, PacketWrite};
+
+pub const CSV_HEADER: &str = "timestamp_ns,channel,arb_id,dlc,flags,data";
+
+/// Writes CSV captures.
+pub struct CsvWriter<W: Write> {
+    inner: W,
+    line: String,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Creates a writer, writing the header row immediately.
+    pub fn new(mut inner: W) -> CaptureResult<Self> {
+        writeln!(inner, "{CSV_HEADER}")?;
+        Ok(Self { inner, line: String::new() })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> PacketWrite for CsvWriter<W> {
+    fn write_packet(&mut self, packet: &RdxUsbPacket) -> CaptureResult<()> {
+        use std::fmt::Write;
+        let (ts, arb_id, flags) = (packet.timestamp_ns, packet.arb_id, packet.flags);
+        self.line.clear();
+        write!(self.line, "{ts},{},{arb_id:#010x},{},{flags:#06x},", packet.channel, packet.dlc).ok();
+        hex_encode(packet.payload(), &mut self.line);
+        self.line.push('\n');
+        self.inner.write_all(self.line.as_bytes())?;
+        Ok(())
+    }
+
+    fn write_annotation(&mut self, annotation: &Annotation) -> CaptureResult<()> {
+        writeln!(self.inner, "# {} {}", annotation.timestamp_ns, super::single_line(&annotation.text))?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> CaptureResult<()> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// Reads CSV captures.
+pub struct CsvReader<R: Read> {
+    lines: std::io::Lines<BufReader<R>>,
+    line_no: usize,
+}
+
+impl<R: Read> CsvReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { lines: BufReader::new(inner).lines(), line_no: 0 }
+    }
+
+    /// Reads the next packet or annotation, or `None` at end of file.
+    pub fn read_entry(&mut self) -> CaptureResult<Option<CaptureEntry>> {
+        loop {
+            let Some(line) = self.lines.next() else { return Ok(None); };
+            let line = line?;
+            self.line_no += 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("timestamp") { continue; }
+            if let Some(comment) = line.strip_prefix('#') {
+                match parse_annotation(comment) {
+                    Some(annotation) => return Ok(Some(CaptureEntry::Annotation(annotation))),
+                    None => continue,
+                }
+            }
+            return parse_line(line)
+                .map(|p| Some(CaptureEntry::Packet(p)))
+                .map_err(|msg| CaptureError::Parse { line: self.line_no, msg: msg.to_string() });
+        }
+    }
+
+    /// Turns the reader into an iterator over packets and annotations.
+    pub fn into_entries(mut self) -> impl Iterator<Item = CaptureResult<CaptureEntry>> {
+        std::iter::from_fn(move || self.read_entry().transpose())
+    }
+}
+
+impl<R: Read> Iterator for CsvReader<R> {
+    type Item = CaptureResult<RdxUsbPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.read_entry().transpose()? {
+                Ok(CaptureEntry::Packet(packet)) => return Some(Ok(packet)),
+                Ok(CaptureEntry::Annotation(_)) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn parse_annotation(comment: &str) -> Option<Annotation> {
+    let (ts, text) = comment.trim_start().split_once(' ').unwrap_or((comment.trim(), ""));
+    Some(Annotation { timestamp_ns: ts.parse().ok()?, text: text.to_string() })
+}
+
+fn parse_int<T: TryFrom<u64>>(s: &str) -> Option<T> {
+    let s = s.trim();
+    let v = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => s.parse().ok()?,
+    };
+    v.try_into().ok()
+}
+
+fn parse_line(line: &str) -> Result<RdxUsbPacket, &'static str> {
+    let mut cols = line.split(',');
+    let mut next = |name| cols.next().ok_or(name);
+    let mut packet: RdxUsbPacket = bytemuck::Zeroable::zeroed();
+    packet.timestamp_ns = parse_int(next("missing timestamp")?).ok_or("bad timestamp")?;
+    packet.channel = parse_int(next("missing channel")?).ok_or("bad channel")?;
+    packet.arb_id = parse_int(next("missing arb_id")?).ok_or("bad arb_id")?;
+    let dlc: u8 = parse_int(next("missing dlc")?).ok_or("bad dlc")?;
+    packet.flags = parse_int(next("missing flags")?).ok_or("bad flags")?;
+    hex_decode(next("missing data")?.trim(), &mut packet.data).ok_or("bad data")?;
+    packet.dlc = dlc;
+    packet.sanitize();
+    Ok(packet)
+}
@@ -0,0 +1,190 @@
+//! Tamper-evident native captures.
+//!
+//! A chained capture sets [`FLAG_CHAINED`] in its header and splits the file into segments ending in
+//! [`RECORD_CHAIN`] records. Each chain record holds `SHA-256(previous link || segment)`, where the first link
+//! is 32 zero bytes and a segment is every byte written since the previous chain record, starting with the file
+//! header. Segments end after every compressed block, or every [`super::native::INDEX_INTERVAL`] packets in
+//! uncompressed captures, so a capture cut short by a crash can still be verified up to its last link.
+//!
+//! Finishing the capture writes a [`RECORD_SEAL`] holding the final link, optionally signed with HMAC-SHA256.
+//! The time index follows the seal and isn't covered by it; it is only a seeking aid derived from the records.
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use super::native::{CAPTURE_MAGIC, CAPTURE_VERSION, FLAG_CHAINED, HEADER_SIZE, RECORD_CHAIN, RECORD_INDEX, RECORD_INDEX_TRAILER, RECORD_SEAL};
+use super::{CaptureError, CaptureResult};
+
+/// Seal flag set when the digest is followed by its HMAC-SHA256 signature.
+const SEAL_SIGNED: u8 = 1 << 0;
+
+/// Whether a [`super::CaptureWriter`] makes its capture tamper-evident.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum CaptureIntegrity {
+    #[default]
+    None,
+    /// Chain a hash over the records as they're written, so editing, removing or reordering any of them shows up
+    /// in [`verify`].
+    Chained,
+    /// Chain hashes like [`Self::Chained`], and sign the final digest with HMAC-SHA256 under this key, so the
+    /// chain can't be recomputed by someone without it.
+    Signed(Vec<u8>),
+}
+
+impl std::fmt::Debug for CaptureIntegrity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // keys stay out of logs
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Chained => write!(f, "Chained"),
+            Self::Signed(_) => write!(f, "Signed(..)"),
+        }
+    }
+}
+
+/// What [`verify`] found in a chained capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Chain records checked, not counting the seal.
+    pub links: u64,
+    /// Bytes from the start of the file covered by a checked link or the seal.
+    pub verified_bytes: u64,
+    /// Whether the capture ends with a seal. Captures that were never finished, e.g. because the recording process
+    /// died, have no seal, and anything past `verified_bytes` is unverified.
+    pub sealed: bool,
+    /// The final digest from the seal.
+    pub digest: Option<[u8; 32]>,
+    /// Whether the seal is signed.
+    pub signed: bool,
+}
+
+/// Checks the hash chain of a native capture, and its signature if `key` is given.
+///
+/// Fails with [`CaptureError::Tampered`] at the first record that doesn't match the chain, and with
+/// [`CaptureError::BadSignature`] if `key` is given but the seal is missing, unsigned or signed with another key.
+pub fn verify(mut input: impl Read, key: Option<&[u8]>) -> CaptureResult<IntegrityReport> {
+    let mut header = [0u8; HEADER_SIZE];
+    input.read_exact(&mut header).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => CaptureError::BadMagic,
+        _ => e.into(),
+    })?;
+    if header[..8] != CAPTURE_MAGIC { return Err(CaptureError::BadMagic); }
+    let version = u16::from_le_bytes([header[8], header[9]]);
+    if version > CAPTURE_VERSION { return Err(CaptureError::UnsupportedVersion(version)); }
+    if u16::from_le_bytes([header[10], header[11]]) & FLAG_CHAINED == 0 { return Err(CaptureError::NotChained); }
+
+    let mut chain = HashChain::new();
+    chain.update(&header);
+    let mut report = IntegrityReport { links: 0, verified_bytes: 0, sealed: false, digest: None, signed: false };
+    let mut offset = HEADER_SIZE as u64;
+    let mut payload = Vec::new();
+    loop {
+        let mut hdr = [0u8; 4];
+        match input.read_exact(&mut hdr) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let kind = u16::from_le_bytes([hdr[0], hdr[1]]);
+        payload.resize(u16::from_le_bytes([hdr[2], hdr[3]]) as usize, 0);
+        input.read_exact(&mut payload).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => CaptureError::Malformed("truncated record"),
+            _ => e.into(),
+        })?;
+        let end = offset + 4 + payload.len() as u64;
+        if report.sealed {
+            // only the index may follow the seal
+            if kind != RECORD_INDEX && kind != RECORD_INDEX_TRAILER { return Err(CaptureError::Tampered { offset }); }
+        } else if kind == RECORD_CHAIN {
+            if payload != chain.link() { return Err(CaptureError::Tampered { offset }); }
+            report.links += 1;
+            report.verified_bytes = end;
+        } else if kind == RECORD_SEAL {
+            // the seal's flags aren't hashed, so only the two layouts a writer produces are accepted
+            let seal_len = match payload.first() {
+                Some(&SEAL_SIGNED) => 68,
+                Some(0) => 36,
+                _ => return Err(CaptureError::Malformed("bad seal record")),
+            };
+            if payload.len() != seal_len || payload[1..4] != [0; 3] { return Err(CaptureError::Malformed("bad seal record")); }
+            report.signed = seal_len == 68;
+            let digest = chain.link();
+            if payload[4..36] != digest { return Err(CaptureError::Tampered { offset }); }
+            if let Some(key) = key {
+                let signature = payload.get(36..68).ok_or(CaptureError::BadSignature)?;
+                if !report.signed || seal_mac(key, &digest).verify_slice(signature).is_err() {
+                    return Err(CaptureError::BadSignature);
+                }
+            }
+            report.sealed = true;
+            report.digest = Some(digest);
+            report.verified_bytes = end;
+        } else {
+            chain.update(&hdr);
+            chain.update(&payload);
+        }
+        offset = end;
+    }
+    if key.is_some() && !report.sealed { return Err(CaptureError::BadSignature); }
+    Ok(report)
+}
+
+/// Verifies a native capture file like [`verify`], decompressing `.zst` files if the `zstd` feature is enabled.
+pub fn verify_file(path: impl AsRef<Path>, key: Option<&[u8]>) -> CaptureResult<IntegrityReport> {
+    let path = path.as_ref();
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zst")) {
+        #[cfg(feature = "zstd")]
+        return verify(zstd::Decoder::with_buffer(file)?, key);
+        #[cfg(not(feature = "zstd"))]
+        return Err(CaptureError::UnknownFormat(path.display().to_string()));
+    }
+    verify(file, key)
+}
+
+/// The running hash of a chained capture, see the module docs.
+pub(crate) struct HashChain {
+    hasher: Sha256,
+}
+
+impl HashChain {
+    pub(crate) fn new() -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update([0; 32]);
+        Self { hasher }
+    }
+
+    /// Hashes bytes written to the capture.
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Ends the current segment, returning its link and starting the next segment from it.
+    pub(crate) fn link(&mut self) -> [u8; 32] {
+        let link: [u8; 32] = self.hasher.finalize_reset().into();
+        self.hasher.update(link);
+        link
+    }
+
+    /// Ends the chain, returning the [`RECORD_SEAL`] payload, signed if there is a key.
+    pub(crate) fn seal(mut self, key: Option<&[u8]>) -> Vec<u8> {
+        let digest = self.link();
+        let mut payload = Vec::with_capacity(68);
+        payload.push(if key.is_some() { SEAL_SIGNED } else { 0 });
+        payload.extend_from_slice(&[0; 3]);
+        payload.extend_from_slice(&digest);
+        if let Some(key) = key {
+            payload.extend_from_slice(&seal_mac(key, &digest).finalize().into_bytes());
+        }
+        payload
+    }
+}
+
+/// HMAC-SHA256 keyed for signing or checking a seal.
+fn seal_mac(key: &[u8], digest: &[u8; 32]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(digest);
+    mac
+}
@@ -0,0 +1,287 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use rdxusb_protocol::RdxUsbPacket;
+
+/// The native `.rdxcap` capture format.
+pub mod native;
+/// SocketCAN `candump -l` log files.
+pub mod candump;
+/// CSV with one frame per row.
+pub mod csv;
+/// pcapng files using the SocketCAN link type, readable by Wireshark.
+pub mod pcapng;
+/// Continuous recording into rotating capture files.
+pub mod recorder;
+/// Playback of native captures with time-based seeking, and synchronized playback of several at once.
+pub mod replay;
+/// Recording bursts of traffic around trigger frames.
+pub mod trigger;
+/// Hash chained, optionally signed captures that show whether they were modified.
+#[cfg(feature = "integrity")]
+pub mod integrity;
+
+pub use native::{BlockCodec, CaptureReader, CaptureWriter, IndexEntry};
+pub use replay::{Replayer, SyncPlayer};
+pub use recorder::{CaptureCompression, Recorder, RotationPolicy};
+pub use trigger::TriggeredRecorder;
+#[cfg(feature = "integrity")]
+pub use integrity::{CaptureIntegrity, IntegrityReport};
+
+/// Capture file formats that packets can be converted between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// The native `.rdxcap` format, which preserves every packet field.
+    Native,
+    /// `candump -l` logs.
+    Candump,
+    /// CSV (timestamp, channel, id, dlc, flags, hex payload).
+    Csv,
+    /// pcapng with the SocketCAN link type.
+    Pcapng,
+}
+
+impl CaptureFormat {
+    /// Guesses the format from a file extension, ignoring any `.zst` compression suffix.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+        let path = match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("zst") => Path::new(path.file_stem()?),
+            _ => path,
+        };
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "rdxcap" => Self::Native,
+            "log" | "candump" => Self::Candump,
+            "csv" => Self::Csv,
+            "pcapng" => Self::Pcapng,
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for CaptureFormat {
+    type Err = CaptureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "native" | "rdxcap" => Self::Native,
+            "candump" => Self::Candump,
+            "csv" => Self::Csv,
+            "pcapng" => Self::Pcapng,
+            _ => return Err(CaptureError::UnknownFormat(s.to_string())),
+        })
+    }
+}
+
+/// Errors from reading, writing, or converting captures.
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(std::io::Error),
+    /// The file doesn't start with the expected magic bytes.
+    BadMagic,
+    /// The file was written by a newer, incompatible version of the format.
+    UnsupportedVersion(u16),
+    /// A text format line couldn't be parsed.
+    Parse { line: usize, msg: String },
+    /// A binary format block couldn't be parsed.
+    Malformed(&'static str),
+    /// The format name isn't recognized.
+    UnknownFormat(String),
+    /// The capture uses a compression codec this build doesn't support.
+    UnsupportedCompression(u8),
+    /// A playback target refused a packet, see [`SyncPlayer`].
+    Sink(String),
+    /// The capture isn't hash chained, so it can't be verified.
+    NotChained,
+    /// The record at this file offset doesn't match the capture's hash chain.
+    Tampered { offset: u64 },
+    /// The capture's seal is missing, unsigned, or signed with a different key.
+    BadSignature,
+}
+
+impl From<std::io::Error> for CaptureError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::Io(error) => write!(f, "io error: {error}"),
+            CaptureError::BadMagic => write!(f, "Not a capture file"),
+            CaptureError::UnsupportedVersion(v) => write!(f, "Unsupported capture version {v}"),
+            CaptureError::Parse { line, msg } => write!(f, "Parse error on line {line}: {msg}"),
+            CaptureError::Malformed(msg) => write!(f, "Malformed capture: {msg}"),
+            CaptureError::UnknownFormat(name) => write!(f, "Unknown capture format {name:?}"),
+            CaptureError::UnsupportedCompression(codec) => write!(f, "Unsupported compression codec {codec}"),
+            CaptureError::Sink(msg) => write!(f, "Playback target failed: {msg}"),
+            CaptureError::NotChained => write!(f, "Capture is not hash chained"),
+            CaptureError::Tampered { offset } => write!(f, "Capture was modified at offset {offset}"),
+            CaptureError::BadSignature => write!(f, "Capture signature is missing or invalid"),
+        }
+    }
+}
+impl core::error::Error for CaptureError {}
+
+pub type CaptureResult<T> = Result<T, CaptureError>;
+
+/// A note an application placed in a capture, such as "auton started", to correlate it with the traffic around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Position in the capture, in the same (device) time base as packet timestamps.
+    pub timestamp_ns: u64,
+    pub text: String,
+}
+
+/// Anything a capture can hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureEntry {
+    Packet(RdxUsbPacket),
+    Annotation(Annotation),
+}
+
+/// A destination for captured packets in any [`CaptureFormat`].
+pub trait PacketWrite {
+    fn write_packet(&mut self, packet: &RdxUsbPacket) -> CaptureResult<()>;
+    fn flush(&mut self) -> CaptureResult<()>;
+
+    /// Writes an annotation. Formats that can't hold annotations ([`CaptureFormat::Pcapng`]) silently drop them.
+    fn write_annotation(&mut self, annotation: &Annotation) -> CaptureResult<()> {
+        let _ = annotation;
+        Ok(())
+    }
+
+    fn write_entry(&mut self, entry: &CaptureEntry) -> CaptureResult<()> {
+        match entry {
+            CaptureEntry::Packet(packet) => self.write_packet(packet),
+            CaptureEntry::Annotation(annotation) => self.write_annotation(annotation),
+        }
+    }
+
+    /// Flushes and writes any trailing data, such as an index. No packets may be written afterwards.
+    fn finish(&mut self) -> CaptureResult<()> {
+        self.flush()
+    }
+}
+
+/// Iterator over packets read from a capture in any [`CaptureFormat`].
+pub type PacketRead<'a> = Box<dyn Iterator<Item = CaptureResult<RdxUsbPacket>> + 'a>;
+
+/// Iterator over packets and annotations read from a capture in any [`CaptureFormat`].
+pub type EntryRead<'a> = Box<dyn Iterator<Item = CaptureResult<CaptureEntry>> + 'a>;
+
+/// Opens a reader over `input` for the given format.
+pub fn packet_reader<'a>(input: impl Read + 'a, format: CaptureFormat) -> CaptureResult<PacketRead<'a>> {
+    Ok(match format {
+        CaptureFormat::Native => Box::new(native::CaptureReader::new(input)?),
+        CaptureFormat::Candump => Box::new(candump::CandumpReader::new(input)),
+        CaptureFormat::Csv => Box::new(csv::CsvReader::new(input)),
+        CaptureFormat::Pcapng => Box::new(pcapng::PcapngReader::new(input)),
+    })
+}
+
+/// Opens a reader over `input` for the given format that also returns annotations.
+pub fn entry_reader<'a>(input: impl Read + 'a, format: CaptureFormat) -> CaptureResult<EntryRead<'a>> {
+    Ok(match format {
+        CaptureFormat::Native => Box::new(native::CaptureReader::new(input)?.into_entries()),
+        CaptureFormat::Candump => Box::new(candump::CandumpReader::new(input).into_entries()),
+        CaptureFormat::Csv => Box::new(csv::CsvReader::new(input).into_entries()),
+        CaptureFormat::Pcapng => Box::new(pcapng::PcapngReader::new(input).map(|p| p.map(CaptureEntry::Packet))),
+    })
+}
+
+/// Opens a writer over `output` for the given format, writing any file header immediately.
+pub fn packet_writer<'a>(output: impl Write + 'a, format: CaptureFormat) -> CaptureResult<Box<dyn PacketWrite + 'a>> {
+    Ok(match format {
+        CaptureFormat::Native => Box::new(native::CaptureWriter::new(output)?),
+        CaptureFormat::Candump => Box::new(candump::CandumpWriter::new(output)),
+        CaptureFormat::Csv => Box::new(csv::CsvWriter::new(output)?),
+        CaptureFormat::Pcapng => Box::new(pcapng::PcapngWriter::new(output)?),
+    })
+}
+
+/// Converts a capture between formats, returning the number of packets converted.
+///
+/// Formats other than [`CaptureFormat::Native`] can't represent every packet field;
+/// see the individual format modules for what is lost. Annotations are carried over unless either side is
+/// [`CaptureFormat::Pcapng`].
+pub fn convert(input: impl Read, from: CaptureFormat, output: impl Write, to: CaptureFormat) -> CaptureResult<u64> {
+    let reader = entry_reader(input, from)?;
+    let mut writer = packet_writer(output, to)?;
+    copy_entries(reader, writer.as_mut())
+}
+
+fn copy_entries(reader: EntryRead<'_>, writer: &mut dyn PacketWrite) -> CaptureResult<u64> {
+    let mut count = 0u64;
+    for entry in reader {
+        let entry = entry?;
+        writer.write_entry(&entry)?;
+        if let CaptureEntry::Packet(_) = entry { count += 1; }
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+/// Opens a capture file for reading, guessing the format from the file extension.
+///
+/// Files ending in `.zst` are decompressed if the `zstd` feature is enabled.
+pub fn open_file(path: impl AsRef<Path>) -> CaptureResult<PacketRead<'static>> {
+    Ok(Box::new(open_file_entries(path)?.filter_map(|entry| match entry {
+        Ok(CaptureEntry::Packet(packet)) => Some(Ok(packet)),
+        Ok(CaptureEntry::Annotation(_)) => None,
+        Err(e) => Some(Err(e)),
+    })))
+}
+
+/// Opens a capture file like [`open_file`], also returning its annotations.
+pub fn open_file_entries(path: impl AsRef<Path>) -> CaptureResult<EntryRead<'static>> {
+    let path = path.as_ref();
+    let format = CaptureFormat::from_path(path).ok_or_else(|| CaptureError::UnknownFormat(path.display().to_string()))?;
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zst")) {
+        #[cfg(feature = "zstd")]
+        return entry_reader(zstd::Decoder::with_buffer(file)?, format);
+        #[cfg(not(feature = "zstd"))]
+        return Err(CaptureError::UnknownFormat(path.display().to_string()));
+    }
+    entry_reader(file, format)
+}
+
+/// Converts a capture file, guessing formats from the file extensions.
+pub fn convert_file(input: impl AsRef<Path>, output: impl AsRef<Path>) -> CaptureResult<u64> {
+    let output = output.as_ref();
+    let to = CaptureFormat::from_path(output).ok_or_else(|| CaptureError::UnknownFormat(output.display().to_string()))?;
+    let reader = open_file_entries(input)?;
+    let mut writer = packet_writer(std::io::BufWriter::new(std::fs::File::create(output)?), to)?;
+    copy_entries(reader, writer.as_mut())
+}
+
+/// Folds line breaks into spaces, for annotations in line-based formats.
+pub(crate) fn single_line(text: &str) -> std::borrow::Cow<'_, str> {
+    if text.contains(['\r', '\n']) {
+        text.replace(['\r', '\n'], " ").into()
+    } else {
+        text.into()
+    }
+}
+
+pub(crate) fn hex_encode(data: &[u8], out: &mut String) {
+    use std::fmt::Write;
+    for b in data {
+        write!(out, "{b:02X}").ok();
+    }
+}
+
+pub(crate) fn hex_decode(s: &str, out: &mut [u8]) -> Option<usize> {
+    if s.len() & 1 != 0 || s.len() / 2 > out.len() { return None; }
+    for (i, chunk) in s.as_bytes().chunks(2).enumerate() {
+        let hi = (chunk[0] as char).to_digit(16)?;
+        let lo = (chunk[1] as char).to_digit(16)?;
+        out[i] = (hi << 4 | lo) as u8;
+    }
+    Some(s.len() / 2)
+}
@@ -0,0 +1,532 @@
+//! Layout: a 16-byte file header followed by records.
+//!
+//! | offset | size | field |
+//! |---|---|---|
+//! | 0 | 8 | magic, `RDXCAP\0\0` |
+//! | 8 | 2 | format version (LE) |
+//! | 10 | 2 | header flags (LE), see [`FLAG_BLOCKS`] |
+//! | 12 | 4 | reserved |
+//!
+//! Each record is a `u16` kind and `u16` length (both LE) followed by `length` bytes of payload.
+//! Readers skip record kinds they don't know, so new kinds can be added without a version bump.
+//!
+//! Compressed captures wrap runs of records in [`RECORD_BLOCK`] records. Each block starts on a record
+//! boundary and stores the timestamp of its first packet, so a reader can seek to a block and decode
+//! from there without touching the rest of the file.
+//!
+//! [`RECORD_ANNOTATION`] records hold application notes; readers that only want packets skip them.
+//!
+//! Finished captures end with [`RECORD_INDEX`] records mapping timestamps to file offsets,
+//! followed by a fixed-size [`RECORD_INDEX_TRAILER`] pointing at the first of them.
+//!
+//! Tamper-evident captures interleave [`RECORD_CHAIN`] records and end in a [`RECORD_SEAL`], see
+//! `super::integrity` (`integrity` feature). Readers without it skip them like any other unknown record.
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+
+use rdxusb_protocol::RdxUsbPacket;
+
+#[cfg(feature = "integrity")]
+use super::integrity::{CaptureIntegrity, HashChain};
+use super::{Annotation, CaptureEntry, CaptureError, CaptureResult, PacketWrite};
+
+/// Magic bytes at the start of every native capture.
+pub const CAPTURE_MAGIC: [u8; 8] = *b"RDXCAP\0\0";
+/// Current native capture format version.
+pub const CAPTURE_VERSION: u16 = 1;
+/// Size of the file header.
+pub const HEADER_SIZE: usize = 16;
+
+/// Header flag set when the file contains compressed blocks.
+pub const FLAG_BLOCKS: u16 = 1 << 0;
+/// Header flag set when the file is hash chained, see `super::integrity`.
+pub const FLAG_CHAINED: u16 = 1 << 1;
+
+/// Record holding a raw [`RdxUsbPacket`].
+pub const RECORD_PACKET: u16 = 1;
+/// Record holding a compressed run of other records.
+///
+/// The payload is a 16-byte block header (codec `u8`, 3 reserved bytes, uncompressed length `u32`,
+/// first packet timestamp `u64`, all LE) followed by the compressed records.
+pub const RECORD_BLOCK: u16 = 2;
+/// Record holding index entries: pairs of first packet timestamp `u64` and record file offset `u64`, both LE.
+pub const RECORD_INDEX: u16 = 3;
+/// Last record of an indexed file: the file offset `u64` (LE) of the first [`RECORD_INDEX`] record, then `RIDX`.
+pub const RECORD_INDEX_TRAILER: u16 = 4;
+/// Size of the [`RECORD_INDEX_TRAILER`] record, including its record header.
+pub const INDEX_TRAILER_SIZE: usize = 16;
+const INDEX_TRAILER_MAGIC: [u8; 4] = *b"RIDX";
+/// Record holding an [`Annotation`]: its timestamp `u64` (LE) followed by the UTF-8 text.
+pub const RECORD_ANNOTATION: u16 = 5;
+/// Record holding the 32-byte SHA-256 link that ends a segment of a chained capture.
+pub const RECORD_CHAIN: u16 = 6;
+/// Record sealing a chained capture: flags `u8` (bit 0 set if signed), 3 reserved bytes, the 32-byte final digest,
+/// then its 32-byte HMAC-SHA256 signature if signed.
+pub const RECORD_SEAL: u16 = 7;
+/// Uncompressed captures get an index entry every this many packets.
+pub const INDEX_INTERVAL: u64 = 1024;
+
+/// Size of the [`RECORD_BLOCK`] header.
+pub const BLOCK_HEADER_SIZE: usize = 16;
+/// Records are buffered until a block reaches this many uncompressed bytes.
+pub const BLOCK_SIZE: usize = 32 * 1024;
+/// Largest uncompressed block a writer produces: just under [`BLOCK_SIZE`] plus the largest record.
+const MAX_BLOCK_RAW_LEN: usize = BLOCK_SIZE + 4 + u16::MAX as usize;
+
+#[cfg(feature = "zstd")]
+const CODEC_ZSTD: u8 = 1;
+#[cfg(feature = "lz4")]
+const CODEC_LZ4: u8 = 2;
+
+/// Compression used for [`RECORD_BLOCK`] records.
+///
+/// Each codec is behind the feature of the same name; without either, no block compression is available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockCodec {
+    /// zstd at the given level.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl BlockCodec {
+    fn id(self) -> u8 {
+        match self {
+            #[cfg(feature = "zstd")]
+            BlockCodec::Zstd(_) => CODEC_ZSTD,
+            #[cfg(feature = "lz4")]
+            BlockCodec::Lz4 => CODEC_LZ4,
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+    fn compress(self, raw: &[u8]) -> CaptureResult<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            BlockCodec::Zstd(level) => Ok(zstd::bulk::compress(raw, level)?),
+            #[cfg(feature = "lz4")]
+            BlockCodec::Lz4 => Ok(lz4_flex::block::compress(raw)),
+        }
+    }
+}
+
+/// An entry in a capture's time index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Timestamp of the first packet at `offset`.
+    pub timestamp_ns: u64,
+    /// File offset of a record boundary.
+    pub offset: u64,
+}
+
+#[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+fn decompress(codec: u8, data: &[u8], raw_len: usize) -> CaptureResult<Vec<u8>> {
+    // the length sizes the output buffer, so a corrupt one mustn't allocate more than any writer could have needed
+    if raw_len > MAX_BLOCK_RAW_LEN { return Err(CaptureError::Malformed("block too large")); }
+    let out: CaptureResult<Vec<u8>> = match codec {
+        #[cfg(feature = "zstd")]
+        CODEC_ZSTD => zstd::bulk::decompress(data, raw_len).map_err(Into::into),
+        #[cfg(feature = "lz4")]
+        CODEC_LZ4 => lz4_flex::block::decompress(data, raw_len).map_err(|_| CaptureError::Malformed("bad lz4 block")),
+        _ => Err(CaptureError::UnsupportedCompression(codec)),
+    };
+    let out = out?;
+    if out.len() != raw_len { return Err(CaptureError::Malformed("block length mismatch")); }
+    Ok(out)
+}
+
+/// The file header of a capture, with [`FLAG_BLOCKS`] set if it has a codec.
+fn file_header(codec: Option<BlockCodec>, mut flags: u16) -> [u8; HEADER_SIZE] {
+    if codec.is_some() { flags |= FLAG_BLOCKS; }
+    let mut header = [0u8; HEADER_SIZE];
+    header[..8].copy_from_slice(&CAPTURE_MAGIC);
+    header[8..10].copy_from_slice(&CAPTURE_VERSION.to_le_bytes());
+    header[10..12].copy_from_slice(&flags.to_le_bytes());
+    header
+}
+
+/// Writes native `.rdxcap` captures.
+///
+/// With a [`BlockCodec`], records are buffered and written in compressed blocks;
+/// call [`PacketWrite::flush`] or [`CaptureWriter::into_inner`] to write out the last partial block.
+/// The time index is only written by [`PacketWrite::finish`] or [`CaptureWriter::finish_into_inner`], which
+/// also seal tamper-evident captures.
+pub struct CaptureWriter<W: Write> {
+    inner: W,
+    codec: Option<BlockCodec>,
+    block: Vec<u8>,
+    block_first_ts: u64,
+    /// bytes written to `inner` so far
+    offset: u64,
+    index: Vec<IndexEntry>,
+    /// packets written since the last uncompressed index entry
+    since_index: u64,
+    finished: bool,
+    #[cfg(feature = "integrity")]
+    chain: Option<HashChain>,
+    /// what the seal is signed with
+    #[cfg(feature = "integrity")]
+    key: Option<Vec<u8>>,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Creates a writer, writing the file header immediately.
+    pub fn new(inner: W) -> CaptureResult<Self> {
+        Self::with_codec(inner, None)
+    }
+
+    /// Creates a writer that compresses records into blocks with `codec`, if any.
+    pub fn with_codec(inner: W, codec: Option<BlockCodec>) -> CaptureResult<Self> {
+        Self::start(inner, codec, &file_header(codec, 0))
+    }
+
+    /// Creates a writer like [`Self::with_codec`] that makes the capture tamper-evident as `integrity` asks.
+    #[cfg(feature = "integrity")]
+    pub fn with_integrity(inner: W, codec: Option<BlockCodec>, integrity: CaptureIntegrity) -> CaptureResult<Self> {
+        let key = match integrity {
+            CaptureIntegrity::None => return Self::with_codec(inner, codec),
+            CaptureIntegrity::Chained => None,
+            CaptureIntegrity::Signed(key) => Some(key),
+        };
+        let header = file_header(codec, FLAG_CHAINED);
+        let mut chain = HashChain::new();
+        chain.update(&header);
+        let mut writer = Self::start(inner, codec, &header)?;
+        writer.chain = Some(chain);
+        writer.key = key;
+        Ok(writer)
+    }
+
+    fn start(mut inner: W, codec: Option<BlockCodec>, header: &[u8; HEADER_SIZE]) -> CaptureResult<Self> {
+        inner.write_all(header)?;
+        Ok(Self {
+            inner,
+            codec,
+            block: Vec::new(),
+            block_first_ts: 0,
+            offset: HEADER_SIZE as u64,
+            index: Vec::new(),
+            since_index: 0,
+            finished: false,
+            #[cfg(feature = "integrity")]
+            chain: None,
+            #[cfg(feature = "integrity")]
+            key: None,
+        })
+    }
+
+    /// Writes a record of an arbitrary kind.
+    pub fn write_record(&mut self, kind: u16, payload: &[u8]) -> CaptureResult<()> {
+        let len: u16 = payload.len().try_into().map_err(|_| CaptureError::Malformed("record too large"))?;
+        if self.codec.is_some() {
+            self.block.extend_from_slice(&kind.to_le_bytes());
+            self.block.extend_from_slice(&len.to_le_bytes());
+            self.block.extend_from_slice(payload);
+            if self.block.len() >= BLOCK_SIZE {
+                self.flush_block()?;
+            }
+            return Ok(());
+        }
+        self.write_raw_record(kind, payload)
+    }
+
+    fn write_raw_record(&mut self, kind: u16, payload: &[u8]) -> CaptureResult<()> {
+        let len: u16 = payload.len().try_into().map_err(|_| CaptureError::Malformed("record too large"))?;
+        #[cfg(feature = "integrity")]
+        if let Some(chain) = &mut self.chain {
+            chain.update(&kind.to_le_bytes());
+            chain.update(&len.to_le_bytes());
+            chain.update(payload);
+        }
+        self.write_unhashed_record(kind, len, payload)
+    }
+
+    /// Writes a record without adding it to the hash chain, for the chain's own records.
+    fn write_unhashed_record(&mut self, kind: u16, len: u16, payload: &[u8]) -> CaptureResult<()> {
+        self.inner.write_all(&kind.to_le_bytes())?;
+        self.inner.write_all(&len.to_le_bytes())?;
+        self.inner.write_all(payload)?;
+        self.offset += 4 + payload.len() as u64;
+        Ok(())
+    }
+
+    /// Compresses and writes out any buffered records.
+    fn flush_block(&mut self) -> CaptureResult<()> {
+        let Some(codec) = self.codec else { return Ok(()); };
+        if self.block.is_empty() { return Ok(()); }
+        let compressed = codec.compress(&self.block)?;
+        let mut payload = Vec::with_capacity(BLOCK_HEADER_SIZE + compressed.len());
+        payload.push(codec.id());
+        payload.extend_from_slice(&[0; 3]);
+        payload.extend_from_slice(&(self.block.len() as u32).to_le_bytes());
+        payload.extend_from_slice(&self.block_first_ts.to_le_bytes());
+        payload.extend_from_slice(&compressed);
+        self.block.clear();
+        self.index.push(IndexEntry { timestamp_ns: self.block_first_ts, offset: self.offset });
+        self.write_raw_record(RECORD_BLOCK, &payload)?;
+        self.write_link()
+    }
+
+    /// Ends the current segment of a chained capture.
+    fn write_link(&mut self) -> CaptureResult<()> {
+        #[cfg(feature = "integrity")]
+        if let Some(chain) = &mut self.chain {
+            let link = chain.link();
+            return self.write_unhashed_record(RECORD_CHAIN, link.len() as u16, &link);
+        }
+        Ok(())
+    }
+
+    fn write_index(&mut self) -> CaptureResult<()> {
+        if self.finished { return Ok(()); }
+        self.flush_block()?;
+        self.finished = true;
+        #[cfg(feature = "integrity")]
+        if let Some(chain) = self.chain.take() {
+            let seal = chain.seal(self.key.as_deref());
+            self.write_unhashed_record(RECORD_SEAL, seal.len() as u16, &seal)?;
+        }
+        let index_offset = self.offset;
+        let index = std::mem::take(&mut self.index);
+        for chunk in index.chunks(u16::MAX as usize / 16) {
+            let payload: Vec<u8> = chunk.iter()
+                .flat_map(|e| e.timestamp_ns.to_le_bytes().into_iter().chain(e.offset.to_le_bytes()))
+                .collect();
+            self.write_raw_record(RECORD_INDEX, &payload)?;
+        }
+        let mut trailer = [0u8; INDEX_TRAILER_SIZE - 4];
+        trailer[..8].copy_from_slice(&index_offset.to_le_bytes());
+        trailer[8..].copy_from_slice(&INDEX_TRAILER_MAGIC);
+        self.write_raw_record(RECORD_INDEX_TRAILER, &trailer)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Writes out any buffered block and returns the underlying writer, without writing the index.
+    pub fn into_inner(mut self) -> CaptureResult<W> {
+        self.flush_block()?;
+        Ok(self.inner)
+    }
+
+    /// Writes out any buffered block and the time index, and returns the underlying writer.
+    pub fn finish_into_inner(mut self) -> CaptureResult<W> {
+        self.write_index()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> PacketWrite for CaptureWriter<W> {
+    fn write_packet(&mut self, packet: &RdxUsbPacket) -> CaptureResult<()> {
+        if self.finished { return Err(CaptureError::Malformed("capture already finished")); }
+        if self.block.is_empty() {
+            self.block_first_ts = packet.timestamp_ns;
+        }
+        if self.codec.is_none() && self.since_index == 0 {
+            if !self.index.is_empty() {
+                self.write_link()?;
+            }
+            self.index.push(IndexEntry { timestamp_ns: packet.timestamp_ns, offset: self.offset });
+        }
+        self.since_index = (self.since_index + 1) % INDEX_INTERVAL;
+        self.write_record(RECORD_PACKET, bytemuck::bytes_of(packet))
+    }
+
+    /// Annotations longer than a record can hold are truncated.
+    fn write_annotation(&mut self, annotation: &Annotation) -> CaptureResult<()> {
+        if self.finished { return Err(CaptureError::Malformed("capture already finished")); }
+        let mut text = annotation.text.as_str();
+        let mut max_len = u16::MAX as usize - 8;
+        if text.len() > max_len {
+            while !text.is_char_boundary(max_len) { max_len -= 1; }
+            text = &text[..max_len];
+        }
+        let mut payload = Vec::with_capacity(8 + text.len());
+        payload.extend_from_slice(&annotation.timestamp_ns.to_le_bytes());
+        payload.extend_from_slice(text.as_bytes());
+        self.write_record(RECORD_ANNOTATION, &payload)
+    }
+
+    fn flush(&mut self) -> CaptureResult<()> {
+        self.flush_block()?;
+        Ok(self.inner.flush()?)
+    }
+
+    fn finish(&mut self) -> CaptureResult<()> {
+        self.write_index()?;
+        Ok(self.inner.flush()?)
+    }
+}
+
+/// Reads native `.rdxcap` captures, decompressing blocks as they're reached.
+pub struct CaptureReader<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+    block: Vec<u8>,
+    block_pos: usize,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Creates a reader, validating the file header.
+    pub fn new(mut inner: R) -> CaptureResult<Self> {
+        let mut header = [0u8; HEADER_SIZE];
+        inner.read_exact(&mut header).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => CaptureError::BadMagic,
+            _ => e.into(),
+        })?;
+        if header[..8] != CAPTURE_MAGIC { return Err(CaptureError::BadMagic); }
+        let version = u16::from_le_bytes([header[8], header[9]]);
+        if version > CAPTURE_VERSION { return Err(CaptureError::UnsupportedVersion(version)); }
+        Ok(Self { inner, buf: Vec::new(), block: Vec::new(), block_pos: 0 })
+    }
+
+    /// Reads the next record from the file itself, without looking inside blocks.
+    fn read_raw_record(&mut self) -> CaptureResult<Option<u16>> {
+        let mut hdr = [0u8; 4];
+        match self.inner.read_exact(&mut hdr) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let kind = u16::from_le_bytes([hdr[0], hdr[1]]);
+        let len = u16::from_le_bytes([hdr[2], hdr[3]]) as usize;
+        self.buf.resize(len, 0);
+        self.inner.read_exact(&mut self.buf).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => CaptureError::Malformed("truncated record"),
+            _ => e.into(),
+        })?;
+        Ok(Some(kind))
+    }
+
+    /// Reads the next record as `(kind, payload)`, or `None` at end of file.
+    ///
+    /// Records inside compressed blocks are returned individually; block records themselves are never returned.
+    pub fn read_record(&mut self) -> CaptureResult<Option<(u16, &[u8])>> {
+        loop {
+            if self.block_pos < self.block.len() {
+                let rest = &self.block[self.block_pos..];
+                if rest.len() < 4 { return Err(CaptureError::Malformed("truncated record in block")); }
+                let kind = u16::from_le_bytes([rest[0], rest[1]]);
+                let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+                let start = self.block_pos + 4;
+                if start + len > self.block.len() { return Err(CaptureError::Malformed("truncated record in block")); }
+                self.block_pos = start + len;
+                return Ok(Some((kind, &self.block[start..start + len])));
+            }
+            match self.read_raw_record()? {
+                None => return Ok(None),
+                Some(RECORD_BLOCK) => {
+                    if self.buf.len() < BLOCK_HEADER_SIZE { return Err(CaptureError::Malformed("short block header")); }
+                    let raw_len = u32::from_le_bytes(self.buf[4..8].try_into().unwrap()) as usize;
+                    self.block = decompress(self.buf[0], &self.buf[BLOCK_HEADER_SIZE..], raw_len)?;
+                    self.block_pos = 0;
+                }
+                Some(kind) => return Ok(Some((kind, &self.buf))),
+            }
+        }
+    }
+
+    /// Reads the next packet, skipping other records, or `None` at end of file.
+    pub fn read_packet(&mut self) -> CaptureResult<Option<RdxUsbPacket>> {
+        while let Some((kind, payload)) = self.read_record()? {
+            if kind != RECORD_PACKET { continue; }
+            return parse_packet(payload).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Reads the next packet or annotation, skipping other records, or `None` at end of file.
+    pub fn read_entry(&mut self) -> CaptureResult<Option<CaptureEntry>> {
+        while let Some((kind, payload)) = self.read_record()? {
+            match kind {
+                RECORD_PACKET => return parse_packet(payload).map(|p| Some(CaptureEntry::Packet(p))),
+                RECORD_ANNOTATION => {
+                    if payload.len() < 8 { return Err(CaptureError::Malformed("short annotation record")); }
+                    return Ok(Some(CaptureEntry::Annotation(Annotation {
+                        timestamp_ns: u64::from_le_bytes(payload[..8].try_into().unwrap()),
+                        text: String::from_utf8_lossy(&payload[8..]).into_owned(),
+                    })));
+                }
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Turns the reader into an iterator over packets and annotations.
+    pub fn into_entries(mut self) -> impl Iterator<Item = CaptureResult<CaptureEntry>> {
+        std::iter::from_fn(move || self.read_entry().transpose())
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek> CaptureReader<R> {
+    /// Reads the time index from the end of the file, or `None` if the capture wasn't finished with one.
+    ///
+    /// Leaves the reader positioned at the first record.
+    pub fn read_index(&mut self) -> CaptureResult<Option<Vec<IndexEntry>>> {
+        let index = self.read_index_inner();
+        self.seek_to_offset(HEADER_SIZE as u64)?;
+        index
+    }
+
+    fn read_index_inner(&mut self) -> CaptureResult<Option<Vec<IndexEntry>>> {
+        let len = self.inner.seek(SeekFrom::End(0))?;
+        if len < (HEADER_SIZE + INDEX_TRAILER_SIZE) as u64 { return Ok(None); }
+        self.inner.seek(SeekFrom::End(-(INDEX_TRAILER_SIZE as i64)))?;
+        let mut trailer = [0u8; INDEX_TRAILER_SIZE];
+        self.inner.read_exact(&mut trailer)?;
+        let kind = u16::from_le_bytes([trailer[0], trailer[1]]);
+        let rec_len = u16::from_le_bytes([trailer[2], trailer[3]]) as usize;
+        if kind != RECORD_INDEX_TRAILER || rec_len != INDEX_TRAILER_SIZE - 4 || trailer[12..] != INDEX_TRAILER_MAGIC {
+            return Ok(None);
+        }
+        let index_offset = u64::from_le_bytes(trailer[4..12].try_into().unwrap());
+        if index_offset < HEADER_SIZE as u64 || index_offset > len { return Err(CaptureError::Malformed("bad index offset")); }
+
+        self.seek_to_offset(index_offset)?;
+        let mut index = Vec::new();
+        while let Some(kind) = self.read_raw_record()? {
+            match kind {
+                RECORD_INDEX => {
+                    index.extend(self.buf.chunks_exact(16).map(|e| IndexEntry {
+                        timestamp_ns: u64::from_le_bytes(e[..8].try_into().unwrap()),
+                        offset: u64::from_le_bytes(e[8..].try_into().unwrap()),
+                    }));
+                }
+                RECORD_INDEX_TRAILER => break,
+                _ => return Err(CaptureError::Malformed("unexpected record in index")),
+            }
+        }
+        Ok(Some(index))
+    }
+
+    /// Moves to a record boundary, such as an [`IndexEntry::offset`], discarding any partially read block.
+    pub fn seek_to_offset(&mut self, offset: u64) -> CaptureResult<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.block.clear();
+        self.block_pos = 0;
+        Ok(())
+    }
+}
+
+fn parse_packet(payload: &[u8]) -> CaptureResult<RdxUsbPacket> {
+    bytemuck::try_pod_read_unaligned(payload)
+        .map(|mut packet: RdxUsbPacket| {
+            packet.sanitize();
+            packet
+        })
+        .map_err(|_| CaptureError::Malformed("bad packet record size"))
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = CaptureResult<RdxUsbPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_packet().transpose()
+    }
+}
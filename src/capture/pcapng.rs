@@ -0,0 +1,308 @@
+//! Each channel is written as its own interface (`can<channel>`) using `LINKTYPE_CAN_SOCKETCAN`,
+//! with nanosecond timestamps. FD-flagged packets and payloads longer than 8 bytes are written as CAN FD frames.
+//!
+//! SocketCAN uses the bit that [`MESSAGE_ARB_ID_DEVICE`] occupies for error frames, so the device bit
+//! and packet flags other than the CAN FD ones are dropped on export.
+//!
+//! [`MESSAGE_ARB_ID_DEVICE`]: rdxusb_protocol::MESSAGE_ARB_ID_DEVICE
+use std::io::{ErrorKind, Read, Write};
+
+use rdxusb_protocol::{RdxUsbPacket, MESSAGE_ARB_ID_EXT, MESSAGE_ARB_ID_RTR, MESSAGE_FLAG_BRS, MESSAGE_FLAG_ESI, MESSAGE_FLAG_FDF};
+
+use super::{CaptureError, CaptureResult, PacketWrite};
+
+/// pcapng link type for SocketCAN frames.
+pub const LINKTYPE_CAN_SOCKETCAN: u16 = 227;
+
+const BLOCK_SHB: u32 = 0x0A0D0D0A;
+const BLOCK_IDB: u32 = 0x00000001;
+const BLOCK_EPB: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+/// Largest block the reader accepts, far above any CAN capture's, so a corrupt length can't exhaust memory.
+const MAX_BLOCK_LEN: usize = 16 << 20;
+
+const OPT_END: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+
+const CAN_EFF_FLAG: u32 = 0x80000000;
+const CAN_RTR_FLAG: u32 = 0x40000000;
+const CAN_ERR_FLAG: u32 = 0x20000000;
+const CANFD_BRS: u8 = 0x01;
+const CANFD_ESI: u8 = 0x02;
+const CANFD_FDF: u8 = 0x04;
+
+/// Writes pcapng captures.
+pub struct PcapngWriter<W: Write> {
+    inner: W,
+    /// interface id for each channel, created on first use
+    interfaces: [Option<u32>; 256],
+    n_interfaces: u32,
+    block: Vec<u8>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Creates a writer, writing the section header immediately.
+    pub fn new(inner: W) -> CaptureResult<Self> {
+        let mut this = Self { inner, interfaces: [None; 256], n_interfaces: 0, block: Vec::new() };
+        let mut body = Vec::with_capacity(16);
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        this.write_block(BLOCK_SHB, &body)?;
+        Ok(this)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> CaptureResult<()> {
+        let padded = body.len().next_multiple_of(4);
+        let total_len = (padded + 12) as u32;
+        self.block.clear();
+        self.block.extend_from_slice(&block_type.to_le_bytes());
+        self.block.extend_from_slice(&total_len.to_le_bytes());
+        self.block.extend_from_slice(body);
+        self.block.resize(8 + padded, 0);
+        self.block.extend_from_slice(&total_len.to_le_bytes());
+        self.inner.write_all(&self.block)?;
+        Ok(())
+    }
+
+    fn interface(&mut self, channel: u8) -> CaptureResult<u32> {
+        if let Some(id) = self.interfaces[channel as usize] { return Ok(id); }
+        let name = format!("can{channel}");
+        let mut body = Vec::new();
+        body.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+        push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+        push_option(&mut body, OPT_IF_TSRESOL, &[9]);
+        push_option(&mut body, OPT_END, &[]);
+        self.write_block(BLOCK_IDB, &body)?;
+
+        let id = self.n_interfaces;
+        self.n_interfaces += 1;
+        self.interfaces[channel as usize] = Some(id);
+        Ok(id)
+    }
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+impl<W: Write> PacketWrite for PcapngWriter<W> {
+    fn write_packet(&mut self, packet: &RdxUsbPacket) -> CaptureResult<()> {
+        let if_id = self.interface(packet.channel)?;
+        let data = packet.payload();
+        let fd = packet.fd() || data.len() > 8;
+
+        let mut can_id = packet.id();
+        if packet.extended() { can_id |= CAN_EFF_FLAG; }
+        if packet.rtr() { can_id |= CAN_RTR_FLAG; }
+        let mut frame = Vec::with_capacity(72);
+        frame.extend_from_slice(&can_id.to_be_bytes());
+        frame.push(if packet.rtr() { packet.dlc.min(8) } else { data.len() as u8 });
+        let mut fd_flags = 0;
+        if fd { fd_flags |= CANFD_FDF; }
+        if packet.brs() { fd_flags |= CANFD_BRS; }
+        if packet.esi() { fd_flags |= CANFD_ESI; }
+        frame.push(fd_flags);
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(data);
+        frame.resize(if fd { 72 } else { 16 }, 0);
+
+        let ts = packet.timestamp_ns;
+        let mut body = Vec::with_capacity(20 + frame.len());
+        body.extend_from_slice(&if_id.to_le_bytes());
+        body.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(ts as u32).to_le_bytes());
+        body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        body.extend_from_slice(&frame);
+        self.write_block(BLOCK_EPB, &body)
+    }
+
+    fn flush(&mut self) -> CaptureResult<()> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+struct Interface {
+    link_type: u16,
+    channel: u8,
+    /// `if_tsresol` option value; defaults to microseconds
+    tsresol: u8,
+}
+
+impl Interface {
+    fn timestamp_ns(&self, ts: u64) -> u64 {
+        let ts = ts as u128;
+        let ns = if self.tsresol & 0x80 != 0 {
+            (ts * 1_000_000_000) >> (self.tsresol & 0x7f).min(127)
+        } else if self.tsresol <= 9 {
+            ts * 10u128.pow(9 - self.tsresol as u32)
+        } else {
+            ts / 10u128.pow((self.tsresol as u32 - 9).min(38))
+        };
+        ns as u64
+    }
+}
+
+/// Reads pcapng captures, yielding SocketCAN frames and skipping everything else.
+pub struct PcapngReader<R: Read> {
+    inner: R,
+    big_endian: bool,
+    interfaces: Vec<Interface>,
+    block: Vec<u8>,
+}
+
+impl<R: Read> PcapngReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, big_endian: false, interfaces: Vec::new(), block: Vec::new() }
+    }
+
+    fn u16_at(&self, buf: &[u8], at: usize) -> Option<u16> {
+        let b: [u8; 2] = buf.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    }
+
+    fn u32_at(&self, buf: &[u8], at: usize) -> Option<u32> {
+        let b: [u8; 4] = buf.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+
+    /// Reads the next block as `(type, body)` into `self.block`, or `None` at end of file.
+    fn read_block(&mut self) -> CaptureResult<Option<u32>> {
+        let mut hdr = [0u8; 8];
+        match self.inner.read_exact(&mut hdr) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        if u32::from_le_bytes(hdr[..4].try_into().unwrap()) == BLOCK_SHB {
+            // the section header decides the byte order of everything after it, including its own length
+            let mut magic = [0u8; 4];
+            self.inner.read_exact(&mut magic)?;
+            self.big_endian = match u32::from_le_bytes(magic) {
+                BYTE_ORDER_MAGIC => false,
+                m if m.swap_bytes() == BYTE_ORDER_MAGIC => true,
+                _ => return Err(CaptureError::BadMagic),
+            };
+            self.interfaces.clear();
+            let total_len = self.u32_at(&hdr, 4).unwrap() as usize;
+            if total_len > MAX_BLOCK_LEN { return Err(CaptureError::Malformed("oversized block")); }
+            let rest = total_len.checked_sub(12).ok_or(CaptureError::Malformed("short block"))?;
+            self.block.resize(rest, 0);
+            self.inner.read_exact(&mut self.block)?;
+            return Ok(Some(BLOCK_SHB));
+        }
+        let block_type = self.u32_at(&hdr, 0).unwrap();
+        let total_len = self.u32_at(&hdr, 4).unwrap() as usize;
+        if total_len > MAX_BLOCK_LEN { return Err(CaptureError::Malformed("oversized block")); }
+        let rest = total_len.checked_sub(8).ok_or(CaptureError::Malformed("short block"))?;
+        self.block.resize(rest, 0);
+        self.inner.read_exact(&mut self.block).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => CaptureError::Malformed("truncated block"),
+            _ => e.into(),
+        })?;
+        // drop the trailing length
+        self.block.truncate(rest.saturating_sub(4));
+        Ok(Some(block_type))
+    }
+
+    fn parse_interface(&self) -> Interface {
+        let body = &self.block;
+        let mut iface = Interface {
+            link_type: self.u16_at(body, 0).unwrap_or(0),
+            channel: self.interfaces.len().min(255) as u8,
+            tsresol: 6,
+        };
+        let mut at = 8;
+        while let (Some(code), Some(len)) = (self.u16_at(body, at), self.u16_at(body, at + 2)) {
+            let len = len as usize;
+            let Some(value) = body.get(at + 4..at + 4 + len) else { break; };
+            match code {
+                OPT_END => break,
+                OPT_IF_NAME => {
+                    let name = String::from_utf8_lossy(value);
+                    let digits = name.len() - name.bytes().rev().take_while(u8::is_ascii_digit).count();
+                    if let Ok(ch) = name[digits..].parse() { iface.channel = ch; }
+                }
+                OPT_IF_TSRESOL if len >= 1 => iface.tsresol = value[0],
+                _ => (),
+            }
+            at += 4 + len.next_multiple_of(4);
+        }
+        iface
+    }
+
+    fn parse_packet(&self) -> CaptureResult<Option<RdxUsbPacket>> {
+        let body = &self.block;
+        let malformed = || CaptureError::Malformed("bad enhanced packet block");
+        let if_id = self.u32_at(body, 0).ok_or_else(malformed)? as usize;
+        let iface = self.interfaces.get(if_id).ok_or(CaptureError::Malformed("unknown interface"))?;
+        if iface.link_type != LINKTYPE_CAN_SOCKETCAN { return Ok(None); }
+        let ts = (self.u32_at(body, 4).ok_or_else(malformed)? as u64) << 32 | self.u32_at(body, 8).ok_or_else(malformed)? as u64;
+        let caplen = self.u32_at(body, 12).ok_or_else(malformed)? as usize;
+        let frame = body.get(20..20usize.checked_add(caplen).ok_or_else(malformed)?).ok_or_else(malformed)?;
+        if frame.len() < 8 { return Err(malformed()); }
+
+        // SocketCAN ids are always big-endian in this link type
+        let can_id = u32::from_be_bytes(frame[..4].try_into().unwrap());
+        if can_id & CAN_ERR_FLAG != 0 { return Ok(None); }
+        let len = frame[4] as usize;
+
+        let mut packet: RdxUsbPacket = bytemuck::Zeroable::zeroed();
+        packet.timestamp_ns = iface.timestamp_ns(ts);
+        packet.channel = iface.channel;
+        packet.arb_id = can_id & 0x1fff_ffff;
+        if can_id & CAN_EFF_FLAG != 0 { packet.arb_id |= MESSAGE_ARB_ID_EXT; }
+        if can_id & CAN_RTR_FLAG != 0 {
+            packet.arb_id |= MESSAGE_ARB_ID_RTR;
+            packet.dlc = len.min(8) as u8;
+        } else {
+            let data = frame.get(8..8 + len).ok_or_else(malformed)?;
+            let n = data.len().min(packet.data.len());
+            packet.data[..n].copy_from_slice(&data[..n]);
+            packet.dlc = n as u8;
+            // older writers leave FDF unset, so 72-byte frames count as FD too
+            let fd_flags = frame[5];
+            if fd_flags & CANFD_FDF != 0 || frame.len() == 72 { packet.flags |= MESSAGE_FLAG_FDF; }
+            if fd_flags & CANFD_BRS != 0 { packet.flags |= MESSAGE_FLAG_BRS; }
+            if fd_flags & CANFD_ESI != 0 { packet.flags |= MESSAGE_FLAG_ESI; }
+        }
+        Ok(Some(packet))
+    }
+
+    /// Reads the next CAN packet, or `None` at end of file.
+    pub fn read_packet(&mut self) -> CaptureResult<Option<RdxUsbPacket>> {
+        while let Some(block_type) = self.read_block()? {
+            match block_type {
+                BLOCK_IDB => {
+                    let iface = self.parse_interface();
+                    self.interfaces.push(iface);
+                }
+                BLOCK_EPB => {
+                    if let Some(packet) = self.parse_packet()? { return Ok(Some(packet)); }
+                }
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read> Iterator for PcapngReader<R> {
+    type Item = CaptureResult<RdxUsbPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_packet().transpose()
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rdxusb_protocol::RdxUsbPacket;
+
+#[cfg(feature = "integrity")]
+use super::CaptureIntegrity;
+use super::{Annotation, BlockCodec, CaptureResult, CaptureWriter, PacketWrite};
+
+/// Compression applied to capture files as they're written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptureCompression {
+    #[default]
+    None,
+    /// Compress the whole file as a zstd stream at the given level. Files get a `.zst` suffix.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// Compress in independently decodable blocks inside the capture, see [`BlockCodec`].
+    Blocks(BlockCodec),
+}
+
+impl CaptureCompression {
+    /// File extension for native captures written with this compression.
+    pub fn extension(self) -> &'static str {
+        match self {
+            CaptureCompression::None | CaptureCompression::Blocks(_) => "rdxcap",
+            #[cfg(feature = "zstd")]
+            CaptureCompression::Zstd(_) => "rdxcap.zst",
+        }
+    }
+}
+
+/// When the [`Recorder`] starts a new file, and how many old ones it keeps.
+#[derive(Debug, Clone, Default)]
+pub struct RotationPolicy {
+    /// Start a new file once the current one reaches this many bytes on disk.
+    ///
+    /// Compressed files only count bytes the compressor has emitted so far, so they can overshoot by its internal buffer.
+    pub max_file_size: Option<u64>,
+    /// Start a new file once the current one has been open this long.
+    pub max_duration: Option<Duration>,
+    /// Delete the oldest files written by this recorder once there are more than this many.
+    pub retained_files: Option<usize>,
+    pub compression: CaptureCompression,
+    /// Whether each file is hash chained and sealed when it's rotated out, see [`CaptureIntegrity`].
+    #[cfg(feature = "integrity")]
+    pub integrity: CaptureIntegrity,
+}
+
+/// Records packets into a directory of native captures, rotating files according to a [`RotationPolicy`].
+///
+/// Files are named `<prefix>-<unix seconds>-<sequence>.rdxcap`. Only files created by this recorder
+/// count towards [`RotationPolicy::retained_files`]; captures left over from earlier runs are never deleted.
+pub struct Recorder {
+    dir: PathBuf,
+    prefix: String,
+    policy: RotationPolicy,
+    current: Option<OpenFile>,
+    files: VecDeque<PathBuf>,
+    seq: u64,
+}
+
+struct OpenFile {
+    writer: CaptureWriter<FileSink>,
+    opened: Instant,
+}
+
+impl Recorder {
+    /// Creates a recorder writing into `dir`, which is created if missing. The first file is opened lazily.
+    pub fn new(dir: impl AsRef<Path>, prefix: &str, policy: RotationPolicy) -> CaptureResult<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            policy,
+            current: None,
+            files: VecDeque::new(),
+            seq: 0,
+        })
+    }
+
+    /// The file currently being written, if any.
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.as_ref().and(self.files.back()).map(PathBuf::as_path)
+    }
+
+    /// Closes the current file; the next packet starts a new one.
+    pub fn rotate(&mut self) -> CaptureResult<()> {
+        if let Some(file) = self.current.take() {
+            file.writer.finish_into_inner()?.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Closes the current file, flushing everything to disk.
+    pub fn finish(mut self) -> CaptureResult<()> {
+        self.rotate()
+    }
+
+    fn needs_rotation(&self) -> bool {
+        let Some(file) = &self.current else { return false; };
+        self.policy.max_file_size.is_some_and(|max| file.writer.get_ref().bytes_written() >= max)
+            || self.policy.max_duration.is_some_and(|max| file.opened.elapsed() >= max)
+    }
+
+    fn open_next(&mut self) -> CaptureResult<&mut OpenFile> {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = self.dir.join(format!("{}-{secs}-{:04}.{}", self.prefix, self.seq, self.policy.compression.extension()));
+        self.seq += 1;
+        let sink = FileSink::create(&path, self.policy.compression)?;
+        let codec = match self.policy.compression {
+            CaptureCompression::Blocks(codec) => Some(codec),
+            _ => None,
+        };
+        log::debug!(target: "rdxusb", "capture: recording to {}", path.display());
+        self.files.push_back(path);
+
+        if let Some(retained) = self.policy.retained_files {
+            while self.files.len() > retained.max(1) {
+                let Some(old) = self.files.pop_front() else { break; };
+                if let Err(e) = std::fs::remove_file(&old) {
+                    log::warn!(target: "rdxusb", "capture: could not remove {}: {e}", old.display());
+                }
+            }
+        }
+        #[cfg(feature = "integrity")]
+        let writer = CaptureWriter::with_integrity(sink, codec, self.policy.integrity.clone())?;
+        #[cfg(not(feature = "integrity"))]
+        let writer = CaptureWriter::with_codec(sink, codec)?;
+        Ok(self.current.insert(OpenFile { writer, opened: Instant::now() }))
+    }
+}
+
+impl PacketWrite for Recorder {
+    fn write_packet(&mut self, packet: &RdxUsbPacket) -> CaptureResult<()> {
+        if self.needs_rotation() {
+            self.rotate()?;
+        }
+        let file = match self.current.as_mut() {
+            Some(file) => file,
+            None => self.open_next()?,
+        };
+        file.writer.write_packet(packet)
+    }
+
+    fn write_annotation(&mut self, annotation: &Annotation) -> CaptureResult<()> {
+        let file = match self.current.as_mut() {
+            Some(file) => file,
+            None => self.open_next()?,
+        };
+        file.writer.write_annotation(annotation)
+    }
+
+    fn flush(&mut self) -> CaptureResult<()> {
+        match self.current.as_mut() {
+            Some(file) => file.writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> CaptureResult<()> {
+        self.rotate()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.rotate().ok();
+    }
+}
+
+/// Counts bytes actually written to the file, after any compression.
+struct CountingWriter {
+    inner: BufWriter<File>,
+    written: u64,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+enum FileSink {
+    Plain(CountingWriter),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, CountingWriter>),
+}
+
+impl FileSink {
+    fn create(path: &Path, compression: CaptureCompression) -> std::io::Result<Self> {
+        let file = CountingWriter { inner: BufWriter::new(File::create(path)?), written: 0 };
+        Ok(match compression {
+            CaptureCompression::None | CaptureCompression::Blocks(_) => Self::Plain(file),
+            #[cfg(feature = "zstd")]
+            CaptureCompression::Zstd(level) => Self::Zstd(zstd::Encoder::new(file, level)?),
+        })
+    }
+
+    fn bytes_written(&self) -> u64 {
+        match self {
+            FileSink::Plain(w) => w.written,
+            #[cfg(feature = "zstd")]
+            FileSink::Zstd(w) => w.get_ref().written,
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            FileSink::Plain(mut w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            FileSink::Zstd(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for FileSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            FileSink::Plain(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            FileSink::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            FileSink::Plain(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            FileSink::Zstd(w) => w.flush(),
+        }
+    }
+}
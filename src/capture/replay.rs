@@ -0,0 +1,186 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rdxusb_protocol::RdxUsbPacket;
+
+use super::native::{IndexEntry, HEADER_SIZE};
+use super::{CaptureEntry, CaptureReader, CaptureResult, PacketRead};
+
+/// Reads packets back out of a native capture, with time-based seeking.
+///
+/// Seeking uses the capture's time index when it has one, and falls back to scanning from the start otherwise
+/// (for example, captures cut short before the index was written).
+pub struct Replayer<R: Read + Seek> {
+    reader: CaptureReader<R>,
+    index: Option<Vec<IndexEntry>>,
+    /// packet read ahead while seeking
+    peeked: Option<RdxUsbPacket>,
+}
+
+impl Replayer<BufReader<File>> {
+    /// Opens a native capture file. Block-compressed captures are supported; whole-file `.zst` captures are not seekable.
+    pub fn open(path: impl AsRef<Path>) -> CaptureResult<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> Replayer<R> {
+    pub fn new(inner: R) -> CaptureResult<Self> {
+        let mut reader = CaptureReader::new(inner)?;
+        let index = reader.read_index()?;
+        Ok(Self { reader, index, peeked: None })
+    }
+
+    /// The capture's time index, if it has one.
+    pub fn index(&self) -> Option<&[IndexEntry]> {
+        self.index.as_deref()
+    }
+
+    /// Positions the replayer so the next packet is the first one with a timestamp at or after `timestamp_ns`.
+    pub fn seek_to(&mut self, timestamp_ns: u64) -> CaptureResult<()> {
+        self.peeked = None;
+        let offset = match &self.index {
+            Some(index) => {
+                // last entry starting strictly before the target, so packets at exactly the target aren't skipped
+                let i = index.partition_point(|e| e.timestamp_ns < timestamp_ns);
+                i.checked_sub(1).map_or(HEADER_SIZE as u64, |i| index[i].offset)
+            }
+            None => HEADER_SIZE as u64,
+        };
+        self.reader.seek_to_offset(offset)?;
+        while let Some(packet) = self.reader.read_packet()? {
+            if packet.timestamp_ns >= timestamp_ns {
+                self.peeked = Some(packet);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns to the start of the capture.
+    pub fn rewind(&mut self) -> CaptureResult<()> {
+        self.peeked = None;
+        self.reader.seek_to_offset(HEADER_SIZE as u64)
+    }
+
+    /// Reads the next packet, or `None` at end of file.
+    pub fn read_packet(&mut self) -> CaptureResult<Option<RdxUsbPacket>> {
+        match self.peeked.take() {
+            Some(packet) => Ok(Some(packet)),
+            None => self.reader.read_packet(),
+        }
+    }
+
+    /// Reads the next packet or annotation, or `None` at end of file.
+    ///
+    /// After a seek, annotations between the seek point and the first packet at or after it are skipped.
+    pub fn read_entry(&mut self) -> CaptureResult<Option<CaptureEntry>> {
+        match self.peeked.take() {
+            Some(packet) => Ok(Some(CaptureEntry::Packet(packet))),
+            None => self.reader.read_entry(),
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for Replayer<R> {
+    type Item = CaptureResult<RdxUsbPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_packet().transpose()
+    }
+}
+
+/// Where a [`SyncPlayer`] track sends each packet once it is due.
+type PlaybackSink<'a> = Box<dyn FnMut(&RdxUsbPacket) -> CaptureResult<()> + 'a>;
+
+struct Track<'a> {
+    packets: PacketRead<'a>,
+    sink: PlaybackSink<'a>,
+    offset_ns: i64,
+    /// next packet, read ahead to order it against the other tracks
+    next: Option<RdxUsbPacket>,
+}
+
+impl Track<'_> {
+    /// Reads the track's next packet, returning its timestamp in the shared time base.
+    fn advance(&mut self) -> CaptureResult<Option<i128>> {
+        self.next = self.packets.next().transpose()?;
+        Ok(self.next.map(|p| p.timestamp_ns as i128 + self.offset_ns as i128))
+    }
+}
+
+/// Replays several captures at once, e.g. one per device of a multi-device capture, releasing packets from all of them
+/// in global timestamp order against one clock so the timing between devices is kept.
+///
+/// Every track's timestamps are taken to be in the same time base; use [`Self::set_offset`] to line up a track
+/// recorded against a different one.
+pub struct SyncPlayer<'a> {
+    tracks: Vec<Track<'a>>,
+    speed: f64,
+}
+
+impl Default for SyncPlayer<'_> {
+    fn default() -> Self {
+        Self { tracks: Vec::new(), speed: 1.0 }
+    }
+}
+
+impl<'a> SyncPlayer<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a capture to play, returning its track number. `sink` is called with each of its packets once it is due,
+    /// and an error from it stops playback.
+    pub fn add_track(
+        &mut self,
+        packets: impl Iterator<Item = CaptureResult<RdxUsbPacket>> + 'a,
+        sink: impl FnMut(&RdxUsbPacket) -> CaptureResult<()> + 'a,
+    ) -> usize {
+        self.tracks.push(Track { packets: Box::new(packets), sink: Box::new(sink), offset_ns: 0, next: None });
+        self.tracks.len() - 1
+    }
+
+    /// Shifts a track's timestamps by `offset_ns` before ordering them against the other tracks.
+    pub fn set_offset(&mut self, track: usize, offset_ns: i64) {
+        if let Some(track) = self.tracks.get_mut(track) {
+            track.offset_ns = offset_ns;
+        }
+    }
+
+    /// Plays back `speed` times faster than the packets were recorded; 1.0 (the default) is real time.
+    /// Zero or less plays as fast as the sinks take packets.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// Plays every track to its end, blocking the calling thread, and returns the number of packets played.
+    pub fn run(&mut self) -> CaptureResult<u64> {
+        let mut due = BinaryHeap::new();
+        for (i, track) in self.tracks.iter_mut().enumerate() {
+            if let Some(ts) = track.advance()? { due.push(Reverse((ts, i))); }
+        }
+        let start = Instant::now();
+        let mut first_ts = None;
+        let mut count = 0u64;
+        while let Some(Reverse((ts, i))) = due.pop() {
+            let first_ts = *first_ts.get_or_insert(ts);
+            if self.speed > 0.0 {
+                // timestamps can go backwards, e.g. after a device reset mid-capture; those packets are due now
+                let at = start + Duration::from_secs_f64((ts - first_ts).max(0) as f64 / 1e9 / self.speed);
+                std::thread::sleep(at.saturating_duration_since(Instant::now()));
+            }
+            let track = &mut self.tracks[i];
+            if let Some(packet) = track.next.take() {
+                (track.sink)(&packet)?;
+                count += 1;
+            }
+            if let Some(ts) = track.advance()? { due.push(Reverse((ts, i))); }
+        }
+        Ok(count)
+    }
+}
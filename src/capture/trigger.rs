@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rdxusb_protocol::RdxUsbPacket;
+
+use super::{Annotation, CaptureResult, PacketWrite, Recorder};
+use crate::filter::Filter;
+
+/// Records only bursts of traffic around frames matching a trigger [`Filter`], such as fault frames.
+///
+/// While armed, the last `pre_trigger` worth of packets is kept in memory. When a packet matches the trigger,
+/// those packets are written out, followed by everything up to `post_trigger` after it; a matching packet
+/// during a burst extends it. Each burst is rotated into its own file.
+///
+/// Durations are measured on packet timestamps, i.e. device time.
+pub struct TriggeredRecorder {
+    recorder: Recorder,
+    trigger: Option<Filter>,
+    pre_trigger_ns: u64,
+    post_trigger_ns: u64,
+    ring: VecDeque<RdxUsbPacket>,
+    /// end of the current burst, if one is being recorded
+    recording_until: Option<u64>,
+    bursts: u64,
+}
+
+impl TriggeredRecorder {
+    /// Wraps `recorder`, armed with `trigger`.
+    pub fn new(recorder: Recorder, trigger: Filter, pre_trigger: Duration, post_trigger: Duration) -> Self {
+        Self {
+            recorder,
+            trigger: Some(trigger),
+            pre_trigger_ns: pre_trigger.as_nanos() as u64,
+            post_trigger_ns: post_trigger.as_nanos() as u64,
+            ring: VecDeque::new(),
+            recording_until: None,
+            bursts: 0,
+        }
+    }
+
+    /// Arms the recorder with a new trigger. A burst being recorded runs to completion.
+    pub fn arm(&mut self, trigger: Filter) {
+        self.trigger = Some(trigger);
+    }
+
+    /// Stops watching for the trigger and drops the pre-trigger buffer. A burst being recorded runs to completion.
+    pub fn disarm(&mut self) {
+        self.trigger = None;
+        self.ring.clear();
+    }
+
+    /// Is a burst currently being recorded?
+    pub fn is_recording(&self) -> bool {
+        self.recording_until.is_some()
+    }
+
+    /// Number of bursts triggered so far.
+    pub fn bursts(&self) -> u64 {
+        self.bursts
+    }
+
+    /// Returns the wrapped recorder. Packets still in the pre-trigger buffer are discarded.
+    pub fn into_inner(self) -> Recorder {
+        self.recorder
+    }
+}
+
+impl PacketWrite for TriggeredRecorder {
+    fn write_packet(&mut self, packet: &RdxUsbPacket) -> CaptureResult<()> {
+        let ts = packet.timestamp_ns;
+        let triggered = self.trigger.as_ref().is_some_and(|f| f.matches(packet));
+
+        if let Some(until) = self.recording_until {
+            if ts <= until || triggered {
+                if triggered { self.recording_until = Some(ts.saturating_add(self.post_trigger_ns)); }
+                return self.recorder.write_packet(packet);
+            }
+            // the burst is over; this packet falls through to the pre-trigger buffer
+            self.recording_until = None;
+            self.recorder.rotate()?;
+        }
+
+        if triggered {
+            log::debug!(target: "rdxusb", "capture: trigger matched at {ts}, recording burst");
+            self.bursts += 1;
+            self.recording_until = Some(ts.saturating_add(self.post_trigger_ns));
+            for buffered in self.ring.drain(..) {
+                self.recorder.write_packet(&buffered)?;
+            }
+            return self.recorder.write_packet(packet);
+        }
+
+        if self.trigger.is_some() {
+            self.ring.push_back(*packet);
+            let cutoff = ts.saturating_sub(self.pre_trigger_ns);
+            while self.ring.front().is_some_and(|p| p.timestamp_ns < cutoff) {
+                self.ring.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// Annotations are only kept while a burst is being recorded.
+    fn write_annotation(&mut self, annotation: &Annotation) -> CaptureResult<()> {
+        if self.recording_until.is_none() { return Ok(()); }
+        self.recorder.write_annotation(annotation)
+    }
+
+    fn flush(&mut self) -> CaptureResult<()> {
+        self.recorder.flush()
+    }
+
+    fn finish(&mut self) -> CaptureResult<()> {
+        self.recording_until = None;
+        self.recorder.rotate()
+    }
+}
@@ -0,0 +1,361 @@
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::thread::JoinHandle;
+
+use rdxusb_protocol::RdxUsbPacket;
+
+use crate::event_loop::{self, EventLoopError};
+
+/// Environment variable naming the daemon socket that C API calls are forwarded to.
+pub const DAEMON_SOCKET_ENV: &str = "RDXUSB_DAEMON";
+
+/// Most packets moved by a single read request, bounding the daemon's per-request allocation.
+const MAX_PACKETS_PER_REQUEST: u32 = 4096;
+
+/// Permissions of the daemon socket: only its owner and group may connect.
+pub const SOCKET_MODE: u32 = 0o660;
+
+const OP_OPEN_DEVICE: u8 = 1;
+const OP_OPEN_FIRST_REDUX_DEVICE: u8 = 2;
+const OP_READ_PACKETS: u8 = 3;
+const OP_READ_PACKETS_ANY: u8 = 4;
+const OP_WRITE_PACKETS: u8 = 5;
+const OP_CLOSE_DEVICE: u8 = 6;
+const OP_CLOSE_ALL_DEVICES: u8 = 7;
+
+/// Serves this process's event loop to other processes over a Unix socket, so that processes sharing a
+/// coprocessor don't each run their own event loop and fight over devices.
+///
+/// Clients send requests with [`DaemonClient`]. Each request is a one byte opcode followed by little endian
+/// arguments, answered with an `i32` status (a handle, a packet count, or a negative error) and any packets read.
+/// Each client is served on its own thread, so reader claims (see [`event_loop::read_packets`]) are per client.
+/// Clients can only read, write and close the handles they opened, which are closed when they disconnect.
+///
+/// The socket is created with [`SOCKET_MODE`], so only processes of the same user or group can connect. Use
+/// [`std::fs::set_permissions`] on [`Self::path`] to narrow or widen that.
+///
+/// Dropping the server stops accepting clients and removes the socket file; connected clients are served until
+/// they disconnect.
+pub struct DaemonServer {
+    path: PathBuf,
+    shutdown: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl DaemonServer {
+    /// Binds the socket at `path`, replacing a stale socket file left by a daemon that didn't exit cleanly.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let listener = match UnixListener::bind(&path) {
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse && UnixStream::connect(&path).is_err() => {
+                std::fs::remove_file(&path)?;
+                UnixListener::bind(&path)?
+            }
+            r => r?,
+        };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(SOCKET_MODE))?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let accept_shutdown = shutdown.clone();
+        let accept_thread = std::thread::Builder::new().name("rdxusb-daemon".to_string()).spawn(move || {
+            for stream in listener.incoming() {
+                if accept_shutdown.load(Ordering::Relaxed) { break; }
+                match stream {
+                    Ok(stream) => {
+                        let spawned = std::thread::Builder::new().name("rdxusb-daemon-client".to_string()).spawn(move || serve_client(stream));
+                        if let Err(e) = spawned {
+                            log::error!(target: "rdxusb", "daemon: could not spawn client thread: {e}");
+                        }
+                    }
+                    Err(e) => {
+                        log::error!(target: "rdxusb", "daemon: accept failed: {e}");
+                        std::thread::sleep(std::time::Duration::from_millis(100));
+                    }
+                }
+            }
+        })?;
+        log::info!(target: "rdxusb", "daemon: serving on {}", path.display());
+        Ok(Self { path, shutdown, accept_thread: Some(accept_thread) })
+    }
+
+    /// The path of the socket the server is listening on.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DaemonServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // wakes the accept loop up so it sees the shutdown flag
+        UnixStream::connect(&self.path).ok();
+        if let Some(thread) = self.accept_thread.take() {
+            thread.join().ok();
+        }
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+fn serve_client(mut stream: UnixStream) {
+    let mut handles = Vec::new();
+    loop {
+        let mut op = [0u8; 1];
+        if stream.read_exact(&mut op).is_err() { break; }
+        if let Err(e) = serve_request(&mut stream, op[0], &mut handles) {
+            log::debug!(target: "rdxusb", "daemon: dropping client: {e}");
+            break;
+        }
+    }
+    for handle in handles {
+        event_loop::close_device(handle).ok();
+    }
+}
+
+/// Reads a handle argument, replacing it with an invalid one unless the client opened it.
+fn read_owned_handle(stream: &mut UnixStream, handles: &[i32]) -> io::Result<Option<i32>> {
+    let handle = i32::from_le_bytes(read_array(stream)?);
+    Ok(handles.contains(&handle).then_some(handle))
+}
+
+/// Clamps a client's buffer size like [`event_loop::open_device`] does, before it can overflow `usize`.
+fn client_capacity(capacity: u64) -> usize {
+    capacity.min(event_loop::MAX_CAPACITY as u64) as usize
+}
+
+fn serve_request(stream: &mut UnixStream, op: u8, handles: &mut Vec<i32>) -> io::Result<()> {
+    match op {
+        OP_OPEN_DEVICE => {
+            let vid = u16::from_le_bytes(read_array(stream)?);
+            let pid = u16::from_le_bytes(read_array(stream)?);
+            let close_on_dc = read_array::<1>(stream)?[0] != 0;
+            let capacity = client_capacity(u64::from_le_bytes(read_array(stream)?));
+            let serial_len = u32::from_le_bytes(read_array(stream)?);
+            let serial_number = if serial_len == u32::MAX {
+                None
+            } else if serial_len > 256 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{serial_len} byte serial number")));
+            } else {
+                let mut serial = vec![0u8; serial_len as usize];
+                stream.read_exact(&mut serial)?;
+                Some(String::from_utf8_lossy(&serial).into_owned())
+            };
+            let status = status_of(event_loop::open_device(vid, pid, serial_number, close_on_dc, capacity));
+            if status >= 0 { handles.push(status); }
+            stream.write_all(&status.to_le_bytes())
+        }
+        OP_OPEN_FIRST_REDUX_DEVICE => {
+            let close_on_dc = read_array::<1>(stream)?[0] != 0;
+            let capacity = client_capacity(u64::from_le_bytes(read_array(stream)?));
+            let status = status_of(event_loop::open_first_redux_device(close_on_dc, capacity));
+            if status >= 0 { handles.push(status); }
+            stream.write_all(&status.to_le_bytes())
+        }
+        OP_READ_PACKETS | OP_READ_PACKETS_ANY => {
+            let handle = read_owned_handle(stream, handles)?;
+            let channel = if op == OP_READ_PACKETS { Some(read_array::<1>(stream)?[0]) } else { None };
+            let max = u32::from_le_bytes(read_array(stream)?).min(MAX_PACKETS_PER_REQUEST);
+            let mut packets = vec![<RdxUsbPacket as bytemuck::Zeroable>::zeroed(); max as usize];
+            let result = match (handle, channel) {
+                (None, _) => Err(EventLoopError::DeviceNotOpened),
+                (Some(handle), Some(channel)) => event_loop::read_packets(handle, channel, &mut packets),
+                (Some(handle), None) => event_loop::read_packets_any(handle, &mut packets),
+            };
+            let n = result.as_ref().map_or(0, |n| *n);
+            stream.write_all(&status_of(result.map(|n| n as i32)).to_le_bytes())?;
+            stream.write_all(bytemuck::cast_slice(&packets[..n]))
+        }
+        OP_WRITE_PACKETS => {
+            let handle = read_owned_handle(stream, handles)?;
+            let n = u32::from_le_bytes(read_array(stream)?);
+            if n > MAX_PACKETS_PER_REQUEST {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{n} packets in one write")));
+            }
+            let mut packets = vec![<RdxUsbPacket as bytemuck::Zeroable>::zeroed(); n as usize];
+            stream.read_exact(bytemuck::cast_slice_mut(&mut packets))?;
+            let result = handle.ok_or(EventLoopError::DeviceNotOpened).and_then(|handle| event_loop::write_packets(handle, &packets));
+            let status = status_of(result.map(|n| n as i32));
+            stream.write_all(&status.to_le_bytes())
+        }
+        OP_CLOSE_DEVICE => {
+            let result = match read_owned_handle(stream, handles)? {
+                Some(handle) => {
+                    handles.retain(|h| *h != handle);
+                    event_loop::close_device(handle).map(|_| 0)
+                }
+                None => Err(EventLoopError::DeviceNotOpened),
+            };
+            stream.write_all(&status_of(result).to_le_bytes())
+        }
+        OP_CLOSE_ALL_DEVICES => {
+            let mut result = Ok(0);
+            for handle in handles.drain(..) {
+                if let Err(e) = event_loop::close_device(handle) { result = Err(e); }
+            }
+            stream.write_all(&status_of(result).to_le_bytes())
+        }
+        op => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown opcode {op}"))),
+    }
+}
+
+fn status_of(result: Result<i32, EventLoopError>) -> i32 {
+    result.unwrap_or_else(|e| e as i32)
+}
+
+fn read_array<const N: usize>(stream: &mut UnixStream) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Forwards event loop calls to a [`DaemonServer`] in another process.
+///
+/// Handles are the daemon's, and are only valid with the client that opened them. Calls from several threads
+/// are serialized over the one connection, so the daemon sees them all as a single reader.
+pub struct DaemonClient {
+    stream: Mutex<UnixStream>,
+}
+
+impl DaemonClient {
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self { stream: Mutex::new(UnixStream::connect(path)?) })
+    }
+
+    /// Like [`event_loop::open_device`].
+    pub fn open_device(&self, vid: u16, pid: u16, serial_number: Option<&str>, close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+        let mut request = vec![OP_OPEN_DEVICE];
+        request.extend_from_slice(&vid.to_le_bytes());
+        request.extend_from_slice(&pid.to_le_bytes());
+        request.push(close_on_dc as u8);
+        request.extend_from_slice(&(capacity as u64).to_le_bytes());
+        match serial_number {
+            Some(serial) => {
+                request.extend_from_slice(&(serial.len() as u32).to_le_bytes());
+                request.extend_from_slice(serial.as_bytes());
+            }
+            None => request.extend_from_slice(&u32::MAX.to_le_bytes()),
+        }
+        self.call(&request, |_, status| Ok(status))
+    }
+
+    /// Like [`event_loop::open_first_redux_device`].
+    pub fn open_first_redux_device(&self, close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+        let mut request = vec![OP_OPEN_FIRST_REDUX_DEVICE, close_on_dc as u8];
+        request.extend_from_slice(&(capacity as u64).to_le_bytes());
+        self.call(&request, |_, status| Ok(status))
+    }
+
+    /// Like [`event_loop::read_packets`].
+    pub fn read_packets(&self, handle_id: i32, channel: u8, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        let mut request = vec![OP_READ_PACKETS];
+        request.extend_from_slice(&handle_id.to_le_bytes());
+        request.push(channel);
+        self.read(request, packets)
+    }
+
+    /// Like [`event_loop::read_packets_any`].
+    pub fn read_packets_any(&self, handle_id: i32, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        let mut request = vec![OP_READ_PACKETS_ANY];
+        request.extend_from_slice(&handle_id.to_le_bytes());
+        self.read(request, packets)
+    }
+
+    fn read(&self, mut request: Vec<u8>, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        let max = packets.len().min(MAX_PACKETS_PER_REQUEST as usize);
+        request.extend_from_slice(&(max as u32).to_le_bytes());
+        self.call(&request, |stream, n| {
+            let n = (n as usize).min(max);
+            stream.read_exact(bytemuck::cast_slice_mut(&mut packets[..n]))?;
+            Ok(n)
+        })
+    }
+
+    /// Like [`event_loop::write_packets`].
+    pub fn write_packets(&self, handle_id: i32, packets: &[RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        let packets = &packets[..packets.len().min(MAX_PACKETS_PER_REQUEST as usize)];
+        let mut request = vec![OP_WRITE_PACKETS];
+        request.extend_from_slice(&handle_id.to_le_bytes());
+        request.extend_from_slice(&(packets.len() as u32).to_le_bytes());
+        request.extend_from_slice(bytemuck::cast_slice(packets));
+        self.call(&request, |_, n| Ok(n as usize))
+    }
+
+    /// Like [`event_loop::close_device`].
+    pub fn close_device(&self, handle_id: i32) -> Result<(), EventLoopError> {
+        let mut request = vec![OP_CLOSE_DEVICE];
+        request.extend_from_slice(&handle_id.to_le_bytes());
+        self.call(&request, |_, _| Ok(()))
+    }
+
+    /// Closes every handle this client opened. Other processes' handles are left alone.
+    pub fn close_all_devices(&self) -> Result<(), EventLoopError> {
+        self.call(&[OP_CLOSE_ALL_DEVICES], |_, _| Ok(()))
+    }
+
+    /// Sends a request and reads its status, then the rest of the response with `f` if the call succeeded.
+    fn call<T>(&self, request: &[u8], f: impl FnOnce(&mut UnixStream, i32) -> io::Result<T>) -> Result<T, EventLoopError> {
+        let mut stream = self.stream.lock().map_err(|_| EventLoopError::EventLoopCrashed)?;
+        let result = stream.write_all(request)
+            .and_then(|_| read_array::<4>(&mut stream))
+            .map(i32::from_le_bytes)
+            .and_then(|status| if status < 0 { Ok(Err(error_from_code(status))) } else { f(&mut stream, status).map(Ok) });
+        result.unwrap_or_else(|e| {
+            log::error!(target: "rdxusb", "daemon: request failed: {e}");
+            Err(EventLoopError::DaemonUnavailable)
+        })
+    }
+}
+
+fn error_from_code(code: i32) -> EventLoopError {
+    match code {
+        -100 => EventLoopError::EventLoopCrashed,
+        -101 => EventLoopError::CannotListDevices,
+        -102 => EventLoopError::DeviceIterInvalid,
+        -105 => EventLoopError::NoDeviceFound,
+        -106 => EventLoopError::AlreadyInitialized,
+        -107 => EventLoopError::NotManualPump,
+        -108 => EventLoopError::BufferTooSmall,
+        -109 => EventLoopError::InvalidArgument,
+        -110 => EventLoopError::CaptureNotActive,
+        -111 => EventLoopError::CaptureFailed,
+        -112 => EventLoopError::Panicked,
+        -200 => EventLoopError::DeviceNotOpened,
+        -201 => EventLoopError::DeviceNotConnected,
+        -202 => EventLoopError::ChannelOutOfRange,
+        -203 => EventLoopError::Timeout,
+        -204 => EventLoopError::InvalidPacket,
+        -205 => EventLoopError::DescriptorUnavailable,
+        -206 => EventLoopError::ChannelClaimed,
+        -207 => EventLoopError::RequestUnsupported,
+        _ => EventLoopError::DaemonUnavailable,
+    }
+}
+
+static CLIENT: Mutex<Option<Arc<DaemonClient>>> = Mutex::new(None);
+static CLIENT_FROM_ENV: Once = Once::new();
+
+/// The daemon this process forwards C API calls to, if any.
+///
+/// On first use this connects to the socket named by [`DAEMON_SOCKET_ENV`] if it is set. If that fails, the
+/// error is logged and the process runs its own event loop as usual.
+pub fn client() -> Option<Arc<DaemonClient>> {
+    CLIENT_FROM_ENV.call_once(|| {
+        let Some(path) = std::env::var_os(DAEMON_SOCKET_ENV) else { return; };
+        match DaemonClient::connect(&path) {
+            Ok(client) => { CLIENT.lock().unwrap().get_or_insert(Arc::new(client)); }
+            Err(e) => log::error!(target: "rdxusb", "daemon: could not connect to {}: {e}", Path::new(&path).display()),
+        }
+    });
+    CLIENT.lock().unwrap().clone()
+}
+
+/// Forwards C API calls to the daemon at `path` from now on, or stops forwarding if `None`.
+pub fn set_client(path: Option<&Path>) -> io::Result<()> {
+    // an explicit choice overrides the environment
+    CLIENT_FROM_ENV.call_once(|| ());
+    let client = path.map(DaemonClient::connect).transpose()?;
+    *CLIENT.lock().unwrap() = client.map(Arc::new);
+    Ok(())
+}
@@ -1,12 +1,16 @@
 #![allow(unused)]
 
-use std::{cell::OnceCell, collections::HashMap, ops::{Deref, DerefMut}, sync::{Arc, Mutex, MutexGuard}};
+use std::{cell::{Cell, OnceCell}, cmp::Reverse, collections::{BinaryHeap, HashMap}, fs::File, io::BufWriter, ops::{Deref, DerefMut}, path::Path, sync::{atomic::{AtomicU32, AtomicU64, Ordering}, Arc, Mutex, MutexGuard}, thread::ThreadId, time::{Duration, Instant, SystemTime}};
 use futures_util::stream::StreamExt;
 use nusb::{DeviceId, DeviceInfo};
-use rdxusb_protocol::RdxUsbPacket;
+use rdxusb_protocol::{is_valid_fd_len, RdxUsbDeviceInfo, RdxUsbPacket, RdxUsbPacketEx, MESSAGE_ARB_ID_DEVICE, PROTOCOL_VERSION_MAJOR_FS, PROTOCOL_VERSION_MAJOR_HS};
 use tokio::runtime::Runtime;
 
-use crate::host::{RdxUsbFsChannel, RdxUsbFsHost, RdxUsbFsWriter, RdxUsbHostError};
+use crate::capture::{Annotation, CaptureWriter, PacketWrite};
+use crate::fault::RdxUsbFaults;
+use crate::host::{RdxUsbBridgeRule, RdxUsbBridgeRules, RdxUsbChannelMeter, RdxUsbClockModel, RdxUsbDescriptorReader, RdxUsbFsChannel, RdxUsbFsErrorFrames, RdxUsbFsHost, RdxUsbFsNotifications, RdxUsbFsWritePoller, RdxUsbFsWriter, RdxUsbHost, RdxUsbHostError, RdxUsbPollReport, RdxUsbSmoothedClock, RdxUsbStatsReader, RdxUsbTalker, RdxUsbTimeoutProfile, RdxUsbTimeouts, RdxUsbUnknownFlagPolicy};
+#[cfg(feature = "unstable-hs")]
+use crate::host::{RdxUsbHsChannel, RdxUsbHsErrorFrames, RdxUsbHsHost, RdxUsbHsNotifications, RdxUsbHsWritePoller, RdxUsbHsWriter};
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -16,9 +20,23 @@ pub enum EventLoopError {
     EventLoopCrashed = -100,
     CannotListDevices = -101,
     DeviceIterInvalid = -102,
+    NoDeviceFound = -105,
+    AlreadyInitialized = -106,
+    NotManualPump = -107,
+    BufferTooSmall = -108,
+    InvalidArgument = -109,
+    CaptureNotActive = -110,
+    CaptureFailed = -111,
+    Panicked = -112,
+    DaemonUnavailable = -113,
     DeviceNotOpened = -200,
     DeviceNotConnected = -201,
     ChannelOutOfRange = -202,
+    Timeout = -203,
+    InvalidPacket = -204,
+    DescriptorUnavailable = -205,
+    ChannelClaimed = -206,
+    RequestUnsupported = -207,
 }
 
 impl EventLoopError {
@@ -27,9 +45,23 @@ impl EventLoopError {
     pub const ERR_DEVICE_ITER_INVALID: i32 = -102;
     pub const ERR_DEVICE_ITER_IDX_OUT_OF_RANGE: i32 = -103;
     pub const ERR_NULL_PTR: i32 = -104;
+    pub const ERR_NO_DEVICE_FOUND: i32 = -105;
+    pub const ERR_ALREADY_INITIALIZED: i32 = -106;
+    pub const ERR_NOT_MANUAL_PUMP: i32 = -107;
+    pub const ERR_BUFFER_TOO_SMALL: i32 = -108;
+    pub const ERR_INVALID_ARGUMENT: i32 = -109;
+    pub const ERR_CAPTURE_NOT_ACTIVE: i32 = -110;
+    pub const ERR_CAPTURE_FAILED: i32 = -111;
+    pub const ERR_PANICKED: i32 = -112;
+    pub const ERR_DAEMON_UNAVAILABLE: i32 = -113;
     pub const ERR_DEVICE_NOT_OPENED: i32 = -200;
     pub const ERR_DEVICE_NOT_CONNECTED: i32 = -201;
     pub const ERR_CHANNEL_OUT_OF_RANGE: i32 = -202;
+    pub const ERR_TIMEOUT: i32 = -203;
+    pub const ERR_INVALID_PACKET: i32 = -204;
+    pub const ERR_DESCRIPTOR_UNAVAILABLE: i32 = -205;
+    pub const ERR_CHANNEL_CLAIMED: i32 = -206;
+    pub const ERR_REQUEST_UNSUPPORTED: i32 = -207;
 
 }
 
@@ -47,19 +79,121 @@ pub enum DeviceIOError {
 
 pub enum DeviceChannels {
     FsDevice(Vec<RdxUsbFsChannel>),
+    #[cfg(feature = "unstable-hs")]
+    HsDevice(Vec<RdxUsbHsChannel>),
 }
 
 pub enum Writer {
     FsDevice(RdxUsbFsWriter),
+    #[cfg(feature = "unstable-hs")]
+    HsDevice(RdxUsbHsWriter),
 }
 
-impl DeviceChannels {}
+pub enum Notifications {
+    FsDevice(RdxUsbFsNotifications),
+    #[cfg(feature = "unstable-hs")]
+    HsDevice(RdxUsbHsNotifications),
+}
+
+pub enum ErrorFrames {
+    FsDevice(RdxUsbFsErrorFrames),
+    #[cfg(feature = "unstable-hs")]
+    HsDevice(RdxUsbHsErrorFrames),
+}
+
+impl DeviceChannels {
+    /// Received packets waiting to be read, across all channels.
+    fn queued(&self) -> usize {
+        match self {
+            DeviceChannels::FsDevice(channels) => channels.iter().map(RdxUsbFsChannel::queued).sum(),
+            #[cfg(feature = "unstable-hs")]
+            DeviceChannels::HsDevice(channels) => channels.iter().map(RdxUsbHsChannel::queued).sum(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            DeviceChannels::FsDevice(channels) => channels.len(),
+            #[cfg(feature = "unstable-hs")]
+            DeviceChannels::HsDevice(channels) => channels.len(),
+        }
+    }
+}
 
 pub struct OpenDevice {
     pub channels: DeviceChannels,
     pub writer: Writer,
+    pub notifications: Option<Notifications>,
+    pub error_frames: Option<ErrorFrames>,
     pub device_id: DeviceId,
     pub protocol: u8,
+    /// Device info read when the device was opened.
+    pub device_info: RdxUsbDeviceInfo,
+    /// Completed bulk IN transfers, see [`RdxUsbFsHost::rx_transfer_counter`].
+    pub rx_transfers: Arc<AtomicU64>,
+    /// Received packets with a clamped dlc, see [`RdxUsbFsHost::dlc_violation_counter`].
+    pub dlc_violations: Arc<AtomicU64>,
+    /// The host's rx rate limit, see [`RdxUsbFsHost::max_rx_rate`].
+    pub max_rx_rate: Arc<AtomicU32>,
+    /// Pauses at the rx rate limit, see [`RdxUsbFsHost::rx_throttle_counter`].
+    pub rx_throttled: Arc<AtomicU64>,
+    /// The host's timestamp smoothing filter, see [`RdxUsbFsHost::smoothed_clock`].
+    pub smoothed_clock: RdxUsbSmoothedClock,
+    /// Traffic counters of the connection, see [`RdxUsbFsHost::stats_reader`].
+    pub stats: RdxUsbStatsReader,
+    /// Packets held back by [`Self::try_read_ordered`].
+    pub reorder: ReorderBuffer,
+}
+
+/// Min-heap of packets by device timestamp, used to restore ordering across channels.
+#[derive(Default)]
+pub struct ReorderBuffer {
+    heap: BinaryHeap<Reverse<PendingPacket>>,
+    seq: u64,
+}
+
+struct PendingPacket {
+    timestamp_ns: u64,
+    /// arrival order, so packets with equal timestamps keep it
+    seq: u64,
+    packet: RdxUsbPacket,
+}
+
+impl PartialEq for PendingPacket {
+    fn eq(&self, other: &Self) -> bool {
+        (self.timestamp_ns, self.seq) == (other.timestamp_ns, other.seq)
+    }
+}
+impl Eq for PendingPacket {}
+impl PartialOrd for PendingPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for PendingPacket {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.timestamp_ns, self.seq).cmp(&(other.timestamp_ns, other.seq))
+    }
+}
+
+impl ReorderBuffer {
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn push(&mut self, packet: RdxUsbPacket) {
+        self.heap.push(Reverse(PendingPacket { timestamp_ns: packet.timestamp_ns, seq: self.seq, packet }));
+        self.seq += 1;
+    }
+
+    /// Removes the packet with the earliest timestamp.
+    pub fn pop(&mut self) -> Option<RdxUsbPacket> {
+        self.heap.pop().map(|Reverse(p)| p.packet)
+    }
 }
 
 impl OpenDevice {
@@ -72,6 +206,25 @@ impl OpenDevice {
                     None => Err(DeviceIOError::NoData)
                 }
             }
+            #[cfg(feature = "unstable-hs")]
+            DeviceChannels::HsDevice(vec) => {
+                if vec.len() <= channel_idx as usize { return Err(DeviceIOError::ChannelOutOfRange); }
+                vec[channel_idx as usize].try_read().ok_or(DeviceIOError::NoData)
+            }
+        }
+    }
+
+    pub fn try_read_echo(&mut self, channel_idx: u8) -> Result<RdxUsbPacket, DeviceIOError> {
+        match &mut self.channels {
+            DeviceChannels::FsDevice(vec) => {
+                let channel = vec.get_mut(channel_idx as usize).ok_or(DeviceIOError::ChannelOutOfRange)?;
+                channel.try_read_echo().map(|p| p.into()).ok_or(DeviceIOError::NoData)
+            }
+            #[cfg(feature = "unstable-hs")]
+            DeviceChannels::HsDevice(vec) => {
+                let channel = vec.get_mut(channel_idx as usize).ok_or(DeviceIOError::ChannelOutOfRange)?;
+                channel.try_read_echo().ok_or(DeviceIOError::NoData)
+            }
         }
     }
 
@@ -81,6 +234,75 @@ impl OpenDevice {
                 if vec.len() <= channel_idx as usize { return Err(RdxUsbHostError::NoInterface); }
                 Ok(vec[channel_idx as usize].read().await?.into())
             }
+            #[cfg(feature = "unstable-hs")]
+            DeviceChannels::HsDevice(vec) => {
+                if vec.len() <= channel_idx as usize { return Err(RdxUsbHostError::NoInterface); }
+                vec[channel_idx as usize].read().await
+            }
+        }
+    }
+
+    /// Subscribes to every channel at once, see [`RdxUsbFsChannel::subscribe`].
+    pub fn subscribe_all(&self, capacity: usize) -> futures_util::stream::BoxStream<'static, RdxUsbPacket> {
+        match &self.channels {
+            DeviceChannels::FsDevice(vec) => futures_util::stream::select_all(vec.iter().map(|ch| ch.subscribe(capacity).map(RdxUsbPacket::from))).boxed(),
+            #[cfg(feature = "unstable-hs")]
+            DeviceChannels::HsDevice(vec) => futures_util::stream::select_all(vec.iter().map(|ch| ch.subscribe(capacity))).boxed(),
+        }
+    }
+
+    /// Largest payload the device can carry, in bytes.
+    pub fn max_payload(&self) -> usize {
+        match &self.writer {
+            Writer::FsDevice(_) => 48,
+            #[cfg(feature = "unstable-hs")]
+            Writer::HsDevice(_) => 64,
+        }
+    }
+
+    /// Can `packet` be sent to this device as-is?
+    pub fn can_send(&self, packet: &RdxUsbPacket) -> bool {
+        packet.dlc as usize <= self.max_payload() && (!packet.fd() || is_valid_fd_len(packet.dlc))
+    }
+
+    /// Reads the queued packet with the earliest device timestamp across all channels.
+    pub fn try_read_any(&mut self) -> Option<RdxUsbPacket> {
+        match &mut self.channels {
+            DeviceChannels::FsDevice(vec) => {
+                let idx = (0..vec.len()).filter_map(|i| Some((vec[i].peek()?.timestamp_ns, i))).min()?.1;
+                vec[idx].try_read().map(|p| p.into())
+            }
+            #[cfg(feature = "unstable-hs")]
+            DeviceChannels::HsDevice(vec) => {
+                let idx = (0..vec.len()).filter_map(|i| Some((vec[i].peek()?.timestamp_ns, i))).min()?.1;
+                vec[idx].try_read()
+            }
+        }
+    }
+
+    /// Like [`Self::try_read_any`], but holds back the `depth` most recent packets in [`Self::reorder`]
+    /// so packets that arrive late on another channel can still be delivered in timestamp order.
+    pub fn try_read_ordered(&mut self, depth: usize) -> Option<RdxUsbPacket> {
+        while self.reorder.len() <= depth {
+            let Some(packet) = self.try_read_any() else { break; };
+            self.reorder.push(packet);
+        }
+        if self.reorder.len() > depth { self.reorder.pop() } else { None }
+    }
+
+    pub fn try_read_notification(&mut self) -> Option<RdxUsbPacket> {
+        match self.notifications.as_mut()? {
+            Notifications::FsDevice(n) => n.try_read().map(|p| p.into()),
+            #[cfg(feature = "unstable-hs")]
+            Notifications::HsDevice(n) => n.try_read(),
+        }
+    }
+
+    pub fn try_read_error_frame(&mut self) -> Option<RdxUsbPacket> {
+        match self.error_frames.as_mut()? {
+            ErrorFrames::FsDevice(e) => e.try_read().map(|p| p.into()),
+            #[cfg(feature = "unstable-hs")]
+            ErrorFrames::HsDevice(e) => e.try_read(),
         }
     }
 
@@ -92,6 +314,13 @@ impl OpenDevice {
                     None => Ok(())
                 }
             }
+            #[cfg(feature = "unstable-hs")]
+            Writer::HsDevice(writer) => {
+                match writer.try_send(*packet) {
+                    Some(s) => Err(s),
+                    None => Ok(())
+                }
+            }
         }
     }
 
@@ -103,8 +332,176 @@ impl OpenDevice {
                     Err(p) => Err(p.into())
                 }
             }
+            #[cfg(feature = "unstable-hs")]
+            Writer::HsDevice(writer) => writer.send(packet).await,
+        }
+    }
+}
+
+/// The host driving an open device, for whichever protocol it speaks.
+enum Host {
+    FsDevice(RdxUsbFsHost),
+    #[cfg(feature = "unstable-hs")]
+    HsDevice(RdxUsbHsHost),
+}
+
+impl Host {
+    async fn poll(&mut self, n_transfers: usize, await_on_full: bool) -> RdxUsbPollReport {
+        match self {
+            Host::FsDevice(host) => host.poll_with_report(n_transfers, await_on_full).await,
+            #[cfg(feature = "unstable-hs")]
+            Host::HsDevice(host) => host.poll_with_report(n_transfers, await_on_full).await,
+        }
+    }
+
+    fn set_fault_injection(&mut self, faults: Option<RdxUsbFaults>) {
+        match self {
+            Host::FsDevice(host) => host.set_fault_injection(faults),
+            #[cfg(feature = "unstable-hs")]
+            Host::HsDevice(host) => host.set_fault_injection(faults),
+        }
+    }
+
+    fn rx_transfer_counter(&self) -> Arc<AtomicU64> {
+        match self {
+            Host::FsDevice(host) => host.rx_transfer_counter(),
+            #[cfg(feature = "unstable-hs")]
+            Host::HsDevice(host) => host.rx_transfer_counter(),
         }
     }
+
+    fn reset(&self) -> Result<(), RdxUsbHostError> {
+        match self {
+            Host::FsDevice(host) => host.reset(),
+            #[cfg(feature = "unstable-hs")]
+            Host::HsDevice(host) => host.reset(),
+        }
+    }
+
+    fn interface(&self) -> &nusb::Interface {
+        match self {
+            Host::FsDevice(host) => host.interface(),
+            #[cfg(feature = "unstable-hs")]
+            Host::HsDevice(host) => host.interface(),
+        }
+    }
+}
+
+enum WritePoller {
+    FsDevice(RdxUsbFsWritePoller),
+    #[cfg(feature = "unstable-hs")]
+    HsDevice(RdxUsbHsWritePoller),
+}
+
+impl WritePoller {
+    async fn poll(&mut self) -> Result<(), RdxUsbHostError> {
+        match self {
+            WritePoller::FsDevice(poller) => poller.poll().await,
+            #[cfg(feature = "unstable-hs")]
+            WritePoller::HsDevice(poller) => poller.poll().await,
+        }
+    }
+}
+
+/// Lifecycle state of a [`Device`] handle.
+///
+/// Handles start out [`DeviceState::Searching`] and cycle through
+/// Searching → Attaching → Connected → Faulted → Searching until closed.
+/// Idle handles may be [`DeviceState::Parked`] in between, see [`set_idle_policy`].
+/// [`DeviceState::Closing`] is terminal and can be entered from any state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum DeviceState {
+    /// Waiting for a matching device to show up.
+    Searching = 0,
+    /// A matching device was found and is being opened.
+    Attaching = 1,
+    /// The device is open and exchanging packets.
+    Connected = 2,
+    /// Opening failed or the connection was lost.
+    Faulted = 3,
+    /// The handle is being closed and will not reconnect.
+    Closing = 4,
+    /// The handle went unused and released its device until it is read from or written to again.
+    Parked = 5,
+}
+
+impl DeviceState {
+    /// Is moving from `self` to `next` a legal transition?
+    pub fn can_transition_to(self, next: DeviceState) -> bool {
+        use DeviceState::*;
+        matches!((self, next),
+            (Searching, Attaching)
+            | (Attaching, Connected)
+            | (Attaching, Faulted)
+            | (Connected, Faulted)
+            | (Faulted, Searching)
+            | (Searching | Connected, Parked)
+            | (Parked, Searching)
+            | (Searching | Attaching | Connected | Faulted | Parked, Closing)
+        )
+    }
+}
+
+/// Packets a capture's writer thread can fall behind by before the poller drops them.
+const CAPTURE_QUEUE_DEPTH: usize = 4096;
+
+/// Work for a capture's writer thread, see [`DeviceCapture`].
+pub enum CaptureCommand {
+    Packet(RdxUsbPacket),
+    /// Annotation timestamped with the last packet written before it.
+    Annotate(String),
+    Finish,
+}
+
+/// Sends work to the writer thread of a running capture.
+pub type CaptureQueue = std::sync::mpsc::SyncSender<CaptureCommand>;
+
+/// A native capture file recording a device handle's received packets.
+///
+/// The file is written on a thread of its own, so a stalled disk drops captured packets instead of stalling
+/// the poller that receives them.
+pub struct DeviceCapture {
+    queue: CaptureQueue,
+    thread: Option<std::thread::JoinHandle<Result<(), EventLoopError>>>,
+}
+
+impl DeviceCapture {
+    fn start(mut writer: CaptureWriter<BufWriter<File>>) -> Result<Self, EventLoopError> {
+        let (queue, commands) = std::sync::mpsc::sync_channel(CAPTURE_QUEUE_DEPTH);
+        let thread = std::thread::Builder::new().name("rdxusb-capture".into()).spawn(move || {
+            let mut last_timestamp_ns = 0;
+            for command in commands {
+                let result = match command {
+                    CaptureCommand::Packet(packet) => {
+                        last_timestamp_ns = packet.timestamp_ns;
+                        writer.write_packet(&packet)
+                    }
+                    CaptureCommand::Annotate(text) => writer.write_annotation(&Annotation { timestamp_ns: last_timestamp_ns, text }),
+                    CaptureCommand::Finish => break,
+                };
+                if let Err(e) = result {
+                    log::warn!(target: "rdxusb", "capture: write failed, stopping capture: {e}");
+                    return Err(EventLoopError::CaptureFailed);
+                }
+            }
+            writer.finish().map_err(|_| EventLoopError::CaptureFailed)
+        }).map_err(|_| EventLoopError::CaptureFailed)?;
+        Ok(Self { queue, thread: Some(thread) })
+    }
+
+    /// Writes out everything queued and the time index, waiting for the writer thread to exit.
+    fn finish(&mut self) -> Result<(), EventLoopError> {
+        let Some(thread) = self.thread.take() else { return Ok(()); };
+        self.queue.send(CaptureCommand::Finish).ok();
+        thread.join().unwrap_or(Err(EventLoopError::CaptureFailed))
+    }
+}
+
+impl Drop for DeviceCapture {
+    fn drop(&mut self) {
+        self.finish().ok();
+    }
 }
 
 #[allow(unused)]
@@ -112,15 +509,108 @@ pub struct Device {
     pub vid: u16,
     pub pid: u16,
     pub serial_number: Option<String>,
+    /// The one attached device this handle opens, for handles opened from a device without a serial number.
+    pub device_id: Option<DeviceId>,
     pub handle: Option<OpenDevice>,
     pub poller_handle: tokio::task::JoinHandle<()>,
     pub device_info_out: tokio::sync::watch::Sender<Option<DeviceInfo>>,
     pub shutdown: Arc<tokio::sync::Notify>,
+    pub bridge_rules: RdxUsbBridgeRules,
+    pub state: tokio::sync::watch::Sender<DeviceState>,
+    /// Rx watchdog timeout in milliseconds, or 0 if disabled.
+    pub rx_watchdog_ms: Arc<AtomicU64>,
+    /// Rx rate limit applied to each connection in packets per second, or 0 if unlimited. See [`set_max_rx_rate`].
+    pub max_rx_rate: u32,
+    /// Timestamp smoothing bandwidth applied to each connection in Hz, or 0 if off. See [`set_timestamp_smoothing`].
+    pub timestamp_smoothing_hz: f64,
+    /// Nominal bit rate of the device's buses, which stats snapshots estimate bus load against. See [`set_stats_bit_rate`].
+    pub stats_bit_rate: u32,
+    /// Faults injected into each connection's received transfers, see [`set_fault_injection`].
+    pub fault_injection: Option<RdxUsbFaults>,
+    /// Devices with higher priorities are opened first when several attach at once. See [`set_reconnect_priority`].
+    pub reconnect_priority: u8,
+    /// Timeouts the poller opens the device with, and waits between reconnect attempts.
+    pub timeouts: Arc<Mutex<RdxUsbTimeouts>>,
+    /// Capture of this handle's received traffic, see [`start_capture`].
+    pub capture: Option<DeviceCapture>,
+    /// Queue of the running capture, which the poller forwards received packets to.
+    pub capture_queue: tokio::sync::watch::Sender<Option<CaptureQueue>>,
+    /// Packets [`read_packets_any`] holds back to restore timestamp ordering across channels.
+    pub reorder_depth: usize,
+    /// Whether reads are restricted to one thread per channel, see [`set_exclusive_readers`].
+    pub exclusive_readers: bool,
+    /// Threads that own reading each channel, see [`read_packets`].
+    pub channel_readers: HashMap<u8, ThreadId>,
+    /// Thread that owns [`read_packets_any`].
+    pub any_reader: Option<ThreadId>,
+    pub opened_at: Instant,
+    /// Packets handed out by [`read_packets`] and [`read_packets_any`], for [`handle_diagnostics`].
+    pub packets_read: u64,
+    /// Times the handle has connected, see [`RdxUsbPacketEx::connection_epoch`].
+    pub connection_epoch: u32,
+    /// Sequence number of the last [`write_reliable`] frame.
+    pub reliable_seq: u8,
+    /// The handle's stats callback, see [`set_stats_callback`].
+    pub stats_callback: Option<StatsCallbackTask>,
+    /// Last time packets were read from or written to the handle.
+    pub last_used: Instant,
+    pub idle_policy: Option<IdlePolicy>,
+    /// Set to make the poller release the device and wait, see [`DeviceState::Parked`].
+    pub parked: tokio::sync::watch::Sender<bool>,
 }
 
 impl Device {
+    /// Makes the calling thread the only reader of `channel`, or of every channel if `None`.
+    ///
+    /// Fails with [`EventLoopError::ChannelClaimed`] if another thread already reads it. Does nothing unless
+    /// [`set_exclusive_readers`] turned claims on for the handle.
+    fn claim_reader(&mut self, channel: Option<u8>) -> Result<(), EventLoopError> {
+        if !self.exclusive_readers { return Ok(()); }
+        let current = std::thread::current().id();
+        if self.any_reader.is_some_and(|owner| owner != current) { return Err(EventLoopError::ChannelClaimed); }
+        match channel {
+            Some(channel) => {
+                let owner = *self.channel_readers.entry(channel).or_insert(current);
+                if owner != current { return Err(EventLoopError::ChannelClaimed); }
+            }
+            None => {
+                if self.channel_readers.values().any(|&owner| owner != current) { return Err(EventLoopError::ChannelClaimed); }
+                self.any_reader = Some(current);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the device to `next` and publishes it to state subscribers.
+    ///
+    /// Returns false and leaves the state untouched if the transition is not legal.
+    pub fn transition(&self, next: DeviceState) -> bool {
+        let mut legal = false;
+        self.state.send_if_modified(|state| {
+            legal = state.can_transition_to(next);
+            if legal {
+                log::trace!(target: "rdxusb", "Device {:04x}:{:04x} {state:?} -> {next:?}", self.vid, self.pid);
+                *state = next;
+            } else {
+                log::trace!(target: "rdxusb", "Device {:04x}:{:04x} rejected transition {state:?} -> {next:?}", self.vid, self.pid);
+            }
+            legal
+        });
+        legal
+    }
+
+    pub fn current_state(&self) -> DeviceState {
+        *self.state.borrow()
+    }
+
+    /// Records that the handle was used, waking it up if it was parked for being idle.
+    fn touch(&mut self) {
+        self.last_used = Instant::now();
+        self.parked.send_if_modified(std::mem::take);
+    }
+
     pub fn matches(&self, vid: u16, pid: u16, serial_number: Option<&str>) -> bool {
-        self.vid == vid && self.pid == pid && (match &self.serial_number {
+        self.device_id.is_none() && self.vid == vid && self.pid == pid && (match &self.serial_number {
             Some(s) => match serial_number {
                 Some(s2) => s2 == s.as_str(),
                 None => false
@@ -129,6 +619,7 @@ impl Device {
         })
     }
     pub fn matches_device_info(&self, info: &DeviceInfo) -> bool {
+        if let Some(id) = self.device_id { return id == info.id(); }
         self.vid == info.vendor_id() && self.pid == info.product_id() && (match &self.serial_number {
             Some(s) => info.serial_number().map_or(false, |ins| s.as_str() == ins),
             None => true,
@@ -140,21 +631,80 @@ impl Device {
 pub struct EventLoop {
     pub devices: HashMap<i32, Device>,
     pub next_handle: i32,
-    pub rt: Runtime,
+    /// Shared so [`pump`] can drive it without holding the event loop lock.
+    pub rt: Arc<Runtime>,
+    pub manual_pump: bool,
+    pub timeouts: RdxUsbTimeouts,
+    pub unknown_flag_policy: RdxUsbUnknownFlagPolicy,
+    pub hotplug_shutdown: Arc<tokio::sync::Notify>,
+    /// Serializes device opens, see [`EventLoopConfig::attach_stagger`].
+    attach_gate: Arc<AttachGate>,
+    #[cfg(windows)]
+    pub hotplug_thread: Option<std::thread::JoinHandle<()>>,
+}
+
+/// Options used to construct the [`EventLoop`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventLoopConfig {
+    /// Number of tokio worker threads to use. Zero uses tokio's default (one per core).
+    pub worker_threads: usize,
+    /// Whether to watch for hotplug events. If false, devices only (re)connect on [`force_scan_devices`].
+    pub hotplug: bool,
+    /// Timeouts used when opening and talking to devices.
+    pub timeouts: RdxUsbTimeouts,
+    /// What to do with received packets carrying flag bits this version doesn't know.
+    pub unknown_flag_policy: RdxUsbUnknownFlagPolicy,
+    /// Run no background threads; USB processing only happens inside [`pump`] calls.
+    /// `worker_threads` is ignored. Hotplug on Windows still uses its own thread.
+    pub manual_pump: bool,
+    /// Pause between device opens. Devices that attach together, e.g. after a hub is power cycled, are opened one
+    /// at a time in [`set_reconnect_priority`] order rather than all at once, which makes some opens fail.
+    pub attach_stagger: Duration,
+}
+
+impl Default for EventLoopConfig {
+    fn default() -> Self {
+        Self {
+            worker_threads: 0,
+            hotplug: true,
+            timeouts: RdxUsbTimeouts::default(),
+            unknown_flag_policy: RdxUsbUnknownFlagPolicy::default(),
+            manual_pump: false,
+            attach_stagger: Duration::from_millis(20),
+        }
+    }
 }
 
 impl EventLoop {
     pub fn new() -> Self {
-        let rt = Runtime::new().expect("Unable to create tokio runtime");
+        Self::with_config(EventLoopConfig::default())
+    }
+
+    pub fn with_config(config: EventLoopConfig) -> Self {
+        let mut builder = if config.manual_pump {
+            // a current-thread runtime only makes progress while something blocks on it, i.e. in `pump`.
+            tokio::runtime::Builder::new_current_thread()
+        } else {
+            tokio::runtime::Builder::new_multi_thread()
+        };
+        builder.enable_all();
+        if config.worker_threads > 0 && !config.manual_pump {
+            builder.worker_threads(config.worker_threads);
+        }
+        let rt = Arc::new(builder.build().expect("Unable to create tokio runtime"));
 
         // Enter the runtime so that `tokio::spawn` is available immediately.
         let _enter = rt.enter();
+        let hotplug_shutdown = Arc::new(tokio::sync::Notify::new());
 
         #[cfg(unix)]
-        let _hotplug_handle = rt.spawn(hotplug());
+        if config.hotplug {
+            crate::spawn_named(rt.handle(), "hotplug", hotplug(hotplug_shutdown.clone()));
+        }
+        crate::spawn_named(rt.handle(), "idle-reaper", idle_reaper());
 
         #[cfg(windows)]
-        {
+        let hotplug_thread = config.hotplug.then(|| {
             // for whatever reason, hotplug isn't `Send`` on Windows so we have to do this nonsense.
             // see https://github.com/kevinmehall/nusb/issues/104
             let thread_rt = tokio::runtime::Builder::new_current_thread()
@@ -162,23 +712,73 @@ impl EventLoop {
                 .build()
                 .unwrap();
 
-            std::thread::spawn(move || {
+            let shutdown = hotplug_shutdown.clone();
+            std::thread::Builder::new().name("rdxusb-hotplug".to_string()).spawn(move || {
                 let local = tokio::task::LocalSet::new();
-                local.spawn_local(hotplug());
+                local.spawn_local(hotplug(shutdown));
                 thread_rt.block_on(local);
-            });
-        }
+            }).expect("rdxusb: could not spawn hotplug thread")
+        });
 
         Self {
             devices: HashMap::new(),
             next_handle: 0i32,
             rt,
+            manual_pump: config.manual_pump,
+            timeouts: config.timeouts,
+            unknown_flag_policy: config.unknown_flag_policy,
+            hotplug_shutdown,
+            attach_gate: Arc::new(AttachGate::new(config.attach_stagger)),
+            #[cfg(windows)]
+            hotplug_thread,
         }
     }
 
-    pub fn update_open_device(&mut self, id: i32, device: OpenDevice) {
-        self.devices.get_mut(&id).unwrap().handle.replace(device);
+    /// Stops every device poller and the hotplug watcher, then tears down the runtime.
+    ///
+    /// Pollers are given a second to release their devices, and the runtime's worker threads are joined,
+    /// so nothing of the event loop is left running once this returns (unless a [`pump`] call is still in progress).
+    pub fn shutdown(mut self) {
+        let mut stats_callbacks = Vec::new();
+        let pollers: Vec<_> = self.devices.drain().map(|(_, mut device)| {
+            device.transition(DeviceState::Closing);
+            device.shutdown.notify_one();
+            stats_callbacks.extend(device.stats_callback.take());
+            device.poller_handle
+        }).collect();
+        stats_callbacks.into_iter().for_each(StatsCallbackTask::stop);
+        self.hotplug_shutdown.notify_one();
+        // blocking on the runtime from within an async context would panic, so only wait when called from outside one.
+        if tokio::runtime::Handle::try_current().is_err() {
+            self.rt.block_on(async {
+                tokio::time::timeout(Duration::from_secs(1), futures_util::future::join_all(pollers)).await.ok();
+            });
+        }
+        #[cfg(windows)]
+        if let Some(thread) = self.hotplug_thread.take() {
+            thread.join().ok();
+        }
+        match Arc::try_unwrap(self.rt) {
+            Ok(rt) => rt.shutdown_timeout(std::time::Duration::from_secs(1)),
+            // a `pump` call on another thread still holds it; the runtime is dropped when that returns.
+            Err(_) => log::trace!(target: "rdxusb", "Runtime still being pumped, deferring shutdown"),
+        }
+    }
+
+    /// Attaches an opened device to its handle, moving it to [`DeviceState::Connected`].
+    ///
+    /// Returns false if the handle was closed in the meantime.
+    pub fn update_open_device(&mut self, id: i32, device: OpenDevice) -> bool {
+        let Some(d) = self.devices.get_mut(&id) else { return false; };
+        if !d.transition(DeviceState::Connected) { return false; }
+        d.handle.replace(device);
+        d.connection_epoch += 1;
+        true
+    }
 
+    /// Moves a handle to `next`. Returns false if the handle is gone or the transition is not legal.
+    pub fn transition_device(&self, id: i32, next: DeviceState) -> bool {
+        self.devices.get(&id).is_some_and(|d| d.transition(next))
     }
 
     pub fn remove_open_device(&mut self, id: i32) {
@@ -187,6 +787,22 @@ impl EventLoop {
         }
     }
 
+    /// Parks or closes a handle. Returns false if it is gone or already parked.
+    fn reap_handle(&mut self, id: i32, action: IdleAction) -> bool {
+        match action {
+            IdleAction::Park => {
+                let Some(device) = self.devices.get(&id) else { return false; };
+                device.parked.send_if_modified(|parked| !std::mem::replace(parked, true))
+            }
+            IdleAction::Close => {
+                let Some(device) = self.devices.remove(&id) else { return false; };
+                device.transition(DeviceState::Closing);
+                device.shutdown.notify_one();
+                true
+            }
+        }
+    }
+
     pub fn acquire_open_device(&mut self, id: i32) -> Result<&mut OpenDevice, EventLoopError> {
         let Some(device) = self.devices.get_mut(&id) else { return Err(EventLoopError::DeviceNotOpened); };
         let Some(open_device) = device.handle.as_mut() else { return Err(EventLoopError::DeviceNotConnected); };
@@ -222,119 +838,570 @@ pub fn try_acquire_event_loop<'a>() -> Result<EventLoopGuard<'a>, EventLoopError
     Ok(EventLoopGuard(event_loop_lock))
 }
 
+/// Acquires the event loop only if it is currently initialized.
+///
+/// Background tasks use this so that they never resurrect an event loop that is being finalized.
+pub fn acquire_initialized_event_loop<'a>() -> Option<EventLoopGuard<'a>> {
+    let event_loop_lock = EVENT_LOOP.lock().ok()?;
+    event_loop_lock.get()?;
+    Some(EventLoopGuard(event_loop_lock))
+}
+
+/// Explicitly initializes the event loop with the given config.
+///
+/// Without this, the event loop is lazily initialized with the default config on first use.
+pub fn init(config: EventLoopConfig) -> Result<(), EventLoopError> {
+    let event_loop_lock = EVENT_LOOP.lock().map_err(|_e| EventLoopError::EventLoopCrashed)?;
+    if event_loop_lock.get().is_some() { return Err(EventLoopError::AlreadyInitialized); }
+    event_loop_lock.get_or_init(|| EventLoop::with_config(config));
+    Ok(())
+}
+
+/// Drives USB processing on the calling thread for `max_duration`, for event loops initialized with
+/// [`EventLoopConfig::manual_pump`].
+///
+/// Every task that is ready runs at least once, even for a zero duration. Must not be called from within
+/// an async context.
+pub fn pump(max_duration: Duration) -> Result<(), EventLoopError> {
+    let rt = {
+        let event_loop = try_acquire_event_loop()?;
+        if !event_loop.manual_pump { return Err(EventLoopError::NotManualPump); }
+        event_loop.rt.clone()
+    };
+    // the lock has to be released first, as the pollers being driven need it.
+    rt.block_on(async {
+        tokio::task::yield_now().await;
+        tokio::time::sleep(max_duration).await;
+    });
+    Ok(())
+}
+
+/// Closes all devices and tears down the event loop, joining its threads.
+///
+/// The event loop may be initialized again afterwards, even if it had crashed. Does nothing if it isn't initialized.
+/// Must not be called from within an async context, or pollers are not waited for.
+pub fn finalize() -> Result<(), EventLoopError> {
+    let event_loop = {
+        // a crashed event loop is torn down like any other so that it can be initialized again.
+        let mut event_loop_lock = EVENT_LOOP.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        EVENT_LOOP.clear_poison();
+        event_loop_lock.take()
+    };
+    // the lock has to be released first, as pollers may still be waiting on it while shutting down.
+    if let Some(event_loop) = event_loop {
+        event_loop.shutdown();
+    }
+    Ok(())
+}
+
+
+/// Packets per bulk OUT transfer for full speed devices, matching one high speed transfer.
+const FS_WRITE_BATCH: usize = 8;
+
+/// Lets device pollers open their devices one at a time, highest [`Device::reconnect_priority`] first, with a
+/// pause between opens.
+struct AttachGate {
+    stagger: Duration,
+    state: Mutex<AttachGateState>,
+    changed: tokio::sync::Notify,
+}
+
+#[derive(Default)]
+struct AttachGateState {
+    /// whether a device is being opened
+    busy: bool,
+    /// (priority, arrival order) of every poller waiting to open
+    waiting: Vec<(u8, u64)>,
+    next_ticket: u64,
+    last_release: Option<tokio::time::Instant>,
+}
+
+impl AttachGate {
+    fn new(stagger: Duration) -> Self {
+        Self { stagger, state: Mutex::default(), changed: tokio::sync::Notify::new() }
+    }
+
+    async fn acquire(self: &Arc<Self>, priority: u8) -> AttachPermit {
+        let ticket = {
+            let mut state = self.state.lock().unwrap();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiting.push((priority, ticket));
+            AttachTicket { gate: self, ticket }
+        };
+        loop {
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            // registered before checking, so a release in between isn't missed
+            notified.as_mut().enable();
+            let not_before = {
+                let mut state = self.state.lock().unwrap();
+                let next = state.waiting.iter().max_by_key(|(priority, ticket)| (*priority, std::cmp::Reverse(*ticket))).copied();
+                if state.busy || next != Some((priority, ticket.ticket)) { None } else {
+                    state.busy = true;
+                    state.waiting.retain(|(_, t)| *t != ticket.ticket);
+                    Some(state.last_release.map(|t| t + self.stagger))
+                }
+            };
+            if let Some(not_before) = not_before {
+                // held from here on, so being cancelled while pausing still frees the gate
+                let permit = AttachPermit(self.clone());
+                if let Some(not_before) = not_before {
+                    tokio::time::sleep_until(not_before).await;
+                }
+                return permit;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// A poller's place in the [`AttachGate`] queue, given up if it stops waiting.
+struct AttachTicket<'a> {
+    gate: &'a AttachGate,
+    ticket: u64,
+}
+
+impl Drop for AttachTicket<'_> {
+    fn drop(&mut self) {
+        let mut state = self.gate.state.lock().unwrap();
+        let len = state.waiting.len();
+        state.waiting.retain(|(_, t)| *t != self.ticket);
+        if state.waiting.len() != len {
+            self.gate.changed.notify_waiters();
+        }
+    }
+}
+
+/// Permission to open a device, held until the open finishes.
+struct AttachPermit(Arc<AttachGate>);
+
+impl Drop for AttachPermit {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.busy = false;
+        state.last_release = Some(tokio::time::Instant::now());
+        self.0.changed.notify_waiters();
+    }
+}
+
+/// Per-handle settings a [`device_poller`] is spawned with.
+#[derive(Debug, Clone)]
+pub struct PollerConfig {
+    pub unknown_flag_policy: RdxUsbUnknownFlagPolicy,
+    pub close_on_dc: bool,
+    pub capacity: usize,
+    /// See [`Device::parked`].
+    pub parked: tokio::sync::watch::Receiver<bool>,
+    /// See [`Device::capture_queue`].
+    pub capture_queue: tokio::sync::watch::Receiver<Option<CaptureQueue>>,
+}
+
+/// Sets up a freshly opened host for its handle: its write poller, bridge, and the [`OpenDevice`] readers use.
+fn attach_host(opened: RdxUsbHost, device_id: DeviceId, unknown_flag_policy: RdxUsbUnknownFlagPolicy, capacity: usize, bridge_rules: &RdxUsbBridgeRules) -> Result<(Host, WritePoller, OpenDevice), RdxUsbHostError> {
+    Ok(match opened {
+        RdxUsbHost::Fs(mut host, channels) => {
+            host.set_unknown_flag_policy(unknown_flag_policy);
+            let (mut write_poller, writer) = host.write_poller(capacity)?;
+            let bridge_writer = write_poller.add_writer(capacity);
+            write_poller.set_max_batch(FS_WRITE_BATCH);
+            host.set_bridge(bridge_rules.clone(), bridge_writer);
+
+            let open_device = OpenDevice {
+                channels: DeviceChannels::FsDevice(channels),
+                writer: Writer::FsDevice(writer),
+                notifications: host.take_notifications().map(Notifications::FsDevice),
+                error_frames: host.take_error_frames().map(ErrorFrames::FsDevice),
+                device_id,
+                protocol: PROTOCOL_VERSION_MAJOR_FS as u8,
+                device_info: host.device_info(),
+                rx_transfers: host.rx_transfer_counter(),
+                dlc_violations: host.dlc_violation_counter(),
+                max_rx_rate: host.max_rx_rate(),
+                rx_throttled: host.rx_throttle_counter(),
+                smoothed_clock: host.smoothed_clock(),
+                stats: host.stats_reader(),
+                reorder: ReorderBuffer::default(),
+            };
+            (Host::FsDevice(host), WritePoller::FsDevice(write_poller), open_device)
+        }
+        #[cfg(feature = "unstable-hs")]
+        RdxUsbHost::Hs(mut host, channels) => {
+            host.set_unknown_flag_policy(unknown_flag_policy);
+            let (mut write_poller, writer) = host.write_poller(capacity)?;
+            let bridge_writer = write_poller.add_writer(capacity);
+            host.set_bridge(bridge_rules.clone(), bridge_writer);
+
+            let open_device = OpenDevice {
+                channels: DeviceChannels::HsDevice(channels),
+                writer: Writer::HsDevice(writer),
+                notifications: host.take_notifications().map(Notifications::HsDevice),
+                error_frames: host.take_error_frames().map(ErrorFrames::HsDevice),
+                device_id,
+                protocol: PROTOCOL_VERSION_MAJOR_HS as u8,
+                device_info: host.device_info(),
+                rx_transfers: host.rx_transfer_counter(),
+                dlc_violations: host.dlc_violation_counter(),
+                max_rx_rate: host.max_rx_rate(),
+                rx_throttled: host.rx_throttle_counter(),
+                smoothed_clock: host.smoothed_clock(),
+                stats: host.stats_reader(),
+                reorder: ReorderBuffer::default(),
+            };
+            (Host::HsDevice(host), WritePoller::HsDevice(write_poller), open_device)
+        }
+    })
+}
 
 pub async fn device_poller(
     id: i32,
     mut device_info_in: tokio::sync::watch::Receiver<Option<DeviceInfo>>,
     shutdown: Arc<tokio::sync::Notify>,
-    close_on_dc: bool,
-    capacity: usize,
+    bridge_rules: RdxUsbBridgeRules,
+    rx_watchdog_ms: Arc<AtomicU64>,
+    timeouts: Arc<Mutex<RdxUsbTimeouts>>,
+    config: PollerConfig,
 ) {
+    let PollerConfig { unknown_flag_policy, close_on_dc, capacity, mut parked, capture_queue } = config;
     log::trace!(target: "rdxusb", "Device poller for task {id} started!");
     loop {
-        let dev_info = match device_info_in.changed().await {
-            Ok(_) => {
+        let changed = tokio::select! {
+            changed = device_info_in.changed() => Some(changed),
+            _ = parked.wait_for(|&p| p) => None,
+        };
+        let dev_info = match changed {
+            Some(Ok(_)) => {
                 match device_info_in.borrow_and_update().clone() {
                     Some(d) => d,
                     None => { continue; }
                 }
             }
-            Err(_e) => { break; }
+            Some(Err(_e)) => { break; }
+            None => {
+                if !wait_unparked(id, &mut parked, &mut device_info_in).await { return; }
+                continue;
+            }
         };
         log::trace!(target: "rdxusb", "poller: Acquired matching deviceinfo");
+        let (attach_gate, priority) = {
+            let Some(event_loop) = acquire_initialized_event_loop() else { return; };
+            if !event_loop.transition_device(id, DeviceState::Attaching) { return; }
+            let Some(device) = event_loop.devices.get(&id) else { return; };
+            (event_loop.attach_gate.clone(), device.reconnect_priority)
+        };
+        let attach_permit = tokio::select! {
+            permit = attach_gate.acquire(priority) => permit,
+            _ = shutdown.notified() => return,
+        };
 
         let device_id = dev_info.id();
-        let (mut host, channels) = match RdxUsbFsHost::open_device(dev_info, capacity).await {
+        let open_timeouts = *timeouts.lock().unwrap();
+        let opened = crate::host::open_device(dev_info, capacity, open_timeouts).await;
+        drop(attach_permit);
+        let opened = opened.and_then(|opened| attach_host(opened, device_id, unknown_flag_policy, capacity, &bridge_rules));
+        let (mut host, mut write_poller, open_device) = match opened {
             Ok(a) => {
-                log::trace!(target: "rdxusb", "poller: Successfully opened device, opening write-poller");
+                log::trace!(target: "rdxusb", "poller: Successfully opened device and its write poller");
                 a
             }
             Err(e) => {
                 log::trace!(target: "rdxusb", "poller: Could not open device: {e:?}");
+                {
+                    let Some(event_loop) = acquire_initialized_event_loop() else { return; };
+                    if !(event_loop.transition_device(id, DeviceState::Faulted) && event_loop.transition_device(id, DeviceState::Searching)) {
+                        return;
+                    }
+                }
+                if !reconnect_backoff(&timeouts, &shutdown).await { return; }
                 continue;
             }
         };
-        let (mut write_poller, writer) = host.write_poller(capacity);
-
-
-        let open_device = OpenDevice {
-            channels: DeviceChannels::FsDevice(channels),
-            writer: Writer::FsDevice(writer),
-            device_id,
-            protocol: 0,
-        };
         {
-            let mut event_loop = acquire_event_loop();
-            event_loop.update_open_device(id, open_device);
+            let Some(mut event_loop) = acquire_initialized_event_loop() else { return; };
+            // the handle may have been closed while we were opening the device.
+            if !event_loop.update_open_device(id, open_device) { return; }
+            let Some(device) = event_loop.devices.get(&id) else { return; };
+            if let Some(handle) = &device.handle {
+                handle.max_rx_rate.store(device.max_rx_rate, Ordering::Relaxed);
+                handle.smoothed_clock.set_bandwidth(device.timestamp_smoothing_hz);
+            }
+            host.set_fault_injection(device.fault_injection);
         }
 
+        let rx_transfers = host.rx_transfer_counter();
+        let iface = host.interface().clone();
         // this will eventually error out on disconnect
-        tokio::select! {
-            val = host.poll(32, false) => {
-                log::trace!(target: "rdxusb", "Read poller exited early! {:?}", val.err());
+        let session = std::panic::AssertUnwindSafe(async {
+            tokio::select! {
+                report = host.poll(32, false) => {
+                    log::trace!(target: "rdxusb", "Read poller exited early! {report:?}");
+                    SessionEnd::Disconnected
+                }
+                val = write_poller.poll() => {
+                    log::trace!(target: "rdxusb", "Write poller exited early! {:?}", val.err());
+                    SessionEnd::Disconnected
+                }
+                _val = capture_packets(id, capacity, capture_queue.clone()) => SessionEnd::Disconnected,
+                _val = rx_watchdog(rx_transfers, rx_watchdog_ms.clone(), iface) => {
+                    log::trace!(target: "rdxusb", "Rx watchdog expired, resetting device");
+                    SessionEnd::WatchdogExpired
+                }
+                // we need a notifier here because oneshot channels won't live on repeat iterations
+                _val = shutdown.notified() => {
+                    log::trace!(target: "rdxusb", "Poller Shutdown requested");
+                    SessionEnd::Shutdown
+                }
+                _val = parked.wait_for(|&p| p) => {
+                    log::trace!(target: "rdxusb", "Handle {id} idle, releasing device");
+                    SessionEnd::Parked
+                }
             }
-            val = write_poller.poll() => {
-                log::trace!(target: "rdxusb", "Write poller exited early! {:?}", val.err());
+        });
+        // a panic in any of the pollers faults the handle like a disconnect would, rather than killing the task
+        // and leaving the handle stuck connected.
+        let session_end = futures_util::FutureExt::catch_unwind(session).await.unwrap_or_else(|panic| {
+            log::error!(target: "rdxusb", "Device poller for handle {id} panicked: {}", panic_message(panic.as_ref()));
+            SessionEnd::Panicked
+        });
+        if session_end == SessionEnd::Shutdown { return; }
+        if session_end == SessionEnd::Parked {
+            {
+                let Some(mut event_loop) = acquire_initialized_event_loop() else { return; };
+                event_loop.remove_open_device(id);
             }
-            // we need a notifier here because oneshot channels won't live on repeat iterations
-            _val = shutdown.notified() => { 
-                log::trace!(target: "rdxusb", "Poller Shutdown requested");
-                return; 
+            if !wait_unparked(id, &mut parked, &mut device_info_in).await { return; }
+            continue;
+        }
+        if matches!(session_end, SessionEnd::WatchdogExpired | SessionEnd::Panicked) {
+            // the device is enumerated but wedged; a reset forces it to re-enumerate and hotplug back in.
+            if let Err(e) = host.reset() {
+                log::trace!(target: "rdxusb", "Could not reset device: {e}");
             }
         }
         {
-            let mut event_loop = acquire_event_loop();
+            let Some(mut event_loop) = acquire_initialized_event_loop() else { return; };
             event_loop.remove_open_device(id);
+            if !event_loop.transition_device(id, DeviceState::Faulted) { return; }
             if close_on_dc {
                 // TODO: close bus
+                event_loop.transition_device(id, DeviceState::Closing);
                 event_loop.devices.remove(&id);
                 return;
             }
+            if !event_loop.transition_device(id, DeviceState::Searching) { return; }
         }
+        if !reconnect_backoff(&timeouts, &shutdown).await { return; }
     }
 }
 
+/// Why a [`device_poller`] stopped serving an open device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionEnd {
+    Disconnected,
+    WatchdogExpired,
+    Panicked,
+    Shutdown,
+    Parked,
+}
 
-pub async fn hotplug() {
-    let mut hotplug_watcher = nusb::watch_devices().expect("rdxusb: Could not start hotplug task");
-    while let Some(event) = hotplug_watcher.next().await {
-        match event {
-            nusb::hotplug::HotplugEvent::Connected(device_info) => {
-                let mut event_loop = acquire_event_loop();
-                'device_iter: for device in event_loop.devices.values_mut() {
-                    if device.matches_device_info(&device_info) {
-                        device.device_info_out.send_replace(Some(device_info));
-                        break 'device_iter;
-                    }
-                }
-            }
-            nusb::hotplug::HotplugEvent::Disconnected(_device_id) => {}
-        }
+/// The message a panic was raised with, if it has one.
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "<non-string panic payload>"
     }
 }
 
-pub fn force_scan_devices(event_loop: EventLoopGuard) -> Result<EventLoopGuard, EventLoopError> {
-    log::trace!(target: "rdxusb", "Force scan devices triggered");
-    let Ok(list_device_iter) = nusb::list_devices() else { return Err(EventLoopError::CannotListDevices); };
-    for device_info in list_device_iter {
-        log::trace!(target: "rdxusb", "Found device: {device_info:?}");
-        'device_loop: for device in event_loop.devices.values() {
-            if device.matches_device_info(&device_info) {
-                log::trace!(target: "rdxusb", "Device matches deviceinfo, triggering hotplug");
-                device.device_info_out.send_replace(Some(device_info));
-                break 'device_loop;
-            }
-        }
+/// Holds a parked poller until its handle is used again, see [`set_idle_policy`].
+///
+/// Returns false if the handle was closed in the meantime.
+async fn wait_unparked(id: i32, parked: &mut tokio::sync::watch::Receiver<bool>, device_info_in: &mut tokio::sync::watch::Receiver<Option<DeviceInfo>>) -> bool {
+    {
+        let Some(event_loop) = acquire_initialized_event_loop() else { return false; };
+        if !event_loop.transition_device(id, DeviceState::Parked) { return false; }
     }
-    Ok(event_loop)
+    if parked.wait_for(|&p| !p).await.is_err() { return false; }
+    let Some(event_loop) = acquire_initialized_event_loop() else { return false; };
+    if !event_loop.transition_device(id, DeviceState::Searching) { return false; }
+    // a device that stayed attached while parked sends no hotplug event, so try the last one seen again
+    device_info_in.mark_changed();
+    true
 }
 
-pub fn open_device(vid: u16, pid: u16, serial_number: Option<String>, close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
-    log::trace!(target: "rdxusb", "Open device {vid:04x} {pid:04x} {serial_number:?} {close_on_dc}");
-    let mut event_loop = try_acquire_event_loop()?;
+/// Waits out the reconnect backoff before a poller looks for its device again.
+///
+/// Returns false if shutdown was requested in the meantime. Hotplug events arriving during the wait are not lost,
+/// as the device info watch keeps the latest one.
+async fn reconnect_backoff(timeouts: &Mutex<RdxUsbTimeouts>, shutdown: &tokio::sync::Notify) -> bool {
+    let backoff = timeouts.lock().unwrap().reconnect_backoff;
+    if backoff.is_zero() { return true; }
+    log::trace!(target: "rdxusb", "poller: Backing off for {backoff:?} before reconnecting");
+    tokio::select! {
+        _ = tokio::time::sleep(backoff) => true,
+        _ = shutdown.notified() => false,
+    }
+}
 
-    let maybe_existing = event_loop.devices.iter_mut().find_map(|(handle, device)| {
-        if device.matches(vid, pid, serial_number.as_ref().map(|s| s.as_str())) {
+/// Forwards the packets a handle receives to its capture's writer thread, subscribing to the device's channels
+/// only while a capture is running. Never resolves.
+async fn capture_packets(id: i32, capacity: usize, mut capture_queue: tokio::sync::watch::Receiver<Option<CaptureQueue>>) {
+    loop {
+        let queue = capture_queue.borrow_and_update().clone();
+        let subscribed = queue.and_then(|queue| {
+            let event_loop = acquire_initialized_event_loop()?;
+            let packets = event_loop.devices.get(&id)?.handle.as_ref()?.subscribe_all(capacity);
+            Some((queue, packets))
+        });
+        let forward = async move {
+            let Some((queue, mut packets)) = subscribed else { return std::future::pending().await; };
+            let mut dropped = false;
+            while let Some(packet) = packets.next().await {
+                match queue.try_send(CaptureCommand::Packet(packet)) {
+                    Ok(()) => dropped = false,
+                    Err(std::sync::mpsc::TrySendError::Full(_)) => {
+                        if !dropped { log::warn!(target: "rdxusb", "capture: writer for handle {id} fell behind, dropping packets"); }
+                        dropped = true;
+                    }
+                    // the writer failed and stopped
+                    Err(std::sync::mpsc::TrySendError::Disconnected(_)) => break,
+                }
+            }
+            std::future::pending().await
+        };
+        // dropping `forward` when the capture changes also drops its subscription
+        tokio::select! {
+            () = forward => {}
+            changed = capture_queue.changed() => if changed.is_err() { return std::future::pending().await; },
+        }
+    }
+}
+
+/// Resolves once the device stops responding: a full watchdog period passes without any bulk IN transfers
+/// completing, and a ping sent then isn't answered within another period.
+///
+/// Quiet but healthy devices answer the ping (or stall it, on firmware without ping support), so only wedged
+/// devices are caught. Never resolves while the watchdog timeout is 0 (disabled).
+async fn rx_watchdog(rx_transfers: Arc<AtomicU64>, timeout_ms: Arc<AtomicU64>, iface: nusb::Interface) {
+    let mut last_count = rx_transfers.load(Ordering::Relaxed);
+    loop {
+        let timeout = match timeout_ms.load(Ordering::Relaxed) {
+            0 => Duration::from_millis(100),
+            ms => Duration::from_millis(ms),
+        };
+        tokio::time::sleep(timeout).await;
+        let count = rx_transfers.load(Ordering::Relaxed);
+        if count == last_count && timeout_ms.load(Ordering::Relaxed) != 0 {
+            match crate::host::ping(&iface, timeout).await {
+                Ok(_) | Err(RdxUsbHostError::EndpointStall) => (),
+                Err(e) => {
+                    log::trace!(target: "rdxusb", "Rx watchdog: device went quiet and did not answer a ping: {e}");
+                    return;
+                }
+            }
+        }
+        last_count = count;
+    }
+}
+
+/// Bumped by [`hotplug`] whenever a known Redux device attaches or detaches, see [`device_list_generation`].
+static DEVICE_LIST_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A counter that changes whenever a known Redux device (see [`crate::vendor`]) is attached or detached,
+/// so UIs can poll one integer and only enumerate devices again when it changes.
+///
+/// It is driven by hotplug events, so it never changes if the event loop was initialized without hotplug.
+pub fn device_list_generation() -> Result<u64, EventLoopError> {
+    // the hotplug watcher only runs once the event loop does
+    try_acquire_event_loop()?;
+    Ok(DEVICE_LIST_GENERATION.load(Ordering::Relaxed))
+}
+
+pub async fn hotplug(shutdown: Arc<tokio::sync::Notify>) {
+    let mut hotplug_watcher = nusb::watch_devices().expect("rdxusb: Could not start hotplug task");
+    // disconnect events only carry the device id, so remember which attached devices count
+    let mut redux_devices: std::collections::HashSet<DeviceId> = crate::vendor::list_redux_devices()
+        .map(|devices| devices.map(|d| d.id()).collect())
+        .unwrap_or_default();
+    loop {
+        let event = tokio::select! {
+            event = hotplug_watcher.next() => match event {
+                Some(event) => event,
+                None => { break; }
+            },
+            _val = shutdown.notified() => { break; }
+        };
+        match event {
+            nusb::hotplug::HotplugEvent::Connected(device_info) => {
+                if crate::vendor::is_redux_device(device_info.vendor_id(), device_info.product_id()) && redux_devices.insert(device_info.id()) {
+                    DEVICE_LIST_GENERATION.fetch_add(1, Ordering::Relaxed);
+                }
+                let Some(mut event_loop) = acquire_initialized_event_loop() else { break; };
+                'device_iter: for device in event_loop.devices.values_mut() {
+                    if device.matches_device_info(&device_info) {
+                        device.device_info_out.send_replace(Some(device_info));
+                        break 'device_iter;
+                    }
+                }
+            }
+            nusb::hotplug::HotplugEvent::Disconnected(device_id) => {
+                if redux_devices.remove(&device_id) {
+                    DEVICE_LIST_GENERATION.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+pub fn force_scan_devices(event_loop: EventLoopGuard) -> Result<EventLoopGuard, EventLoopError> {
+    log::trace!(target: "rdxusb", "Force scan devices triggered");
+    let Ok(list_device_iter) = nusb::list_devices() else { return Err(EventLoopError::CannotListDevices); };
+    for device_info in list_device_iter {
+        log::trace!(target: "rdxusb", "Found device: {device_info:?}");
+        'device_loop: for device in event_loop.devices.values() {
+            if device.matches_device_info(&device_info) {
+                log::trace!(target: "rdxusb", "Device matches deviceinfo, triggering hotplug");
+                device.device_info_out.send_replace(Some(device_info));
+                break 'device_loop;
+            }
+        }
+    }
+    Ok(event_loop)
+}
+
+/// Largest buffer size a handle is opened with; larger ones are clamped to it.
+pub const MAX_CAPACITY: usize = 1 << 16;
+
+/// Opens a handle to the device matching `vid`, `pid` and `serial_number`, buffering up to `capacity` packets
+/// (at most [`MAX_CAPACITY`]) in each direction.
+pub fn open_device(vid: u16, pid: u16, serial_number: Option<String>, close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+    open_device_inner(vid, pid, serial_number, None, close_on_dc, capacity)
+}
+
+/// Opens one specific attached device, such as an entry from a device listing.
+///
+/// Devices with a serial number are matched by their vid/pid/serial number tuple exactly as with [`open_device`].
+/// Devices without one are pinned to their [`DeviceId`], which changes when the device is replugged, so such a
+/// handle stays disconnected once the device goes away.
+pub fn open_device_info(device_info: &DeviceInfo, close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+    let serial_number = device_info.serial_number().map(|s| s.to_string());
+    let device_id = serial_number.is_none().then(|| device_info.id());
+    open_device_inner(device_info.vendor_id(), device_info.product_id(), serial_number, device_id, close_on_dc, capacity)
+}
+
+fn open_device_inner(vid: u16, pid: u16, serial_number: Option<String>, device_id: Option<DeviceId>, close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+    log::trace!(target: "rdxusb", "Open device {vid:04x} {pid:04x} {serial_number:?} {device_id:?} {close_on_dc}");
+    let capacity = capacity.min(MAX_CAPACITY);
+    let mut event_loop = try_acquire_event_loop()?;
+
+    let maybe_existing = event_loop.devices.iter_mut().find_map(|(handle, device)| {
+        let matches = match device_id {
+            Some(id) => device.device_id == Some(id),
+            None => device.matches(vid, pid, serial_number.as_ref().map(|s| s.as_str())),
+        };
+        if matches {
             Some(*handle)
         } else { None }
     });
@@ -350,17 +1417,57 @@ pub fn open_device(vid: u16, pid: u16, serial_number: Option<String>, close_on_d
     let handle = event_loop.next_handle;
     event_loop.next_handle += 1;
     let shutdown = Arc::new(tokio::sync::Notify::new());
+    let bridge_rules: RdxUsbBridgeRules = Arc::new(Mutex::new(Vec::new()));
+    let (state, _) = tokio::sync::watch::channel(DeviceState::Searching);
+    let rx_watchdog_ms = Arc::new(AtomicU64::new(event_loop.timeouts.rx_watchdog.as_millis() as u64));
+    let timeouts = Arc::new(Mutex::new(event_loop.timeouts));
+    let (parked, parked_rx) = tokio::sync::watch::channel(false);
+    let (capture_queue, capture_queue_rx) = tokio::sync::watch::channel(None);
+
+    let config = PollerConfig {
+        unknown_flag_policy: event_loop.unknown_flag_policy,
+        close_on_dc,
+        capacity,
+        parked: parked_rx,
+        capture_queue: capture_queue_rx,
+    };
 
     log::trace!(target: "rdxusb", "Spawn device poller for new handle {handle}");
-    let device_poller_task = event_loop.rt.spawn(device_poller(handle, rx, shutdown.clone(), close_on_dc, capacity));
+    let task_name = format!("poller:{vid:04x}:{pid:04x}:{}", serial_number.as_deref().unwrap_or("*"));
+    let device_poller = device_poller(handle, rx, shutdown.clone(), bridge_rules.clone(), rx_watchdog_ms.clone(), timeouts.clone(), config);
+    let device_poller_task = crate::spawn_named(event_loop.rt.handle(), &task_name, device_poller);
     let device_entry = Device {
         vid,
         pid,
         serial_number,
+        device_id,
         handle: None,
         device_info_out: tx,
         poller_handle: device_poller_task,
         shutdown,
+        bridge_rules,
+        state,
+        rx_watchdog_ms,
+        max_rx_rate: 0,
+        timestamp_smoothing_hz: 0.0,
+        stats_bit_rate: DEFAULT_STATS_BIT_RATE,
+        fault_injection: None,
+        reconnect_priority: 0,
+        timeouts,
+        capture: None,
+        capture_queue,
+        reorder_depth: 0,
+        exclusive_readers: false,
+        channel_readers: HashMap::new(),
+        any_reader: None,
+        opened_at: Instant::now(),
+        packets_read: 0,
+        connection_epoch: 0,
+        reliable_seq: 0,
+        stats_callback: None,
+        last_used: Instant::now(),
+        idle_policy: None,
+        parked,
     };
 
     event_loop.devices.insert(handle, device_entry);
@@ -368,8 +1475,25 @@ pub fn open_device(vid: u16, pid: u16, serial_number: Option<String>, close_on_d
     Ok(handle)
 }
 
+/// Opens the first attached known Redux device (see [`crate::vendor`]) by its vid/pid/serial number.
+pub fn open_first_redux_device(close_on_dc: bool, capacity: usize) -> Result<i32, EventLoopError> {
+    let Ok(mut device_iter) = crate::vendor::list_redux_devices() else { return Err(EventLoopError::CannotListDevices); };
+    let Some(device_info) = device_iter.next() else { return Err(EventLoopError::NoDeviceFound); };
+    let serial_number = device_info.serial_number().map(|s| s.to_string());
+    open_device(device_info.vendor_id(), device_info.product_id(), serial_number, close_on_dc, capacity)
+}
+
+/// Reads packets received on `channel`.
+///
+/// Concurrent readers of a channel split its frames between them unpredictably. Handles with
+/// [`set_exclusive_readers`] turned on give each channel a single reader instead: the first thread to read it
+/// owns it until [`release_reader`] is called, and reads from any other thread fail with
+/// [`EventLoopError::ChannelClaimed`].
 pub fn read_packets(handle_id: i32, channel: u8, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
     let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.touch();
+    device.claim_reader(Some(channel))?;
     let open_device = event_loop.acquire_open_device(handle_id)?;
 
     let mut packets_read = 0usize;
@@ -386,15 +1510,337 @@ pub fn read_packets(handle_id: i32, channel: u8, packets: &mut [RdxUsbPacket]) -
             }
         }
     }
+    if let Some(device) = event_loop.devices.get_mut(&handle_id) {
+        device.packets_read += packets_read as u64;
+    }
+    Ok(packets_read)
+}
+
+/// Reads packets from every channel of a device, merged in non-decreasing device timestamp order.
+///
+/// All channels share one USB pipe, so as long as the device sends frames in timestamp order
+/// (as single-controller devices do), packets come out strictly ordered. Devices that timestamp
+/// channels independently may send a frame after later-stamped frames from another channel;
+/// [`set_reorder_depth`] holds back enough packets to put those back in order. Frames delayed by more
+/// than the reorder depth, or dropped because their channel queue was full, can still break ordering.
+///
+/// With [`set_exclusive_readers`] turned on, this has a single reader thread like [`read_packets`], and it can't
+/// be combined with another thread reading individual channels.
+pub fn read_packets_any(handle_id: i32, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.touch();
+    device.claim_reader(None)?;
+    let depth = device.reorder_depth;
+    let Some(open_device) = device.handle.as_mut() else { return Err(EventLoopError::DeviceNotConnected); };
+
+    let mut packets_read = 0usize;
+    for packet in packets {
+        let Some(p) = open_device.try_read_ordered(depth) else { break; };
+        *packet = p;
+        packets_read += 1;
+    }
+    device.packets_read += packets_read as u64;
+    Ok(packets_read)
+}
+
+/// Like [`read_packets`], but fills in the host metadata of [`RdxUsbPacketEx`] as well.
+pub fn read_packets_ex(handle_id: i32, channel: u8, packets: &mut [RdxUsbPacketEx]) -> Result<usize, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.touch();
+    device.claim_reader(Some(channel))?;
+    let meta = PacketMeta::of(handle_id, device);
+    let Some(open_device) = device.handle.as_mut() else { return Err(EventLoopError::DeviceNotConnected); };
+
+    let mut packets_read = 0usize;
+    for packet in packets {
+        match open_device.try_read(channel) {
+            Ok(p) => {
+                *packet = meta.wrap(p, packets_read);
+                packets_read += 1;
+            }
+            Err(DeviceIOError::ChannelOutOfRange) => { return Err(EventLoopError::ChannelOutOfRange); }
+            Err(DeviceIOError::NoData) => { break; }
+        }
+    }
+    device.packets_read += packets_read as u64;
+    Ok(packets_read)
+}
+
+/// Like [`read_packets_any`], but fills in the host metadata of [`RdxUsbPacketEx`] as well.
+pub fn read_packets_any_ex(handle_id: i32, packets: &mut [RdxUsbPacketEx]) -> Result<usize, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.touch();
+    device.claim_reader(None)?;
+    let depth = device.reorder_depth;
+    let meta = PacketMeta::of(handle_id, device);
+    let Some(open_device) = device.handle.as_mut() else { return Err(EventLoopError::DeviceNotConnected); };
+
+    let mut packets_read = 0usize;
+    for packet in packets {
+        let Some(p) = open_device.try_read_ordered(depth) else { break; };
+        *packet = meta.wrap(p, packets_read);
+        packets_read += 1;
+    }
+    device.packets_read += packets_read as u64;
+    Ok(packets_read)
+}
+
+/// Metadata shared by the packets of one extended read.
+struct PacketMeta {
+    host_timestamp_ns: u64,
+    connection_epoch: u32,
+    origin_handle: i32,
+    first_sequence: u64,
+    /// the smoothing filter's clock model as of the read
+    clock: Option<RdxUsbClockModel>,
+}
+
+impl PacketMeta {
+    fn of(handle_id: i32, device: &Device) -> Self {
+        Self {
+            host_timestamp_ns: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64),
+            connection_epoch: device.connection_epoch,
+            origin_handle: handle_id,
+            first_sequence: device.packets_read,
+            clock: device.handle.as_ref().and_then(|h| h.smoothed_clock.model()),
+        }
+    }
+
+    fn wrap(&self, packet: RdxUsbPacket, idx: usize) -> RdxUsbPacketEx {
+        RdxUsbPacketEx {
+            packet,
+            host_timestamp_ns: self.host_timestamp_ns,
+            connection_epoch: self.connection_epoch,
+            origin_handle: self.origin_handle,
+            sequence: self.first_sequence + idx as u64,
+            smoothed_timestamp_ns: self.clock.map_or(0, |c| c.to_host_ns(packet.timestamp_ns)),
+        }
+    }
+}
+
+/// Restricts each channel of a handle to a single reader thread, see [`read_packets`].
+///
+/// Off by default, since callers that read from a thread pool can't keep to one thread. Turning it off drops
+/// every claim.
+pub fn set_exclusive_readers(handle_id: i32, exclusive: bool) -> Result<(), EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.exclusive_readers = exclusive;
+    if !exclusive {
+        device.channel_readers.clear();
+        device.any_reader = None;
+    }
+    Ok(())
+}
+
+/// Gives up the calling thread's claim on reading `channel`, or on [`read_packets_any`] if `None`,
+/// so another thread can take over reading it.
+///
+/// Any thread may release a claim, which lets an application hand reading off from a thread that has exited.
+pub fn release_reader(handle_id: i32, channel: Option<u8>) -> Result<(), EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    match channel {
+        Some(channel) => { device.channel_readers.remove(&channel); }
+        None => device.any_reader = None,
+    }
+    Ok(())
+}
+
+/// Clears a halt (stall) condition on one of a connected device's bulk endpoints, see [`RdxUsbFsHost::clear_halt`].
+pub fn clear_halt(handle_id: i32, endpoint: u8) -> Result<(), EventLoopError> {
+    // clearing is a blocking control transfer, so the event loop lock is released first.
+    let iface = device_interface(handle_id)?;
+    iface.clear_halt(endpoint).map_err(|_| EventLoopError::DeviceNotConnected)
+}
+
+/// Pings a connected device with [`rdxusb_protocol::RdxUsbCtrl::Ping`], returning the round trip time.
+///
+/// Fails with [`EventLoopError::RequestUnsupported`] if the device's firmware predates pings.
+pub fn ping(handle_id: i32, timeout: Duration) -> Result<Duration, EventLoopError> {
+    let iface = device_interface(handle_id)?;
+    let rt = try_acquire_event_loop()?.rt.clone();
+    rt.block_on(crate::host::ping(&iface, timeout)).map_err(|e| match e {
+        RdxUsbHostError::Timeout => EventLoopError::Timeout,
+        RdxUsbHostError::EndpointStall => EventLoopError::RequestUnsupported,
+        _ => EventLoopError::DeviceNotConnected,
+    })
+}
+
+/// The USB interface of a connected device, for requests made outside the event loop lock.
+fn device_interface(handle_id: i32) -> Result<nusb::Interface, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let open_device = event_loop.acquire_open_device(handle_id)?;
+    let iface = match &open_device.channels {
+        DeviceChannels::FsDevice(channels) => channels.first().map(|ch| ch.interface()),
+        #[cfg(feature = "unstable-hs")]
+        DeviceChannels::HsDevice(channels) => channels.first().map(|ch| ch.interface()),
+    };
+    iface.ok_or(EventLoopError::DeviceNotConnected)
+}
+
+/// Usage of one open device handle, for finding handles an application forgot about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandleDiagnostics {
+    pub handle_id: i32,
+    pub vid: u16,
+    pub pid: u16,
+    /// Time since the handle was opened.
+    pub age: Duration,
+    /// Packets read from the handle so far. A handle that stays at zero is likely leaked.
+    pub packets_read: u64,
+    /// Time since packets were last read from or written to the handle.
+    pub idle: Duration,
+}
+
+/// Lists every open device handle, oldest first.
+pub fn handle_diagnostics() -> Result<Vec<HandleDiagnostics>, EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let mut handles: Vec<_> = event_loop.devices.iter().map(|(&handle_id, device)| HandleDiagnostics {
+        handle_id,
+        vid: device.vid,
+        pid: device.pid,
+        age: device.opened_at.elapsed(),
+        packets_read: device.packets_read,
+        idle: device.last_used.elapsed(),
+    }).collect();
+    handles.sort_by_key(|h| Reverse(h.age));
+    Ok(handles)
+}
+
+/// What to do with a handle nothing reads from or writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    /// Release the device and stop reconnecting until the handle is read from or written to again,
+    /// which fails with [`EventLoopError::DeviceNotConnected`] until the device is reopened.
+    Park,
+    /// Close the handle as with [`close_device`].
+    Close,
+}
+
+impl TryFrom<u8> for IdleAction {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Park),
+            2 => Ok(Self::Close),
+            v => Err(v),
+        }
+    }
+}
+
+/// Parks or closes a handle once it went unused for `after`, see [`set_idle_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdlePolicy {
+    pub after: Duration,
+    pub action: IdleAction,
+}
+
+/// How often the idle reaper looks for handles past their [`IdlePolicy`].
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Applies every handle's [`IdlePolicy`] until the event loop is finalized.
+async fn idle_reaper() {
+    let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(mut event_loop) = acquire_initialized_event_loop() else { return; };
+        let idle: Vec<_> = event_loop.devices.iter().filter_map(|(&handle_id, device)| {
+            let policy = device.idle_policy?;
+            (device.last_used.elapsed() >= policy.after).then_some((handle_id, policy.action))
+        }).collect();
+        for (handle_id, action) in idle {
+            if event_loop.reap_handle(handle_id, action) {
+                log::debug!(target: "rdxusb", "Handle {handle_id} went idle: {action:?}");
+            }
+        }
+    }
+}
+
+/// Parks or closes a handle when it goes unused for a while, or never if `policy` is `None` (the default).
+///
+/// Handles opened with `close_on_dc` false otherwise keep reconnecting for as long as the process runs,
+/// even if the application forgot about them. Packet reads and writes count as use.
+pub fn set_idle_policy(handle_id: i32, policy: Option<IdlePolicy>) -> Result<(), EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.idle_policy = policy;
+    Ok(())
+}
+
+/// Parks or closes every handle that went unused for at least `min_idle`, regardless of its [`IdlePolicy`].
+///
+/// Returns the handles that were parked or closed; already parked handles are skipped when parking.
+/// [`handle_diagnostics`] lists how long each handle has been idle.
+pub fn reap_idle_handles(min_idle: Duration, action: IdleAction) -> Result<Vec<i32>, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let idle: Vec<i32> = event_loop.devices.iter()
+        .filter(|(_, device)| device.last_used.elapsed() >= min_idle)
+        .map(|(&handle_id, _)| handle_id)
+        .collect();
+    Ok(idle.into_iter().filter(|&handle_id| event_loop.reap_handle(handle_id, action)).collect())
+}
+
+/// Reads echoes of frames sent on `channel` with [`rdxusb_protocol::MESSAGE_FLAG_ECHO`] set.
+pub fn read_echoes(handle_id: i32, channel: u8, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let open_device = event_loop.acquire_open_device(handle_id)?;
+
+    let mut packets_read = 0usize;
+    for packet in packets {
+        *packet = match open_device.try_read_echo(channel) {
+            Ok(p) => p,
+            Err(DeviceIOError::ChannelOutOfRange) => { return Err(EventLoopError::ChannelOutOfRange); }
+            Err(DeviceIOError::NoData) => { break; }
+        };
+        packets_read += 1;
+    }
+    Ok(packets_read)
+}
+
+pub fn read_notifications(handle_id: i32, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let open_device = event_loop.acquire_open_device(handle_id)?;
+
+    let mut packets_read = 0usize;
+    for packet in packets {
+        let Some(p) = open_device.try_read_notification() else { break; };
+        *packet = p;
+        packets_read += 1;
+    }
+    Ok(packets_read)
+}
+
+pub fn read_error_frames(handle_id: i32, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let open_device = event_loop.acquire_open_device(handle_id)?;
+
+    let mut packets_read = 0usize;
+    for packet in packets {
+        let Some(p) = open_device.try_read_error_frame() else { break; };
+        *packet = p;
+        packets_read += 1;
+    }
     Ok(packets_read)
 }
 
 pub fn write_packets(handle_id: i32, packets: &[RdxUsbPacket]) -> Result<usize, EventLoopError> {
     let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.touch();
     let open_device = event_loop.acquire_open_device(handle_id)?;
     let mut packets_written = 0usize;
 
     for packet in packets {
+        if !open_device.can_send(packet) {
+            // report the bad packet once everything before it has been queued
+            if packets_written == 0 { return Err(EventLoopError::InvalidPacket); }
+            break;
+        }
         match open_device.try_write(packet) {
             Ok(_) => {
                 packets_written += 1;
@@ -407,19 +1853,652 @@ pub fn write_packets(handle_id: i32, packets: &[RdxUsbPacket]) -> Result<usize,
     Ok(packets_written)
 }
 
-pub fn close_device(handle_id: i32) -> Result<(), EventLoopError> {
+/// Sends a device-addressed command until the device echoes it back, see [`RdxUsbFsChannel::write_reliable`].
+///
+/// Blocks the calling thread until the echo arrives or every attempt went unanswered, and returns the echo.
+/// Other echoes read from the packet's channel in the meantime are discarded, so this shouldn't be combined with
+/// [`read_echoes`] on the same channel.
+pub fn write_reliable(handle_id: i32, packet: &RdxUsbPacket, retries: u32, timeout: Duration) -> Result<RdxUsbPacket, EventLoopError> {
+    if packet.arb_id & MESSAGE_ARB_ID_DEVICE == 0 { return Err(EventLoopError::InvalidPacket); }
+    let mut packet = *packet;
+    {
+        let mut event_loop = try_acquire_event_loop()?;
+        let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+        device.reliable_seq = crate::host::next_reliable_seq(device.reliable_seq);
+        packet.flags = crate::host::reliable_flags(packet.flags, device.reliable_seq);
+    }
+    for _ in 0..=retries {
+        let deadline = Instant::now() + timeout;
+        while write_packets(handle_id, std::slice::from_ref(&packet))? == 0 {
+            if Instant::now() >= deadline { return Err(EventLoopError::Timeout); }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        while Instant::now() < deadline {
+            {
+                let mut event_loop = try_acquire_event_loop()?;
+                let open_device = event_loop.acquire_open_device(handle_id)?;
+                loop {
+                    match open_device.try_read_echo(packet.channel) {
+                        Ok(echo) if crate::host::is_reliable_ack(packet.arb_id, packet.flags, echo.arb_id, echo.flags) => return Ok(echo),
+                        Ok(_) => (),
+                        Err(DeviceIOError::ChannelOutOfRange) => return Err(EventLoopError::ChannelOutOfRange),
+                        Err(DeviceIOError::NoData) => break,
+                    }
+                }
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+    Err(EventLoopError::Timeout)
+}
+
+/// A [`crate::capture::SyncPlayer`] sink that sends each packet on a device handle, waiting for room in its
+/// tx queue when it is full.
+pub fn playback_sink(handle_id: i32) -> impl FnMut(&RdxUsbPacket) -> crate::capture::CaptureResult<()> {
+    move |packet| loop {
+        match write_packets(handle_id, std::slice::from_ref(packet)) {
+            Ok(0) => std::thread::sleep(Duration::from_millis(1)),
+            Ok(_) => return Ok(()),
+            Err(e) => return Err(crate::capture::CaptureError::Sink(format!("handle {handle_id}: {e:?}"))),
+        }
+    }
+}
+
+pub fn add_bridge_rule(handle_id: i32, rule: RdxUsbBridgeRule) -> Result<(), EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.bridge_rules.lock().unwrap().push(rule);
+    Ok(())
+}
+
+pub fn clear_bridge_rules(handle_id: i32) -> Result<(), EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.bridge_rules.lock().unwrap().clear();
+    Ok(())
+}
+
+/// Sets how long a connected device may go without completing any bulk IN transfers before it is
+/// considered wedged, reset, and moved to [`DeviceState::Faulted`].
+///
+/// Devices that go quiet are pinged first, and only reset if the ping goes unanswered too.
+/// A timeout of 0 disables the watchdog.
+pub fn set_rx_watchdog(handle_id: i32, timeout_ms: u64) -> Result<(), EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.rx_watchdog_ms.store(timeout_ms, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Sets how long the device's poller waits for a bulk IN transfer to complete before it cancels and resubmits
+/// the transfers in flight, or 0 to wait indefinitely (the default). See [`RdxUsbTimeouts::transfer_watchdog`].
+///
+/// Unlike the rx watchdog this keeps the connection, so it can recover a wedged IN pipe without a reset.
+/// Applies from the next time the device is (re)connected; trips are counted in
+/// [`ConnectionDescription::transfer_watchdog_trips`].
+pub fn set_transfer_watchdog(handle_id: i32, timeout_ms: u64) -> Result<(), EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.timeouts.lock().unwrap().transfer_watchdog = Duration::from_millis(timeout_ms);
+    Ok(())
+}
+
+/// Turns on smoothing of the device's timestamps with the given loop bandwidth in Hz, or off with 0 (the default).
+///
+/// The poller then tracks the device clock with a PLL and [`read_packets_ex`] fills in
+/// [`RdxUsbPacketEx::smoothed_timestamp_ns`], the packet's timestamp on the host wall clock without the jitter of the
+/// device clock and of USB delays. See [`RdxUsbFsHost::set_timestamp_smoothing`]. The setting carries over across
+/// reconnects, but the filter relocks on each one.
+pub fn set_timestamp_smoothing(handle_id: i32, bandwidth_hz: f64) -> Result<(), EventLoopError> {
     let mut event_loop = try_acquire_event_loop()?;
-    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Ok(()); };
-    device.shutdown.notify_one();
-    event_loop.devices.remove(&handle_id);
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.timestamp_smoothing_hz = bandwidth_hz;
+    if let Some(handle) = &device.handle {
+        handle.smoothed_clock.set_bandwidth(bandwidth_hz);
+    }
     Ok(())
 }
 
-pub fn close_all_devices() -> Result<(), EventLoopError> {
+/// Bounds how many received packets per second the device's poller processes, or 0 for no limit.
+///
+/// On a coprocessor such as a roboRIO, this keeps a device flooding the bus from taking a whole core away from
+/// the robot program. The limit carries over across reconnects; hits are counted in [`ConnectionDescription::rx_throttled`].
+pub fn set_max_rx_rate(handle_id: i32, packets_per_sec: u32) -> Result<(), EventLoopError> {
     let mut event_loop = try_acquire_event_loop()?;
-    event_loop.devices.retain(|_handle, device| {
-        device.shutdown.notify_one();
-        false
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.max_rx_rate = packets_per_sec;
+    if let Some(handle) = &device.handle {
+        handle.max_rx_rate.store(packets_per_sec, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Sets the nominal bit rate of the device's buses in bits per second, which [`HandleStats::bus_load`] is estimated
+/// against. Defaults to the 1 Mbit/s of FRC CAN buses; 0 reports no bus load.
+pub fn set_stats_bit_rate(handle_id: i32, bit_rate: u32) -> Result<(), EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.stats_bit_rate = bit_rate;
+    Ok(())
+}
+
+/// Injects `faults` into the transfers the device's poller receives, or stops with `None` (the default), to
+/// regression-test how applications ride out misbehaving devices. See [`RdxUsbFsHost::set_fault_injection`].
+///
+/// Applies from the next time the device is (re)connected.
+pub fn set_fault_injection(handle_id: i32, faults: Option<RdxUsbFaults>) -> Result<(), EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.fault_injection = faults;
+    Ok(())
+}
+
+/// Frame rates and bus traffic of one channel of a connected device over the last `window`,
+/// see [`RdxUsbFsHost::meter`].
+pub fn channel_meter(handle_id: i32, channel: u8, window: Duration) -> Result<RdxUsbChannelMeter, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let open_device = event_loop.acquire_open_device(handle_id)?;
+    open_device.stats.meter(window).get(channel as usize).copied().ok_or(EventLoopError::ChannelOutOfRange)
+}
+
+/// The `max` arbitration ids received most often on one channel of a connected device lately,
+/// see [`RdxUsbFsHost::top_talkers`].
+pub fn top_talkers(handle_id: i32, channel: u8, max: usize) -> Result<Vec<RdxUsbTalker>, EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let open_device = event_loop.acquire_open_device(handle_id)?;
+    if channel as usize >= open_device.channels.len() { return Err(EventLoopError::ChannelOutOfRange); }
+    Ok(open_device.stats.top_talkers(channel, max))
+}
+
+/// Sets the order devices are opened in when several attach at once, highest first (0 by default).
+///
+/// Giving critical devices such as a gyro a higher priority brings them back first after a hub power cycle.
+/// Devices of equal priority are opened in the order they were found. See [`EventLoopConfig::attach_stagger`].
+pub fn set_reconnect_priority(handle_id: i32, priority: u8) -> Result<(), EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.reconnect_priority = priority;
+    Ok(())
+}
+
+/// Replaces the timeouts of a device handle.
+///
+/// The rx watchdog and reconnect backoff apply right away; the open, control and write timeouts apply from the next time
+/// the device is (re)connected.
+pub fn set_timeouts(handle_id: i32, timeouts: RdxUsbTimeouts) -> Result<(), EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    *device.timeouts.lock().unwrap() = timeouts;
+    device.rx_watchdog_ms.store(timeouts.rx_watchdog.as_millis() as u64, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Applies one of the named [`RdxUsbTimeoutProfile`]s to a device handle, see [`set_timeouts`].
+pub fn set_timeout_profile(handle_id: i32, profile: RdxUsbTimeoutProfile) -> Result<(), EventLoopError> {
+    set_timeouts(handle_id, profile.timeouts())
+}
+
+/// Returns the timeouts currently used by a device handle.
+///
+/// The rx watchdog reflects any later [`set_rx_watchdog`] call.
+pub fn get_timeouts(handle_id: i32) -> Result<RdxUsbTimeouts, EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    let mut timeouts = *device.timeouts.lock().unwrap();
+    timeouts.rx_watchdog = Duration::from_millis(device.rx_watchdog_ms.load(Ordering::Relaxed));
+    Ok(timeouts)
+}
+
+/// Sets how many packets [`read_packets_any`] holds back to restore timestamp ordering across channels.
+///
+/// Held back packets are only released once that many newer ones have arrived, so this adds latency
+/// and keeps the last `depth` packets of a burst queued until more traffic comes in. 0 (the default) disables it.
+pub fn set_reorder_depth(handle_id: i32, depth: usize) -> Result<(), EventLoopError> {
+    let mut event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    device.reorder_depth = depth;
+    Ok(())
+}
+
+/// Gets the current lifecycle state of a device handle.
+pub fn device_state(handle_id: i32) -> Result<DeviceState, EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    Ok(device.current_state())
+}
+
+/// Everything known about a device handle, for display in configuration tools. See [`describe_device`].
+#[derive(Debug, Clone)]
+pub struct DeviceDescription {
+    pub handle_id: i32,
+    pub state: DeviceState,
+    /// Vendor id the handle was opened with.
+    pub vid: u16,
+    /// Product id the handle was opened with.
+    pub pid: u16,
+    /// Serial number the handle was opened with, if any.
+    pub serial_number: Option<String>,
+    /// Descriptors of the USB device the handle last matched, if any.
+    pub usb: Option<UsbDescription>,
+    /// Details of the open connection, if the handle is [`DeviceState::Connected`].
+    pub connection: Option<ConnectionDescription>,
+}
+
+/// USB descriptors of an attached device.
+#[derive(Debug, Clone)]
+pub struct UsbDescription {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub device_address: u8,
+    pub speed: Option<nusb::Speed>,
+}
+
+/// What the device reported about itself when it was opened, plus live counters.
+#[derive(Debug, Clone)]
+pub struct ConnectionDescription {
+    pub device_info: RdxUsbDeviceInfo,
+    /// Major protocol version the host speaks with the device.
+    pub protocol: u8,
+    /// Number of channels, including channel 0.
+    pub channels: usize,
+    /// Largest payload the device can carry, in bytes.
+    pub max_payload: usize,
+    /// Completed bulk IN transfers since the device was opened.
+    pub rx_transfers: u64,
+    /// Received packets whose dlc had to be clamped since the device was opened.
+    pub dlc_violations: u64,
+    /// Times the poller paused at the rx rate limit since the device was opened, see [`set_max_rx_rate`].
+    pub rx_throttled: u64,
+    /// Times the poller resubmitted its IN transfers because none completed in time, see [`set_transfer_watchdog`].
+    pub transfer_watchdog_trips: u64,
+    /// Median time the write poller's bulk OUT transfers took to complete, see [`crate::host::RdxUsbLatencyStats`].
+    pub tx_latency_p50: Duration,
+    /// 99th percentile of the same.
+    pub tx_latency_p99: Duration,
+}
+
+impl DeviceDescription {
+    /// Formats the description as a single JSON object, with `null` for anything not known.
+    pub fn to_json(&self) -> String {
+        fn opt_str(s: &Option<String>) -> String {
+            s.as_deref().map_or_else(|| "null".to_string(), |s| format!("\"{}\"", crate::json_escape(s)))
+        }
+        let usb = match &self.usb {
+            Some(usb) => format!(
+                "{{\"vid\":{},\"pid\":{},\"serial_number\":{},\"manufacturer\":{},\"product\":{},\"device_address\":{},\"speed\":{}}}",
+                usb.vid, usb.pid, opt_str(&usb.serial_number), opt_str(&usb.manufacturer), opt_str(&usb.product),
+                usb.device_address, opt_str(&usb.speed.map(|s| format!("{s:?}"))),
+            ),
+            None => "null".to_string(),
+        };
+        let connection = match &self.connection {
+            Some(conn) => {
+                let info = conn.device_info;
+                let (sku, interface_idx, n_channels) = (info.sku, info.interface_idx, info.n_channels);
+                let (major, minor, timestamp_units) = (info.protocol_version_major, info.protocol_version_minor, info.timestamp_units);
+                format!(
+                    "{{\"device_info\":{{\"sku\":{sku},\"interface_idx\":{interface_idx},\"n_channels\":{n_channels},\"protocol_version_major\":{major},\"protocol_version_minor\":{minor},\"timestamp_units\":{timestamp_units}}},\"protocol\":{},\"channels\":{},\"max_payload\":{},\"rx_transfers\":{},\"dlc_violations\":{},\"rx_throttled\":{},\"transfer_watchdog_trips\":{},\"tx_latency_p50_us\":{},\"tx_latency_p99_us\":{}}}",
+                    conn.protocol, conn.channels, conn.max_payload, conn.rx_transfers, conn.dlc_violations, conn.rx_throttled, conn.transfer_watchdog_trips,
+                    conn.tx_latency_p50.as_micros(), conn.tx_latency_p99.as_micros(),
+                )
+            }
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"handle_id\":{},\"state\":\"{:?}\",\"vid\":{},\"pid\":{},\"serial_number\":{},\"usb\":{usb},\"connection\":{connection}}}",
+            self.handle_id, self.state, self.vid, self.pid, opt_str(&self.serial_number),
+        )
+    }
+}
+
+/// Gathers everything known about a device handle into one [`DeviceDescription`].
+pub fn describe_device(handle_id: i32) -> Result<DeviceDescription, EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    let usb = device.device_info_out.borrow().as_ref().map(|info| UsbDescription {
+        vid: info.vendor_id(),
+        pid: info.product_id(),
+        serial_number: info.serial_number().map(str::to_string),
+        manufacturer: info.manufacturer_string().map(str::to_string),
+        product: info.product_string().map(str::to_string),
+        device_address: info.device_address(),
+        speed: info.speed(),
+    });
+    let connection = device.handle.as_ref().map(|handle| {
+        let stats = handle.stats.read();
+        ConnectionDescription {
+            device_info: handle.device_info,
+            protocol: handle.protocol,
+            channels: match &handle.channels {
+                DeviceChannels::FsDevice(vec) => vec.len(),
+                #[cfg(feature = "unstable-hs")]
+                DeviceChannels::HsDevice(vec) => vec.len(),
+            },
+            max_payload: handle.max_payload(),
+            rx_transfers: handle.rx_transfers.load(Ordering::Relaxed),
+            dlc_violations: handle.dlc_violations.load(Ordering::Relaxed),
+            rx_throttled: handle.rx_throttled.load(Ordering::Relaxed),
+            transfer_watchdog_trips: stats.transfer_watchdog_trips,
+            tx_latency_p50: stats.tx_latency.p50,
+            tx_latency_p99: stats.tx_latency.p99,
+        }
     });
+    Ok(DeviceDescription {
+        handle_id,
+        state: device.current_state(),
+        vid: device.vid,
+        pid: device.pid,
+        serial_number: device.serial_number.clone(),
+        usb,
+        connection,
+    })
+}
+
+/// Opens a [`RdxUsbDescriptorReader`] on the USB device a handle last matched, using the handle's control timeout.
+///
+/// The device must still be attached, but the handle doesn't need to be connected.
+pub fn descriptor_reader(handle_id: i32) -> Result<RdxUsbDescriptorReader, EventLoopError> {
+    let (info, timeout) = {
+        let event_loop = try_acquire_event_loop()?;
+        let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+        let Some(info) = device.device_info_out.borrow().clone() else { return Err(EventLoopError::DeviceNotConnected); };
+        let timeout = device.timeouts.lock().unwrap().control;
+        (info, timeout)
+    };
+    // opening talks to the OS, so the event loop lock is released first.
+    RdxUsbDescriptorReader::open(&info, timeout).map_err(|_| EventLoopError::DeviceNotConnected)
+}
+
+/// Starts recording the packets a device handle receives on any channel into a native capture file at `path`.
+///
+/// Recording continues across reconnects until [`stop_capture`] or the handle is closed. Starting a new capture
+/// finishes the previous one.
+///
+/// The file is written on a thread of its own. If the disk can't keep up, packets are dropped from the capture
+/// rather than holding up the device; a write error stops the capture, and [`stop_capture`] then reports it.
+pub fn start_capture(handle_id: i32, path: impl AsRef<Path>) -> Result<(), EventLoopError> {
+    if !try_acquire_event_loop()?.devices.contains_key(&handle_id) { return Err(EventLoopError::DeviceNotOpened); }
+    let file = File::create(path).map_err(|_| EventLoopError::CaptureFailed)?;
+    let writer = CaptureWriter::new(BufWriter::new(file)).map_err(|_| EventLoopError::CaptureFailed)?;
+    let capture = DeviceCapture::start(writer)?;
+    let previous = {
+        let mut event_loop = try_acquire_event_loop()?;
+        let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+        device.capture_queue.send_replace(Some(capture.queue.clone()));
+        device.capture.replace(capture)
+    };
+    // finishing waits on the previous writer thread, so the event loop lock is released first.
+    drop(previous);
+    Ok(())
+}
+
+/// Finishes a device handle's capture, writing out its time index.
+pub fn stop_capture(handle_id: i32) -> Result<(), EventLoopError> {
+    let capture = {
+        let mut event_loop = try_acquire_event_loop()?;
+        let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+        device.capture_queue.send_replace(None);
+        device.capture.take()
+    };
+    let Some(mut capture) = capture else { return Err(EventLoopError::CaptureNotActive); };
+    capture.finish()
+}
+
+/// Adds an annotation, such as "auton started", to a device handle's capture.
+///
+/// It is timestamped with the last captured packet, so it lands right after the traffic received before this call.
+pub fn annotate_capture(handle_id: i32, text: &str) -> Result<(), EventLoopError> {
+    let queue = {
+        let event_loop = try_acquire_event_loop()?;
+        let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+        let Some(capture) = &device.capture else { return Err(EventLoopError::CaptureNotActive); };
+        capture.queue.clone()
+    };
+    // waits for room behind queued packets, without holding the event loop lock
+    queue.send(CaptureCommand::Annotate(text.to_string())).map_err(|_| EventLoopError::CaptureFailed)
+}
+
+/// Traffic of a device handle over one sampling interval, see [`watch_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HandleStats {
+    pub connected: bool,
+    /// Frames received from the bus per second, including ones dropped because their queue was full.
+    pub rx_frames_per_sec: f64,
+    pub tx_frames_per_sec: f64,
+    /// Frames dropped because their channel's rx queue was full during the interval.
+    pub rx_drops: u64,
+    /// Received packets waiting to be read, across all channels.
+    pub rx_queue_depth: usize,
+    /// Estimated fraction of its bus the traffic of the busiest channel occupies, at the bit rate set with
+    /// [`set_stats_bit_rate`]. See [`RdxUsbChannelMeter::bus_load`].
+    pub bus_load: f64,
+}
+
+/// The 1 Mbit/s bit rate of FRC CAN buses.
+const DEFAULT_STATS_BIT_RATE: u32 = 1_000_000;
+
+/// Counter totals of a handle at one point in time.
+struct StatsSample {
+    at: Instant,
+    rx: u64,
+    tx: u64,
+    drops: u64,
+    /// nominal bits seen on each channel's bus
+    bus_bits: Vec<u64>,
+}
+
+/// Samples a handle's counters every `interval`, passing the rates to `emit` until it returns false or the handle is
+/// closed.
+async fn sample_stats(handle_id: i32, interval: Duration, mut emit: impl FnMut(HandleStats) -> bool) {
+    let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(1)));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last: Option<StatsSample> = None;
+    loop {
+        ticker.tick().await;
+        let (sample, rx_queue_depth, bit_rate) = {
+            let Some(event_loop) = acquire_initialized_event_loop() else { return; };
+            let Some(device) = event_loop.devices.get(&handle_id) else { return; };
+            let bit_rate = device.stats_bit_rate;
+            device.handle.as_ref().map(|handle| {
+                let stats = handle.stats.read();
+                let sample = StatsSample {
+                    at: Instant::now(),
+                    rx: stats.channels.iter().map(|c| c.rx_packets + c.rx_drops).sum(),
+                    tx: stats.channels.iter().map(|c| c.tx_packets).sum(),
+                    drops: stats.channels.iter().map(|c| c.rx_drops).sum(),
+                    bus_bits: stats.channels.iter().map(|c| c.bus_bits).collect(),
+                };
+                (Some(sample), handle.channels.queued(), bit_rate)
+            }).unwrap_or((None, 0, bit_rate))
+        };
+        let stats = match (&sample, &last) {
+            (Some(now), Some(prev)) => {
+                let secs = now.at.duration_since(prev.at).as_secs_f64().max(f64::EPSILON);
+                // counters start over when the device reconnects
+                let rx = now.rx.checked_sub(prev.rx).unwrap_or(now.rx) as f64 / secs;
+                let tx = now.tx.checked_sub(prev.tx).unwrap_or(now.tx) as f64 / secs;
+                let busiest = now.bus_bits.iter().enumerate().map(|(i, &bits)| {
+                    prev.bus_bits.get(i).and_then(|&prev| bits.checked_sub(prev)).unwrap_or(bits)
+                }).max().unwrap_or(0);
+                HandleStats {
+                    connected: true,
+                    rx_frames_per_sec: rx,
+                    tx_frames_per_sec: tx,
+                    rx_drops: now.drops.checked_sub(prev.drops).unwrap_or(now.drops),
+                    rx_queue_depth,
+                    bus_load: if bit_rate == 0 { 0.0 } else { busiest as f64 / secs / bit_rate as f64 },
+                }
+            }
+            (Some(_), None) => HandleStats { connected: true, rx_queue_depth, ..Default::default() },
+            (None, _) => HandleStats::default(),
+        };
+        last = sample;
+        if !emit(stats) { return; }
+    }
+}
+
+/// Watches a device handle's traffic, sampled every `interval`, so dashboards can show live meters without polling.
+///
+/// Sampling stops once every receiver is dropped or the handle is closed.
+pub fn watch_stats(handle_id: i32, interval: Duration) -> Result<tokio::sync::watch::Receiver<HandleStats>, EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    if !event_loop.devices.contains_key(&handle_id) { return Err(EventLoopError::DeviceNotOpened); }
+    let (tx, rx) = tokio::sync::watch::channel(HandleStats::default());
+    crate::spawn_named(event_loop.rt.handle(), &format!("stats:{handle_id}"), sample_stats(handle_id, interval, move |stats| tx.send(stats).is_ok()));
+    Ok(rx)
+}
+
+pub type StatsCallback = Box<dyn FnMut(&HandleStats) + Send>;
+
+thread_local! {
+    /// The stats callback this thread is inside of, so it can remove itself without waiting on itself.
+    static RUNNING_STATS_CALLBACK: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A handle's stats callback and the task calling it, see [`set_stats_callback`].
+pub struct StatsCallbackTask {
+    task: tokio::task::JoinHandle<()>,
+    /// Locked for the duration of every call, so [`Self::stop`] can wait out a call in progress.
+    callback: Arc<Mutex<Option<StatsCallback>>>,
+}
+
+impl StatsCallbackTask {
+    fn spawn(rt: &tokio::runtime::Handle, handle_id: i32, interval: Duration, callback: StatsCallback) -> Self {
+        let callback = Arc::new(Mutex::new(Some(callback)));
+        let slot = callback.clone();
+        let task = crate::spawn_named(rt, &format!("stats-callback:{handle_id}"), sample_stats(handle_id, interval, move |stats| {
+            let mut guard = slot.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            let Some(callback) = guard.as_mut() else { return false; };
+            RUNNING_STATS_CALLBACK.set(Arc::as_ptr(&slot) as usize);
+            callback(&stats);
+            RUNNING_STATS_CALLBACK.set(0);
+            true
+        }));
+        Self { task, callback }
+    }
+
+    /// Stops calling the callback. Once this returns, a call in progress on another thread has finished and no
+    /// further calls are made.
+    ///
+    /// Must not be called with the event loop lock held, since the callback may be waiting for it.
+    fn stop(self) {
+        self.task.abort();
+        // from within the callback itself, the call in progress can't be waited out, but the aborted task won't
+        // make another.
+        if RUNNING_STATS_CALLBACK.get() == Arc::as_ptr(&self.callback) as usize { return; }
+        self.callback.lock().unwrap_or_else(std::sync::PoisonError::into_inner).take();
+    }
+}
+
+impl Drop for StatsCallbackTask {
+    fn drop(&mut self) {
+        // handles closed by the event loop itself, e.g. on disconnect, stop without waiting as the lock is held.
+        self.task.abort();
+    }
+}
+
+/// Calls `callback` from the event loop with a device handle's traffic every `interval`, replacing any callback set
+/// before, or removes the callback if `None`.
+///
+/// Once this returns, the previous callback is not running and won't be called again, unless this is called from
+/// within that callback. [`close_device`] and [`close_all_devices`] give the same guarantee.
+pub fn set_stats_callback(handle_id: i32, interval: Duration, callback: Option<StatsCallback>) -> Result<(), EventLoopError> {
+    let previous = {
+        let mut event_loop = try_acquire_event_loop()?;
+        let rt = event_loop.rt.handle().clone();
+        let Some(device) = event_loop.devices.get_mut(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+        let next = callback.map(|callback| StatsCallbackTask::spawn(&rt, handle_id, interval, callback));
+        std::mem::replace(&mut device.stats_callback, next)
+    };
+    // the callback may be waiting for the event loop lock, so it's only waited out once that is released
+    if let Some(previous) = previous { previous.stop(); }
+    Ok(())
+}
+
+/// Subscribes to lifecycle state transitions of a device handle.
+///
+/// The receiver reports [`DeviceState::Closing`] as its final value before the sender is dropped.
+pub fn subscribe_device_state(handle_id: i32) -> Result<tokio::sync::watch::Receiver<DeviceState>, EventLoopError> {
+    let event_loop = try_acquire_event_loop()?;
+    let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+    Ok(device.state.subscribe())
+}
+
+/// Resolves once the device handle is [`DeviceState::Connected`], or fails with [`EventLoopError::Timeout`].
+///
+/// Fails with [`EventLoopError::DeviceNotOpened`] if the handle is closed while waiting.
+/// The timer runs on the event loop, so this can be awaited from any executor.
+pub async fn wait_connected(handle_id: i32, timeout: Duration) -> Result<(), EventLoopError> {
+    let (mut state, rt) = {
+        let event_loop = try_acquire_event_loop()?;
+        let Some(device) = event_loop.devices.get(&handle_id) else { return Err(EventLoopError::DeviceNotOpened); };
+        (device.state.subscribe(), event_loop.rt.handle().clone())
+    };
+    let waiter = crate::spawn_named(&rt, &format!("wait_connected:{handle_id}"), async move {
+        let waited = tokio::time::timeout(timeout, state.wait_for(|s| matches!(s, DeviceState::Connected | DeviceState::Closing))).await;
+        match waited {
+            Ok(Ok(s)) if *s == DeviceState::Connected => Ok(()),
+            Ok(_) => Err(EventLoopError::DeviceNotOpened),
+            Err(_) => Err(EventLoopError::Timeout),
+        }
+    });
+    waiter.await.map_err(|_| EventLoopError::EventLoopCrashed)?
+}
+
+/// Blocks the calling thread until [`wait_connected`] resolves. Must not be called from within an async context.
+pub fn wait_connected_blocking(handle_id: i32, timeout: Duration) -> Result<(), EventLoopError> {
+    let rt = try_acquire_event_loop()?.rt.clone();
+    // in manual pump mode, this also drives the event loop while waiting.
+    rt.block_on(wait_connected(handle_id, timeout))
+}
+
+/// Resolves once every one of `handle_ids` is [`DeviceState::Connected`] at the same time,
+/// or fails with [`EventLoopError::Timeout`].
+pub async fn wait_all_connected(handle_ids: &[i32], timeout: Duration) -> Result<(), EventLoopError> {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        for &handle_id in handle_ids {
+            wait_connected(handle_id, deadline.saturating_duration_since(std::time::Instant::now())).await?;
+        }
+        // a device may have dropped out while we were waiting on the others
+        let mut all_connected = true;
+        for &handle_id in handle_ids {
+            all_connected &= device_state(handle_id)? == DeviceState::Connected;
+        }
+        if all_connected { return Ok(()); }
+    }
+}
+
+/// Blocking version of [`wait_all_connected`].
+pub fn wait_all_connected_blocking(handle_ids: &[i32], timeout: Duration) -> Result<(), EventLoopError> {
+    let rt = try_acquire_event_loop()?.rt.clone();
+    rt.block_on(wait_all_connected(handle_ids, timeout))
+}
+
+pub fn close_device(handle_id: i32) -> Result<(), EventLoopError> {
+    let stats_callback = {
+        let mut event_loop = try_acquire_event_loop()?;
+        let Some(mut device) = event_loop.devices.remove(&handle_id) else { return Ok(()); };
+        device.transition(DeviceState::Closing);
+        device.shutdown.notify_one();
+        device.stats_callback.take()
+    };
+    if let Some(stats_callback) = stats_callback { stats_callback.stop(); }
+    Ok(())
+}
+
+pub fn close_all_devices() -> Result<(), EventLoopError> {
+    let stats_callbacks: Vec<_> = {
+        let mut event_loop = try_acquire_event_loop()?;
+        event_loop.devices.drain().filter_map(|(_handle, mut device)| {
+            device.transition(DeviceState::Closing);
+            device.shutdown.notify_one();
+            device.stats_callback.take()
+        }).collect()
+    };
+    stats_callbacks.into_iter().for_each(StatsCallbackTask::stop);
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,235 @@
+use nusb::transfer::{Completion, TransferError};
+
+use crate::host::{RdxUsbHostError, RdxUsbHostResult, RdxUsbInTransport};
+
+/// How often an [`RdxUsbFaultInjector`] injects each kind of fault, as the chance of hitting any one transfer.
+///
+/// Faults are drawn in the order of the fields below, and a transfer gets at most one of the failing kinds.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RdxUsbFaults {
+    /// Seeds the generator faults are drawn from, so runs over the same traffic inject the same faults.
+    pub seed: u64,
+    /// The device goes away: the transfer and every one after it fail with [`TransferError::Disconnected`].
+    pub disconnect: f64,
+    /// The endpoint stalls: the transfer and every one after it fail with [`TransferError::Stall`] until the
+    /// halt is cleared.
+    pub stall: f64,
+    /// The transfer fails with [`TransferError::Fault`], like a transient bus error.
+    pub error: f64,
+    /// The transfer is cut short at a random length.
+    pub partial: f64,
+    /// The transfer's bytes are replaced with random ones.
+    pub garbage: f64,
+}
+
+/// Faults an [`RdxUsbFaultInjector`] has injected so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RdxUsbInjectedFaults {
+    pub disconnects: u64,
+    pub stalls: u64,
+    pub errors: u64,
+    pub partial: u64,
+    pub garbage: u64,
+}
+
+/// Wraps an [`RdxUsbInTransport`], injecting [`RdxUsbFaults`] into the transfers completing through it, so a host's
+/// recovery from misbehaving devices can be regression-tested deterministically.
+///
+/// Hosts wrap their own transport in one with [`RdxUsbGenericHost::set_fault_injection`](crate::host::RdxUsbGenericHost::set_fault_injection).
+/// Transfers that already failed or were cancelled pass through untouched.
+pub struct RdxUsbFaultInjector<T> {
+    inner: T,
+    faults: RdxUsbFaults,
+    rng: u64,
+    /// set by an injected stall, until the halt is cleared
+    halted: bool,
+    /// set by an injected disconnect, for good
+    disconnected: bool,
+    injected: RdxUsbInjectedFaults,
+}
+
+impl<T: RdxUsbInTransport> RdxUsbFaultInjector<T> {
+    pub fn new(inner: T, faults: RdxUsbFaults) -> Self {
+        // xorshift gets stuck at zero
+        Self { inner, faults, rng: faults.seed.max(1), halted: false, disconnected: false, injected: RdxUsbInjectedFaults::default() }
+    }
+
+    /// Faults injected so far.
+    pub fn injected(&self) -> RdxUsbInjectedFaults {
+        self.injected
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn inject(&mut self, mut completion: Completion<Vec<u8>>) -> Completion<Vec<u8>> {
+        if completion.status.is_err() { return completion; }
+        let failure = if self.disconnected {
+            Some(TransferError::Disconnected)
+        } else if self.halted {
+            Some(TransferError::Stall)
+        } else if self.chance(self.faults.disconnect) {
+            self.disconnected = true;
+            self.injected.disconnects += 1;
+            Some(TransferError::Disconnected)
+        } else if self.chance(self.faults.stall) {
+            self.halted = true;
+            self.injected.stalls += 1;
+            Some(TransferError::Stall)
+        } else if self.chance(self.faults.error) {
+            self.injected.errors += 1;
+            Some(TransferError::Fault)
+        } else {
+            None
+        };
+        if let Some(e) = failure {
+            completion.data.clear();
+            completion.status = Err(e);
+            return completion;
+        }
+        if !completion.data.is_empty() && self.chance(self.faults.partial) {
+            self.injected.partial += 1;
+            let len = (self.next_random() % completion.data.len() as u64) as usize;
+            completion.data.truncate(len);
+        }
+        if self.chance(self.faults.garbage) {
+            self.injected.garbage += 1;
+            for chunk in completion.data.chunks_mut(8) {
+                let random = self.next_random().to_le_bytes();
+                chunk.copy_from_slice(&random[..chunk.len()]);
+            }
+        }
+        completion
+    }
+
+    /// Draws whether a fault with probability `p` happens.
+    fn chance(&mut self, p: f64) -> bool {
+        // the top 53 bits make a uniform f64 in [0, 1)
+        p > 0.0 && ((self.next_random() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    /// xorshift64, which is plenty for fault injection.
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+impl<T: RdxUsbInTransport> RdxUsbInTransport for RdxUsbFaultInjector<T> {
+    fn submit(&mut self, buf: Vec<u8>, len: usize) {
+        self.inner.submit(buf, len);
+    }
+
+    fn pending(&self) -> usize {
+        self.inner.pending()
+    }
+
+    // nothing happens after the inner future completes, so this is as cancel safe as it is
+    async fn next_complete(&mut self) -> Completion<Vec<u8>> {
+        let completion = self.inner.next_complete().await;
+        self.inject(completion)
+    }
+
+    fn cancel_all(&mut self) {
+        self.inner.cancel_all();
+    }
+
+    fn clear_halt(&mut self) -> RdxUsbHostResult<()> {
+        if self.disconnected { return Err(RdxUsbHostError::DeviceDisconnected); }
+        self.inner.clear_halt()?;
+        self.halted = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Completes every transfer at once with `len` bytes counting up from zero.
+    #[derive(Default)]
+    struct Loopback {
+        pending: usize,
+        halts_cleared: u32,
+    }
+
+    impl RdxUsbInTransport for Loopback {
+        fn submit(&mut self, _buf: Vec<u8>, _len: usize) {
+            self.pending += 1;
+        }
+
+        fn pending(&self) -> usize {
+            self.pending
+        }
+
+        async fn next_complete(&mut self) -> Completion<Vec<u8>> {
+            self.pending -= 1;
+            Completion { data: (0..32).collect(), status: Ok(()) }
+        }
+
+        fn cancel_all(&mut self) {}
+
+        fn clear_halt(&mut self) -> RdxUsbHostResult<()> {
+            self.halts_cleared += 1;
+            Ok(())
+        }
+    }
+
+    fn complete(injector: &mut RdxUsbFaultInjector<Loopback>) -> Completion<Vec<u8>> {
+        injector.submit(Vec::new(), 32);
+        futures_util::FutureExt::now_or_never(injector.next_complete()).unwrap()
+    }
+
+    fn run(faults: RdxUsbFaults, transfers: usize) -> (Vec<Completion<Vec<u8>>>, RdxUsbInjectedFaults) {
+        let mut injector = RdxUsbFaultInjector::new(Loopback::default(), faults);
+        let completions = (0..transfers).map(|_| complete(&mut injector)).collect();
+        (completions, injector.injected())
+    }
+
+    #[test]
+    fn no_faults_passes_through() {
+        let (completions, injected) = run(RdxUsbFaults::default(), 100);
+        assert!(completions.iter().all(|c| c.status.is_ok() && c.data == (0..32).collect::<Vec<u8>>()));
+        assert_eq!(injected, RdxUsbInjectedFaults::default());
+    }
+
+    #[test]
+    fn faults_repeat_with_the_seed() {
+        let faults = RdxUsbFaults { seed: 42, error: 0.1, partial: 0.1, garbage: 0.1, ..Default::default() };
+        let (first, injected) = run(faults, 1000);
+        let (second, _) = run(faults, 1000);
+        assert!(first.iter().zip(&second).all(|(a, b)| a.status == b.status && a.data == b.data));
+        // roughly 100 of each out of 1000
+        for n in [injected.errors, injected.partial, injected.garbage] {
+            assert!((50..150).contains(&n), "{injected:?}");
+        }
+        assert!(first.iter().filter(|c| c.status.is_ok()).all(|c| c.data.len() <= 32));
+
+        let (other, _) = run(RdxUsbFaults { seed: 43, ..faults }, 1000);
+        assert!(first.iter().zip(&other).any(|(a, b)| a.status != b.status || a.data != b.data));
+    }
+
+    #[test]
+    fn stalls_until_cleared() {
+        let mut injector = RdxUsbFaultInjector::new(Loopback::default(), RdxUsbFaults { stall: 1.0, ..Default::default() });
+        assert_eq!(complete(&mut injector).status, Err(TransferError::Stall));
+        injector.faults.stall = 0.0;
+        assert_eq!(complete(&mut injector).status, Err(TransferError::Stall));
+        injector.clear_halt().unwrap();
+        assert_eq!(complete(&mut injector).status, Ok(()));
+        assert_eq!((injector.injected().stalls, injector.into_inner().halts_cleared), (1, 1));
+    }
+
+    #[test]
+    fn disconnects_for_good() {
+        let mut injector = RdxUsbFaultInjector::new(Loopback::default(), RdxUsbFaults { disconnect: 1.0, ..Default::default() });
+        assert_eq!(complete(&mut injector).status, Err(TransferError::Disconnected));
+        injector.faults.disconnect = 0.0;
+        assert!(matches!(injector.clear_halt(), Err(RdxUsbHostError::DeviceDisconnected)));
+        assert_eq!(complete(&mut injector).status, Err(TransferError::Disconnected));
+        assert_eq!(injector.injected().disconnects, 1);
+    }
+}
@@ -0,0 +1,450 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use rdxusb_protocol::{RdxUsbFsPacket, RdxUsbPacket};
+
+/// Longest expression [`Filter::parse`] accepts, in bytes.
+pub const MAX_FILTER_LEN: usize = 1024;
+
+/// Deepest nesting of operators and parentheses [`Filter::parse`] accepts.
+pub const MAX_FILTER_DEPTH: usize = 64;
+
+/// A packet filter parsed from a small expression language.
+///
+/// Expressions are made of packet fields, integer literals, and operators with Rust-like precedence:
+///
+/// | syntax | meaning |
+/// |---|---|
+/// | `id` | arbitration id without flag bits |
+/// | `arb_id` | raw arbitration id, including flag bits |
+/// | `ext`, `rtr`, `device` | arbitration id flags (1 or 0) |
+/// | `channel`, `dlc`, `flags` | the corresponding packet fields |
+/// | `0x7f0`, `0b101`, `42` | integer literals |
+/// | `!`, `~` | logical not, bitwise not |
+/// | `&`, `^`, `\|` | bitwise and, xor, or |
+/// | `==`, `!=`, `<`, `<=`, `>`, `>=` | comparisons |
+/// | `&&`, `\|\|` | logical and, or |
+///
+/// A packet matches if the expression evaluates to a nonzero value, e.g.
+/// `id==0x123 || (id&0x7f0)==0x240 && !rtr`.
+///
+/// Expressions are at most [`MAX_FILTER_LEN`] bytes long and nest at most [`MAX_FILTER_DEPTH`] levels deep, so
+/// filters from untrusted clients can't exhaust the stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parses a filter expression.
+    pub fn parse(s: &str) -> Result<Self, FilterParseError> {
+        if s.len() > MAX_FILTER_LEN { return Err(FilterParseError::TooLong(s.len())); }
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0, depth: 0 };
+        let (expr, _) = parser.expr(0)?;
+        match parser.peek() {
+            Some((pos, _)) => Err(FilterParseError::UnexpectedToken(pos)),
+            None => Ok(Self { expr }),
+        }
+    }
+
+    /// Returns true if the packet matches the filter.
+    pub fn matches(&self, packet: &RdxUsbPacket) -> bool {
+        self.matches_fields(&Fields { arb_id: packet.arb_id, dlc: packet.dlc, channel: packet.channel, flags: packet.flags })
+    }
+
+    /// Returns true if the full-speed packet matches the filter.
+    pub fn matches_fs(&self, packet: &RdxUsbFsPacket) -> bool {
+        self.matches_fields(&Fields { arb_id: packet.arb_id, dlc: packet.dlc, channel: packet.channel, flags: packet.flags })
+    }
+
+    fn matches_fields(&self, fields: &Fields) -> bool {
+        self.expr.eval(fields) != 0
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Errors from [`Filter::parse`]. Positions are byte offsets into the expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterParseError {
+    /// A character that isn't part of the expression language.
+    UnexpectedChar(usize, char),
+    /// A token that doesn't fit the grammar at this position.
+    UnexpectedToken(usize),
+    /// The expression ended early.
+    UnexpectedEnd,
+    /// A name that isn't a known packet field.
+    UnknownField(usize, String),
+    /// A numeric literal that couldn't be parsed.
+    InvalidNumber(usize),
+    /// The expression is longer than [`MAX_FILTER_LEN`]; holds its length.
+    TooLong(usize),
+    /// The expression nests deeper than [`MAX_FILTER_DEPTH`].
+    TooDeep(usize),
+}
+
+impl Display for FilterParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterParseError::UnexpectedChar(pos, c) => write!(f, "unexpected character {c:?} at {pos}"),
+            FilterParseError::UnexpectedToken(pos) => write!(f, "unexpected token at {pos}"),
+            FilterParseError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            FilterParseError::UnknownField(pos, name) => write!(f, "unknown field {name:?} at {pos}"),
+            FilterParseError::InvalidNumber(pos) => write!(f, "invalid number at {pos}"),
+            FilterParseError::TooLong(len) => write!(f, "expression is {len} bytes long, the limit is {MAX_FILTER_LEN}"),
+            FilterParseError::TooDeep(pos) => write!(f, "expression nests deeper than {MAX_FILTER_DEPTH} levels at {pos}"),
+        }
+    }
+}
+impl core::error::Error for FilterParseError {}
+
+struct Fields {
+    arb_id: u32,
+    dlc: u8,
+    channel: u8,
+    flags: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Id,
+    ArbId,
+    Ext,
+    Rtr,
+    Device,
+    Channel,
+    Dlc,
+    Flags,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "id" => Self::Id,
+            "arb_id" => Self::ArbId,
+            "ext" => Self::Ext,
+            "rtr" => Self::Rtr,
+            "device" => Self::Device,
+            "channel" => Self::Channel,
+            "dlc" => Self::Dlc,
+            "flags" => Self::Flags,
+            _ => return None,
+        })
+    }
+
+    fn get(self, f: &Fields) -> u64 {
+        match self {
+            Field::Id => (f.arb_id & 0x1fff_ffff) as u64,
+            Field::ArbId => f.arb_id as u64,
+            Field::Ext => (f.arb_id & crate::MESSAGE_ARB_ID_EXT != 0) as u64,
+            Field::Rtr => (f.arb_id & crate::MESSAGE_ARB_ID_RTR != 0) as u64,
+            Field::Device => (f.arb_id & crate::MESSAGE_ARB_ID_DEVICE != 0) as u64,
+            Field::Channel => f.channel as u64,
+            Field::Dlc => f.dlc as u64,
+            Field::Flags => f.flags as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+}
+
+impl BinOp {
+    /// Binding power; higher binds tighter.
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 3,
+            BinOp::BitOr => 4,
+            BinOp::BitXor => 5,
+            BinOp::BitAnd => 6,
+        }
+    }
+
+    fn apply(self, l: u64, r: u64) -> u64 {
+        match self {
+            BinOp::Or => (l != 0 || r != 0) as u64,
+            BinOp::And => (l != 0 && r != 0) as u64,
+            BinOp::Eq => (l == r) as u64,
+            BinOp::Ne => (l != r) as u64,
+            BinOp::Lt => (l < r) as u64,
+            BinOp::Le => (l <= r) as u64,
+            BinOp::Gt => (l > r) as u64,
+            BinOp::Ge => (l >= r) as u64,
+            BinOp::BitOr => l | r,
+            BinOp::BitXor => l ^ r,
+            BinOp::BitAnd => l & r,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Literal(u64),
+    Field(Field),
+    Not(Box<Expr>),
+    BitNot(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Recursion is bounded by the tree's height, which the parser keeps within [`MAX_FILTER_DEPTH`].
+    fn eval(&self, f: &Fields) -> u64 {
+        match self {
+            Expr::Literal(v) => *v,
+            Expr::Field(field) => field.get(f),
+            Expr::Not(e) => (e.eval(f) == 0) as u64,
+            Expr::BitNot(e) => !e.eval(f),
+            Expr::Binary(BinOp::Or, l, r) => (l.eval(f) != 0 || r.eval(f) != 0) as u64,
+            Expr::Binary(BinOp::And, l, r) => (l.eval(f) != 0 && r.eval(f) != 0) as u64,
+            Expr::Binary(op, l, r) => op.apply(l.eval(f), r.eval(f)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u64),
+    Ident(String),
+    Op(BinOp),
+    Not,
+    BitNot,
+    LParen,
+    RParen,
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, FilterParseError> {
+    let bytes = s.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let two = bytes.get(i..i + 2).unwrap_or(&[]);
+        let (tok, len) = match (c, two) {
+            (b' ' | b'\t' | b'\r' | b'\n', _) => { i += 1; continue; }
+            (_, b"||") => (Token::Op(BinOp::Or), 2),
+            (_, b"&&") => (Token::Op(BinOp::And), 2),
+            (_, b"==") => (Token::Op(BinOp::Eq), 2),
+            (_, b"!=") => (Token::Op(BinOp::Ne), 2),
+            (_, b"<=") => (Token::Op(BinOp::Le), 2),
+            (_, b">=") => (Token::Op(BinOp::Ge), 2),
+            (b'<', _) => (Token::Op(BinOp::Lt), 1),
+            (b'>', _) => (Token::Op(BinOp::Gt), 1),
+            (b'|', _) => (Token::Op(BinOp::BitOr), 1),
+            (b'^', _) => (Token::Op(BinOp::BitXor), 1),
+            (b'&', _) => (Token::Op(BinOp::BitAnd), 1),
+            (b'!', _) => (Token::Not, 1),
+            (b'~', _) => (Token::BitNot, 1),
+            (b'(', _) => (Token::LParen, 1),
+            (b')', _) => (Token::RParen, 1),
+            (b'0'..=b'9', _) => {
+                let len = bytes[i..].iter().take_while(|b| b.is_ascii_alphanumeric() || **b == b'_').count();
+                let lit = s[i..i + len].replace('_', "");
+                let parsed = if let Some(hex) = lit.strip_prefix("0x").or_else(|| lit.strip_prefix("0X")) {
+                    u64::from_str_radix(hex, 16)
+                } else if let Some(bin) = lit.strip_prefix("0b").or_else(|| lit.strip_prefix("0B")) {
+                    u64::from_str_radix(bin, 2)
+                } else {
+                    lit.parse()
+                };
+                (Token::Number(parsed.map_err(|_| FilterParseError::InvalidNumber(start))?), len)
+            }
+            (b'a'..=b'z' | b'A'..=b'Z' | b'_', _) => {
+                let len = bytes[i..].iter().take_while(|b| b.is_ascii_alphanumeric() || **b == b'_').count();
+                (Token::Ident(s[i..i + len].to_string()), len)
+            }
+            _ => {
+                let ch = s[i..].chars().next().unwrap_or_default();
+                return Err(FilterParseError::UnexpectedChar(start, ch));
+            }
+        };
+        tokens.push((start, tok));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    pos: usize,
+    /// Calls to [`Self::unary`] currently on the stack. Each one holds at most one [`Self::expr`] per precedence
+    /// level above it, so this bounds the recursion of both.
+    depth: usize,
+}
+
+/// A parsed subexpression along with the height of its tree.
+type Node = (Expr, usize);
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens.get(self.pos).map(|(p, t)| (*p, t))
+    }
+
+    fn next(&mut self) -> Result<(usize, &Token), FilterParseError> {
+        let (p, t) = self.tokens.get(self.pos).ok_or(FilterParseError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok((*p, t))
+    }
+
+    /// Byte offset of the next token, or of the last one at the end of the expression.
+    fn here(&self) -> usize {
+        self.tokens.get(self.pos).or(self.tokens.last()).map_or(0, |(p, _)| *p)
+    }
+
+    /// Runs `f` one level deeper, failing once the parser recurses past [`MAX_FILTER_DEPTH`].
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, FilterParseError>) -> Result<T, FilterParseError> {
+        if self.depth >= MAX_FILTER_DEPTH { return Err(FilterParseError::TooDeep(self.here())); }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    /// Precedence-climbing parse of binary operators binding tighter than `min_prec`.
+    fn expr(&mut self, min_prec: u8) -> Result<Node, FilterParseError> {
+        let (mut lhs, mut height) = self.unary()?;
+        while let Some((pos, Token::Op(op))) = self.peek() {
+            let op = *op;
+            if op.precedence() <= min_prec { break; }
+            self.pos += 1;
+            let (rhs, rhs_height) = self.expr(op.precedence())?;
+            height = parent_height(pos, height.max(rhs_height))?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok((lhs, height))
+    }
+
+    fn unary(&mut self) -> Result<Node, FilterParseError> {
+        self.nested(|p| {
+            let (pos, tok) = p.next()?;
+            match tok {
+                Token::Number(n) => Ok((Expr::Literal(*n), 1)),
+                Token::Ident(name) => match Field::from_name(name) {
+                    Some(field) => Ok((Expr::Field(field), 1)),
+                    None => Err(FilterParseError::UnknownField(pos, name.clone())),
+                },
+                Token::Not => {
+                    let (inner, height) = p.unary()?;
+                    Ok((Expr::Not(Box::new(inner)), parent_height(pos, height)?))
+                }
+                Token::BitNot => {
+                    let (inner, height) = p.unary()?;
+                    Ok((Expr::BitNot(Box::new(inner)), parent_height(pos, height)?))
+                }
+                Token::LParen => {
+                    let inner = p.expr(0)?;
+                    match p.next()? {
+                        (_, Token::RParen) => Ok(inner),
+                        (pos, _) => Err(FilterParseError::UnexpectedToken(pos)),
+                    }
+                }
+                Token::RParen | Token::Op(_) => Err(FilterParseError::UnexpectedToken(pos)),
+            }
+        })
+    }
+}
+
+/// Height of a new node over a subtree `height` tall, failing if the tree grows past [`MAX_FILTER_DEPTH`].
+fn parent_height(pos: usize, height: usize) -> Result<usize, FilterParseError> {
+    if height >= MAX_FILTER_DEPTH { return Err(FilterParseError::TooDeep(pos)); }
+    Ok(height + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(arb_id: u32, dlc: u8) -> RdxUsbPacket {
+        RdxUsbPacket { arb_id, dlc, ..bytemuck::Zeroable::zeroed() }
+    }
+
+    fn eval(s: &str) -> u64 {
+        Filter::parse(s).unwrap().expr.eval(&Fields { arb_id: 0, dlc: 0, channel: 0, flags: 0 })
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 | 2 & 3"), 3);
+        assert_eq!(eval("(1 | 2) & 3"), 3);
+        assert_eq!(eval("6 ^ 3 & 1"), 7);
+        assert_eq!(eval("1 | 6 == 6"), 0);
+        assert_eq!(eval("0 || 1 && 0"), 0);
+        assert_eq!(eval("1 || 1 && 0"), 1);
+        assert_eq!(eval("!0 == 1"), 1);
+        assert_eq!(eval("~0 & 0xff"), 0xff);
+        assert_eq!(eval("8 > 4 == 1"), 1);
+    }
+
+    #[test]
+    fn matches_fields() {
+        let filter = Filter::parse("id==0x123 || (id&0x7f0)==0x240 && !rtr").unwrap();
+        assert!(filter.matches(&packet(0x123, 8)));
+        assert!(filter.matches(&packet(0x245, 8)));
+        assert!(!filter.matches(&packet(0x245 | crate::MESSAGE_ARB_ID_RTR, 0)));
+        assert!(!filter.matches(&packet(0x250, 8)));
+
+        let fs = RdxUsbFsPacket { arb_id: 0x245, dlc: 8, ..bytemuck::Zeroable::zeroed() };
+        assert!(filter.matches_fs(&fs));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Filter::parse(""), Err(FilterParseError::UnexpectedEnd));
+        assert_eq!(Filter::parse("id =="), Err(FilterParseError::UnexpectedEnd));
+        assert_eq!(Filter::parse("id $ 1"), Err(FilterParseError::UnexpectedChar(3, '$')));
+        assert_eq!(Filter::parse("id 1"), Err(FilterParseError::UnexpectedToken(3)));
+        assert_eq!(Filter::parse("(id == 1"), Err(FilterParseError::UnexpectedEnd));
+        assert_eq!(Filter::parse("id == 1)"), Err(FilterParseError::UnexpectedToken(7)));
+        assert_eq!(Filter::parse("speed > 1"), Err(FilterParseError::UnknownField(0, "speed".to_string())));
+        assert_eq!(Filter::parse("id == 0xfg"), Err(FilterParseError::InvalidNumber(6)));
+        assert_eq!(Filter::parse("id == 99999999999999999999"), Err(FilterParseError::InvalidNumber(6)));
+    }
+
+    #[test]
+    fn length_limit() {
+        let long = format!("id == {}", "0".repeat(MAX_FILTER_LEN));
+        assert_eq!(Filter::parse(&long), Err(FilterParseError::TooLong(long.len())));
+        let padded = format!("{:<1$}", "id == 1", MAX_FILTER_LEN);
+        assert!(Filter::parse(&padded).is_ok());
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |n: usize| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        assert!(Filter::parse(&nested(MAX_FILTER_DEPTH - 1)).is_ok());
+        assert!(matches!(Filter::parse(&nested(MAX_FILTER_DEPTH)), Err(FilterParseError::TooDeep(_))));
+
+        let nots = |n: usize| format!("{}1", "!".repeat(n));
+        assert!(Filter::parse(&nots(MAX_FILTER_DEPTH - 1)).is_ok());
+        assert!(matches!(Filter::parse(&nots(MAX_FILTER_DEPTH)), Err(FilterParseError::TooDeep(_))));
+
+        // left-associative chains build deep trees without deep recursion
+        let chain = |n: usize| vec!["1"; n + 1].join("|");
+        assert!(Filter::parse(&chain(MAX_FILTER_DEPTH - 1)).is_ok());
+        assert!(matches!(Filter::parse(&chain(MAX_FILTER_DEPTH)), Err(FilterParseError::TooDeep(_))));
+
+        // the worst case that fits within the length limit is still rejected rather than overflowing the stack
+        assert!(matches!(Filter::parse(&"!".repeat(MAX_FILTER_LEN)), Err(FilterParseError::TooDeep(_))));
+    }
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use rdxusb_protocol::{is_valid_fd_len, RdxUsbPacket, MESSAGE_ARB_ID_EXT, MESSAGE_FLAG_FDF};
+
+/// How a [`TrafficGenerator`] picks each frame's arbitration id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdPattern {
+    /// Every frame has the same id.
+    Fixed(u32),
+    /// Ids count up from `start` to `end` inclusive, then wrap around.
+    Sequential { start: u32, end: u32 },
+    /// Ids are picked at random from `start` to `end` inclusive.
+    Random { start: u32, end: u32 },
+}
+
+/// What a [`TrafficGenerator`] puts in each frame's payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadPattern {
+    Zeros,
+    /// The frame's sequence number as a little-endian u64, so receivers can spot dropped or reordered frames.
+    Counter,
+    Random,
+    /// The same bytes in every frame, repeated or cut to the payload length.
+    Fixed(Vec<u8>),
+}
+
+/// How fast a [`TrafficGenerator`] sends, in frames per second, over the course of a run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateProfile {
+    Constant(f64),
+    /// Ramps linearly from `from` to `to` over `over`, then holds `to`.
+    Ramp { from: f64, to: f64, over: Duration },
+}
+
+impl RateProfile {
+    /// Frames due in the first `elapsed` of a run.
+    fn frames_by(&self, elapsed: Duration) -> f64 {
+        let t = elapsed.as_secs_f64();
+        match *self {
+            Self::Constant(rate) => rate * t,
+            Self::Ramp { from, to, over } => {
+                let ramp = over.as_secs_f64();
+                if t < ramp {
+                    from * t + (to - from) * t * t / (2.0 * ramp)
+                } else {
+                    (from + to) * ramp / 2.0 + to * (t - ramp)
+                }
+            }
+        }
+    }
+}
+
+/// Generates test traffic, for stress-testing devices and measuring the throughput of the transmit path.
+///
+/// The generator only decides what to send and when; drive it by periodically passing the time since the run
+/// started to [`Self::due`] and handing the frames to a writer, e.g. with [`crate::host::RdxUsbFsWriter::write_many`].
+#[derive(Debug, Clone)]
+pub struct TrafficGenerator {
+    rate: RateProfile,
+    ids: IdPattern,
+    payload: PayloadPattern,
+    len: u8,
+    channel: u8,
+    extended: bool,
+    /// frames generated so far
+    sent: u64,
+    rng: u64,
+}
+
+impl TrafficGenerator {
+    /// A generator sending 8 byte [`PayloadPattern::Counter`] frames with standard id 0x100 on channel 0.
+    pub fn new(rate: RateProfile) -> Self {
+        Self {
+            rate,
+            ids: IdPattern::Fixed(0x100),
+            payload: PayloadPattern::Counter,
+            len: 8,
+            channel: 0,
+            extended: false,
+            sent: 0,
+            rng: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    pub fn ids(mut self, ids: IdPattern) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    pub fn payload(mut self, payload: PayloadPattern) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Payload length in bytes. Lengths over 8 send CAN FD frames, rounded up to the next valid FD length.
+    pub fn len(mut self, len: u8) -> Self {
+        self.len = (len.min(64)..=64).find(|&l| is_valid_fd_len(l)).unwrap_or(64);
+        self
+    }
+
+    pub fn channel(mut self, channel: u8) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Whether ids are sent as 29-bit extended ids.
+    pub fn extended(mut self, extended: bool) -> Self {
+        self.extended = extended;
+        self
+    }
+
+    /// Seeds the random id and payload patterns, so runs can be repeated exactly.
+    pub fn seed(mut self, seed: u64) -> Self {
+        // xorshift gets stuck at zero
+        self.rng = seed.max(1);
+        self
+    }
+
+    /// Number of frames generated so far.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Appends the frames that should have been sent `elapsed` into the run and haven't been generated yet.
+    pub fn due(&mut self, elapsed: Duration, out: &mut Vec<RdxUsbPacket>) {
+        let total = self.rate.frames_by(elapsed).max(0.0) as u64;
+        while self.sent < total {
+            out.push(self.next_packet());
+        }
+    }
+
+    /// Generates the next frame regardless of the rate.
+    pub fn next_packet(&mut self) -> RdxUsbPacket {
+        let seq = self.sent;
+        self.sent += 1;
+        let id = match self.ids {
+            IdPattern::Fixed(id) => id,
+            IdPattern::Sequential { start, end } => start + (seq % (end.saturating_sub(start) as u64 + 1)) as u32,
+            IdPattern::Random { start, end } => start + (self.next_random() % (end.saturating_sub(start) as u64 + 1)) as u32,
+        };
+        let mut data = [0u8; 64];
+        let payload = &mut data[..self.len as usize];
+        match &self.payload {
+            PayloadPattern::Zeros => (),
+            PayloadPattern::Counter => {
+                let counter = seq.to_le_bytes();
+                let n = payload.len().min(counter.len());
+                payload[..n].copy_from_slice(&counter[..n]);
+            }
+            PayloadPattern::Random => {
+                for chunk in payload.chunks_mut(8) {
+                    let random = self.next_random().to_le_bytes();
+                    chunk.copy_from_slice(&random[..chunk.len()]);
+                }
+            }
+            PayloadPattern::Fixed(bytes) if !bytes.is_empty() => {
+                for (b, src) in payload.iter_mut().zip(bytes.iter().cycle()) {
+                    *b = *src;
+                }
+            }
+            PayloadPattern::Fixed(_) => (),
+        }
+        RdxUsbPacket {
+            timestamp_ns: 0,
+            arb_id: if self.extended { id & 0x1fff_ffff | MESSAGE_ARB_ID_EXT } else { id & 0x7ff },
+            dlc: self.len,
+            channel: self.channel,
+            flags: if self.len > 8 { MESSAGE_FLAG_FDF } else { 0 },
+            data,
+        }
+    }
+
+    /// xorshift64, which is plenty for test traffic.
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
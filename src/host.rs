@@ -1,287 +1,3769 @@
 #![allow(dead_code)]
 
-use std::fmt::Display;
+use std::{collections::{HashMap, VecDeque}, fmt::Display, future::Future, pin::Pin, sync::{atomic::{AtomicU32, AtomicU64, Ordering}, Arc, Mutex}, time::{Duration, Instant, SystemTime}};
 
 use bytemuck::AnyBitPattern;
-use futures_util::StreamExt;
-use nusb::{transfer::{ControlIn, ControlOut, ControlType, Recipient, RequestBuffer}, DeviceInfo};
-use rdxusb_protocol::{RdxUsbCtrl, RdxUsbDeviceInfo, RdxUsbFsPacket, ENDPOINT_OUT};
-use ringbuf::{storage::Heap, traits::Consumer};
-use async_ringbuf::{traits::{AsyncProducer, AsyncConsumer, Producer, Split}, AsyncHeapRb, AsyncRb};
+use futures_util::{Stream, StreamExt};
+use nusb::{transfer::{ControlIn, ControlOut, ControlType, Direction, EndpointType, Recipient, RequestBuffer}, DeviceId, DeviceInfo};
+use rdxusb_protocol::{RdxUsbBitTiming, RdxUsbBusState, RdxUsbChannelMode, RdxUsbCtrl, RdxUsbDeviceInfo, RdxUsbDeviceTime, RdxUsbFsPacket, RdxUsbIdMaskFilter, RdxUsbPacket, RdxUsbTimestampUnits, ENDPOINT_IN, ENDPOINT_OUT, KNOWN_FLAGS, MESSAGE_ARB_ID_DEVICE, MESSAGE_FLAG_ECHO, MESSAGE_FLAG_SEQ_MASK, MESSAGE_FLAG_SEQ_SHIFT, NOTIFICATION_CHANNEL, PROTOCOL_VERSION_MAJOR_HS};
+#[cfg(feature = "unstable-hs")]
+use rdxusb_protocol::{RdxUsbHsTransferHeader, PROTOCOL_VERSION_MINOR_HS_FRAMED};
+use ringbuf::{storage::Heap, traits::{Consumer, Observer}};
+use async_ringbuf::{traits::{AsyncObserver, AsyncProducer, AsyncConsumer, Producer, Split}, AsyncHeapRb, AsyncRb};
+use crate::fault::{RdxUsbFaultInjector, RdxUsbFaults};
 
-/// USB full-speed spec host.
-pub struct RdxUsbFsHost {
+/// A host for devices speaking the packet format `P`; see [`RdxUsbFsHost`] and [`RdxUsbHsHost`].
+///
+/// Host operations rely on tokio timers, so they must be run from within a tokio runtime.
+pub struct RdxUsbGenericHost<P> {
+    device: nusb::Device,
     iface: nusb::Interface,
+    /// the same interface, as seen by the channels
+    shared_iface: RdxUsbSharedInterface,
+    /// what [`Self::reopen`] looks for
+    identity: RdxUsbDeviceMatch,
+    kernel_driver: RdxUsbKernelDriverPolicy,
+    endpoints: RdxUsbEndpoints,
+    tx_ownership: RdxUsbTxOwnership,
+    timeouts: RdxUsbTimeouts,
     n_channels: u8,
-    rx_queue: Vec<<AsyncRb<Heap<RdxUsbFsPacket>> as async_ringbuf::traits::Split>::Prod>
+    device_info: RdxUsbDeviceInfo,
+    notifications: Option<RdxUsbNotifications<P>>,
+    errors: Option<RdxUsbErrorFrames<P>>,
+    n_transfers: usize,
+    overflow_policy: RdxUsbOverflowPolicy,
+    tx_q_size: usize,
+    connection: tokio::sync::watch::Sender<RdxUsbConnectionState>,
+    /// faults [`Self::poll`] injects into the transfers it reads, see [`Self::set_fault_injection`]
+    faults: Option<RdxUsbFaults>,
+    rx: RxPath<P>,
 }
 
-#[derive(Debug)]
-pub enum RdxUsbHostError {
-    UnsupportedProtocol,
-    InvalidChannel,
-    NoInterface,
-    NusbError(nusb::Error),
-    TransferCancelled, 
-    EndpointStall,
-    DeviceDisconnected,
-    UsbFault,
-    TransferUnknownError,
-    DataDecodeError,
+/// The reading end of a packet queue.
+type PacketCons<P> = <AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons;
+
+/// What a host's poll loop reads transfers into: the channels' queues and everything counted along the way.
+///
+/// It holds no USB handles, so the poll loop can run on any [`RdxUsbInTransport`].
+struct RxPath<P> {
+    rx_transfers: Arc<AtomicU64>,
+    dlc_violations: Arc<AtomicU64>,
+    framing_errors: Arc<AtomicU64>,
+    lost_transfers: Arc<AtomicU64>,
+    /// sequence number of the last framed transfer
+    last_seq: Option<u8>,
+    /// whether the device frames its IN transfers, see [`RdxUsbHostPacket::framed`]
+    framed: bool,
+    rx_queue: Vec<<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Prod>,
+    rx_filters: Vec<RdxUsbChannelFilters>,
+    rx_subscribers: Vec<RdxUsbChannelSubscribers<P>>,
+    rx_latest: Vec<RdxUsbChannelLatest<P>>,
+    /// packets of the current transfer headed for each rx queue, pushed together once the transfer is parsed
+    rx_batch: Vec<Vec<P>>,
+    echo_queue: Vec<<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Prod>,
+    notification_queue: <AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Prod,
+    error_queue: <AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Prod,
+    bridge: Option<(RdxUsbBridgeRules, RdxUsbWriter<P>)>,
+    unknown_flag_policy: RdxUsbUnknownFlagPolicy,
+    /// whether the poll loop recovers from IN endpoint stalls
+    clear_halt_on_stall: bool,
+    retry_policy: RdxUsbRetryPolicy,
+    /// units received timestamps are converted from
+    timestamp_units: RdxUsbTimestampUnits,
+    warned_unknown_flags: bool,
+    stats: Arc<RdxUsbStatsCounters>,
+    rx_throttle: RxThrottle,
+    clock: RdxUsbSmoothedClock,
 }
 
-impl From<nusb::Error> for RdxUsbHostError {
-    fn from(value: nusb::Error) -> Self {
-        Self::NusbError(value)
+/// USB full-speed spec host.
+///
+/// Full speed devices exchange [`RdxUsbFsPacket`]s, one per bulk transfer.
+pub type RdxUsbFsHost = RdxUsbGenericHost<RdxUsbFsPacket>;
+pub type RdxUsbFsChannel = RdxUsbChannel<RdxUsbFsPacket>;
+pub type RdxUsbFsWriter = RdxUsbWriter<RdxUsbFsPacket>;
+pub type RdxUsbFsWritePoller = RdxUsbWritePoller<RdxUsbFsPacket>;
+pub type RdxUsbFsNotifications = RdxUsbNotifications<RdxUsbFsPacket>;
+pub type RdxUsbFsErrorFrames = RdxUsbErrorFrames<RdxUsbFsPacket>;
+pub type RdxUsbFsSubscription = RdxUsbSubscription<RdxUsbFsPacket>;
+
+/// USB high-speed spec host.
+///
+/// High speed devices exchange full-size [`RdxUsbPacket`]s, several of which may share one bulk transfer.
+#[cfg(feature = "unstable-hs")]
+pub type RdxUsbHsHost = RdxUsbGenericHost<RdxUsbPacket>;
+#[cfg(feature = "unstable-hs")]
+pub type RdxUsbHsChannel = RdxUsbChannel<RdxUsbPacket>;
+#[cfg(feature = "unstable-hs")]
+pub type RdxUsbHsWriter = RdxUsbWriter<RdxUsbPacket>;
+#[cfg(feature = "unstable-hs")]
+pub type RdxUsbHsWritePoller = RdxUsbWritePoller<RdxUsbPacket>;
+#[cfg(feature = "unstable-hs")]
+pub type RdxUsbHsNotifications = RdxUsbNotifications<RdxUsbPacket>;
+#[cfg(feature = "unstable-hs")]
+pub type RdxUsbHsErrorFrames = RdxUsbErrorFrames<RdxUsbPacket>;
+#[cfg(feature = "unstable-hs")]
+pub type RdxUsbHsSubscription = RdxUsbSubscription<RdxUsbPacket>;
+
+/// Bulk max packet size of high speed devices.
+#[cfg(feature = "unstable-hs")]
+pub const HS_MAX_PACKET_SIZE: usize = 512;
+
+/// A packet format a host can exchange with a device, which is all that sets full and high speed hosts apart.
+pub trait RdxUsbHostPacket: bytemuck::Pod + Into<RdxUsbPacket> + Send + Sync {
+    /// Size of one packet on the wire.
+    const SIZE: usize = core::mem::size_of::<Self>();
+    /// Size of the bulk IN transfers the poll loop submits.
+    const TRANSFER_SIZE: usize;
+    /// Most packets sent in one bulk OUT transfer.
+    const MAX_OUT_BATCH: usize;
+    /// Packets a write poller sends per bulk OUT transfer unless told otherwise.
+    const DEFAULT_OUT_BATCH: usize;
+
+    fn arb_id(&self) -> u32;
+    fn dlc(&self) -> u8;
+    fn channel(&self) -> u8;
+    fn set_channel(&mut self, channel: u8);
+    fn flags(&self) -> u16;
+    fn set_flags(&mut self, flags: u16);
+    fn timestamp_ns(&self) -> u64;
+    fn set_timestamp_ns(&mut self, timestamp_ns: u64);
+    /// The arbitration id without flag bits.
+    fn id(&self) -> u32;
+    fn error_frame(&self) -> bool;
+    fn echo(&self) -> bool;
+    /// Clamps an out of range dlc, returning whether it was.
+    fn sanitize(&mut self) -> bool;
+
+    /// Whether a device with these bulk endpoints can carry this format.
+    fn endpoints_supported(endpoints: &RdxUsbEndpoints) -> bool;
+    /// Whether the device starts its IN transfers with a header.
+    fn framed(device_info: &RdxUsbDeviceInfo) -> bool;
+    /// Splits a received bulk transfer into its packets, along with its sequence number if `framed`.
+    ///
+    /// Returns `None` for a framed transfer whose header or checksum doesn't match.
+    fn split_transfer(transfer: &[u8], framed: bool) -> Option<(Option<u8>, &[Self])>;
+}
+
+impl RdxUsbHostPacket for RdxUsbFsPacket {
+    const TRANSFER_SIZE: usize = Self::SIZE;
+    const MAX_OUT_BATCH: usize = usize::MAX;
+    const DEFAULT_OUT_BATCH: usize = 1;
+
+    fn arb_id(&self) -> u32 { self.arb_id }
+    fn dlc(&self) -> u8 { self.dlc }
+    fn channel(&self) -> u8 { self.channel }
+    fn set_channel(&mut self, channel: u8) { self.channel = channel; }
+    fn flags(&self) -> u16 { self.flags }
+    fn set_flags(&mut self, flags: u16) { self.flags = flags; }
+    fn timestamp_ns(&self) -> u64 { self.timestamp_ns }
+    fn set_timestamp_ns(&mut self, timestamp_ns: u64) { self.timestamp_ns = timestamp_ns; }
+    fn id(&self) -> u32 { RdxUsbFsPacket::id(self) }
+    fn error_frame(&self) -> bool { RdxUsbFsPacket::error_frame(self) }
+    fn echo(&self) -> bool { RdxUsbFsPacket::echo(self) }
+    fn sanitize(&mut self) -> bool { RdxUsbFsPacket::sanitize(self) }
+
+    fn endpoints_supported(_endpoints: &RdxUsbEndpoints) -> bool {
+        true
+    }
+
+    fn framed(_device_info: &RdxUsbDeviceInfo) -> bool {
+        false
+    }
+
+    fn split_transfer(transfer: &[u8], _framed: bool) -> Option<(Option<u8>, &[Self])> {
+        // a short transfer carries no packet
+        Some((None, bytemuck::try_from_bytes(transfer).map(std::slice::from_ref).unwrap_or_default()))
     }
 }
 
-impl From<nusb::transfer::TransferError> for RdxUsbHostError {
-    fn from(value: nusb::transfer::TransferError) -> Self {
+#[cfg(feature = "unstable-hs")]
+impl RdxUsbHostPacket for RdxUsbPacket {
+    const TRANSFER_SIZE: usize = HS_MAX_PACKET_SIZE;
+    const MAX_OUT_BATCH: usize = HS_MAX_PACKET_SIZE / Self::SIZE;
+    const DEFAULT_OUT_BATCH: usize = Self::MAX_OUT_BATCH;
+
+    fn arb_id(&self) -> u32 { self.arb_id }
+    fn dlc(&self) -> u8 { self.dlc }
+    fn channel(&self) -> u8 { self.channel }
+    fn set_channel(&mut self, channel: u8) { self.channel = channel; }
+    fn flags(&self) -> u16 { self.flags }
+    fn set_flags(&mut self, flags: u16) { self.flags = flags; }
+    fn timestamp_ns(&self) -> u64 { self.timestamp_ns }
+    fn set_timestamp_ns(&mut self, timestamp_ns: u64) { self.timestamp_ns = timestamp_ns; }
+    fn id(&self) -> u32 { RdxUsbPacket::id(self) }
+    fn error_frame(&self) -> bool { RdxUsbPacket::error_frame(self) }
+    fn echo(&self) -> bool { RdxUsbPacket::echo(self) }
+    fn sanitize(&mut self) -> bool { RdxUsbPacket::sanitize(self) }
+
+    fn endpoints_supported(endpoints: &RdxUsbEndpoints) -> bool {
+        // both bulk endpoints need the high speed max packet size, or transfers carrying several packets would be split.
+        endpoints.in_max_packet_size == HS_MAX_PACKET_SIZE && endpoints.out_max_packet_size == HS_MAX_PACKET_SIZE
+    }
+
+    fn framed(device_info: &RdxUsbDeviceInfo) -> bool {
+        device_info.protocol_version_minor >= PROTOCOL_VERSION_MINOR_HS_FRAMED
+    }
+
+    fn split_transfer(transfer: &[u8], framed: bool) -> Option<(Option<u8>, &[Self])> {
+        if framed {
+            return RdxUsbHsTransferHeader::parse(transfer).map(|(header, packets)| (Some(header.seq), packets));
+        }
+        // trailing bytes that don't make up a whole packet are dropped.
+        Some((None, bytemuck::try_cast_slice(&transfer[..transfer.len() - transfer.len() % Self::SIZE]).unwrap_or_default()))
+    }
+}
+
+/// What the host poll loops do with received packets that set flag bits outside [`KNOWN_FLAGS`].
+///
+/// Packets that are let through always keep their flags unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum RdxUsbUnknownFlagPolicy {
+    /// Pass the packet through silently.
+    #[default]
+    Ignore = 0,
+    /// Pass the packet through, logging a warning the first time it happens on a device.
+    WarnOnce = 1,
+    /// Drop the packet.
+    Reject = 2,
+}
+
+impl TryFrom<u8> for RdxUsbUnknownFlagPolicy {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            nusb::transfer::TransferError::Cancelled => RdxUsbHostError::TransferCancelled,
-            nusb::transfer::TransferError::Stall => RdxUsbHostError::EndpointStall,
-            nusb::transfer::TransferError::Disconnected => RdxUsbHostError::DeviceDisconnected,
-            nusb::transfer::TransferError::Fault => RdxUsbHostError::UsbFault,
-            nusb::transfer::TransferError::Unknown => RdxUsbHostError::TransferUnknownError,
+            0 => Ok(Self::Ignore),
+            1 => Ok(Self::WarnOnce),
+            2 => Ok(Self::Reject),
+            v => Err(v),
         }
     }
 }
 
-impl From<bytemuck::PodCastError> for RdxUsbHostError {
-    fn from(_value: bytemuck::PodCastError) -> Self {
-        RdxUsbHostError::DataDecodeError
+/// Applies the unknown flag policy, returning whether a packet with `flags` should be delivered.
+fn accept_flags(policy: RdxUsbUnknownFlagPolicy, warned: &mut bool, flags: u16) -> bool {
+    let unknown = flags & !KNOWN_FLAGS;
+    if unknown == 0 { return true; }
+    match policy {
+        RdxUsbUnknownFlagPolicy::Ignore => true,
+        RdxUsbUnknownFlagPolicy::WarnOnce => {
+            if !*warned {
+                *warned = true;
+                log::warn!(target: "rdxusb", "Device sent unknown packet flag bits {unknown:#06x}; newer firmware than this host supports?");
+            }
+            true
+        }
+        RdxUsbUnknownFlagPolicy::Reject => {
+            log::trace!(target: "rdxusb", "Dropping packet with unknown flag bits {unknown:#06x}");
+            false
+        }
     }
 }
 
-impl Display for RdxUsbHostError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Timeouts applied to host operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdxUsbTimeouts {
+    /// Maximum time a single control transfer may take.
+    pub control: Duration,
+    /// Maximum time opening a device may take, including its initial control transfers.
+    pub open: Duration,
+    /// Maximum time a bulk OUT transfer may take before it is cancelled, e.g. if the device stops reading its
+    /// OUT endpoint. Zero waits indefinitely.
+    pub write: Duration,
+    /// How long the event loop waits after a device faults or fails to open before trying it again.
+    pub reconnect_backoff: Duration,
+    /// How long a connected device may go without completing any bulk IN transfers before the event loop resets it.
+    /// Zero disables the check.
+    pub rx_watchdog: Duration,
+    /// How long [`RdxUsbFsHost::poll`] waits for a bulk IN transfer to complete before it cancels and resubmits the
+    /// transfers in flight, for devices whose IN pipe silently wedges. Devices on a quiet bus complete no transfers
+    /// either, so this should be well above their longest gap between frames. Zero disables the check.
+    pub transfer_watchdog: Duration,
+}
+
+impl Default for RdxUsbTimeouts {
+    fn default() -> Self {
+        RdxUsbTimeoutProfile::Default.timeouts()
+    }
+}
+
+impl From<RdxUsbTimeoutProfile> for RdxUsbTimeouts {
+    fn from(value: RdxUsbTimeoutProfile) -> Self {
+        value.timeouts()
+    }
+}
+
+/// Named sets of [`RdxUsbTimeouts`], for picking sensible timeouts without tuning each one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum RdxUsbTimeoutProfile {
+    /// Balanced timeouts for devices on a local bus, with the rx watchdog off.
+    #[default]
+    Default = 0,
+    /// Short timeouts that notice a stalled device quickly.
+    ///
+    /// This enables the rx watchdog, which pings devices that go quiet to tell them apart from wedged ones.
+    Realtime = 1,
+    /// Long timeouts for slow hubs, long cables or busy hosts, backing off between reconnect attempts.
+    Patient = 2,
+}
+
+impl RdxUsbTimeoutProfile {
+    pub fn timeouts(self) -> RdxUsbTimeouts {
         match self {
-            RdxUsbHostError::UnsupportedProtocol => write!(f, "Unsupported protocol"),
-            RdxUsbHostError::InvalidChannel => write!(f, "Invalid channel"),
-            RdxUsbHostError::NoInterface => write!(f, "No valid USB interface"),
-            RdxUsbHostError::NusbError(error) => write!(f, "nusb error: {error}"),
-            RdxUsbHostError::TransferCancelled => write!(f, "Transfer cancelled"),
-            RdxUsbHostError::EndpointStall => write!(f, "Endpoint stall"),
-            RdxUsbHostError::DeviceDisconnected => write!(f, "Device disconnected"),
-            RdxUsbHostError::UsbFault => write!(f, "USB fault"),
-            RdxUsbHostError::TransferUnknownError => write!(f, "Unknown transfer error"),
-            RdxUsbHostError::DataDecodeError => write!(f, "Received undecodable data"),
+            Self::Default => RdxUsbTimeouts {
+                control: Duration::from_millis(500),
+                write: Duration::from_secs(1),
+                open: Duration::from_secs(2),
+                reconnect_backoff: Duration::ZERO,
+                rx_watchdog: Duration::ZERO,
+                transfer_watchdog: Duration::ZERO,
+            },
+            Self::Realtime => RdxUsbTimeouts {
+                control: Duration::from_millis(100),
+                write: Duration::from_millis(100),
+                open: Duration::from_millis(500),
+                reconnect_backoff: Duration::ZERO,
+                rx_watchdog: Duration::from_millis(250),
+                transfer_watchdog: Duration::ZERO,
+            },
+            Self::Patient => RdxUsbTimeouts {
+                control: Duration::from_secs(2),
+                write: Duration::from_secs(5),
+                open: Duration::from_secs(10),
+                reconnect_backoff: Duration::from_secs(1),
+                rx_watchdog: Duration::ZERO,
+                transfer_watchdog: Duration::ZERO,
+            },
         }
     }
 }
-impl core::error::Error for RdxUsbHostError {}
 
+impl TryFrom<u8> for RdxUsbTimeoutProfile {
+    type Error = u8;
 
-pub type RdxUsbHostResult<T> = Result<T, RdxUsbHostError>;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Default),
+            1 => Ok(Self::Realtime),
+            2 => Ok(Self::Patient),
+            v => Err(v),
+        }
+    }
+}
 
-impl RdxUsbFsHost {
-    /// Opens the device with the [`DeviceInfo`] and specified rx queue buffer size.
-    /// Returns a usb device handle
-    pub async fn open_device(dev_info: DeviceInfo, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbFsChannel>)> {
+impl std::str::FromStr for RdxUsbTimeoutProfile {
+    type Err = String;
 
-        let Some(iface) = dev_info.interfaces().find(|iface| {
-            iface.class() == 0xff && iface.subclass() == 0x0 && iface.protocol() == 0x0
-        }) else { return Err(RdxUsbHostError::NoInterface); };
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(Self::Default),
+            "realtime" => Ok(Self::Realtime),
+            "patient" => Ok(Self::Patient),
+            _ => Err(format!("Unknown timeout profile {s:?}")),
+        }
+    }
+}
 
-        let iface_idx = iface.interface_number();
+/// How the poll loops and write pollers ride out transient transfer errors, so one glitched transfer on a
+/// flaky hub doesn't end the session.
+///
+/// Only [`nusb::transfer::TransferError::Fault`] and [`nusb::transfer::TransferError::Unknown`] are retried;
+/// stalls, cancellations and disconnects are handled as before. Retried errors still count towards
+/// [`RdxUsbStats::usb_errors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdxUsbRetryPolicy {
+    /// Consecutive failed transfers retried before the error is returned. Zero disables retries.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each consecutive retry after it.
+    pub backoff: Duration,
+}
 
-        let mut handle: RdxUsbHostResult<nusb::Device> = Err(RdxUsbHostError::UsbFault);
-        for _ in 0..3 {
-            handle = match dev_info.open() {
-                Ok(o) => { Ok(o) }
-                Err(e) => {
-                    // windows needs a sleep retry
-                    #[cfg(windows)]
-                    std::thread::sleep(std::time::Duration::from_millis(10));
-                    Err(e.into())
-                }
-            };
-            if handle.is_ok() { break; }
-        }
-        let handle = handle?;
-
-        handle.detach_kernel_driver(iface_idx).ok();
-        // TODO: properly introspect for our device
-        // we probably don't need to right now
-        //let cfg = handle.active_configuration().unwrap();
-        //for iface in cfg.interfaces() {
-        //    eprintln!("iface number: {}", iface.interface_number());
-        //    for alt_stg in iface.alt_settings() {
-        //        eprintln!("\talt_stg idx: {}", alt_stg.alternate_setting());
-        //        for endpoint in alt_stg.endpoints() {
-        //            eprintln!("\tep {}, {:?} ({})", endpoint.address(), endpoint.direction(), endpoint.max_packet_size());
-        //        }
-        //    }
-        //}
-
-
-        let iface = handle.claim_interface(iface_idx)?;
-        let cfg = Self::get_device_info(&iface).await?;
-        let icount = cfg.n_channels;
+impl RdxUsbRetryPolicy {
+    /// Returns every transfer error immediately.
+    pub const NONE: Self = Self { max_retries: 0, backoff: Duration::ZERO };
 
-        // TODO: split into RdxUsbFsHost or RdxUsbHsHost here.
+    /// The wait before retry number `retry`, counting from zero.
+    fn backoff(&self, retry: u32) -> Duration {
+        self.backoff.saturating_mul(1 << retry.min(16))
+    }
 
-        let mut dev = RdxUsbFsHost {
-            iface: iface.clone(),
-            n_channels: icount,
-            rx_queue: Vec::with_capacity(icount as usize),
-        };
+    /// Whether a transfer that failed with `error` after `retries` retries is tried again.
+    fn retries(&self, error: nusb::transfer::TransferError, retries: u32) -> bool {
+        matches!(error, nusb::transfer::TransferError::Fault | nusb::transfer::TransferError::Unknown) && retries < self.max_retries
+    }
+}
 
-        let mut v = Vec::with_capacity(icount as usize);
-        for i in 0..=icount {
-            //let (tx, rx) = tokio::sync::mpsc::channel(rx_q_size);
-            let (prod, cons) = AsyncHeapRb::new(rx_q_size).split();
+impl Default for RdxUsbRetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 3, backoff: Duration::from_millis(1) }
+    }
+}
 
-            v.push(RdxUsbFsChannel {
-                iface: iface.clone(),
-                channel: i,
-                rx_queue: cons,
-            });
-            dev.rx_queue.push(prod);
+/// Packets a request's reply subscription holds, enough to ride out a burst of unrelated traffic.
+const REQUEST_REPLY_CAPACITY: usize = 64;
+
+/// Sequence number of the next reliably delivered command, skipping 0 which devices never deduplicate.
+pub(crate) fn next_reliable_seq(seq: u8) -> u8 {
+    seq % 15 + 1
+}
+
+/// Flags of a reliably delivered frame: echoed, and tagged with `seq`.
+pub(crate) fn reliable_flags(flags: u16, seq: u8) -> u16 {
+    flags & !MESSAGE_FLAG_SEQ_MASK | MESSAGE_FLAG_ECHO | (seq as u16) << MESSAGE_FLAG_SEQ_SHIFT
+}
+
+/// Is an echo the acknowledgment of a reliably delivered frame?
+pub(crate) fn is_reliable_ack(sent_arb_id: u32, sent_flags: u16, echo_arb_id: u32, echo_flags: u16) -> bool {
+    (sent_arb_id ^ echo_arb_id) & 0x1fff_ffff == 0 && (sent_flags ^ echo_flags) & MESSAGE_FLAG_SEQ_MASK == 0
+}
+
+async fn with_timeout<T>(timeout: Duration, fut: impl Future<Output = RdxUsbHostResult<T>>) -> RdxUsbHostResult<T> {
+    tokio::time::timeout(timeout, fut).await.map_err(|_| RdxUsbHostError::Timeout)?
+}
+
+/// Runs a bulk OUT transfer within `timeout`, or indefinitely if it is zero.
+///
+/// A transfer that times out is cancelled by dropping it, and fails with [`RdxUsbHostError::WriteTimeout`].
+async fn with_write_timeout<T>(timeout: Duration, fut: impl Future<Output = RdxUsbHostResult<T>>) -> RdxUsbHostResult<T> {
+    if timeout.is_zero() { return fut.await; }
+    tokio::time::timeout(timeout, fut).await.map_err(|_| RdxUsbHostError::WriteTimeout)?
+}
+
+/// Forwards frames received on one channel of a device back out on another channel of the same device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdxUsbBridgeRule {
+    /// Channel to forward frames from
+    pub src_channel: u8,
+    /// Channel to retransmit matching frames on
+    pub dst_channel: u8,
+    /// Arbitration id to match, compared only on the bits set in `mask`
+    pub id: u32,
+    /// Arbitration id bits that must match `id`. A mask of 0 forwards every frame.
+    pub mask: u32,
+}
+
+impl RdxUsbBridgeRule {
+    /// Does a frame received on `channel` with `arb_id` get forwarded by this rule?
+    pub fn matches(&self, channel: u8, arb_id: u32) -> bool {
+        self.src_channel == channel && (arb_id & self.mask) == (self.id & self.mask)
+    }
+}
+
+/// Bridge rule list shared between the host and whoever edits it while the host polls.
+pub type RdxUsbBridgeRules = Arc<Mutex<Vec<RdxUsbBridgeRule>>>;
+
+/// Acceptance filters of one channel, shared between the channel and the host's poll loop.
+type RdxUsbChannelFilters = Arc<Mutex<Vec<RdxUsbIdMaskFilter>>>;
+
+/// An extra reader of one channel, only handed the frames its filter lets through.
+struct RdxUsbChannelSubscriber<P> {
+    filter: Option<RdxUsbIdMaskFilter>,
+    tx: <AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Prod,
+}
+
+/// Extra readers of one channel, shared between the channel and the host's poll loop.
+type RdxUsbChannelSubscribers<P> = Arc<Mutex<Vec<RdxUsbChannelSubscriber<P>>>>;
+
+/// Most recent frame of each arbitration id received on one channel, or `None` while the cache is off.
+type RdxUsbChannelLatest<P> = Arc<Mutex<Option<HashMap<u32, P>>>>;
+
+/// Hands a packet to every live subscriber of a channel whose filter matches it, dropping the ones whose receiver
+/// is gone.
+///
+/// Subscribers that are full miss the packet rather than stall the poll loop.
+fn publish_to_subscribers<P: Copy>(subscribers: &RdxUsbChannelSubscribers<P>, arb_id: u32, pkt: P) {
+    let mut subscribers = subscribers.lock().unwrap();
+    if subscribers.is_empty() { return; }
+    subscribers.retain(|s| !s.tx.is_closed());
+    for subscriber in subscribers.iter_mut() {
+        if subscriber.filter.is_none_or(|f| f.matches(arb_id)) {
+            subscriber.tx.try_push(pkt).ok();
         }
+    }
+}
 
-        Ok((dev, v))
+/// Counters of one channel, see [`RdxUsbChannelStats`].
+#[derive(Debug, Default)]
+struct ChannelCounters {
+    rx_packets: AtomicU64,
+    tx_packets: AtomicU64,
+    rx_drops: AtomicU64,
+    bus_bits: AtomicU64,
+}
+
+/// Live counters behind [`RdxUsbStats`], shared between a host, its channels, and its write pollers.
+#[derive(Debug, Default)]
+struct RdxUsbStatsCounters {
+    channels: Vec<ChannelCounters>,
+    usb_errors: AtomicU64,
+    rx_bytes: AtomicU64,
+    tx_bytes: AtomicU64,
+    transfer_watchdog_trips: AtomicU64,
+    transfer_retries: AtomicU64,
+    tx_latency: LatencyHistogram,
+    /// shared with the event loop, see [`RdxUsbFsHost::rx_throttle_counter`]
+    rx_throttled: Arc<AtomicU64>,
+    meter: Mutex<BusMeter>,
+    /// observers of all traffic, kept here since every rx and tx path already shares these counters
+    taps: Mutex<RdxUsbTaps>,
+}
+
+impl RdxUsbStatsCounters {
+    fn new(n_channels: usize) -> Arc<Self> {
+        Arc::new(Self {
+            channels: (0..n_channels).map(|_| ChannelCounters::default()).collect(),
+            meter: Mutex::new(BusMeter::new(n_channels)),
+            ..Default::default()
+        })
     }
 
-    /// This drives the event loop.
-    /// 
-    /// **n_transfers** determines the maximum number of transfers to be flighted at a time.
-    pub async fn poll(&mut self, n_transfers: usize, await_on_full: bool) -> RdxUsbHostResult<()> {
-        let mut read_queue = self.iface.bulk_in_queue(rdxusb_protocol::ENDPOINT_IN);
+    /// Counts a batch of received packets that passed the channel's filters, `queued` of which fit in its rx queue.
+    fn record_rx_batch(&self, channel: u8, len: usize, queued: usize) {
+        let Some(counters) = self.channels.get(channel as usize) else { return; };
+        counters.rx_packets.fetch_add(queued as u64, Ordering::Relaxed);
+        counters.rx_drops.fetch_add(len.saturating_sub(queued) as u64, Ordering::Relaxed);
+    }
 
-        while read_queue.pending() < n_transfers {
-            read_queue.submit(RequestBuffer::new(RdxUsbFsPacket::SIZE))
+    /// Counts sent frames, and hands them to the host's taps.
+    fn record_tx(&self, packets: impl Iterator<Item = RdxUsbPacket>) {
+        let mut meter = self.meter.lock().unwrap();
+        let mut taps = self.taps.lock().unwrap();
+        let now = Instant::now();
+        for pkt in packets {
+            let bits = frame_bits(pkt.arb_id, pkt.dlc, pkt.flags);
+            if let Some(counters) = self.channels.get(pkt.channel as usize) {
+                counters.tx_packets.fetch_add(1, Ordering::Relaxed);
+                counters.bus_bits.fetch_add(bits as u64, Ordering::Relaxed);
+            }
+            meter.record(now, pkt.channel, false, bits);
+            publish_to_taps(&mut taps, RdxUsbDirection::Tx, pkt);
         }
-        loop {
-            let buf = read_queue.next_complete().await.into_result()?;
-            //println!("Received message: len={} {buf:?}", buf.len());
-            if let Ok(pkt) = bytemuck::try_from_bytes::<RdxUsbFsPacket>(buf.as_slice()) {
-                if (pkt.channel as usize) < self.rx_queue.len() {
-                    if await_on_full {
-                        self.rx_queue[pkt.channel as usize].push(pkt.clone()).await.ok();
-                    } else {
-                        self.rx_queue[pkt.channel as usize].try_push(pkt.clone()).ok();
-                    }
-                }
-            } 
+    }
+
+    /// Hands a received packet of any kind to the host's taps.
+    fn tap_rx(&self, pkt: impl Into<RdxUsbPacket>) {
+        let mut taps = self.taps.lock().unwrap();
+        if taps.0.is_empty() { return; }
+        publish_to_taps(&mut taps, RdxUsbDirection::Rx, pkt.into());
+    }
 
-            read_queue.submit(RequestBuffer::reuse(buf, RdxUsbFsPacket::SIZE))
+    /// Meters a frame received from the bus, whether or not the channel's filters pass it.
+    fn record_bus_rx(&self, channel: u8, arb_id: u32, bits: u32) {
+        if let Some(counters) = self.channels.get(channel as usize) {
+            counters.bus_bits.fetch_add(bits as u64, Ordering::Relaxed);
         }
-        //println!("Packet id: {:#08x} ts: {}", header.arbitration_id(), u32::from_le_bytes(buf[20..24].try_into().unwrap()));
+        let mut meter = self.meter.lock().unwrap();
+        let now = Instant::now();
+        meter.record(now, channel, true, bits);
+        meter.record_talker(now, channel, arb_id);
     }
 
-    async fn get_device_info(iface: &nusb::Interface) -> RdxUsbHostResult<RdxUsbDeviceInfo> {
-        let res = iface.control_in(ControlIn { 
-            control_type: ControlType::Vendor,
-            recipient: Recipient::Interface,
-            request: RdxUsbCtrl::DeviceInfo as u8,
-            value: 1,
-            index: 0,
-            length: core::mem::size_of::<RdxUsbDeviceInfo>() as u16,
-        }).await.into_result()?;
-        Ok(bytemuck::try_from_bytes::<RdxUsbDeviceInfo>(&res.as_slice())?.clone())
+    fn record_usb_error(&self) {
+        self.usb_errors.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub async fn get_device_config(&self) -> RdxUsbHostResult<RdxUsbDeviceInfo> {
-        Self::get_device_info(&self.iface).await
+    fn snapshot(&self) -> RdxUsbStats {
+        RdxUsbStats {
+            channels: self.channels.iter().map(|c| RdxUsbChannelStats {
+                rx_packets: c.rx_packets.load(Ordering::Relaxed),
+                tx_packets: c.tx_packets.load(Ordering::Relaxed),
+                rx_drops: c.rx_drops.load(Ordering::Relaxed),
+                bus_bits: c.bus_bits.load(Ordering::Relaxed),
+            }).collect(),
+            usb_errors: self.usb_errors.load(Ordering::Relaxed),
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            rx_throttled: self.rx_throttled.load(Ordering::Relaxed),
+            transfer_watchdog_trips: self.transfer_watchdog_trips.load(Ordering::Relaxed),
+            transfer_retries: self.transfer_retries.load(Ordering::Relaxed),
+            tx_latency: self.tx_latency.snapshot(),
+        }
+    }
+}
+
+/// Feeds a host's [`RdxUsbTap`]s.
+#[derive(Default)]
+struct RdxUsbTaps(Vec<<AsyncRb<Heap<RdxUsbTapEvent>> as async_ringbuf::traits::Split>::Prod>);
+
+impl std::fmt::Debug for RdxUsbTaps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RdxUsbTaps({})", self.0.len())
     }
+}
 
-    pub fn write_poller(&self, n_packets: usize) -> (RdxUsbFsWritePoller, RdxUsbFsWriter) {
-        RdxUsbFsWritePoller::new(self.iface.clone(), n_packets)
+/// Hands a packet to every live tap, dropping the ones whose receiver is gone. Full taps miss the packet.
+fn publish_to_taps(taps: &mut RdxUsbTaps, direction: RdxUsbDirection, packet: RdxUsbPacket) {
+    if taps.0.is_empty() { return; }
+    taps.0.retain(|t| !t.is_closed());
+    let event = RdxUsbTapEvent { direction, host_time: SystemTime::now(), packet };
+    for tap in taps.0.iter_mut() {
+        tap.try_push(event).ok();
     }
+}
 
+/// Which way a packet seen by a tap was going, see [`RdxUsbFsHost::register_tap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RdxUsbDirection {
+    /// Received from the device, including notifications, error frames and echoes.
+    Rx,
+    /// Handed to the device for sending.
+    Tx,
 }
 
-pub struct RdxUsbFsWriter(<AsyncRb<Heap<RdxUsbFsPacket>> as async_ringbuf::traits::Split>::Prod);
+/// A packet seen by a tap, see [`RdxUsbFsHost::register_tap`].
+#[derive(Debug, Clone, Copy)]
+pub struct RdxUsbTapEvent {
+    pub direction: RdxUsbDirection,
+    /// When the host received the packet or handed it to the device.
+    pub host_time: SystemTime,
+    /// The packet, widened to a high speed packet for full speed devices. Received packets have their
+    /// timestamps converted to nanoseconds already.
+    pub packet: RdxUsbPacket,
+}
 
-impl RdxUsbFsWriter {
-    pub fn try_send(&mut self, packet: RdxUsbFsPacket) -> Option<RdxUsbFsPacket> {
-        self.0.try_push(packet).err()
+/// A copy of all of a host's traffic in both directions, see [`RdxUsbFsHost::register_tap`].
+pub struct RdxUsbTap(<AsyncRb<Heap<RdxUsbTapEvent>> as async_ringbuf::traits::Split>::Cons);
+
+impl RdxUsbTap {
+    pub async fn read(&mut self) -> RdxUsbHostResult<RdxUsbTapEvent> {
+        match self.0.pop().await {
+            Some(v) => Ok(v),
+            None => Err(RdxUsbHostError::DeviceDisconnected)
+        }
     }
-    pub async fn send(&mut self, packet: RdxUsbFsPacket) -> Result<(), RdxUsbFsPacket> {
-        self.0.push(packet).await
+
+    pub fn try_read(&mut self) -> Option<RdxUsbTapEvent> {
+        self.0.try_pop()
     }
 }
 
-pub struct RdxUsbFsWritePoller {
-    iface: nusb::Interface,
-    tx_queue: <AsyncRb<Heap<RdxUsbFsPacket>> as async_ringbuf::traits::Split>::Cons,
+/// Yields the host's traffic, ending once the host and everything sending through it are dropped.
+impl Stream for RdxUsbTap {
+    type Item = RdxUsbTapEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
 }
 
-impl RdxUsbFsWritePoller {
-    pub fn new(iface: nusb::Interface, n_packets: usize) -> (Self, RdxUsbFsWriter) {
-        let (prod, cons) = AsyncHeapRb::new(n_packets).split();
+/// Traffic counters of a host since it was opened, see [`RdxUsbFsHost::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RdxUsbStats {
+    /// Per channel counters, indexed by channel.
+    pub channels: Vec<RdxUsbChannelStats>,
+    /// Bulk transfers that failed, including stalls that were cleared.
+    pub usb_errors: u64,
+    /// Bytes received in bulk IN transfers, including framing and notifications.
+    pub rx_bytes: u64,
+    /// Bytes sent in bulk OUT transfers.
+    pub tx_bytes: u64,
+    /// Times the poll loop paused because it reached its rx rate limit, see [`RdxUsbFsHost::set_max_rx_rate`].
+    pub rx_throttled: u64,
+    /// Times the poll loop resubmitted its IN transfers because none completed in time,
+    /// see [`RdxUsbTimeouts::transfer_watchdog`].
+    pub transfer_watchdog_trips: u64,
+    /// Failed transfers that were retried, see [`RdxUsbRetryPolicy`].
+    pub transfer_retries: u64,
+    /// How long the write poller's bulk OUT transfers took to complete.
+    pub tx_latency: RdxUsbLatencyStats,
+}
+
+/// Percentiles of the time from submitting a bulk OUT transfer to its completion, see [`RdxUsbStats::tx_latency`].
+///
+/// This covers the host's USB stack and the device accepting the transfer, but not the time packets spent queued in
+/// a writer before that. Latencies are bucketed to within 25%, and each percentile is the upper edge of its bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RdxUsbLatencyStats {
+    /// Transfers measured, counting retried ones once.
+    pub samples: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Transfer latencies in microseconds, bucketed logarithmically with 4 buckets per doubling.
+#[derive(Debug)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; Self::BUCKETS],
+    max_us: AtomicU64,
+}
 
-        (Self { iface, tx_queue: cons, }, RdxUsbFsWriter(prod))
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self { buckets: std::array::from_fn(|_| AtomicU64::new(0)), max_us: AtomicU64::new(0) }
     }
+}
 
-    pub async fn poll(&mut self) -> Result<(), RdxUsbHostError> {
-        let mut buffer= Vec::with_capacity(64);
-        while let Some(msg) = self.tx_queue.next().await {
-            buffer.clear();
-            buffer.extend_from_slice(bytemuck::bytes_of(&msg));
-            buffer = self.iface.bulk_out(ENDPOINT_OUT, buffer).await.into_result()?.reuse();
-        }
-        Ok(())
+impl LatencyHistogram {
+    /// Enough buckets for latencies up to about 2^33 us; longer ones land in the last.
+    const BUCKETS: usize = 128;
+
+    fn bucket(us: u64) -> usize {
+        if us < 4 { return us as usize; }
+        let octave = 63 - us.leading_zeros() as usize;
+        let sub = (us >> (octave - 2)) as usize & 3;
+        ((octave - 1) * 4 + sub).min(Self::BUCKETS - 1)
+    }
+
+    /// Exclusive upper edge of a bucket, in microseconds.
+    fn bucket_end(bucket: usize) -> u64 {
+        if bucket < 4 { return bucket as u64 + 1; }
+        let (octave, sub) = (bucket / 4 + 1, bucket as u64 % 4);
+        (5 + sub) << (octave - 2)
+    }
+
+    fn record(&self, latency: Duration) {
+        let us = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.buckets[Self::bucket(us)].fetch_add(1, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> RdxUsbLatencyStats {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let samples: u64 = counts.iter().sum();
+        let max = self.max_us.load(Ordering::Relaxed);
+        let percentile = |p: u64| {
+            if samples == 0 { return Duration::ZERO; }
+            // the sample at rank ceil(samples * p / 100)
+            let rank = (samples * p).div_ceil(100).max(1);
+            let mut seen = 0;
+            let bucket = counts.iter().position(|&n| { seen += n; seen >= rank }).unwrap_or(Self::BUCKETS - 1);
+            Duration::from_micros(Self::bucket_end(bucket).min(max))
+        };
+        RdxUsbLatencyStats { samples, p50: percentile(50), p90: percentile(90), p99: percentile(99), max: Duration::from_micros(max) }
     }
 }
 
+/// Reads a host's [`RdxUsbStats`] from another task, e.g. to sample them periodically. See [`RdxUsbFsHost::stats_reader`].
+#[derive(Clone)]
+pub struct RdxUsbStatsReader(Arc<RdxUsbStatsCounters>);
 
-pub struct RdxUsbFsChannel {
-    iface: nusb::Interface,
-    channel: u8,
-    rx_queue: <AsyncRb<Heap<RdxUsbFsPacket>> as async_ringbuf::traits::Split>::Cons,
+impl RdxUsbStatsReader {
+    pub fn read(&self) -> RdxUsbStats {
+        self.0.snapshot()
+    }
+
+    /// Traffic rates of each channel over the last `window`, see [`RdxUsbFsHost::meter`].
+    pub fn meter(&self, window: Duration) -> Vec<RdxUsbChannelMeter> {
+        self.0.meter.lock().unwrap().read(Instant::now(), window)
+    }
+
+    /// The arbitration ids received most often on `channel`, see [`RdxUsbFsHost::top_talkers`].
+    pub fn top_talkers(&self, channel: u8, max: usize) -> Vec<RdxUsbTalker> {
+        self.0.meter.lock().unwrap().top_talkers(Instant::now(), channel, max)
+    }
 }
 
-impl RdxUsbFsChannel {
-    pub async fn control_in_struct<T: AnyBitPattern>(&self, req: RdxUsbCtrl) -> RdxUsbHostResult<T> {
-        let res = self.iface.control_in(ControlIn {
-            control_type: ControlType::Vendor,
-            recipient: Recipient::Interface,
-            request: req as u8,
-            value: self.channel as u16,
-            index: 0,
-            length: core::mem::size_of::<T>() as u16,
-        }).await.into_result()?;
-        Ok(bytemuck::try_from_bytes::<T>(&res.as_slice())?.clone())
+/// Length of one [`BusMeter`] bucket, which is how finely windows are resolved.
+const METER_BUCKET: Duration = Duration::from_millis(100);
+/// Buckets kept per channel, bounding the window a meter can be read over (10 seconds).
+const METER_BUCKETS: usize = 100;
+
+/// Nominal bits on the wire of a frame, including the interframe space but not stuff bits.
+///
+/// CAN FD frames are counted as if sent entirely at the nominal bit rate, so bit rate switched frames
+/// are overestimated.
+pub(crate) fn frame_bits(arb_id: u32, dlc: u8, flags: u16) -> u32 {
+    let ext = arb_id & rdxusb_protocol::MESSAGE_ARB_ID_EXT != 0;
+    let data = if arb_id & rdxusb_protocol::MESSAGE_ARB_ID_RTR != 0 { 0 } else { dlc as u32 * 8 };
+    if flags & rdxusb_protocol::MESSAGE_FLAG_FDF != 0 {
+        let crc = if dlc <= 16 { 17 } else { 21 };
+        39 + if ext { 19 } else { 0 } + crc + data
+    } else {
+        (if ext { 67 } else { 47 }) + data
     }
+}
 
-    pub async fn control_out_struct(&self, req: RdxUsbCtrl, data: &[u8]) -> RdxUsbHostResult<()> {
-        self.iface.control_out(ControlOut {
-            control_type: ControlType::Vendor,
-            recipient: Recipient::Interface,
-            request: req as u8,
-            value: self.channel as u16,
-            index: 0,
-            data,
-        }).await.into_result()?;
-        Ok(())
+/// Arbitration ids tracked per channel by [`BusMeter::record_talker`]; the slowest is forgotten to make room.
+const MAX_TALKERS: usize = 512;
+/// Time constant of the per arbitration id receive rates.
+const TALKER_TAU: Duration = Duration::from_secs(1);
+
+/// Exponentially weighted receive rate of one arbitration id.
+#[derive(Debug, Clone, Copy)]
+struct TalkerRate {
+    /// frames per second as of `updated`
+    rate: f64,
+    updated: Instant,
+}
+
+impl TalkerRate {
+    fn at(&self, now: Instant) -> f64 {
+        let dt = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.rate * (-dt / TALKER_TAU.as_secs_f64()).exp()
     }
+}
 
-    pub fn interface(&self) -> &nusb::Interface {
-        &self.iface
+#[derive(Debug, Default, Clone, Copy)]
+struct MeterBucket {
+    rx_frames: u32,
+    tx_frames: u32,
+    bits: u64,
+}
+
+/// Frame and bit counts of each channel in [`METER_BUCKET`] slices of the last [`METER_BUCKETS`] buckets.
+#[derive(Debug)]
+struct BusMeter {
+    epoch: Instant,
+    /// index of the bucket holding the current time, counted from `epoch`
+    current: u64,
+    channels: Vec<[MeterBucket; METER_BUCKETS]>,
+    /// receive rate of each arbitration id (with its extended flag), per channel
+    talkers: Vec<HashMap<u32, TalkerRate>>,
+}
+
+impl Default for BusMeter {
+    fn default() -> Self {
+        Self::new(0)
     }
+}
 
-    pub async fn read(&mut self) -> RdxUsbHostResult<RdxUsbFsPacket> {
-        match self.rx_queue.pop().await {
-            Some(v) => Ok(v),
-            None => Err(RdxUsbHostError::DeviceDisconnected)
+impl BusMeter {
+    fn new(n_channels: usize) -> Self {
+        Self {
+            epoch: Instant::now(),
+            current: 0,
+            channels: vec![[MeterBucket::default(); METER_BUCKETS]; n_channels],
+            talkers: vec![HashMap::new(); n_channels],
         }
     }
 
-    pub fn try_read(&mut self) -> Option<RdxUsbFsPacket> {
-        self.rx_queue.try_pop()
+    fn record_talker(&mut self, now: Instant, channel: u8, arb_id: u32) {
+        let Some(talkers) = self.talkers.get_mut(channel as usize) else { return; };
+        let key = arb_id & (0x1fff_ffff | rdxusb_protocol::MESSAGE_ARB_ID_EXT);
+        if !talkers.contains_key(&key) && talkers.len() >= MAX_TALKERS {
+            let slowest = talkers.iter().min_by(|a, b| a.1.at(now).total_cmp(&b.1.at(now))).map(|(&id, _)| id);
+            if let Some(slowest) = slowest { talkers.remove(&slowest); }
+        }
+        let talker = talkers.entry(key).or_insert(TalkerRate { rate: 0.0, updated: now });
+        talker.rate = talker.at(now) + 1.0 / TALKER_TAU.as_secs_f64();
+        talker.updated = now;
     }
 
-    pub async fn write(&mut self, mut pkt: RdxUsbFsPacket) -> RdxUsbHostResult<()> {
-        pkt.channel = self.channel;
-        let v = Vec::from(bytemuck::bytes_of(&pkt));
-        self.iface.bulk_out(rdxusb_protocol::ENDPOINT_OUT, v).await.into_result()?;
-        Ok(())
+    fn top_talkers(&self, now: Instant, channel: u8, max: usize) -> Vec<RdxUsbTalker> {
+        let Some(talkers) = self.talkers.get(channel as usize) else { return Vec::new(); };
+        let mut top: Vec<_> = talkers.iter().map(|(&arb_id, rate)| RdxUsbTalker { arb_id, frames_per_sec: rate.at(now) }).collect();
+        top.sort_unstable_by(|a, b| b.frames_per_sec.total_cmp(&a.frames_per_sec));
+        top.truncate(max);
+        top
     }
 
-    pub async fn write_buf(&mut self, vbuf: Vec<u8>) -> RdxUsbHostResult<Vec<u8>> {
-        Ok(self.iface.bulk_out(rdxusb_protocol::ENDPOINT_OUT, vbuf).await.into_result()?.reuse())
+    /// Moves on to the bucket holding `now`, clearing the ones skipped over.
+    fn advance(&mut self, now: Instant) {
+        let idx = (now.saturating_duration_since(self.epoch).as_nanos() / METER_BUCKET.as_nanos()) as u64;
+        if idx <= self.current { return; }
+        for stale in (self.current + 1).max(idx.saturating_sub(METER_BUCKETS as u64 - 1))..=idx {
+            for buckets in &mut self.channels {
+                buckets[stale as usize % METER_BUCKETS] = MeterBucket::default();
+            }
+        }
+        self.current = idx;
+    }
+
+    fn record(&mut self, now: Instant, channel: u8, rx: bool, bits: u32) {
+        self.advance(now);
+        let current = self.current as usize % METER_BUCKETS;
+        let Some(buckets) = self.channels.get_mut(channel as usize) else { return; };
+        let bucket = &mut buckets[current];
+        if rx { bucket.rx_frames += 1; } else { bucket.tx_frames += 1; }
+        bucket.bits += bits as u64;
+    }
+
+    fn read(&mut self, now: Instant, window: Duration) -> Vec<RdxUsbChannelMeter> {
+        self.advance(now);
+        let n = (window.as_nanos().div_ceil(METER_BUCKET.as_nanos()) as u64).clamp(1, METER_BUCKETS as u64);
+        let first = self.current.saturating_sub(n - 1);
+        // the current bucket is only partly over, and the meter may be younger than the window
+        let elapsed = now.saturating_duration_since(self.epoch).saturating_sub(METER_BUCKET * first as u32).as_secs_f64();
+        self.channels.iter().map(|buckets| {
+            let sum = (first..=self.current).map(|i| buckets[i as usize % METER_BUCKETS]).fold(MeterBucket::default(), |a, b| MeterBucket {
+                rx_frames: a.rx_frames + b.rx_frames,
+                tx_frames: a.tx_frames + b.tx_frames,
+                bits: a.bits + b.bits,
+            });
+            if elapsed <= 0.0 { return RdxUsbChannelMeter::default(); }
+            RdxUsbChannelMeter {
+                rx_frames_per_sec: sum.rx_frames as f64 / elapsed,
+                tx_frames_per_sec: sum.tx_frames as f64 / elapsed,
+                bits_per_sec: sum.bits as f64 / elapsed,
+            }
+        }).collect()
+    }
+}
+
+/// Traffic rates of one channel over a recent window, see [`RdxUsbFsHost::meter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RdxUsbChannelMeter {
+    /// Frames received from the bus, including ones the channel's filters or a full rx queue dropped.
+    pub rx_frames_per_sec: f64,
+    pub tx_frames_per_sec: f64,
+    /// Nominal bits on the wire of the frames in both directions, not counting stuff bits.
+    pub bits_per_sec: f64,
+}
+
+/// Receive rate of one arbitration id, see [`RdxUsbFsHost::top_talkers`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RdxUsbTalker {
+    /// Arbitration id, with [`rdxusb_protocol::MESSAGE_ARB_ID_EXT`] set for extended ids.
+    pub arb_id: u32,
+    /// Frames per second, averaged over roughly the last second.
+    pub frames_per_sec: f64,
+}
+
+impl RdxUsbChannelMeter {
+    pub fn frames_per_sec(&self) -> f64 {
+        self.rx_frames_per_sec + self.tx_frames_per_sec
+    }
+
+    /// Estimated fraction of the bus the traffic occupies at a nominal bit rate of `bit_rate` bits per second.
+    ///
+    /// Stuff bits typically add 10-20% on top, so buses are effectively saturated somewhat below 1.0.
+    pub fn bus_load(&self, bit_rate: u32) -> f64 {
+        if bit_rate == 0 { return 0.0; }
+        self.bits_per_sec / bit_rate as f64
+    }
+}
+
+/// Bounds how many received packets a poll loop processes per second, see [`RdxUsbFsHost::set_max_rx_rate`].
+///
+/// The limit is enforced in short bursts, so a throttled poll loop yields many times a second instead of
+/// stalling for a whole second at a time.
+#[derive(Debug)]
+struct RxThrottle {
+    /// packets per second, or 0 for no limit
+    limit: Arc<AtomicU32>,
+    burst_start: tokio::time::Instant,
+    processed: u32,
+}
+
+impl RxThrottle {
+    /// Most bursts per second; lower limits get fewer, longer bursts of one packet each.
+    const MAX_BURSTS_PER_SEC: u32 = 100;
+
+    fn new() -> Self {
+        Self { limit: Arc::new(AtomicU32::new(0)), burst_start: tokio::time::Instant::now(), processed: 0 }
+    }
+
+    /// Counts `n` processed packets, sleeping out the rest of the burst once its share of the limit is used up.
+    async fn take(&mut self, n: usize, stats: &RdxUsbStatsCounters) {
+        let limit = self.limit.load(Ordering::Relaxed);
+        if limit == 0 { return; }
+        let bursts = limit.min(Self::MAX_BURSTS_PER_SEC);
+        let burst_len = Duration::from_secs(1) / bursts;
+        let now = tokio::time::Instant::now();
+        if now >= self.burst_start + burst_len {
+            self.burst_start = now;
+            self.processed = 0;
+        }
+        self.processed = self.processed.saturating_add(n as u32);
+        if self.processed >= limit / bursts {
+            stats.rx_throttled.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep_until(self.burst_start + burst_len).await;
+            self.burst_start = tokio::time::Instant::now();
+            self.processed = 0;
+        }
+    }
+}
+
+/// A device's clock as tracked by a poll loop's timestamp smoothing filter, see [`RdxUsbFsHost::set_timestamp_smoothing`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RdxUsbClockModel {
+    /// device timestamp of the last update
+    device_ns: u64,
+    /// smoothed host wall clock time matching `device_ns`
+    host_ns: u64,
+    /// host nanoseconds per device nanosecond
+    rate: f64,
+}
+
+impl RdxUsbClockModel {
+    /// Maps a device timestamp (in nanoseconds) onto the host wall clock, in nanoseconds since the UNIX epoch.
+    ///
+    /// The result includes the average delay between the device timestamping a packet and the host receiving it.
+    pub fn to_host_ns(&self, device_ns: u64) -> u64 {
+        let elapsed = device_ns as i64 - self.device_ns as i64;
+        self.host_ns.saturating_add_signed((self.rate * elapsed as f64) as i64)
+    }
+
+    /// How fast the device clock runs relative to the host's, as host nanoseconds per device nanosecond.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+}
+
+/// Second order PLL locking a device's clock onto the host wall clock, fed with the newest device timestamp of
+/// each received transfer. Device clock jitter and USB delay jitter faster than its bandwidth are filtered out.
+#[derive(Debug, Default)]
+struct ClockFilter {
+    model: Option<RdxUsbClockModel>,
+    /// updates since the loop last locked
+    updates: u32,
+}
+
+impl ClockFilter {
+    /// Updates the loop runs at [`Self::ACQUISITION_GAIN`] times its bandwidth after locking, to settle quickly.
+    const ACQUISITION_UPDATES: u32 = 64;
+    const ACQUISITION_GAIN: f64 = 10.0;
+    /// Larger phase errors are taken as the device clock jumping, e.g. after a reset, and relock the loop.
+    const MAX_ERROR_NS: i64 = 1_000_000_000;
+
+    fn update(&mut self, bandwidth_hz: f64, device_ns: u64, host_ns: u64) {
+        let relock = RdxUsbClockModel { device_ns, host_ns, rate: 1.0 };
+        let Some(model) = self.model.as_mut().filter(|m| device_ns >= m.device_ns) else {
+            self.model = Some(relock);
+            self.updates = 0;
+            return;
+        };
+        let predicted = model.to_host_ns(device_ns);
+        let error = host_ns as i64 - predicted as i64;
+        if error.abs() > Self::MAX_ERROR_NS {
+            self.model = Some(relock);
+            self.updates = 0;
+            return;
+        }
+        let bandwidth = if self.updates < Self::ACQUISITION_UPDATES { bandwidth_hz * Self::ACQUISITION_GAIN } else { bandwidth_hz };
+        let omega = 2.0 * std::f64::consts::PI * bandwidth;
+        let dt = (device_ns - model.device_ns) as f64 * 1e-9;
+        // critically damped: proportional gain 2 * zeta * omega with zeta = 1/sqrt(2), integral gain omega^2
+        let kp = (std::f64::consts::SQRT_2 * omega * dt).min(1.0);
+        model.host_ns = predicted.saturating_add_signed((kp * error as f64) as i64);
+        model.rate = (model.rate + omega * omega * dt * error as f64 * 1e-9).clamp(0.9, 1.1);
+        model.device_ns = device_ns;
+        self.updates = self.updates.saturating_add(1);
+    }
+}
+
+/// A poll loop's timestamp smoothing filter, shared so readers can map device timestamps while it runs.
+/// See [`RdxUsbFsHost::set_timestamp_smoothing`].
+#[derive(Debug, Clone, Default)]
+pub struct RdxUsbSmoothedClock(Arc<SmoothedClockInner>);
+
+#[derive(Debug, Default)]
+struct SmoothedClockInner {
+    /// loop bandwidth in Hz as f64 bits, or 0 if smoothing is off
+    bandwidth: AtomicU64,
+    filter: Mutex<ClockFilter>,
+}
+
+impl RdxUsbSmoothedClock {
+    /// Sets the filter's loop bandwidth in Hz, or 0 to turn smoothing off.
+    ///
+    /// Lower bandwidths filter out more jitter but follow changes in the device clock's rate more slowly;
+    /// 0.1 to 1 Hz suits most devices.
+    pub fn set_bandwidth(&self, bandwidth_hz: f64) {
+        let bandwidth_hz = if bandwidth_hz.is_finite() { bandwidth_hz.max(0.0) } else { 0.0 };
+        self.0.bandwidth.store(bandwidth_hz.to_bits(), Ordering::Relaxed);
+        if bandwidth_hz == 0.0 {
+            *self.0.filter.lock().unwrap() = ClockFilter::default();
+        }
+    }
+
+    pub fn bandwidth(&self) -> f64 {
+        f64::from_bits(self.0.bandwidth.load(Ordering::Relaxed))
+    }
+
+    /// The current clock model, or `None` if smoothing is off or nothing was received since it was turned on.
+    pub fn model(&self) -> Option<RdxUsbClockModel> {
+        self.0.filter.lock().unwrap().model
+    }
+
+    /// Feeds the device timestamp of a packet the poll loop just received.
+    fn sample(&self, device_ns: u64) {
+        let bandwidth = self.bandwidth();
+        if bandwidth == 0.0 { return; }
+        let host_ns = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        self.0.filter.lock().unwrap().update(bandwidth, device_ns, host_ns);
+    }
+}
+
+/// Traffic counters of one channel, see [`RdxUsbStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RdxUsbChannelStats {
+    /// Packets that passed the channel's filters and were queued for reading.
+    pub rx_packets: u64,
+    /// Packets put into bulk OUT transfers, including any in a transfer that then failed.
+    pub tx_packets: u64,
+    /// Packets that passed the channel's filters but were dropped because its rx queue was full.
+    pub rx_drops: u64,
+    /// Nominal bits on the wire of the frames seen on the bus in both directions, whether or not the channel's
+    /// filters pass them, not counting stuff bits. See [`RdxUsbChannelMeter::bits_per_sec`].
+    pub bus_bits: u64,
+}
+
+/// A host's connection to its device as last seen by its poll loop, see [`RdxUsbFsHost::connection_state`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RdxUsbConnectionState {
+    /// The poll loop is running.
+    Connected,
+    /// The poll loop stopped because the device went away.
+    Disconnected,
+    /// The poll loop stopped because of another error, described by the message.
+    Error(String),
+}
+
+impl RdxUsbConnectionState {
+    fn of_poll_exit(exit: &RdxUsbPollExit) -> Self {
+        match exit {
+            RdxUsbPollExit::Disconnected => Self::Disconnected,
+            RdxUsbPollExit::Cancelled => Self::Error(RdxUsbHostError::TransferCancelled.to_string()),
+            RdxUsbPollExit::FatalUsbError(e) => Self::Error(e.to_string()),
+        }
+    }
+}
+
+/// Why a host's poll loop stopped, see [`RdxUsbPollReport`].
+#[derive(Debug)]
+pub enum RdxUsbPollExit {
+    /// The device went away. Reopening it (see [`RdxUsbFsHost::reopen`]) can succeed once it is back.
+    Disconnected,
+    /// The in-flight transfers were cancelled, e.g. because the interface was released.
+    Cancelled,
+    /// Any other error, which usually needs a reset or the device replugged rather than a reopen.
+    FatalUsbError(RdxUsbHostError),
+}
+
+/// What [`RdxUsbFsHost::poll_with_report`] processed before it stopped, and why it did.
+#[derive(Debug)]
+pub struct RdxUsbPollReport {
+    pub exit: RdxUsbPollExit,
+    /// Packets received, including ones later dropped by filters or full queues.
+    pub packets: u64,
+    /// Bulk IN transfers completed.
+    pub transfers: u64,
+}
+
+impl RdxUsbPollReport {
+    /// The exit reason as an [`RdxUsbHostError`], for callers that only propagate errors.
+    pub fn into_error(self) -> RdxUsbHostError {
+        match self.exit {
+            RdxUsbPollExit::Disconnected => RdxUsbHostError::DeviceDisconnected,
+            RdxUsbPollExit::Cancelled => RdxUsbHostError::TransferCancelled,
+            RdxUsbPollExit::FatalUsbError(e) => e,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RdxUsbPollCounts {
+    packets: u64,
+    transfers: u64,
+}
+
+/// Changes in whether a channel is receiving traffic, see [`RdxUsbFsChannel::activity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RdxUsbChannelActivity {
+    /// Traffic started, or resumed after the channel was silent.
+    Active,
+    /// No traffic arrived for the silence timeout.
+    Silent,
+}
+
+/// Turns a channel's packets into [`RdxUsbChannelActivity`] transitions, ending when `packets` does.
+fn activity_events<S: Stream + Unpin>(packets: S, silence: Duration) -> impl Stream<Item = RdxUsbChannelActivity> {
+    futures_util::stream::unfold((packets, false), move |(mut packets, active)| async move {
+        if !active {
+            packets.next().await?;
+            return Some((RdxUsbChannelActivity::Active, (packets, true)));
+        }
+        loop {
+            match tokio::time::timeout(silence, packets.next()).await {
+                Ok(Some(_)) => continue,
+                Ok(None) => return None,
+                Err(_) => return Some((RdxUsbChannelActivity::Silent, (packets, false))),
+            }
+        }
+    })
+}
+
+/// Ownership of an interface's bulk OUT endpoint, shared between a host and its channels.
+///
+/// A write poller claims it for as long as it lives, so its batched transfers never interleave with ones sent
+/// by another poller or directly by [`RdxUsbFsChannel::write`].
+///
+/// Holds the id of the current claim, or 0 if unclaimed.
+#[derive(Debug, Clone, Default)]
+struct RdxUsbTxOwnership(Arc<AtomicU64>);
+
+/// Source of claim ids, unique across hosts so a stale claim can never match a newer one.
+static NEXT_TX_CLAIM: AtomicU64 = AtomicU64::new(1);
+
+impl RdxUsbTxOwnership {
+    fn claim(&self) -> RdxUsbHostResult<RdxUsbTxClaim> {
+        let id = NEXT_TX_CLAIM.fetch_add(1, Ordering::Relaxed);
+        match self.0.compare_exchange(0, id, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => Ok(RdxUsbTxClaim { ownership: self.0.clone(), id }),
+            Err(_) => Err(RdxUsbHostError::TxPathClaimed),
+        }
+    }
+
+    fn is_claimed(&self) -> bool {
+        self.0.load(Ordering::Acquire) != 0
+    }
+
+    /// Drops the current claim, for a reopened interface whose old write poller holds the dead one.
+    ///
+    /// The old claim then releases nothing when dropped, so it can't undo a claim made since.
+    fn release(&self) {
+        self.0.store(0, Ordering::Release);
+    }
+}
+
+/// Releases the OUT endpoint when dropped, unless [`RdxUsbTxOwnership::release`] already did.
+#[derive(Debug)]
+struct RdxUsbTxClaim {
+    ownership: Arc<AtomicU64>,
+    id: u64,
+}
+
+impl Drop for RdxUsbTxClaim {
+    fn drop(&mut self) {
+        self.ownership.compare_exchange(self.id, 0, Ordering::AcqRel, Ordering::Acquire).ok();
+    }
+}
+
+fn filters_accept(filters: &Mutex<Vec<RdxUsbIdMaskFilter>>, arb_id: u32) -> bool {
+    let filters = filters.lock().unwrap();
+    filters.is_empty() || filters.iter().any(|f| f.matches(arb_id))
+}
+
+#[derive(Debug)]
+pub enum RdxUsbHostError {
+    UnsupportedProtocol,
+    InvalidChannel,
+    NoInterface,
+    NusbError(nusb::Error),
+    TransferCancelled, 
+    EndpointStall,
+    DeviceDisconnected,
+    UsbFault,
+    TransferUnknownError,
+    DataDecodeError,
+    Timeout,
+    /// The kernel driver bound to the interface couldn't be detached under [`RdxUsbKernelDriverPolicy::Detach`].
+    KernelDriverDetach(RdxUsbDetachError),
+    /// Another transmit path already owns the interface's OUT endpoint, see [`RdxUsbFsHost::write_poller`].
+    TxPathClaimed,
+    /// No attached device has the requested id or path.
+    DeviceNotFound,
+    /// A reopened device came back with a different channel count, protocol, or endpoints.
+    DeviceChanged,
+    /// A bulk OUT transfer didn't complete within [`RdxUsbTimeouts::write`] and was cancelled.
+    WriteTimeout,
+    /// A frame sent with [`RdxUsbFsChannel::write_reliable`] lacks [`MESSAGE_ARB_ID_DEVICE`].
+    NotDeviceAddressed,
+}
+
+/// Why detaching a kernel driver failed.
+#[derive(Debug)]
+pub enum RdxUsbDetachError {
+    /// The process lacks the permissions to detach drivers, e.g. a missing udev rule on Linux.
+    PermissionDenied,
+    /// The platform doesn't support detaching kernel drivers (Windows and macOS).
+    Unsupported,
+    Other(nusb::Error),
+}
+
+impl From<nusb::Error> for RdxUsbDetachError {
+    fn from(value: nusb::Error) -> Self {
+        match value.kind() {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            std::io::ErrorKind::Unsupported => Self::Unsupported,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl Display for RdxUsbDetachError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RdxUsbDetachError::PermissionDenied => write!(f, "permission denied"),
+            RdxUsbDetachError::Unsupported => write!(f, "not supported on this platform"),
+            RdxUsbDetachError::Other(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// Whether opening a device detaches a kernel driver bound to its interface before claiming it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RdxUsbKernelDriverPolicy {
+    /// Don't try to detach. Claiming fails if a kernel driver holds the interface.
+    Skip,
+    /// Try to detach, logging failures and claiming the interface anyway.
+    Tolerate,
+    /// Try to detach, failing the open with [`RdxUsbHostError::KernelDriverDetach`] if that fails.
+    Detach,
+}
+
+impl Default for RdxUsbKernelDriverPolicy {
+    /// [`Self::Tolerate`] on Linux, where kernel drivers can actually be detached, and [`Self::Skip`] elsewhere.
+    fn default() -> Self {
+        if cfg!(target_os = "linux") { Self::Tolerate } else { Self::Skip }
+    }
+}
+
+impl From<nusb::Error> for RdxUsbHostError {
+    fn from(value: nusb::Error) -> Self {
+        Self::NusbError(value)
+    }
+}
+
+impl From<nusb::transfer::TransferError> for RdxUsbHostError {
+    fn from(value: nusb::transfer::TransferError) -> Self {
+        match value {
+            nusb::transfer::TransferError::Cancelled => RdxUsbHostError::TransferCancelled,
+            nusb::transfer::TransferError::Stall => RdxUsbHostError::EndpointStall,
+            nusb::transfer::TransferError::Disconnected => RdxUsbHostError::DeviceDisconnected,
+            nusb::transfer::TransferError::Fault => RdxUsbHostError::UsbFault,
+            nusb::transfer::TransferError::Unknown => RdxUsbHostError::TransferUnknownError,
+        }
+    }
+}
+
+impl From<bytemuck::PodCastError> for RdxUsbHostError {
+    fn from(_value: bytemuck::PodCastError) -> Self {
+        RdxUsbHostError::DataDecodeError
+    }
+}
+
+impl Display for RdxUsbHostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RdxUsbHostError::UnsupportedProtocol => write!(f, "Unsupported protocol"),
+            RdxUsbHostError::InvalidChannel => write!(f, "Invalid channel"),
+            RdxUsbHostError::NoInterface => write!(f, "No valid USB interface"),
+            RdxUsbHostError::NusbError(error) => write!(f, "nusb error: {error}"),
+            RdxUsbHostError::TransferCancelled => write!(f, "Transfer cancelled"),
+            RdxUsbHostError::EndpointStall => write!(f, "Endpoint stall"),
+            RdxUsbHostError::DeviceDisconnected => write!(f, "Device disconnected"),
+            RdxUsbHostError::UsbFault => write!(f, "USB fault"),
+            RdxUsbHostError::TransferUnknownError => write!(f, "Unknown transfer error"),
+            RdxUsbHostError::DataDecodeError => write!(f, "Received undecodable data"),
+            RdxUsbHostError::Timeout => write!(f, "Operation timed out"),
+            RdxUsbHostError::KernelDriverDetach(error) => write!(f, "Could not detach kernel driver: {error}"),
+            RdxUsbHostError::TxPathClaimed => write!(f, "The OUT endpoint is owned by a write poller"),
+            RdxUsbHostError::DeviceNotFound => write!(f, "Device not found"),
+            RdxUsbHostError::DeviceChanged => write!(f, "Reopened device has a different configuration"),
+            RdxUsbHostError::WriteTimeout => write!(f, "Bulk write timed out"),
+            RdxUsbHostError::NotDeviceAddressed => write!(f, "Reliable writes must be device-addressed"),
+        }
+    }
+}
+impl core::error::Error for RdxUsbHostError {}
+
+
+pub type RdxUsbHostResult<T> = Result<T, RdxUsbHostError>;
+
+/// Host for whichever protocol a device speaks, as picked by [`open_device`].
+pub enum RdxUsbHost {
+    Fs(RdxUsbFsHost, Vec<RdxUsbFsChannel>),
+    #[cfg(feature = "unstable-hs")]
+    Hs(RdxUsbHsHost, Vec<RdxUsbHsChannel>),
+}
+
+/// Opens the device with the host matching the protocol version it reports:
+/// [`RdxUsbHsHost`] for [`PROTOCOL_VERSION_MAJOR_HS`], and [`RdxUsbFsHost`] otherwise.
+///
+/// The high speed host is experimental and needs the `unstable-hs` feature; without it, high speed devices
+/// fail with [`RdxUsbHostError::UnsupportedProtocol`].
+pub async fn open_device(dev_info: DeviceInfo, rx_q_size: usize, timeouts: RdxUsbTimeouts) -> RdxUsbHostResult<RdxUsbHost> {
+    RdxUsbHostBuilder::new().rx_queue_size(rx_q_size).timeouts(timeouts).open(dev_info).await
+}
+
+/// Finds the attached device with the given id, e.g. one noted from an earlier [`nusb::list_devices`].
+///
+/// Ids are only valid while the device stays attached; fails with [`RdxUsbHostError::DeviceNotFound`] otherwise.
+pub fn find_device_by_id(id: DeviceId) -> RdxUsbHostResult<DeviceInfo> {
+    nusb::list_devices()?.find(|info| info.id() == id).ok_or(RdxUsbHostError::DeviceNotFound)
+}
+
+/// Finds the attached device at a platform path, as returned by [`device_path`].
+pub fn find_device_by_path(path: &str) -> RdxUsbHostResult<DeviceInfo> {
+    nusb::list_devices()?.find(|info| device_path(info) == path).ok_or(RdxUsbHostError::DeviceNotFound)
+}
+
+/// A path identifying where a device is attached, which stays the same across replugs into the same port:
+/// the sysfs path on Linux, the instance id on Windows, and the IOKit location id on macOS.
+pub fn device_path(info: &DeviceInfo) -> String {
+    #[cfg(target_os = "linux")]
+    return info.sysfs_path().display().to_string();
+    #[cfg(target_os = "windows")]
+    return info.instance_id().to_string_lossy().into_owned();
+    #[cfg(target_os = "macos")]
+    return format!("{:#010x}", info.location_id());
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    return format!("{}:{}", info.bus_id(), info.device_address());
+}
+
+/// What a host's poll loop does when a packet's queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RdxUsbOverflowPolicy {
+    /// Drop the packet, so one slow reader can't stall the others. (default)
+    #[default]
+    Drop,
+    /// Wait for the reader to make room, which stops receiving on every channel until it does.
+    Wait,
+}
+
+impl RdxUsbOverflowPolicy {
+    /// The `await_on_full` argument of the hosts' `poll` methods.
+    pub fn await_on_full(self) -> bool {
+        self == Self::Wait
+    }
+}
+
+/// Options for opening a device, for when [`open_device`]'s single queue size isn't enough.
+///
+/// Queue sizes, the in-flight transfer count, and the overflow policy are kept by the opened host, so
+/// [`RdxUsbFsHost::run`] and [`RdxUsbFsHost::tx_queue_size`] pick them up.
+#[derive(Debug, Clone)]
+pub struct RdxUsbHostBuilder {
+    rx_q_size: usize,
+    channel_q_sizes: HashMap<u8, usize>,
+    tx_q_size: usize,
+    n_transfers: usize,
+    kernel_driver: RdxUsbKernelDriverPolicy,
+    filters: HashMap<u8, Vec<RdxUsbIdMaskFilter>>,
+    overflow_policy: RdxUsbOverflowPolicy,
+    timeouts: RdxUsbTimeouts,
+    timestamp_units: Option<RdxUsbTimestampUnits>,
+    clear_halt_on_stall: bool,
+    retry_policy: RdxUsbRetryPolicy,
+}
+
+impl Default for RdxUsbHostBuilder {
+    fn default() -> Self {
+        Self {
+            rx_q_size: 256,
+            channel_q_sizes: HashMap::new(),
+            tx_q_size: 256,
+            n_transfers: 32,
+            kernel_driver: RdxUsbKernelDriverPolicy::default(),
+            filters: HashMap::new(),
+            overflow_policy: RdxUsbOverflowPolicy::default(),
+            timeouts: RdxUsbTimeouts::default(),
+            timestamp_units: None,
+            clear_halt_on_stall: true,
+            retry_policy: RdxUsbRetryPolicy::default(),
+        }
+    }
+}
+
+impl RdxUsbHostBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size of every rx queue not overridden with [`Self::channel_queue_size`], including the notification
+    /// and error frame queues.
+    pub fn rx_queue_size(mut self, size: usize) -> Self {
+        self.rx_q_size = size;
+        self
+    }
+
+    /// Size of one channel's rx and echo queues.
+    pub fn channel_queue_size(mut self, channel: u8, size: usize) -> Self {
+        self.channel_q_sizes.insert(channel, size);
+        self
+    }
+
+    /// Packets the host's write poller queues before writers have to wait, see [`RdxUsbFsHost::tx_queue_size`].
+    pub fn tx_queue_size(mut self, size: usize) -> Self {
+        self.tx_q_size = size;
+        self
+    }
+
+    /// Bulk IN transfers kept in flight by [`RdxUsbFsHost::run`].
+    pub fn in_flight_transfers(mut self, n_transfers: usize) -> Self {
+        self.n_transfers = n_transfers.max(1);
+        self
+    }
+
+    /// Whether to detach a kernel driver bound to the interface before claiming it.
+    pub fn kernel_driver_policy(mut self, policy: RdxUsbKernelDriverPolicy) -> Self {
+        self.kernel_driver = policy;
+        self
+    }
+
+    /// Host-side acceptance filters a channel starts with, see [`RdxUsbFsChannel::set_filters`].
+    pub fn filters(mut self, channel: u8, filters: &[RdxUsbIdMaskFilter]) -> Self {
+        self.filters.insert(channel, filters.to_vec());
+        self
+    }
+
+    /// What [`RdxUsbFsHost::run`] does when a queue is full.
+    pub fn overflow_policy(mut self, policy: RdxUsbOverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: RdxUsbTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Treats the device's timestamps as being in `units` regardless of what it reports,
+    /// for firmware that misreports them. By default the reported units are used.
+    pub fn timestamp_units(mut self, units: RdxUsbTimestampUnits) -> Self {
+        self.timestamp_units = Some(units);
+        self
+    }
+
+    /// Whether [`RdxUsbFsHost::poll`] clears a stalled IN endpoint and carries on (the default), see
+    /// [`RdxUsbFsHost::set_clear_halt_on_stall`].
+    pub fn clear_halt_on_stall(mut self, clear: bool) -> Self {
+        self.clear_halt_on_stall = clear;
+        self
+    }
+
+    /// How the host and its write poller retry transient transfer errors, see [`RdxUsbFsHost::set_retry_policy`].
+    pub fn retry_policy(mut self, policy: RdxUsbRetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// The timestamp units packets from a device reporting `cfg` are normalized from.
+    fn effective_timestamp_units(&self, cfg: &RdxUsbDeviceInfo) -> RdxUsbTimestampUnits {
+        self.timestamp_units.or_else(|| cfg.timestamp_units()).unwrap_or_else(|| {
+            let units = cfg.timestamp_units;
+            log::warn!(target: "rdxusb", "Device reports unknown timestamp units {units}, assuming nanoseconds");
+            RdxUsbTimestampUnits::Nanoseconds
+        })
+    }
+
+    fn channel_q_size(&self, channel: u8) -> usize {
+        self.channel_q_sizes.get(&channel).copied().unwrap_or(self.rx_q_size)
+    }
+
+    fn channel_filters(&self, channel: u8) -> RdxUsbChannelFilters {
+        Arc::new(Mutex::new(self.filters.get(&channel).cloned().unwrap_or_default()))
+    }
+
+    /// Opens the device with the host matching the protocol version it reports, like [`open_device`].
+    pub async fn open(&self, dev_info: DeviceInfo) -> RdxUsbHostResult<RdxUsbHost> {
+        with_timeout(self.timeouts.open, async {
+            let identity = RdxUsbDeviceMatch::of(&dev_info);
+            let (handle, iface, cfg) = claim_interface(dev_info, self.timeouts, self.kernel_driver).await?;
+            Ok(match cfg.protocol_version_major {
+                #[cfg(feature = "unstable-hs")]
+                PROTOCOL_VERSION_MAJOR_HS => {
+                    let (host, channels) = RdxUsbHsHost::from_claimed(handle, iface, cfg, identity, self)?;
+                    RdxUsbHost::Hs(host, channels)
+                }
+                #[cfg(not(feature = "unstable-hs"))]
+                PROTOCOL_VERSION_MAJOR_HS => return Err(RdxUsbHostError::UnsupportedProtocol),
+                _ => {
+                    let (host, channels) = RdxUsbFsHost::from_claimed(handle, iface, cfg, identity, self)?;
+                    RdxUsbHost::Fs(host, channels)
+                }
+            })
+        }).await
+    }
+
+    /// Opens a full speed device, failing with [`RdxUsbHostError::UnsupportedProtocol`] if it speaks the high speed protocol.
+    pub async fn open_fs(&self, dev_info: DeviceInfo) -> RdxUsbHostResult<(RdxUsbFsHost, Vec<RdxUsbFsChannel>)> {
+        with_timeout(self.timeouts.open, async {
+            let identity = RdxUsbDeviceMatch::of(&dev_info);
+            let (handle, iface, cfg) = claim_interface(dev_info, self.timeouts, self.kernel_driver).await?;
+            if cfg.protocol_version_major == PROTOCOL_VERSION_MAJOR_HS { return Err(RdxUsbHostError::UnsupportedProtocol); }
+            RdxUsbFsHost::from_claimed(handle, iface, cfg, identity, self)
+        }).await
+    }
+
+    /// Opens a high speed device, failing with [`RdxUsbHostError::UnsupportedProtocol`] unless it reports [`PROTOCOL_VERSION_MAJOR_HS`].
+    #[cfg(feature = "unstable-hs")]
+    pub async fn open_hs(&self, dev_info: DeviceInfo) -> RdxUsbHostResult<(RdxUsbHsHost, Vec<RdxUsbHsChannel>)> {
+        with_timeout(self.timeouts.open, async {
+            let identity = RdxUsbDeviceMatch::of(&dev_info);
+            let (handle, iface, cfg) = claim_interface(dev_info, self.timeouts, self.kernel_driver).await?;
+            if cfg.protocol_version_major != PROTOCOL_VERSION_MAJOR_HS { return Err(RdxUsbHostError::UnsupportedProtocol); }
+            RdxUsbHsHost::from_claimed(handle, iface, cfg, identity, self)
+        }).await
+    }
+}
+
+async fn get_device_info(iface: &nusb::Interface, timeout: Duration) -> RdxUsbHostResult<RdxUsbDeviceInfo> {
+    with_timeout(timeout, async {
+        let res = iface.control_in(ControlIn { 
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Interface,
+            request: RdxUsbCtrl::DeviceInfo as u8,
+            value: 1,
+            index: 0,
+            length: core::mem::size_of::<RdxUsbDeviceInfo>() as u16,
+        }).await.into_result()?;
+        Ok(bytemuck::try_from_bytes::<RdxUsbDeviceInfo>(&res.as_slice())?.clone())
+    }).await
+}
+
+/// Checks the device answers control requests with [`RdxUsbCtrl::Ping`], returning the round trip time.
+///
+/// Fails with [`RdxUsbHostError::EndpointStall`] if the device's firmware doesn't support pings; it is still responsive.
+pub async fn ping(iface: &nusb::Interface, timeout: Duration) -> RdxUsbHostResult<Duration> {
+    static NONCE: std::sync::atomic::AtomicU16 = std::sync::atomic::AtomicU16::new(0);
+    let nonce = NONCE.fetch_add(1, Ordering::Relaxed);
+    let start = Instant::now();
+    with_timeout(timeout, async {
+        let res = iface.control_in(ControlIn {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Interface,
+            request: RdxUsbCtrl::Ping as u8,
+            value: nonce,
+            index: 0,
+            length: 2,
+        }).await.into_result()?;
+        if res.as_slice() != nonce.to_le_bytes() { return Err(RdxUsbHostError::DataDecodeError); }
+        Ok(start.elapsed())
+    }).await
+}
+
+/// Sends a request after which the device reboots.
+///
+/// Devices may drop off the bus before completing the status stage, so a disconnect counts as success.
+async fn send_reboot_request(iface: &nusb::Interface, timeout: Duration, req: RdxUsbCtrl) -> RdxUsbHostResult<()> {
+    let result = with_timeout(timeout, async {
+        iface.control_out(ControlOut {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Interface,
+            request: req as u8,
+            value: 0,
+            index: 0,
+            data: &[],
+        }).await.into_result()?;
+        Ok(())
+    }).await;
+    match result {
+        Err(RdxUsbHostError::DeviceDisconnected) => Ok(()),
+        result => result,
+    }
+}
+
+/// Blinks the device's status LED for `duration` (rounded up to whole seconds) with [`RdxUsbCtrl::Identify`].
+async fn send_identify(iface: &nusb::Interface, timeout: Duration, duration: Duration) -> RdxUsbHostResult<()> {
+    let secs = duration.as_secs() + (duration.subsec_nanos() > 0) as u64;
+    with_timeout(timeout, async {
+        iface.control_out(ControlOut {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Interface,
+            request: RdxUsbCtrl::Identify as u8,
+            value: secs.min(u16::MAX as u64) as u16,
+            index: 0,
+            data: &[],
+        }).await.into_result()?;
+        Ok(())
+    }).await
+}
+
+/// Bulk endpoints of a claimed RdxUSB interface, as listed in its descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdxUsbEndpoints {
+    pub in_address: u8,
+    pub out_address: u8,
+    pub in_max_packet_size: usize,
+    pub out_max_packet_size: usize,
+}
+
+impl Default for RdxUsbEndpoints {
+    /// The fixed full speed endpoints firmware has always used, [`ENDPOINT_IN`] and [`ENDPOINT_OUT`].
+    fn default() -> Self {
+        Self { in_address: ENDPOINT_IN, out_address: ENDPOINT_OUT, in_max_packet_size: 64, out_max_packet_size: 64 }
+    }
+}
+
+impl RdxUsbEndpoints {
+    /// Finds the first bulk IN and bulk OUT endpoints of the interface's active alternate setting.
+    ///
+    /// Falls back to the [`Default`] endpoints for either direction the descriptors don't list.
+    pub fn discover(iface: &nusb::Interface) -> Self {
+        let mut endpoints = Self::default();
+        let alt_setting = iface.get_alt_setting();
+        let Some(alt) = iface.descriptors().find(|alt| alt.alternate_setting() == alt_setting) else {
+            log::debug!(target: "rdxusb", "No descriptors for the active alternate setting, using the default endpoints");
+            return endpoints;
+        };
+        let bulk = || alt.endpoints().filter(|ep| ep.transfer_type() == EndpointType::Bulk);
+        match bulk().find(|ep| ep.direction() == Direction::In) {
+            Some(ep) => (endpoints.in_address, endpoints.in_max_packet_size) = (ep.address(), ep.max_packet_size()),
+            None => log::debug!(target: "rdxusb", "No bulk IN endpoint in the descriptors, using {ENDPOINT_IN:#04x}"),
+        }
+        match bulk().find(|ep| ep.direction() == Direction::Out) {
+            Some(ep) => (endpoints.out_address, endpoints.out_max_packet_size) = (ep.address(), ep.max_packet_size()),
+            None => log::debug!(target: "rdxusb", "No bulk OUT endpoint in the descriptors, using {ENDPOINT_OUT:#04x}"),
+        }
+        endpoints
+    }
+}
+
+/// A host's claimed interface, shared with its channels so reopening the host moves them over too.
+#[derive(Clone)]
+struct RdxUsbSharedInterface(Arc<Mutex<nusb::Interface>>);
+
+impl RdxUsbSharedInterface {
+    fn new(iface: nusb::Interface) -> Self {
+        Self(Arc::new(Mutex::new(iface)))
+    }
+
+    fn get(&self) -> nusb::Interface {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn replace(&self, iface: nusb::Interface) {
+        *self.0.lock().unwrap() = iface;
+    }
+}
+
+/// The vid/pid/serial number a host was opened with. Without a serial number, any device with the vid/pid matches.
+#[derive(Debug, Clone)]
+struct RdxUsbDeviceMatch {
+    vid: u16,
+    pid: u16,
+    serial_number: Option<String>,
+}
+
+impl RdxUsbDeviceMatch {
+    fn of(info: &DeviceInfo) -> Self {
+        Self { vid: info.vendor_id(), pid: info.product_id(), serial_number: info.serial_number().map(str::to_string) }
+    }
+
+    fn matches(&self, info: &DeviceInfo) -> bool {
+        self.vid == info.vendor_id() && self.pid == info.product_id()
+            && self.serial_number.as_deref().is_none_or(|s| info.serial_number() == Some(s))
+    }
+}
+
+/// Waits for a device matching `identity` to attach and claims it, retrying every reconnect backoff.
+async fn reclaim_interface(identity: &RdxUsbDeviceMatch, timeouts: RdxUsbTimeouts, kernel_driver: RdxUsbKernelDriverPolicy) -> RdxUsbHostResult<(nusb::Device, nusb::Interface, RdxUsbDeviceInfo)> {
+    loop {
+        if let Some(info) = nusb::list_devices()?.find(|info| identity.matches(info)) {
+            match with_timeout(timeouts.open, claim_interface(info, timeouts, kernel_driver)).await {
+                Ok(claimed) => return Ok(claimed),
+                Err(e) => log::debug!(target: "rdxusb", "Could not reopen {:04x}:{:04x}, retrying: {e}", identity.vid, identity.pid),
+            }
+        }
+        tokio::time::sleep(timeouts.reconnect_backoff).await;
+    }
+}
+
+/// Opens the device, claims its RdxUSB interface, and reads its device info.
+async fn claim_interface(dev_info: DeviceInfo, timeouts: RdxUsbTimeouts, kernel_driver: RdxUsbKernelDriverPolicy) -> RdxUsbHostResult<(nusb::Device, nusb::Interface, RdxUsbDeviceInfo)> {
+
+    let Some(iface) = dev_info.interfaces().find(|iface| {
+        iface.class() == 0xff && iface.subclass() == 0x0 && iface.protocol() == 0x0
+    }) else { return Err(RdxUsbHostError::NoInterface); };
+
+    let iface_idx = iface.interface_number();
+
+    let mut handle: RdxUsbHostResult<nusb::Device> = Err(RdxUsbHostError::UsbFault);
+    for _ in 0..3 {
+        handle = match dev_info.open() {
+            Ok(o) => { Ok(o) }
+            Err(e) => {
+                // windows needs a sleep retry
+                #[cfg(windows)]
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                Err(e.into())
+            }
+        };
+        if handle.is_ok() { break; }
+    }
+    let handle = handle?;
+
+    match kernel_driver {
+        RdxUsbKernelDriverPolicy::Skip => {}
+        RdxUsbKernelDriverPolicy::Tolerate => if let Err(e) = handle.detach_kernel_driver(iface_idx) {
+            log::debug!(target: "rdxusb", "Could not detach kernel driver, claiming anyway: {}", RdxUsbDetachError::from(e));
+        }
+        RdxUsbKernelDriverPolicy::Detach => handle.detach_kernel_driver(iface_idx)
+            .map_err(|e| RdxUsbHostError::KernelDriverDetach(e.into()))?,
+    }
+    let iface = handle.claim_interface(iface_idx)?;
+    let cfg = get_device_info(&iface, timeouts.control).await?;
+    Ok((handle, iface, cfg))
+}
+
+/// Reads descriptors exactly as a device presents them, for diagnostic tools.
+///
+/// This only opens the device, without claiming any interface, so it also works on devices another host has open.
+/// Reads are blocking control transfers.
+#[derive(Clone)]
+pub struct RdxUsbDescriptorReader {
+    device: nusb::Device,
+    timeout: Duration,
+}
+
+impl RdxUsbDescriptorReader {
+    pub fn new(device: nusb::Device, timeout: Duration) -> Self {
+        Self { device, timeout }
+    }
+
+    pub fn open(dev_info: &DeviceInfo, timeout: Duration) -> RdxUsbHostResult<Self> {
+        Ok(Self::new(dev_info.open()?, timeout))
+    }
+
+    /// Reads a descriptor of any type, e.g. [`nusb::descriptors::DESCRIPTOR_TYPE_DEVICE`].
+    ///
+    /// `language_id` only matters for string descriptors and should be 0 otherwise.
+    pub fn raw_descriptor(&self, desc_type: u8, desc_index: u8, language_id: u16) -> RdxUsbHostResult<Vec<u8>> {
+        Ok(self.device.get_descriptor(desc_type, desc_index, language_id, self.timeout)?)
+    }
+
+    /// The active configuration descriptor, followed by all of its interface, endpoint and class-specific descriptors.
+    ///
+    /// This is the copy cached by the OS, so it works even where the OS doesn't allow requesting it from the device.
+    pub fn configuration_descriptors(&self) -> RdxUsbHostResult<Vec<u8>> {
+        let config = match self.device.active_configuration() {
+            Ok(config) => config,
+            Err(_) => self.device.configurations().next().ok_or(RdxUsbHostError::NoInterface)?,
+        };
+        Ok(config.to_vec())
+    }
+
+    /// The language ids the device has string descriptors in, e.g. [`nusb::descriptors::language_id::US_ENGLISH`].
+    pub fn string_languages(&self) -> RdxUsbHostResult<Vec<u16>> {
+        Ok(self.device.get_string_descriptor_supported_languages(self.timeout)?.collect())
+    }
+
+    /// Reads string descriptor `desc_index` in the given language.
+    pub fn string_descriptor(&self, desc_index: std::num::NonZeroU8, language_id: u16) -> RdxUsbHostResult<String> {
+        Ok(self.device.get_string_descriptor(desc_index, language_id, self.timeout)?)
+    }
+}
+
+/// Number of clock samples the [`RdxUsbClock`] estimate is fitted over.
+const CLOCK_SAMPLES: usize = 32;
+
+/// Translates device timestamps ([`RdxUsbPacket::timestamp_ns`]) into host time.
+///
+/// The device clock is modeled as running at a constant rate relative to the host's monotonic clock,
+/// fitted by least squares over the most recent samples taken by an [`RdxUsbClockPoller`].
+/// Each sample assumes the device read its clock halfway through the control transfer.
+#[derive(Debug, Clone)]
+pub struct RdxUsbClock(Arc<Mutex<ClockEstimate>>);
+
+#[derive(Debug)]
+struct ClockEstimate {
+    /// host instant and wall clock time all host times are relative to
+    epoch: Instant,
+    wall_epoch: SystemTime,
+    /// (device ns, host ns since epoch)
+    samples: VecDeque<(u64, u64)>,
+    /// host ns = intercept + skew * (device ns - device ns of the first sample)
+    intercept: f64,
+    skew: f64,
+}
+
+impl ClockEstimate {
+    fn refit(&mut self) {
+        let Some(&(x0, _)) = self.samples.front() else { return; };
+        let n = self.samples.len() as f64;
+        let points = self.samples.iter().map(|&(x, y)| (x.wrapping_sub(x0) as i64 as f64, y as f64));
+        let (mean_x, mean_y) = points.clone().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x / n, sy + y / n));
+        let (sxy, sxx) = points.fold((0.0, 0.0), |(sxy, sxx), (x, y)| (sxy + (x - mean_x) * (y - mean_y), sxx + (x - mean_x) * (x - mean_x)));
+        // a single sample (or samples all at the same device time) can't tell skew apart from offset
+        self.skew = if sxx > 0.0 { sxy / sxx } else { 1.0 };
+        self.intercept = mean_y - self.skew * mean_x;
+    }
+
+    fn host_ns(&self, device_ns: u64) -> Option<f64> {
+        let &(x0, _) = self.samples.front()?;
+        Some(self.intercept + self.skew * (device_ns.wrapping_sub(x0) as i64 as f64))
+    }
+}
+
+impl RdxUsbClock {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(ClockEstimate {
+            epoch: Instant::now(),
+            wall_epoch: SystemTime::now(),
+            samples: VecDeque::with_capacity(CLOCK_SAMPLES),
+            intercept: 0.0,
+            skew: 1.0,
+        })))
+    }
+
+    /// Adds a sample of the device clock reading `device_ns` during a request sent at `sent` and answered at `received`.
+    pub fn add_sample(&self, sent: Instant, received: Instant, device_ns: u64) {
+        let mut estimate = self.0.lock().unwrap();
+        let midpoint = sent + (received - sent) / 2;
+        let host_ns = midpoint.saturating_duration_since(estimate.epoch).as_nanos() as u64;
+        if estimate.samples.len() == CLOCK_SAMPLES {
+            estimate.samples.pop_front();
+        }
+        estimate.samples.push_back((device_ns, host_ns));
+        estimate.refit();
+    }
+
+    /// Has at least one sample been taken?
+    pub fn is_synced(&self) -> bool {
+        !self.0.lock().unwrap().samples.is_empty()
+    }
+
+    /// Estimated device clock rate relative to the host, in parts per million (positive if the device runs fast).
+    pub fn skew_ppm(&self) -> f64 {
+        (1.0 / self.0.lock().unwrap().skew - 1.0) * 1e6
+    }
+
+    /// The host monotonic time a device timestamp corresponds to, or `None` before the first sample.
+    pub fn to_host_instant(&self, device_ns: u64) -> Option<Instant> {
+        let estimate = self.0.lock().unwrap();
+        let host_ns = estimate.host_ns(device_ns)?;
+        if host_ns >= 0.0 {
+            estimate.epoch.checked_add(Duration::from_nanos(host_ns as u64))
+        } else {
+            estimate.epoch.checked_sub(Duration::from_nanos(-host_ns as u64))
+        }
+    }
+
+    /// The wall clock time a device timestamp corresponds to, or `None` before the first sample.
+    ///
+    /// Wall clock time is derived from the monotonic estimate, so later adjustments of the system clock aren't reflected.
+    pub fn to_system_time(&self, device_ns: u64) -> Option<SystemTime> {
+        let estimate = self.0.lock().unwrap();
+        let host_ns = estimate.host_ns(device_ns)?;
+        if host_ns >= 0.0 {
+            estimate.wall_epoch.checked_add(Duration::from_nanos(host_ns as u64))
+        } else {
+            estimate.wall_epoch.checked_sub(Duration::from_nanos(-host_ns as u64))
+        }
+    }
+}
+
+/// Periodically samples the device clock with [`RdxUsbCtrl::GetTime`] to keep an [`RdxUsbClock`] up to date.
+pub struct RdxUsbClockPoller {
+    iface: nusb::Interface,
+    control_timeout: Duration,
+    period: Duration,
+    clock: RdxUsbClock,
+    timestamp_units: RdxUsbTimestampUnits,
+}
+
+impl RdxUsbClockPoller {
+    pub fn new(iface: nusb::Interface, control_timeout: Duration, period: Duration) -> (Self, RdxUsbClock) {
+        let clock = RdxUsbClock::new();
+        (Self { iface, control_timeout, period, clock: clock.clone(), timestamp_units: RdxUsbTimestampUnits::Nanoseconds }, clock)
+    }
+
+    /// Takes one sample of the device clock.
+    pub async fn sample(&mut self) -> RdxUsbHostResult<()> {
+        let sent = Instant::now();
+        let time = with_timeout(self.control_timeout, async {
+            let res = self.iface.control_in(ControlIn {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Interface,
+                request: RdxUsbCtrl::GetTime as u8,
+                value: 0,
+                index: 0,
+                length: core::mem::size_of::<RdxUsbDeviceTime>() as u16,
+            }).await.into_result()?;
+            Ok(*bytemuck::try_from_bytes::<RdxUsbDeviceTime>(res.as_slice())?)
+        }).await?;
+        self.clock.add_sample(sent, Instant::now(), self.timestamp_units.to_ns(time.timestamp_ns));
+        Ok(())
+    }
+
+    /// Samples the device clock every period until a request fails.
+    pub async fn poll(&mut self) -> RdxUsbHostResult<()> {
+        let mut interval = tokio::time::interval(self.period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.sample().await?;
+        }
+    }
+}
+
+/// Packets a write poller sends on its own at fixed intervals, like SocketCAN's broadcast manager.
+///
+/// Cloning gives another handle to the same schedules. See [`RdxUsbFsWritePoller::scheduler`].
+pub struct RdxUsbScheduler<P>(Arc<SchedulerShared<P>>);
+
+impl<P> Clone for RdxUsbScheduler<P> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+struct SchedulerShared<P> {
+    entries: Mutex<Vec<ScheduleEntry<P>>>,
+    /// wakes the write poller when schedules change
+    changed: tokio::sync::Notify,
+    next_id: AtomicU64,
+}
+
+struct ScheduleEntry<P> {
+    id: u64,
+    packet: P,
+    period: Duration,
+    next: tokio::time::Instant,
+}
+
+impl<P: Copy> RdxUsbScheduler<P> {
+    fn new() -> Self {
+        Self(Arc::new(SchedulerShared {
+            entries: Mutex::new(Vec::new()),
+            changed: tokio::sync::Notify::new(),
+            next_id: AtomicU64::new(0),
+        }))
+    }
+
+    /// Sends `packet` every `period`, starting right away, until the returned handle is cancelled or dropped.
+    pub fn schedule_periodic(&self, packet: P, period: Duration) -> RdxUsbScheduleHandle<P> {
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        let period = period.max(Duration::from_micros(100));
+        self.0.entries.lock().unwrap().push(ScheduleEntry { id, packet, period, next: tokio::time::Instant::now() });
+        self.0.changed.notify_one();
+        RdxUsbScheduleHandle { id, scheduler: self.clone() }
+    }
+
+    /// When the next scheduled packet is due, if any are scheduled.
+    fn next_due(&self) -> Option<tokio::time::Instant> {
+        self.0.entries.lock().unwrap().iter().map(|e| e.next).min()
+    }
+
+    /// Collects every packet due at `now` and moves its schedule on by a period.
+    fn take_due(&self, now: tokio::time::Instant, due: &mut Vec<P>) {
+        for entry in self.0.entries.lock().unwrap().iter_mut().filter(|e| e.next <= now) {
+            due.push(entry.packet);
+            entry.next += entry.period;
+            // after a stall, skip the missed periods instead of sending a burst
+            if entry.next <= now {
+                entry.next = now + entry.period;
+            }
+        }
+    }
+
+    fn with_entry(&self, id: u64, f: impl FnOnce(&mut ScheduleEntry<P>)) {
+        if let Some(entry) = self.0.entries.lock().unwrap().iter_mut().find(|e| e.id == id) {
+            f(entry);
+        }
+        self.0.changed.notify_one();
+    }
+}
+
+/// A periodic transmission created by [`RdxUsbScheduler::schedule_periodic`]. Dropping it cancels the schedule.
+pub struct RdxUsbScheduleHandle<P: Copy> {
+    id: u64,
+    scheduler: RdxUsbScheduler<P>,
+}
+
+impl<P: Copy> RdxUsbScheduleHandle<P> {
+    /// Replaces the packet sent from the next period on. The timing of the schedule is unaffected.
+    pub fn update(&self, packet: P) {
+        self.scheduler.with_entry(self.id, |entry| entry.packet = packet);
+    }
+
+    /// Changes the period, taking effect after the next transmission.
+    pub fn set_period(&self, period: Duration) {
+        self.scheduler.with_entry(self.id, |entry| entry.period = period.max(Duration::from_micros(100)));
+    }
+
+    /// Stops sending the packet.
+    pub fn cancel(self) {}
+}
+
+impl<P: Copy> Drop for RdxUsbScheduleHandle<P> {
+    fn drop(&mut self) {
+        self.scheduler.0.entries.lock().unwrap().retain(|e| e.id != self.id);
+        self.scheduler.0.changed.notify_one();
+    }
+}
+
+/// A write poller's [`RdxUsbRetryPolicy`], with a copy of the transfer in flight to resend if it fails.
+#[derive(Default)]
+struct OutRetry {
+    policy: RdxUsbRetryPolicy,
+    spare: Vec<u8>,
+}
+
+/// Sends one OUT transfer for the write pollers, returning the buffer for reuse.
+///
+/// Some firmware revisions stall the OUT endpoint after a malformed packet; with `clear_halt_on_stall`, the halt is
+/// cleared and the stalled transfer dropped instead of failing. Transient errors are retried as `retry` allows.
+async fn bulk_out(iface: &nusb::Interface, endpoint: u8, mut buffer: Vec<u8>, timeout: Duration, clear_halt_on_stall: bool, retry: &mut OutRetry, stats: &RdxUsbStatsCounters) -> RdxUsbHostResult<Vec<u8>> {
+    let (capacity, len) = (buffer.capacity(), buffer.len());
+    let mut retries = 0;
+    let submitted = Instant::now();
+    loop {
+        if retry.policy.max_retries > 0 {
+            // nusb keeps the buffer of a failed transfer but not its contents
+            retry.spare.clear();
+            retry.spare.extend_from_slice(&buffer);
+        }
+        let completion = with_write_timeout(timeout, async { Ok(iface.bulk_out(endpoint, buffer).await) }).await
+            .inspect_err(|_| stats.record_usb_error())?;
+        let Err(e) = completion.status else {
+            stats.tx_latency.record(submitted.elapsed());
+            stats.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+            return Ok(completion.data.reuse());
+        };
+        stats.record_usb_error();
+        match e {
+            e if retry.policy.retries(e, retries) => {
+                stats.transfer_retries.fetch_add(1, Ordering::Relaxed);
+                log::debug!(target: "rdxusb", "OUT transfer failed with {e}, resending it");
+                tokio::time::sleep(retry.policy.backoff(retries)).await;
+                retries += 1;
+                buffer = std::mem::replace(&mut retry.spare, completion.data.reuse());
+            }
+            nusb::transfer::TransferError::Stall if clear_halt_on_stall => {
+                log::debug!(target: "rdxusb", "OUT endpoint stalled, clearing halt and dropping the transfer");
+                iface.clear_halt(endpoint)?;
+                return Ok(Vec::with_capacity(capacity));
+            }
+            e => return Err(e.into()),
+        }
+    }
+}
+
+/// A source of bulk IN transfers for a host's poll loop, see [`RdxUsbGenericHost::poll_through`].
+///
+/// Hosts read their device's IN endpoint through this, so their recovery from stalls, errors, and wedged
+/// transfers can also be driven by wrappers like [`RdxUsbFaultInjector`] or by transports that aren't USB at all.
+pub trait RdxUsbInTransport: Send {
+    /// Queues a transfer of up to `len` bytes, reusing `buf`'s allocation.
+    fn submit(&mut self, buf: Vec<u8>, len: usize);
+    /// Number of transfers submitted and not yet returned by [`Self::next_complete`].
+    fn pending(&self) -> usize;
+    /// Waits for the oldest pending transfer to complete. Dropping the future must not lose the completion.
+    fn next_complete(&mut self) -> impl Future<Output = nusb::transfer::Completion<Vec<u8>>> + Send;
+    /// Cancels every pending transfer. They still complete, with [`nusb::transfer::TransferError::Cancelled`].
+    fn cancel_all(&mut self);
+    /// Clears a halt condition on the endpoint after a stall.
+    fn clear_halt(&mut self) -> RdxUsbHostResult<()>;
+}
+
+impl RdxUsbInTransport for nusb::transfer::Queue<RequestBuffer> {
+    fn submit(&mut self, buf: Vec<u8>, len: usize) {
+        nusb::transfer::Queue::submit(self, RequestBuffer::reuse(buf, len));
+    }
+
+    fn pending(&self) -> usize {
+        nusb::transfer::Queue::pending(self)
+    }
+
+    fn next_complete(&mut self) -> impl Future<Output = nusb::transfer::Completion<Vec<u8>>> + Send {
+        nusb::transfer::Queue::next_complete(self)
+    }
+
+    fn cancel_all(&mut self) {
+        nusb::transfer::Queue::cancel_all(self);
+    }
+
+    fn clear_halt(&mut self) -> RdxUsbHostResult<()> {
+        Ok(nusb::transfer::Queue::clear_halt(self)?)
+    }
+}
+
+/// Consecutive IN endpoint stalls a poll loop recovers from before giving up on the device.
+const MAX_IN_STALL_RECOVERIES: u32 = 3;
+
+/// Restarts a bulk IN queue: cancels the transfers still in flight, clears the endpoint's halt if it stalled,
+/// and resubmits `n_transfers` transfers of `len` bytes.
+async fn restart_in_queue(queue: &mut impl RdxUsbInTransport, n_transfers: usize, len: usize, clear_halt: bool) -> RdxUsbHostResult<()> {
+    queue.cancel_all();
+    while queue.pending() > 0 {
+        // the cancelled transfers complete with errors, which are expected here
+        let _ = queue.next_complete().await;
+    }
+    if clear_halt {
+        queue.clear_halt()?;
+    }
+    while queue.pending() < n_transfers {
+        queue.submit(Vec::new(), len);
+    }
+    Ok(())
+}
+
+/// Waits for the next bulk IN transfer to complete, or returns `None` if none did within `watchdog`.
+/// A zero `watchdog` waits indefinitely.
+async fn next_in_complete(queue: &mut impl RdxUsbInTransport, watchdog: Duration) -> Option<nusb::transfer::Completion<Vec<u8>>> {
+    if watchdog.is_zero() {
+        return Some(queue.next_complete().await);
+    }
+    // next_complete is cancel safe, so timing out loses no completions
+    tokio::time::timeout(watchdog, queue.next_complete()).await.ok()
+}
+
+/// The next packet already waiting in a write poller's queues, taking priority writers first.
+fn try_next_queued<S: Stream + Unpin>(priority: &mut futures_util::stream::SelectAll<S>, normal: &mut futures_util::stream::SelectAll<S>) -> Option<S::Item> {
+    futures_util::FutureExt::now_or_never(priority.next()).flatten()
+        .or_else(|| futures_util::FutureExt::now_or_never(normal.next()).flatten())
+}
+
+/// Waits until `deadline`, or forever if there is none.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+impl RdxUsbFsHost {
+    /// Opens the device with the [`DeviceInfo`] and specified rx queue buffer size.
+    /// Returns a usb device handle
+    pub async fn open_device(dev_info: DeviceInfo, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbFsChannel>)> {
+        Self::open_device_with_timeouts(dev_info, rx_q_size, RdxUsbTimeouts::default()).await
+    }
+
+    /// Opens the device like [`Self::open_device`], but with non-default timeouts.
+    ///
+    /// Fails with [`RdxUsbHostError::UnsupportedProtocol`] if the device speaks the high speed protocol; see [`open_device`].
+    pub async fn open_device_with_timeouts(dev_info: DeviceInfo, rx_q_size: usize, timeouts: RdxUsbTimeouts) -> RdxUsbHostResult<(Self, Vec<RdxUsbFsChannel>)> {
+        RdxUsbHostBuilder::new().rx_queue_size(rx_q_size).timeouts(timeouts).open_fs(dev_info).await
+    }
+
+    /// Opens exactly the device with the given id, see [`find_device_by_id`].
+    pub async fn open_by_id(id: DeviceId, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbFsChannel>)> {
+        Self::open_device(find_device_by_id(id)?, rx_q_size).await
+    }
+
+    /// Opens exactly the device attached at a platform path, see [`device_path`].
+    pub async fn open_by_path(path: &str, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbFsChannel>)> {
+        Self::open_device(find_device_by_path(path)?, rx_q_size).await
+    }
+}
+
+#[cfg(feature = "unstable-hs")]
+impl RdxUsbHsHost {
+    /// Opens the device with the [`DeviceInfo`] and specified rx queue buffer size.
+    /// Returns a usb device handle
+    pub async fn open_device(dev_info: DeviceInfo, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbHsChannel>)> {
+        Self::open_device_with_timeouts(dev_info, rx_q_size, RdxUsbTimeouts::default()).await
+    }
+
+    /// Opens the device like [`Self::open_device`], but with non-default timeouts.
+    ///
+    /// Fails with [`RdxUsbHostError::UnsupportedProtocol`] unless the device reports [`PROTOCOL_VERSION_MAJOR_HS`].
+    pub async fn open_device_with_timeouts(dev_info: DeviceInfo, rx_q_size: usize, timeouts: RdxUsbTimeouts) -> RdxUsbHostResult<(Self, Vec<RdxUsbHsChannel>)> {
+        RdxUsbHostBuilder::new().rx_queue_size(rx_q_size).timeouts(timeouts).open_hs(dev_info).await
+    }
+
+    /// Opens exactly the device with the given id, see [`find_device_by_id`].
+    pub async fn open_by_id(id: DeviceId, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbHsChannel>)> {
+        Self::open_device(find_device_by_id(id)?, rx_q_size).await
+    }
+
+    /// Opens exactly the device attached at a platform path, see [`device_path`].
+    pub async fn open_by_path(path: &str, rx_q_size: usize) -> RdxUsbHostResult<(Self, Vec<RdxUsbHsChannel>)> {
+        Self::open_device(find_device_by_path(path)?, rx_q_size).await
+    }
+
+    /// Does the device frame its IN transfers with an [`RdxUsbHsTransferHeader`]?
+    pub fn framed(&self) -> bool {
+        self.rx.framed
+    }
+
+    /// Counter of framed transfers [`Self::poll`] dropped because their header or checksum didn't match.
+    pub fn framing_error_counter(&self) -> Arc<AtomicU64> {
+        self.rx.framing_errors.clone()
+    }
+
+    /// Counter of framed transfers missing from the sequence, as seen by [`Self::poll`].
+    pub fn lost_transfer_counter(&self) -> Arc<AtomicU64> {
+        self.rx.lost_transfers.clone()
+    }
+}
+
+impl<P: RdxUsbHostPacket> RdxUsbGenericHost<P> {
+    fn from_claimed(handle: nusb::Device, iface: nusb::Interface, cfg: RdxUsbDeviceInfo, identity: RdxUsbDeviceMatch, opts: &RdxUsbHostBuilder) -> RdxUsbHostResult<(Self, Vec<RdxUsbChannel<P>>)> {
+        let endpoints = RdxUsbEndpoints::discover(&iface);
+        if !P::endpoints_supported(&endpoints) {
+            log::trace!(target: "rdxusb", "Device endpoints ({} byte IN, {} byte OUT) don't suit its protocol", endpoints.in_max_packet_size, endpoints.out_max_packet_size);
+            return Err(RdxUsbHostError::UnsupportedProtocol);
+        }
+        let icount = cfg.n_channels;
+        let timeouts = opts.timeouts;
+
+        let (rx, notifications, errors) = RxPath::new(&cfg, opts);
+        let mut dev = RdxUsbGenericHost {
+            device: handle,
+            iface: iface.clone(),
+            shared_iface: RdxUsbSharedInterface::new(iface.clone()),
+            identity,
+            kernel_driver: opts.kernel_driver,
+            endpoints,
+            tx_ownership: RdxUsbTxOwnership::default(),
+            timeouts,
+            n_channels: icount,
+            device_info: cfg,
+            notifications: Some(notifications),
+            errors: Some(errors),
+            n_transfers: opts.n_transfers,
+            overflow_policy: opts.overflow_policy,
+            tx_q_size: opts.tx_q_size,
+            connection: tokio::sync::watch::Sender::new(RdxUsbConnectionState::Connected),
+            faults: None,
+            rx,
+        };
+
+        let mut v = Vec::with_capacity(icount as usize);
+        for i in 0..=icount {
+            let filters = opts.channel_filters(i);
+            let subscribers = RdxUsbChannelSubscribers::default();
+            let latest = RdxUsbChannelLatest::default();
+            let (cons, echo_cons) = dev.rx.add_channel(opts.channel_q_size(i), filters.clone(), subscribers.clone(), latest.clone());
+            v.push(RdxUsbChannel {
+                iface: dev.shared_iface.clone(),
+                endpoint: endpoints.out_address,
+                tx_ownership: dev.tx_ownership.clone(),
+                control_timeout: timeouts.control,
+                write_timeout: timeouts.write,
+                channel: i,
+                rx_queue: cons,
+                filters,
+                subscribers,
+                latest,
+                echo_queue: echo_cons,
+                tx_buffer: Vec::with_capacity(P::SIZE),
+                reliable_seq: 0,
+                stats: dev.rx.stats.clone(),
+            });
+        }
+
+        Ok((dev, v))
+    }
+
+    /// This drives the event loop.
+    /// 
+    /// **n_transfers** determines the maximum number of transfers to be flighted at a time.
+    pub async fn poll(&mut self, n_transfers: usize, await_on_full: bool) -> RdxUsbHostResult<()> {
+        Err(self.poll_with_report(n_transfers, await_on_full).await.into_error())
+    }
+
+    /// Drives the event loop like [`Self::poll`], but returns why it stopped along with what it processed.
+    pub async fn poll_with_report(&mut self, n_transfers: usize, await_on_full: bool) -> RdxUsbPollReport {
+        let queue = self.in_transport();
+        match self.faults {
+            Some(faults) => self.poll_through(&mut RdxUsbFaultInjector::new(queue, faults), n_transfers, await_on_full).await,
+            None => self.poll_through(&mut { queue }, n_transfers, await_on_full).await,
+        }
+    }
+
+    /// Drives the event loop like [`Self::poll_with_report`], reading transfers from `transport` instead of the
+    /// device's IN endpoint, e.g. to pass them through an [`RdxUsbFaultInjector`] or replay them.
+    pub async fn poll_through(&mut self, transport: &mut impl RdxUsbInTransport, n_transfers: usize, await_on_full: bool) -> RdxUsbPollReport {
+        self.connection.send_if_modified(|state| {
+            let changed = *state != RdxUsbConnectionState::Connected;
+            *state = RdxUsbConnectionState::Connected;
+            changed
+        });
+        let mut counts = RdxUsbPollCounts::default();
+        let exit = self.rx.poll(transport, n_transfers, self.timeouts.transfer_watchdog, await_on_full, &mut counts).await;
+        self.connection.send_replace(RdxUsbConnectionState::of_poll_exit(&exit));
+        RdxUsbPollReport { exit, packets: counts.packets, transfers: counts.transfers }
+    }
+
+    /// A queue of bulk IN transfers on the device's IN endpoint, which [`Self::poll`] reads through.
+    pub fn in_transport(&self) -> nusb::transfer::Queue<RequestBuffer> {
+        self.iface.bulk_in_queue(self.endpoints.in_address)
+    }
+
+    /// Has [`Self::poll`] inject `faults` into the transfers it reads, or stops it with `None` (the default).
+    ///
+    /// Applies from the next call to [`Self::poll`], whose injector starts over from the seed, so a session replays
+    /// the same faults given the same traffic.
+    pub fn set_fault_injection(&mut self, faults: Option<RdxUsbFaults>) {
+        self.faults = faults;
+    }
+
+    /// Drives the event loop like [`Self::poll`], with the in-flight transfer count and overflow policy
+    /// the host was opened with (see [`RdxUsbHostBuilder`]).
+    pub async fn run(&mut self) -> RdxUsbHostResult<()> {
+        self.poll(self.n_transfers, self.overflow_policy.await_on_full()).await
+    }
+
+    /// Like [`Self::run`], but returns the report from [`Self::poll_with_report`].
+    pub async fn run_with_report(&mut self) -> RdxUsbPollReport {
+        self.poll_with_report(self.n_transfers, self.overflow_policy.await_on_full()).await
+    }
+
+    /// The tx queue size the host was opened with, for [`Self::write_poller`].
+    pub fn tx_queue_size(&self) -> usize {
+        self.tx_q_size
+    }
+
+    /// Sets what [`Self::poll`] does with packets carrying flag bits this host doesn't know.
+    pub fn set_unknown_flag_policy(&mut self, policy: RdxUsbUnknownFlagPolicy) {
+        self.rx.unknown_flag_policy = policy;
+    }
+
+    /// Sets whether [`Self::poll`] recovers when the device stalls its IN endpoint, as some firmware does after
+    /// resetting its USB stack.
+    ///
+    /// When enabled (the default), the transfers in flight are cancelled, the halt is cleared and the transfers
+    /// are resubmitted, keeping the session alive. A poll loop stalled several times in a row without receiving
+    /// anything in between still fails with [`RdxUsbHostError::EndpointStall`], as it does when disabled.
+    pub fn set_clear_halt_on_stall(&mut self, clear: bool) {
+        self.rx.clear_halt_on_stall = clear;
+    }
+
+    /// Sets how [`Self::poll`] and write pollers created after this call retry transient transfer errors.
+    pub fn set_retry_policy(&mut self, policy: RdxUsbRetryPolicy) {
+        self.rx.retry_policy = policy;
+    }
+
+    /// Takes the stream of device notifications (packets sent on [`NOTIFICATION_CHANNEL`]).
+    ///
+    /// Notifications are buffered from the moment the device is opened. Returns `None` if already taken.
+    pub fn take_notifications(&mut self) -> Option<RdxUsbNotifications<P>> {
+        self.notifications.take()
+    }
+
+    /// Takes the stream of error frames (packets with [`rdxusb_protocol::MESSAGE_FLAG_ERR`] set) reported on any channel.
+    ///
+    /// Error frames are buffered from the moment the device is opened. Returns `None` if already taken.
+    pub fn take_error_frames(&mut self) -> Option<RdxUsbErrorFrames<P>> {
+        self.errors.take()
+    }
+
+    /// Counter of completed bulk IN transfers, incremented by [`Self::poll`].
+    ///
+    /// Watching this from another task is a cheap way to tell whether the IN pipe is still alive.
+    pub fn rx_transfer_counter(&self) -> Arc<AtomicU64> {
+        self.rx.rx_transfers.clone()
+    }
+
+    /// Counter of received packets whose dlc overran their data buffer and was clamped by [`Self::poll`].
+    pub fn dlc_violation_counter(&self) -> Arc<AtomicU64> {
+        self.rx.dlc_violations.clone()
+    }
+
+    /// Bounds how many received packets [`Self::poll`] processes per second, or 0 (the default) for no limit.
+    ///
+    /// Once the limit is reached the poll loop sleeps until it may continue, so a device flooding the bus can't
+    /// keep a core busy. Packets it doesn't get to back up on the device, which drops them once its buffers fill.
+    pub fn set_max_rx_rate(&self, packets_per_sec: u32) {
+        self.rx.rx_throttle.limit.store(packets_per_sec, Ordering::Relaxed);
+    }
+
+    /// The limit set with [`Self::set_max_rx_rate`], shared so another task can change it while [`Self::poll`] runs.
+    pub fn max_rx_rate(&self) -> Arc<AtomicU32> {
+        self.rx.rx_throttle.limit.clone()
+    }
+
+    /// Counter of times [`Self::poll`] paused at its rx rate limit, also reported by [`Self::stats`].
+    pub fn rx_throttle_counter(&self) -> Arc<AtomicU64> {
+        self.rx.stats.rx_throttled.clone()
+    }
+
+    /// Turns on smoothing of the device's timestamps with the given loop bandwidth in Hz, or off with 0 (the default).
+    ///
+    /// [`Self::poll`] then locks a PLL onto the device clock, which maps device timestamps onto the host wall clock
+    /// without the jitter of the device clock and of USB delays, see [`RdxUsbSmoothedClock`]. Packets keep their
+    /// device timestamps either way.
+    pub fn set_timestamp_smoothing(&self, bandwidth_hz: f64) {
+        self.rx.clock.set_bandwidth(bandwidth_hz);
+    }
+
+    /// The filter set up with [`Self::set_timestamp_smoothing`], shared so another task can use it while [`Self::poll`] runs.
+    pub fn smoothed_clock(&self) -> RdxUsbSmoothedClock {
+        self.rx.clock.clone()
+    }
+
+    /// Watches the host's connection as [`Self::poll`] sees it: [`RdxUsbConnectionState::Connected`] while it runs,
+    /// and why it stopped once it returns, so applications can react without inspecting errors from reads.
+    ///
+    /// The watch closes once the host is dropped.
+    pub fn connection_state(&self) -> tokio::sync::watch::Receiver<RdxUsbConnectionState> {
+        self.connection.subscribe()
+    }
+
+    /// Issues a USB port reset to the device.
+    ///
+    /// The device will disconnect and re-enumerate, so this host must be reopened afterwards with [`Self::reopen`].
+    pub fn reset(&self) -> RdxUsbHostResult<()> {
+        Ok(self.device.reset()?)
+    }
+
+    /// Waits for the device to come back after [`Self::poll`] fails, e.g. with [`RdxUsbHostError::DeviceDisconnected`],
+    /// then claims it again.
+    ///
+    /// The device is matched by the vid/pid/serial number it was opened with. Channels keep their queues, filters,
+    /// and subscriptions, and keep working on the new interface, as do notifications, error frames, and bridging;
+    /// readers only see a gap in traffic. Pollers from [`Self::write_poller`] and [`Self::clock_poller`] hold the old interface and must be made again.
+    /// The old write poller's claim on the OUT endpoint is released, so [`Self::write_poller`] can be called again
+    /// while it is still being dropped.
+    ///
+    /// Fails with [`RdxUsbHostError::Timeout`] if the device doesn't return within `timeout`, and with
+    /// [`RdxUsbHostError::DeviceChanged`] if it comes back with a different channel count, protocol, or endpoints.
+    pub async fn reopen(&mut self, timeout: Duration) -> RdxUsbHostResult<()> {
+        let (device, iface, cfg) = tokio::time::timeout(timeout, reclaim_interface(&self.identity, self.timeouts, self.kernel_driver)).await
+            .map_err(|_| RdxUsbHostError::Timeout)??;
+        if cfg.n_channels != self.n_channels || cfg.protocol_version_major != self.device_info.protocol_version_major
+            || RdxUsbEndpoints::discover(&iface) != self.endpoints {
+            return Err(RdxUsbHostError::DeviceChanged);
+        }
+        self.shared_iface.replace(iface.clone());
+        self.tx_ownership.release();
+        self.device = device;
+        self.iface = iface;
+        self.device_info = cfg;
+        self.rx.framed = P::framed(&cfg);
+        self.rx.last_seq = None;
+        Ok(())
+    }
+
+    /// Clears a halt (stall) condition on one of the device's bulk endpoints, see [`Self::endpoints`].
+    ///
+    /// The poll loop and write pollers do this on their own after a stall unless told not to; see
+    /// [`Self::set_clear_halt_on_stall`] and [`RdxUsbWritePoller::set_clear_halt_on_stall`].
+    pub fn clear_halt(&self, endpoint: u8) -> RdxUsbHostResult<()> {
+        Ok(self.iface.clear_halt(endpoint)?)
+    }
+
+    /// The bulk endpoints found in the interface's descriptors when the device was opened.
+    pub fn endpoints(&self) -> RdxUsbEndpoints {
+        self.endpoints
+    }
+
+    /// Reads the device's raw USB descriptors, using this host's control timeout.
+    pub fn descriptor_reader(&self) -> RdxUsbDescriptorReader {
+        RdxUsbDescriptorReader::new(self.device.clone(), self.timeouts.control)
+    }
+
+    /// The device info read when the device was opened. See [`Self::get_device_config`] to read it again.
+    pub fn device_info(&self) -> RdxUsbDeviceInfo {
+        self.device_info
+    }
+
+    pub async fn get_device_config(&self) -> RdxUsbHostResult<RdxUsbDeviceInfo> {
+        get_device_info(&self.iface, self.timeouts.control).await
+    }
+
+    /// Pings the device within the control timeout, see [`ping`].
+    pub async fn ping(&self) -> RdxUsbHostResult<Duration> {
+        ping(&self.iface, self.timeouts.control).await
+    }
+
+    pub fn interface(&self) -> &nusb::Interface {
+        &self.iface
+    }
+
+    /// Reboots the device's firmware with [`RdxUsbCtrl::Reset`], e.g. to recover a wedged device.
+    ///
+    /// Unlike [`Self::reset`], which only resets the USB port, this restarts the firmware itself.
+    /// The host stops working once the device drops off the bus; reopen it after it re-enumerates.
+    pub async fn reboot(&self) -> RdxUsbHostResult<()> {
+        send_reboot_request(&self.iface, self.timeouts.control, RdxUsbCtrl::Reset).await
+    }
+
+    /// Reboots the device into its bootloader with [`RdxUsbCtrl::EnterBootloader`] to start a firmware update.
+    pub async fn enter_bootloader(&self) -> RdxUsbHostResult<()> {
+        send_reboot_request(&self.iface, self.timeouts.control, RdxUsbCtrl::EnterBootloader).await
+    }
+
+    /// Blinks the device's status LED for `duration` with [`RdxUsbCtrl::Identify`], to find which physical unit
+    /// has this serial number. A zero duration stops blinking.
+    pub async fn identify(&self, duration: Duration) -> RdxUsbHostResult<()> {
+        send_identify(&self.iface, self.timeouts.control, duration).await
+    }
+
+    /// The timeouts this host was opened with, or changed to with [`Self::set_transfer_watchdog`].
+    pub fn timeouts(&self) -> RdxUsbTimeouts {
+        self.timeouts
+    }
+
+    /// Sets how long [`Self::poll`] waits for an IN transfer before resubmitting them, or zero to wait indefinitely.
+    /// See [`RdxUsbTimeouts::transfer_watchdog`].
+    pub fn set_transfer_watchdog(&mut self, watchdog: Duration) {
+        self.timeouts.transfer_watchdog = watchdog;
+    }
+
+    /// Creates the write poller that owns the device's OUT endpoint, sending what its writers queue.
+    ///
+    /// Only one write poller can exist per device at a time; while it does, creating another fails with
+    /// [`RdxUsbHostError::TxPathClaimed`], as do direct channel writes. Use
+    /// [`RdxUsbWritePoller::add_writer`] for more writers.
+    pub fn write_poller(&self, n_packets: usize) -> RdxUsbHostResult<(RdxUsbWritePoller<P>, RdxUsbWriter<P>)> {
+        let (mut poller, writer) = RdxUsbWritePoller::new(self.iface.clone(), n_packets, self.tx_ownership.claim()?);
+        poller.stats = self.rx.stats.clone();
+        poller.endpoint = self.endpoints.out_address;
+        poller.write_timeout = self.timeouts.write;
+        poller.retry.policy = self.rx.retry_policy;
+        Ok((poller, writer))
+    }
+
+    /// A snapshot of the traffic counters of this host, its channels, and its write pollers.
+    pub fn stats(&self) -> RdxUsbStats {
+        self.rx.stats.snapshot()
+    }
+
+    /// A handle for reading [`Self::stats`] that stays valid while the host is busy polling.
+    pub fn stats_reader(&self) -> RdxUsbStatsReader {
+        RdxUsbStatsReader(self.rx.stats.clone())
+    }
+
+    /// Frame rates and bus traffic of each channel over the last `window`, indexed by channel, for diagnosing
+    /// saturated buses.
+    ///
+    /// Windows are rounded up to 100ms and capped at 10 seconds. Both received frames (whether or not the channel's
+    /// filters pass them) and frames sent from this host count; see [`RdxUsbChannelMeter::bus_load`].
+    pub fn meter(&self, window: Duration) -> Vec<RdxUsbChannelMeter> {
+        self.rx.stats.meter.lock().unwrap().read(Instant::now(), window)
+    }
+
+    /// Opens an observer that receives a copy of every packet passing through this host in both directions, for
+    /// logging, sniffing, and debugging tools.
+    ///
+    /// Received packets are seen before acceptance filters and queues, and sent packets as they are handed to the
+    /// device by any channel or write poller. A tap that falls more than `capacity` packets behind misses packets
+    /// instead of holding up the host, so it never affects other readers. Dropping it unregisters it.
+    pub fn register_tap(&self, capacity: usize) -> RdxUsbTap {
+        let (tx, rx) = AsyncHeapRb::new(capacity.max(1)).split();
+        self.rx.stats.taps.lock().unwrap().0.push(tx);
+        RdxUsbTap(rx)
+    }
+
+    /// The `max` arbitration ids received most often on `channel` lately, busiest first, to find which messages
+    /// load a bus.
+    ///
+    /// Rates decay exponentially with a time constant of one second. Up to 512 ids are tracked per channel;
+    /// beyond that the slowest is forgotten to make room for a new one.
+    pub fn top_talkers(&self, channel: u8, max: usize) -> Vec<RdxUsbTalker> {
+        self.rx.stats.meter.lock().unwrap().top_talkers(Instant::now(), channel, max)
+    }
+
+    /// Creates a poller sampling the device clock every `period`, and the [`RdxUsbClock`] it keeps up to date.
+    pub fn clock_poller(&self, period: Duration) -> (RdxUsbClockPoller, RdxUsbClock) {
+        let (mut poller, clock) = RdxUsbClockPoller::new(self.iface.clone(), self.timeouts.control, period);
+        poller.timestamp_units = self.rx.timestamp_units;
+        (poller, clock)
+    }
+
+    /// The units the device's timestamps are converted from. Received packets always carry nanoseconds.
+    pub fn timestamp_units(&self) -> RdxUsbTimestampUnits {
+        self.rx.timestamp_units
+    }
+
+    /// Forwards received frames matching any of `rules` into `writer` from within [`Self::poll`].
+    ///
+    /// `writer` should come from a separate [`Self::write_poller`] that is polled alongside this host.
+    /// Frames that don't fit in the writer's queue are dropped.
+    pub fn set_bridge(&mut self, rules: RdxUsbBridgeRules, writer: RdxUsbWriter<P>) {
+        self.rx.bridge = Some((rules, writer));
+    }
+
+}
+
+impl<P: RdxUsbHostPacket> RxPath<P> {
+    /// Makes an rx path for a device with `cfg`, along with the readers of its notifications and error frames.
+    /// Channels are added with [`Self::add_channel`].
+    fn new(cfg: &RdxUsbDeviceInfo, opts: &RdxUsbHostBuilder) -> (Self, RdxUsbNotifications<P>, RdxUsbErrorFrames<P>) {
+        let icount = cfg.n_channels as usize;
+        let (notification_prod, notification_cons) = AsyncHeapRb::new(opts.rx_q_size).split();
+        let (error_prod, error_cons) = AsyncHeapRb::new(opts.rx_q_size).split();
+        let rx = Self {
+            rx_transfers: Arc::new(AtomicU64::new(0)),
+            dlc_violations: Arc::new(AtomicU64::new(0)),
+            framing_errors: Arc::new(AtomicU64::new(0)),
+            lost_transfers: Arc::new(AtomicU64::new(0)),
+            last_seq: None,
+            framed: P::framed(cfg),
+            rx_queue: Vec::with_capacity(icount + 1),
+            rx_filters: Vec::with_capacity(icount + 1),
+            rx_subscribers: Vec::with_capacity(icount + 1),
+            rx_latest: Vec::with_capacity(icount + 1),
+            rx_batch: Vec::with_capacity(icount + 1),
+            echo_queue: Vec::with_capacity(icount + 1),
+            notification_queue: notification_prod,
+            error_queue: error_prod,
+            bridge: None,
+            unknown_flag_policy: RdxUsbUnknownFlagPolicy::default(),
+            clear_halt_on_stall: opts.clear_halt_on_stall,
+            retry_policy: opts.retry_policy,
+            timestamp_units: opts.effective_timestamp_units(cfg),
+            warned_unknown_flags: false,
+            stats: RdxUsbStatsCounters::new(icount + 1),
+            rx_throttle: RxThrottle::new(),
+            clock: RdxUsbSmoothedClock::default(),
+        };
+        (rx, RdxUsbNotifications(notification_cons), RdxUsbErrorFrames(error_cons))
+    }
+
+    /// Adds the queues of the next channel, returning the reading ends of its rx and echo queues.
+    fn add_channel(&mut self, q_size: usize, filters: RdxUsbChannelFilters, subscribers: RdxUsbChannelSubscribers<P>, latest: RdxUsbChannelLatest<P>)
+        -> (PacketCons<P>, PacketCons<P>) {
+        let (prod, cons) = AsyncHeapRb::new(q_size).split();
+        let (echo_prod, echo_cons) = AsyncHeapRb::new(q_size).split();
+        self.rx_queue.push(prod);
+        self.rx_filters.push(filters);
+        self.rx_subscribers.push(subscribers);
+        self.rx_latest.push(latest);
+        self.rx_batch.push(Vec::with_capacity(P::TRANSFER_SIZE / P::SIZE));
+        self.echo_queue.push(echo_prod);
+        (cons, echo_cons)
+    }
+
+    /// Reads transfers from `transport` until it fails, returning why.
+    async fn poll(&mut self, transport: &mut impl RdxUsbInTransport, n_transfers: usize, watchdog: Duration, await_on_full: bool, counts: &mut RdxUsbPollCounts) -> RdxUsbPollExit {
+        match self.poll_transfers(transport, n_transfers, watchdog, await_on_full, counts).await {
+            Err(RdxUsbHostError::DeviceDisconnected) => RdxUsbPollExit::Disconnected,
+            Err(RdxUsbHostError::TransferCancelled) => RdxUsbPollExit::Cancelled,
+            Err(e) => RdxUsbPollExit::FatalUsbError(e),
+        }
+    }
+
+    async fn poll_transfers(&mut self, read_queue: &mut impl RdxUsbInTransport, n_transfers: usize, watchdog: Duration, await_on_full: bool, counts: &mut RdxUsbPollCounts) -> RdxUsbHostResult<std::convert::Infallible> {
+        while read_queue.pending() < n_transfers {
+            read_queue.submit(Vec::new(), P::TRANSFER_SIZE);
+        }
+        let mut stalls = 0;
+        let mut retries = 0;
+        loop {
+            let Some(completion) = next_in_complete(read_queue, watchdog).await else {
+                self.stats.transfer_watchdog_trips.fetch_add(1, Ordering::Relaxed);
+                log::debug!(target: "rdxusb", "No IN transfer completed in {:?}, resubmitting transfers", watchdog);
+                restart_in_queue(read_queue, n_transfers, P::TRANSFER_SIZE, false).await?;
+                continue;
+            };
+            let buf = match completion.into_result() {
+                Ok(buf) => buf,
+                Err(nusb::transfer::TransferError::Stall) if self.clear_halt_on_stall && stalls < MAX_IN_STALL_RECOVERIES => {
+                    self.stats.record_usb_error();
+                    stalls += 1;
+                    log::debug!(target: "rdxusb", "IN endpoint stalled, clearing halt and resubmitting transfers");
+                    restart_in_queue(read_queue, n_transfers, P::TRANSFER_SIZE, true).await?;
+                    continue;
+                }
+                Err(e) if self.retry_policy.retries(e, retries) => {
+                    self.stats.record_usb_error();
+                    self.stats.transfer_retries.fetch_add(1, Ordering::Relaxed);
+                    log::debug!(target: "rdxusb", "IN transfer failed with {e}, resubmitting it");
+                    tokio::time::sleep(self.retry_policy.backoff(retries)).await;
+                    retries += 1;
+                    read_queue.submit(Vec::new(), P::TRANSFER_SIZE);
+                    continue;
+                }
+                Err(e) => {
+                    self.stats.record_usb_error();
+                    return Err(e.into());
+                }
+            };
+            stalls = 0;
+            retries = 0;
+            self.stats.rx_bytes.fetch_add(buf.len() as u64, Ordering::Relaxed);
+            self.rx_transfers.fetch_add(1, Ordering::Relaxed);
+            counts.transfers += 1;
+            let Some((seq, packets)) = P::split_transfer(&buf, self.framed) else {
+                self.framing_errors.fetch_add(1, Ordering::Relaxed);
+                log::trace!(target: "rdxusb", "Dropped a {} byte transfer with a bad header or checksum", buf.len());
+                read_queue.submit(buf, P::TRANSFER_SIZE);
+                continue;
+            };
+            if let Some(seq) = seq {
+                if let Some(last) = self.last_seq {
+                    let lost = seq.wrapping_sub(last).wrapping_sub(1);
+                    if lost != 0 {
+                        self.lost_transfers.fetch_add(lost as u64, Ordering::Relaxed);
+                        log::trace!(target: "rdxusb", "Lost {lost} transfers before sequence number {seq}");
+                    }
+                }
+                self.last_seq = Some(seq);
+            }
+            self.rx_throttle.take(packets.len(), &self.stats).await;
+            counts.packets += packets.len() as u64;
+            if let Some(last) = packets.last() {
+                // the newest packet of a transfer waited the least for it to complete
+                self.clock.sample(self.timestamp_units.to_ns(last.timestamp_ns()));
+            }
+            for pkt in packets {
+                let mut pkt = *pkt;
+                if pkt.sanitize() {
+                    self.dlc_violations.fetch_add(1, Ordering::Relaxed);
+                    log::trace!(target: "rdxusb", "Clamped out of range dlc on a packet from channel {}", pkt.channel());
+                }
+                pkt.set_timestamp_ns(self.timestamp_units.to_ns(pkt.timestamp_ns()));
+                if !accept_flags(self.unknown_flag_policy, &mut self.warned_unknown_flags, pkt.flags()) {
+                    continue;
+                }
+                self.stats.tap_rx(pkt);
+                let (channel, arb_id) = (pkt.channel(), pkt.arb_id());
+                if channel == NOTIFICATION_CHANNEL {
+                    // notifications aren't bus traffic, so they skip bridging and the channel queues.
+                    if await_on_full {
+                        self.notification_queue.push(pkt).await.ok();
+                    } else {
+                        self.notification_queue.try_push(pkt).ok();
+                    }
+                    continue;
+                }
+                if pkt.error_frame() {
+                    // neither are error frames
+                    if await_on_full {
+                        self.error_queue.push(pkt).await.ok();
+                    } else {
+                        self.error_queue.try_push(pkt).ok();
+                    }
+                    continue;
+                }
+                if pkt.echo() {
+                    // echoes are our own frames coming back, not bus traffic.
+                    if let Some(queue) = self.echo_queue.get_mut(channel as usize) {
+                        if await_on_full {
+                            queue.push(pkt).await.ok();
+                        } else {
+                            queue.try_push(pkt).ok();
+                        }
+                    }
+                    continue;
+                }
+                self.stats.record_bus_rx(channel, arb_id, frame_bits(arb_id, pkt.dlc(), pkt.flags()));
+                if let Some((rules, writer)) = &mut self.bridge {
+                    for rule in rules.lock().unwrap().iter().filter(|r| r.matches(channel, arb_id)) {
+                        let mut fwd = pkt;
+                        fwd.set_channel(rule.dst_channel);
+                        writer.try_send(fwd);
+                    }
+                }
+                if (channel as usize) < self.rx_queue.len() && filters_accept(&self.rx_filters[channel as usize], arb_id) {
+                    self.rx_batch[channel as usize].push(pkt);
+                    publish_to_subscribers(&self.rx_subscribers[channel as usize], arb_id, pkt);
+                    if let Some(latest) = self.rx_latest[channel as usize].lock().unwrap().as_mut() {
+                        latest.insert(pkt.id(), pkt);
+                    }
+                }
+            }
+            self.flush_rx_batches(await_on_full).await;
+
+            read_queue.submit(buf, P::TRANSFER_SIZE);
+        }
+    }
+
+    /// Pushes the packets of a transfer into their rx queues.
+    ///
+    /// Pushing a transfer's packets for each channel at once publishes the queue's write index and wakes its reader
+    /// once per transfer instead of once per packet, which keeps the poller and reader cores from trading the index's
+    /// cache line back and forth at high frame rates.
+    async fn flush_rx_batches(&mut self, await_on_full: bool) {
+        for (channel, (batch, queue)) in self.rx_batch.iter_mut().zip(self.rx_queue.iter_mut()).enumerate() {
+            if batch.is_empty() { continue; }
+            let queued = if await_on_full {
+                queue.push_exact(batch).await.map_or_else(|pushed| pushed, |_| batch.len())
+            } else {
+                queue.push_slice(batch)
+            };
+            self.stats.record_rx_batch(channel as u8, batch.len(), queued);
+            batch.clear();
+        }
+    }
+}
+
+pub struct RdxUsbWriter<P>(<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Prod);
+
+impl<P: RdxUsbHostPacket> RdxUsbWriter<P> {
+    pub fn try_send(&mut self, packet: P) -> Option<P> {
+        self.0.try_push(packet).err()
+    }
+    pub async fn send(&mut self, packet: P) -> Result<(), P> {
+        self.0.push(packet).await
+    }
+
+    /// Queues as many of `packets` as there is room for, in order, and returns how many were queued.
+    pub fn write_many(&mut self, packets: &[P]) -> usize {
+        self.0.push_slice(packets)
+    }
+}
+
+/// Receives error frames reported by the device, see [`rdxusb_protocol::MESSAGE_FLAG_ERR`].
+pub struct RdxUsbErrorFrames<P>(<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons);
+
+impl<P: RdxUsbHostPacket> RdxUsbErrorFrames<P> {
+    pub async fn read(&mut self) -> RdxUsbHostResult<P> {
+        match self.0.pop().await {
+            Some(v) => Ok(v),
+            None => Err(RdxUsbHostError::DeviceDisconnected)
+        }
+    }
+
+    pub fn try_read(&mut self) -> Option<P> {
+        self.0.try_pop()
+    }
+}
+
+/// Receives notifications originating from the device itself, see [`NOTIFICATION_CHANNEL`].
+pub struct RdxUsbNotifications<P>(<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons);
+
+impl<P: RdxUsbHostPacket> RdxUsbNotifications<P> {
+    pub async fn read(&mut self) -> RdxUsbHostResult<P> {
+        match self.0.pop().await {
+            Some(v) => Ok(v),
+            None => Err(RdxUsbHostError::DeviceDisconnected)
+        }
+    }
+
+    pub fn try_read(&mut self) -> Option<P> {
+        self.0.try_pop()
+    }
+}
+
+/// Sends queued packets, packing up to [`Self::set_max_batch`] of those already waiting into each bulk transfer.
+pub struct RdxUsbWritePoller<P> {
+    iface: nusb::Interface,
+    /// bulk OUT endpoint address
+    endpoint: u8,
+    /// the queue of every writer feeding this poller
+    tx_queues: futures_util::stream::SelectAll<<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons>,
+    /// queues of writers from [`Self::add_priority_writer`], drained before `tx_queues`
+    priority_queues: futures_util::stream::SelectAll<<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons>,
+    _claim: RdxUsbTxClaim,
+    max_batch: usize,
+    scheduler: RdxUsbScheduler<P>,
+    clear_halt_on_stall: bool,
+    write_timeout: Duration,
+    retry: OutRetry,
+    stats: Arc<RdxUsbStatsCounters>,
+}
+
+impl<P: RdxUsbHostPacket> RdxUsbWritePoller<P> {
+    fn new(iface: nusb::Interface, n_packets: usize, claim: RdxUsbTxClaim) -> (Self, RdxUsbWriter<P>) {
+        let (prod, cons) = AsyncHeapRb::new(n_packets).split();
+        let tx_queues = futures_util::stream::select_all([cons]);
+
+        (Self { iface, endpoint: ENDPOINT_OUT, tx_queues, priority_queues: Default::default(), _claim: claim, max_batch: P::DEFAULT_OUT_BATCH, scheduler: RdxUsbScheduler::new(), clear_halt_on_stall: true, write_timeout: RdxUsbTimeouts::default().write, retry: OutRetry::default(), stats: Arc::default() }, RdxUsbWriter(prod))
+    }
+
+    /// Adds another writer with its own queue of `n_packets`, e.g. for bridged frames.
+    ///
+    /// Queues are served in turn, so a burst on one writer doesn't starve the others.
+    pub fn add_writer(&mut self, n_packets: usize) -> RdxUsbWriter<P> {
+        let (prod, cons) = AsyncHeapRb::new(n_packets).split();
+        self.tx_queues.push(cons);
+        RdxUsbWriter(prod)
+    }
+
+    /// Adds a writer whose packets jump ahead of everything queued by the other writers and the scheduler,
+    /// for frames like safety heartbeats that mustn't wait behind bulk telemetry.
+    ///
+    /// Priority writers are served in turn among themselves. A priority writer that never lets up starves the rest.
+    pub fn add_priority_writer(&mut self, n_packets: usize) -> RdxUsbWriter<P> {
+        let (prod, cons) = AsyncHeapRb::new(n_packets).split();
+        self.priority_queues.push(cons);
+        RdxUsbWriter(prod)
+    }
+
+    /// Periodic transmissions sent by [`Self::poll`], in between queued packets.
+    pub fn scheduler(&self) -> RdxUsbScheduler<P> {
+        self.scheduler.clone()
+    }
+
+    /// Sends up to `packets` already-queued packets per bulk transfer, at most [`RdxUsbHostPacket::MAX_OUT_BATCH`].
+    ///
+    /// Full speed pollers send one per transfer by default. Every full speed packet is exactly one max packet, so
+    /// the device sees the same USB packets either way; batching only saves the per-transfer overhead on the host
+    /// when bursting. High speed pollers fill whole transfers by default.
+    pub fn set_max_batch(&mut self, packets: usize) {
+        self.max_batch = packets.clamp(1, P::MAX_OUT_BATCH);
+    }
+
+    /// Whether a stalled OUT endpoint is cleared and polling continues (the default), or [`Self::poll`] fails
+    /// with [`RdxUsbHostError::EndpointStall`]. The transfer that stalled is dropped either way.
+    pub fn set_clear_halt_on_stall(&mut self, clear: bool) {
+        self.clear_halt_on_stall = clear;
+    }
+
+    /// How long a bulk OUT transfer may take before it is cancelled and [`Self::poll`] fails with
+    /// [`RdxUsbHostError::WriteTimeout`], or zero to wait indefinitely. Defaults to the host's [`RdxUsbTimeouts::write`].
+    pub fn set_write_timeout(&mut self, timeout: Duration) {
+        self.write_timeout = timeout;
+    }
+
+    /// How failed bulk OUT transfers are retried. Defaults to the host's [`RdxUsbGenericHost::set_retry_policy`].
+    pub fn set_retry_policy(&mut self, policy: RdxUsbRetryPolicy) {
+        self.retry.policy = policy;
+    }
+
+    pub async fn poll(&mut self) -> Result<(), RdxUsbHostError> {
+        let mut buffer = Vec::with_capacity(P::SIZE * self.max_batch);
+        let mut due = Vec::new();
+        // runs until every writer is dropped
+        while !(self.tx_queues.is_empty() && self.priority_queues.is_empty()) {
+            buffer.clear();
+            tokio::select! {
+                biased;
+                msg = self.priority_queues.next(), if !self.priority_queues.is_empty() => {
+                    let Some(msg) = msg else { continue; };
+                    buffer.extend_from_slice(bytemuck::bytes_of(&msg));
+                }
+                _ = sleep_until(self.scheduler.next_due()) => {
+                    due.clear();
+                    self.scheduler.take_due(tokio::time::Instant::now(), &mut due);
+                    // more packets may be due than fit in one transfer
+                    for packets in due.chunks(P::MAX_OUT_BATCH) {
+                        buffer.clear();
+                        buffer.extend_from_slice(bytemuck::cast_slice(packets));
+                        self.stats.record_tx(packets.iter().map(|&p| p.into()));
+                        buffer = bulk_out(&self.iface, self.endpoint, buffer, self.write_timeout, self.clear_halt_on_stall, &mut self.retry, &self.stats).await?;
+                    }
+                    continue;
+                }
+                _ = self.scheduler.0.changed.notified() => { continue; }
+                msg = self.tx_queues.next(), if !self.tx_queues.is_empty() => {
+                    let Some(msg) = msg else { continue; };
+                    buffer.extend_from_slice(bytemuck::bytes_of(&msg));
+                }
+            }
+            while buffer.len() < P::SIZE * self.max_batch {
+                let Some(msg) = try_next_queued(&mut self.priority_queues, &mut self.tx_queues) else { break; };
+                buffer.extend_from_slice(bytemuck::bytes_of(&msg));
+            }
+            if buffer.is_empty() { continue; }
+            self.stats.record_tx(bytemuck::cast_slice::<u8, P>(&buffer).iter().map(|&p| p.into()));
+            buffer = bulk_out(&self.iface, self.endpoint, buffer, self.write_timeout, self.clear_halt_on_stall, &mut self.retry, &self.stats).await?;
+        }
+        Ok(())
+    }
+}
+
+
+pub struct RdxUsbChannel<P> {
+    iface: RdxUsbSharedInterface,
+    /// bulk OUT endpoint address
+    endpoint: u8,
+    tx_ownership: RdxUsbTxOwnership,
+    control_timeout: Duration,
+    write_timeout: Duration,
+    channel: u8,
+    rx_queue: <AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons,
+    filters: RdxUsbChannelFilters,
+    subscribers: RdxUsbChannelSubscribers<P>,
+    latest: RdxUsbChannelLatest<P>,
+    echo_queue: <AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons,
+    /// reused by [`Self::write`] so steady traffic doesn't allocate per packet
+    tx_buffer: Vec<u8>,
+    /// sequence number of the last [`Self::write_reliable`] frame
+    reliable_seq: u8,
+    stats: Arc<RdxUsbStatsCounters>,
+}
+
+impl<P: RdxUsbHostPacket> RdxUsbChannel<P> {
+    pub async fn control_in_struct<T: AnyBitPattern>(&self, req: RdxUsbCtrl) -> RdxUsbHostResult<T> {
+        with_timeout(self.control_timeout, async {
+            let res = self.iface.get().control_in(ControlIn {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Interface,
+                request: req as u8,
+                value: self.channel as u16,
+                index: 0,
+                length: core::mem::size_of::<T>() as u16,
+            }).await.into_result()?;
+            Ok(*bytemuck::try_from_bytes::<T>(res.as_slice())?)
+        }).await
+    }
+
+    pub async fn control_out_struct(&self, req: RdxUsbCtrl, data: &[u8]) -> RdxUsbHostResult<()> {
+        with_timeout(self.control_timeout, async {
+            self.iface.get().control_out(ControlOut {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Interface,
+                request: req as u8,
+                value: self.channel as u16,
+                index: 0,
+                data,
+            }).await.into_result()?;
+            Ok(())
+        }).await
+    }
+
+    /// The claimed interface, which changes if the host is reopened.
+    pub fn interface(&self) -> nusb::Interface {
+        self.iface.get()
+    }
+
+    pub async fn read(&mut self) -> RdxUsbHostResult<P> {
+        match self.rx_queue.pop().await {
+            Some(v) => Ok(v),
+            None => Err(RdxUsbHostError::DeviceDisconnected)
+        }
+    }
+
+    pub fn try_read(&mut self) -> Option<P> {
+        self.rx_queue.try_pop()
+    }
+
+    /// Number of received packets waiting to be read.
+    pub fn queued(&self) -> usize {
+        self.rx_queue.occupied_len()
+    }
+
+    /// Opens another reader of this channel, which receives every packet from now on independently of this one.
+    ///
+    /// Subscriptions see the same traffic as the channel (after its acceptance filters). One that falls more than
+    /// `capacity` packets behind misses packets instead of holding up the host. Dropping it unsubscribes.
+    pub fn subscribe(&self, capacity: usize) -> RdxUsbSubscription<P> {
+        self.add_subscriber(None, capacity)
+    }
+
+    /// Opens another reader of this channel that only receives frames whose arbitration id matches `id` on the
+    /// bits set in `mask`, so each subsystem of an application can get its own feed of the frames it cares about.
+    ///
+    /// Frames are matched on the host as they arrive and this doesn't change what the channel itself receives;
+    /// otherwise it behaves like [`Self::subscribe`].
+    pub fn subscribe_id(&self, id: u32, mask: u32, capacity: usize) -> RdxUsbSubscription<P> {
+        self.add_subscriber(Some(RdxUsbIdMaskFilter { id, mask }), capacity)
+    }
+
+    fn add_subscriber(&self, filter: Option<RdxUsbIdMaskFilter>, capacity: usize) -> RdxUsbSubscription<P> {
+        let (tx, rx) = AsyncHeapRb::new(capacity.max(1)).split();
+        self.subscribers.lock().unwrap().push(RdxUsbChannelSubscriber { filter, tx });
+        RdxUsbSubscription(rx)
+    }
+
+    /// Starts or stops keeping the most recent frame of each arbitration id this channel receives, see [`Self::latest`].
+    ///
+    /// The cache sees the same traffic as the channel (after its acceptance filters), and reading it doesn't take
+    /// packets from the channel. Stopping it forgets every cached frame.
+    pub fn set_latest_cache(&self, enabled: bool) {
+        let mut latest = self.latest.lock().unwrap();
+        match (enabled, latest.is_some()) {
+            (true, false) => *latest = Some(HashMap::new()),
+            (false, true) => *latest = None,
+            _ => (),
+        }
+    }
+
+    /// The most recent frame received with arbitration id `id` (without flag bits), or `None` if none was
+    /// received since the cache was enabled with [`Self::set_latest_cache`].
+    ///
+    /// Status frames often only matter for their latest value; the frame's timestamp tells how old it is.
+    pub fn latest(&self, id: u32) -> Option<P> {
+        self.latest.lock().unwrap().as_ref()?.get(&id).copied()
+    }
+
+    /// Reports when this channel goes silent for `silence` and when traffic resumes, for applications that only
+    /// care whether the bus is alive rather than about particular frames.
+    ///
+    /// The first event is [`RdxUsbChannelActivity::Active`] once traffic is seen. Only frames passing the channel's
+    /// filters count. Reading it doesn't take packets from the channel, and the stream ends once the host is dropped.
+    pub fn activity(&self, silence: Duration) -> impl Stream<Item = RdxUsbChannelActivity> {
+        activity_events(self.subscribe(1), silence)
+    }
+
+    /// Reads the next echo of a frame sent on this channel with [`rdxusb_protocol::MESSAGE_FLAG_ECHO`] set.
+    ///
+    /// Its timestamp is when the device actually transmitted the frame, so it measures bus latency and confirms
+    /// the frame won arbitration.
+    pub async fn read_echo(&mut self) -> RdxUsbHostResult<P> {
+        match self.echo_queue.pop().await {
+            Some(v) => Ok(v),
+            None => Err(RdxUsbHostError::DeviceDisconnected)
+        }
+    }
+
+    pub fn try_read_echo(&mut self) -> Option<P> {
+        self.echo_queue.try_pop()
+    }
+
+    /// Waits for a packet, then returns it along with the packets already queued behind it, up to `max` in total.
+    ///
+    /// At high message rates this wakes the reading task once per batch instead of once per packet.
+    pub async fn read_batch(&mut self, max: usize) -> RdxUsbHostResult<Vec<P>> {
+        if max == 0 { return Ok(Vec::new()); }
+        let first = self.read().await?;
+        let mut batch = Vec::with_capacity(max.min(self.rx_queue.occupied_len() + 1));
+        batch.push(first);
+        while batch.len() < max {
+            let Some(packet) = self.rx_queue.try_pop() else { break; };
+            batch.push(packet);
+        }
+        Ok(batch)
+    }
+
+    /// Like the channel's [`Stream`] implementation, but yields batches from [`Self::read_batch`].
+    pub fn batches(&mut self, max: usize) -> impl Stream<Item = Vec<P>> + '_ {
+        futures_util::stream::unfold(self, move |channel| async move {
+            let batch = channel.read_batch(max).await.ok()?;
+            Some((batch, channel))
+        })
+    }
+
+    /// Moves the packets already queued into `out` without waiting, returning how many were read.
+    ///
+    /// Unlike [`Self::read_batch`] this doesn't allocate, and the whole batch is taken from the queue at once.
+    pub fn read_batch_into(&mut self, out: &mut [P]) -> usize {
+        self.rx_queue.pop_slice(out)
+    }
+
+    /// The packet [`Self::try_read`] would return next, without removing it.
+    pub fn peek(&self) -> Option<&P> {
+        self.rx_queue.first()
+    }
+
+    /// Only queue frames matching at least one of `filters` on this channel; an empty list accepts everything.
+    ///
+    /// Filtering happens in the host's poll loop, before frames reach this channel's queue. Bridging is unaffected.
+    /// Use [`Self::push_filters_to_device`] to also filter on the device and save USB bandwidth.
+    pub fn set_filters(&self, filters: &[RdxUsbIdMaskFilter]) {
+        let mut current = self.filters.lock().unwrap();
+        current.clear();
+        current.extend_from_slice(filters);
+    }
+
+    /// The filters set with [`Self::set_filters`].
+    pub fn filters(&self) -> Vec<RdxUsbIdMaskFilter> {
+        self.filters.lock().unwrap().clone()
+    }
+
+    /// Sends the current filter list to the device with [`RdxUsbCtrl::SetFilters`].
+    ///
+    /// Devices without hardware filtering stall the request; the host-side filters keep working regardless.
+    pub async fn push_filters_to_device(&self) -> RdxUsbHostResult<()> {
+        let filters = self.filters();
+        self.control_out_struct(RdxUsbCtrl::SetFilters, bytemuck::cast_slice(&filters)).await
+    }
+
+    /// Sets this channel's nominal bit timing with [`RdxUsbCtrl::SetBitrate`], e.g. `channel.set_bitrate(1_000_000)`.
+    ///
+    /// Devices with a fixed bitrate stall the request.
+    pub async fn set_bitrate(&self, timing: impl Into<RdxUsbBitTiming>) -> RdxUsbHostResult<()> {
+        let timing = timing.into();
+        self.control_out_struct(RdxUsbCtrl::SetBitrate, bytemuck::bytes_of(&timing)).await
+    }
+
+    /// Sets this channel's CAN FD data phase bit timing with [`RdxUsbCtrl::SetDataBitrate`].
+    ///
+    /// Devices without CAN FD stall the request.
+    pub async fn set_data_bitrate(&self, timing: impl Into<RdxUsbBitTiming>) -> RdxUsbHostResult<()> {
+        let timing = timing.into();
+        self.control_out_struct(RdxUsbCtrl::SetDataBitrate, bytemuck::bytes_of(&timing)).await
+    }
+
+    /// Switches this channel's controller mode with [`RdxUsbCtrl::SetMode`], e.g. to
+    /// [`RdxUsbChannelMode::ListenOnly`] for diagnostic tools that must not disturb the bus.
+    ///
+    /// Devices that don't support the mode stall the request.
+    pub async fn set_mode(&self, mode: RdxUsbChannelMode) -> RdxUsbHostResult<()> {
+        self.control_out_struct(RdxUsbCtrl::SetMode, &[mode as u8]).await
+    }
+
+    /// Reads this channel's controller state and error counters with [`RdxUsbCtrl::GetBusState`],
+    /// e.g. to notice a bus going error passive before it ends up bus-off.
+    pub async fn bus_state(&self) -> RdxUsbHostResult<RdxUsbBusState> {
+        self.control_in_struct(RdxUsbCtrl::GetBusState).await
+    }
+
+    /// Blinks the status LED of this channel's device, see [`RdxUsbGenericHost::identify`].
+    pub async fn identify(&self, duration: Duration) -> RdxUsbHostResult<()> {
+        send_identify(&self.iface.get(), self.control_timeout, duration).await
+    }
+
+    /// Sends a packet on this channel directly, without a write poller.
+    ///
+    /// Fails with [`RdxUsbHostError::TxPathClaimed`] while the host has a write poller; send through its writer instead.
+    /// Fails with [`RdxUsbHostError::WriteTimeout`] if the device doesn't take the packet within [`RdxUsbTimeouts::write`].
+    pub async fn write(&mut self, pkt: P) -> RdxUsbHostResult<()> {
+        self.write_many(std::slice::from_ref(&pkt)).await.map(|_| ())
+    }
+
+    /// Sends a device-addressed command until the device echoes it back, for configuration frames that must not
+    /// be lost.
+    ///
+    /// The frame goes out with [`MESSAGE_FLAG_ECHO`] and a sequence number in [`MESSAGE_FLAG_SEQ_MASK`], and is sent
+    /// again with the same sequence number each time no echo arrives within `timeout`, up to `retries` times.
+    /// Devices apply a retried frame only once, but firmware predating sequence numbers applies every copy, so only
+    /// idempotent commands should be sent this way. Returns the echo.
+    ///
+    /// Other echoes read from the channel in the meantime are discarded. Fails with
+    /// [`RdxUsbHostError::NotDeviceAddressed`] if `pkt` lacks [`MESSAGE_ARB_ID_DEVICE`], and with
+    /// [`RdxUsbHostError::Timeout`] once every attempt went unanswered.
+    pub async fn write_reliable(&mut self, mut pkt: P, retries: u32, timeout: Duration) -> RdxUsbHostResult<P> {
+        if pkt.arb_id() & MESSAGE_ARB_ID_DEVICE == 0 { return Err(RdxUsbHostError::NotDeviceAddressed); }
+        self.reliable_seq = next_reliable_seq(self.reliable_seq);
+        pkt.set_flags(reliable_flags(pkt.flags(), self.reliable_seq));
+        for _ in 0..=retries {
+            self.write(pkt).await?;
+            let acked = tokio::time::timeout(timeout, async {
+                loop {
+                    let echo = self.read_echo().await?;
+                    if is_reliable_ack(pkt.arb_id(), pkt.flags(), echo.arb_id(), echo.flags()) { return Ok(echo); }
+                }
+            }).await;
+            if let Ok(result) = acked { return result; }
+        }
+        Err(RdxUsbHostError::Timeout)
+    }
+
+    /// Sends a packet and waits for the first received packet `is_reply` accepts, e.g. a parameter query
+    /// and the frame answering it.
+    ///
+    /// The reply is looked for among packets arriving after the request is sent, through a separate subscription,
+    /// so it is also left in the channel for [`Self::read`]. Fails with [`RdxUsbHostError::Timeout`] if no reply
+    /// arrives within `timeout`, and otherwise like [`Self::write`].
+    pub async fn request(&mut self, pkt: P, mut is_reply: impl FnMut(&P) -> bool, timeout: Duration) -> RdxUsbHostResult<P> {
+        // subscribing first means a reply that beats the write completing isn't missed
+        let mut replies = self.subscribe(REQUEST_REPLY_CAPACITY);
+        with_timeout(timeout, async {
+            self.write(pkt).await?;
+            loop {
+                let reply = replies.read().await?;
+                if is_reply(&reply) { return Ok(reply); }
+            }
+        }).await
+    }
+
+    /// Sends packets on this channel in a single bulk transfer, returning how many were sent: all of them at full
+    /// speed, and as many as fit in one transfer ([`RdxUsbHostPacket::MAX_OUT_BATCH`]) at high speed.
+    ///
+    /// Flushes a burst with one await instead of one per packet. Fails like [`Self::write`].
+    pub async fn write_many(&mut self, packets: &[P]) -> RdxUsbHostResult<usize> {
+        if self.tx_ownership.is_claimed() { return Err(RdxUsbHostError::TxPathClaimed); }
+        let packets = &packets[..packets.len().min(P::MAX_OUT_BATCH)];
+        if packets.is_empty() { return Ok(0); }
+        let mut buffer = std::mem::take(&mut self.tx_buffer);
+        buffer.clear();
+        for pkt in packets {
+            let mut pkt = *pkt;
+            pkt.set_channel(self.channel);
+            buffer.extend_from_slice(bytemuck::bytes_of(&pkt));
+        }
+        let len = buffer.len() as u64;
+        self.stats.record_tx(bytemuck::cast_slice::<u8, P>(&buffer).iter().map(|&p| p.into()));
+        let iface = self.iface.get();
+        let completion = with_write_timeout(self.write_timeout, async { Ok(iface.bulk_out(self.endpoint, buffer).await) }).await
+            .inspect_err(|_| self.stats.record_usb_error())?;
+        // the buffer comes back even if the transfer failed, so a retry doesn't allocate either
+        self.tx_buffer = completion.data.reuse();
+        completion.status.inspect_err(|_| self.stats.record_usb_error())?;
+        self.stats.tx_bytes.fetch_add(len, Ordering::Relaxed);
+        Ok(packets.len())
+    }
+
+    pub async fn write_buf(&mut self, vbuf: Vec<u8>) -> RdxUsbHostResult<Vec<u8>> {
+        if self.tx_ownership.is_claimed() { return Err(RdxUsbHostError::TxPathClaimed); }
+        let iface = self.iface.get();
+        with_write_timeout(self.write_timeout, async { Ok(iface.bulk_out(self.endpoint, vbuf).await.into_result()?.reuse()) }).await
+    }
+}
+
+/// Yields received packets, ending once the host is dropped.
+impl<P: RdxUsbHostPacket> Stream for RdxUsbChannel<P> {
+    type Item = P;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+        self.rx_queue.poll_next_unpin(cx)
+    }
+}
+
+/// An additional reader of a channel's traffic, see [`RdxUsbChannel::subscribe`].
+pub struct RdxUsbSubscription<P>(<AsyncRb<Heap<P>> as async_ringbuf::traits::Split>::Cons);
+
+impl<P: RdxUsbHostPacket> RdxUsbSubscription<P> {
+    pub async fn read(&mut self) -> RdxUsbHostResult<P> {
+        match self.0.pop().await {
+            Some(v) => Ok(v),
+            None => Err(RdxUsbHostError::DeviceDisconnected)
+        }
+    }
+
+    pub fn try_read(&mut self) -> Option<P> {
+        self.0.try_pop()
+    }
+}
+
+/// Yields received packets, ending once the host is dropped.
+impl<P: RdxUsbHostPacket> Stream for RdxUsbSubscription<P> {
+    type Item = P;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST_EPOCH_NS: u64 = 1_700_000_000_000_000_000;
+
+    /// Times `ms` milliseconds after a meter was created.
+    fn meter_clock(meter: &BusMeter) -> impl Fn(u64) -> Instant {
+        let epoch = meter.epoch;
+        move |ms| epoch + Duration::from_millis(ms)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    /// Feeds `filter` one update per millisecond for `secs` seconds of a device clock running `ppm` fast, with
+    /// the host seeing each timestamp `jitter(i)` nanoseconds late. Returns the device time of the last update.
+    fn run_clock(filter: &mut ClockFilter, bandwidth_hz: f64, start_ns: u64, secs: u64, ppm: f64, jitter: impl Fn(u64) -> i64) -> u64 {
+        let mut device_ns = start_ns;
+        for i in 0..secs * 1000 {
+            device_ns = start_ns + i * 1_000_000;
+            let host_ns = HOST_EPOCH_NS + (device_ns as f64 / (1.0 + ppm * 1e-6)) as u64;
+            filter.update(bandwidth_hz, device_ns, host_ns.saturating_add_signed(jitter(i)));
+        }
+        device_ns
+    }
+
+    /// Deterministic jitter of up to +-`amplitude` ns.
+    fn jitter(amplitude: i64) -> impl Fn(u64) -> i64 {
+        move |i| (i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 40) as i64 % (amplitude + 1) * if i % 2 == 0 { 1 } else { -1 }
+    }
+
+    #[test]
+    fn clock_filter_locks_on_first_update() {
+        let mut filter = ClockFilter::default();
+        filter.update(1.0, 5_000, HOST_EPOCH_NS);
+        let model = filter.model.unwrap();
+        assert_eq!(model.rate(), 1.0);
+        assert_eq!(model.to_host_ns(5_000), HOST_EPOCH_NS);
+        assert_eq!(model.to_host_ns(1_005_000), HOST_EPOCH_NS + 1_000_000);
+        assert_eq!(model.to_host_ns(0), HOST_EPOCH_NS - 5_000);
+    }
+
+    #[test]
+    fn clock_filter_tracks_rate() {
+        let mut filter = ClockFilter::default();
+        let last = run_clock(&mut filter, 1.0, 0, 30, 100.0, |_| 0);
+        let model = filter.model.unwrap();
+        let expected_rate = 1.0 / (1.0 + 100e-6);
+        assert!((model.rate() - expected_rate).abs() < 1e-6, "rate {}", model.rate());
+        // a second past the last update, the model still lands on the device clock
+        let ahead = last + 1_000_000_000;
+        let expected = HOST_EPOCH_NS + (ahead as f64 * expected_rate) as u64;
+        assert!(model.to_host_ns(ahead).abs_diff(expected) < 1_000);
+    }
+
+    #[test]
+    fn clock_filter_smooths_jitter() {
+        let mut filter = ClockFilter::default();
+        let last = run_clock(&mut filter, 0.5, 0, 30, 50.0, jitter(100_000));
+        let model = filter.model.unwrap();
+        let expected = HOST_EPOCH_NS + (last as f64 / (1.0 + 50e-6)) as u64;
+        // far below the 100us of jitter on the samples
+        assert!(model.to_host_ns(last).abs_diff(expected) < 10_000);
+    }
+
+    #[test]
+    fn clock_filter_relocks_on_jumps() {
+        let mut filter = ClockFilter::default();
+        run_clock(&mut filter, 1.0, 10_000_000_000, 2, 100.0, |_| 0);
+        assert!(filter.updates > ClockFilter::ACQUISITION_UPDATES);
+
+        // the device reset and its clock started over
+        filter.update(1.0, 1_000, HOST_EPOCH_NS + 20_000_000_000);
+        assert_eq!(filter.updates, 0);
+        assert_eq!(filter.model.unwrap(), RdxUsbClockModel { device_ns: 1_000, host_ns: HOST_EPOCH_NS + 20_000_000_000, rate: 1.0 });
+
+        // the host clock jumped by more than a second
+        filter.update(1.0, 2_000_000, HOST_EPOCH_NS + 25_000_000_000);
+        assert_eq!(filter.updates, 0);
+        assert_eq!(filter.model.unwrap().to_host_ns(2_000_000), HOST_EPOCH_NS + 25_000_000_000);
+    }
+
+    #[test]
+    fn clock_filter_clamps_rate() {
+        let mut filter = ClockFilter::default();
+        // a device clock running at half speed can't be followed, but the rate stays bounded
+        for i in 0..10_000u64 {
+            filter.update(10.0, i * 1_000_000, HOST_EPOCH_NS + i * 2_000_000);
+        }
+        let rate = filter.model.unwrap().rate();
+        assert!((0.9..=1.1).contains(&rate), "rate {rate}");
+    }
+
+    #[test]
+    fn smoothed_clock_bandwidth() {
+        let clock = RdxUsbSmoothedClock::default();
+        clock.sample(1_000);
+        assert_eq!(clock.model(), None);
+
+        clock.set_bandwidth(1.0);
+        clock.sample(1_000);
+        assert!(clock.model().is_some());
+
+        clock.set_bandwidth(f64::NAN);
+        assert_eq!(clock.bandwidth(), 0.0);
+        assert_eq!(clock.model(), None);
+        clock.set_bandwidth(-1.0);
+        assert_eq!(clock.bandwidth(), 0.0);
+    }
+
+    #[test]
+    fn latency_buckets() {
+        for us in (0..100_000).chain([u32::MAX as u64, 1 << 32]) {
+            let bucket = LatencyHistogram::bucket(us);
+            assert!(us < LatencyHistogram::bucket_end(bucket), "{us}us past bucket {bucket}");
+            if bucket > 0 {
+                assert!(us >= LatencyHistogram::bucket_end(bucket - 1), "{us}us before bucket {bucket}");
+            }
+        }
+        // within 25%
+        assert_eq!(LatencyHistogram::bucket_end(LatencyHistogram::bucket(1000)), 1024);
+        assert_eq!(LatencyHistogram::bucket_end(LatencyHistogram::bucket(1024)), 1280);
+        assert_eq!(LatencyHistogram::bucket(u64::MAX), LatencyHistogram::BUCKETS - 1);
+    }
+
+    #[test]
+    fn latency_percentiles() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.snapshot(), RdxUsbLatencyStats::default());
+
+        for _ in 0..90 { histogram.record(Duration::from_micros(100)); }
+        for _ in 0..9 { histogram.record(Duration::from_micros(1000)); }
+        histogram.record(Duration::from_micros(5000));
+        let stats = histogram.snapshot();
+        assert_eq!(stats.samples, 100);
+        assert_eq!(stats.p50, Duration::from_micros(112));
+        assert_eq!(stats.p90, Duration::from_micros(112));
+        assert_eq!(stats.p99, Duration::from_micros(1024));
+        assert_eq!(stats.max, Duration::from_micros(5000));
+
+        // percentiles never pass the largest latency seen
+        let histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_micros(1000));
+        let stats = histogram.snapshot();
+        assert_eq!((stats.samples, stats.p50, stats.p99, stats.max), (1, Duration::from_micros(1000), Duration::from_micros(1000), Duration::from_micros(1000)));
+
+        histogram.record(Duration::from_secs(u64::MAX));
+        assert_eq!(histogram.snapshot().max, Duration::from_micros(u64::MAX));
+    }
+
+    #[test]
+    fn fs_split_transfer() {
+        let pkt = RdxUsbFsPacket { arb_id: 0x123, dlc: 8, ..bytemuck::Zeroable::zeroed() };
+        assert_eq!(RdxUsbFsPacket::split_transfer(bytemuck::bytes_of(&pkt), false), Some((None, &[pkt][..])));
+        // a short transfer carries no packet
+        assert_eq!(RdxUsbFsPacket::split_transfer(&bytemuck::bytes_of(&pkt)[1..], false), Some((None, &[][..])));
+    }
+
+    #[cfg(feature = "unstable-hs")]
+    #[test]
+    fn hs_split_unframed_transfer() {
+        let pkts = [1, 2, 3].map(|arb_id| RdxUsbPacket { arb_id, dlc: 8, ..bytemuck::Zeroable::zeroed() });
+        let mut transfer = bytemuck::cast_slice::<_, u8>(&pkts).to_vec();
+        // trailing bytes that don't make up a whole packet are dropped
+        transfer.extend_from_slice(&[0xff; 5]);
+        assert_eq!(RdxUsbPacket::split_transfer(&transfer, false), Some((None, &pkts[..])));
+    }
+
+    /// Completes submitted transfers with `script`'s results in order, then with `Disconnected` once it runs out.
+    #[derive(Default)]
+    struct ScriptedTransport {
+        script: VecDeque<Result<Vec<u8>, nusb::transfer::TransferError>>,
+        pending: usize,
+        cancelled: usize,
+        halts_cleared: u32,
+    }
+
+    impl ScriptedTransport {
+        fn new(script: impl IntoIterator<Item = Result<Vec<u8>, nusb::transfer::TransferError>>) -> Self {
+            Self { script: script.into_iter().collect(), ..Default::default() }
+        }
+    }
+
+    impl RdxUsbInTransport for ScriptedTransport {
+        fn submit(&mut self, _buf: Vec<u8>, _len: usize) {
+            self.pending += 1;
+        }
+
+        fn pending(&self) -> usize {
+            self.pending
+        }
+
+        async fn next_complete(&mut self) -> nusb::transfer::Completion<Vec<u8>> {
+            self.pending -= 1;
+            let result = if self.cancelled > 0 {
+                self.cancelled -= 1;
+                Err(nusb::transfer::TransferError::Cancelled)
+            } else {
+                self.script.pop_front().unwrap_or(Err(nusb::transfer::TransferError::Disconnected))
+            };
+            match result {
+                Ok(data) => nusb::transfer::Completion { data, status: Ok(()) },
+                Err(e) => nusb::transfer::Completion { data: Vec::new(), status: Err(e) },
+            }
+        }
+
+        fn cancel_all(&mut self) {
+            self.cancelled = self.pending;
+        }
+
+        fn clear_halt(&mut self) -> RdxUsbHostResult<()> {
+            self.halts_cleared += 1;
+            Ok(())
+        }
+    }
+
+    fn fs_transfer(arb_id: u32) -> Result<Vec<u8>, nusb::transfer::TransferError> {
+        Ok(bytemuck::bytes_of(&RdxUsbFsPacket { arb_id, dlc: 8, ..bytemuck::Zeroable::zeroed() }).to_vec())
+    }
+
+    /// A full speed rx path with one channel, and the reading end of that channel's queue.
+    fn fs_rx_path() -> (RxPath<RdxUsbFsPacket>, PacketCons<RdxUsbFsPacket>) {
+        let cfg = RdxUsbDeviceInfo { n_channels: 1, ..bytemuck::Zeroable::zeroed() };
+        let (mut rx, _, _) = RxPath::new(&cfg, &RdxUsbHostBuilder::new());
+        let (cons, _) = rx.add_channel(1024, Default::default(), Default::default(), Default::default());
+        (rx, cons)
+    }
+
+    fn poll_rx(rx: &mut RxPath<RdxUsbFsPacket>, transport: &mut impl RdxUsbInTransport) -> (RdxUsbPollExit, RdxUsbPollCounts) {
+        let mut counts = RdxUsbPollCounts::default();
+        // without a rate limit, retry backoff, or watchdog, nothing in the loop waits on a timer
+        let exit = futures_util::FutureExt::now_or_never(rx.poll(transport, 4, Duration::ZERO, false, &mut counts)).expect("poll loop blocked");
+        (exit, counts)
+    }
+
+    #[test]
+    fn rx_path_delivers_transfers() {
+        let (mut rx, mut cons) = fs_rx_path();
+        let mut transport = ScriptedTransport::new([fs_transfer(1), fs_transfer(2)]);
+        let (exit, counts) = poll_rx(&mut rx, &mut transport);
+        assert!(matches!(exit, RdxUsbPollExit::Disconnected), "{exit:?}");
+        assert_eq!((counts.transfers, counts.packets), (2, 2));
+        assert_eq!([cons.try_pop().unwrap().arb_id, cons.try_pop().unwrap().arb_id], [1, 2]);
+    }
+
+    #[test]
+    fn rx_path_recovers_from_stalls() {
+        let (mut rx, cons) = fs_rx_path();
+        let mut transport = ScriptedTransport::new([fs_transfer(1), Err(nusb::transfer::TransferError::Stall), fs_transfer(2)]);
+        let (exit, counts) = poll_rx(&mut rx, &mut transport);
+        assert!(matches!(exit, RdxUsbPollExit::Disconnected), "{exit:?}");
+        assert_eq!((counts.packets, transport.halts_cleared), (2, 1));
+        assert_eq!(cons.occupied_len(), 2);
+    }
+
+    #[test]
+    fn rx_path_gives_up_on_stalls() {
+        let (mut rx, _cons) = fs_rx_path();
+        let stalls = (0..=MAX_IN_STALL_RECOVERIES).map(|_| Err(nusb::transfer::TransferError::Stall));
+        let mut transport = ScriptedTransport::new(stalls);
+        let (exit, _) = poll_rx(&mut rx, &mut transport);
+        assert!(matches!(exit, RdxUsbPollExit::FatalUsbError(RdxUsbHostError::EndpointStall)), "{exit:?}");
+        assert_eq!(transport.halts_cleared, MAX_IN_STALL_RECOVERIES);
+
+        // without clearing halts, the first stall is fatal
+        let (mut rx, _cons) = fs_rx_path();
+        rx.clear_halt_on_stall = false;
+        let mut transport = ScriptedTransport::new([Err(nusb::transfer::TransferError::Stall)]);
+        let (exit, _) = poll_rx(&mut rx, &mut transport);
+        assert!(matches!(exit, RdxUsbPollExit::FatalUsbError(RdxUsbHostError::EndpointStall)), "{exit:?}");
+        assert_eq!(transport.halts_cleared, 0);
+    }
+
+    #[test]
+    fn rx_path_survives_injected_faults() {
+        let faults = RdxUsbFaults { seed: 7, stall: 0.05, partial: 0.2, garbage: 0.2, ..Default::default() };
+        let run = || {
+            let (mut rx, _cons) = fs_rx_path();
+            let transport = ScriptedTransport::new((0..200).map(fs_transfer));
+            let mut injector = RdxUsbFaultInjector::new(transport, faults);
+            let (exit, counts) = poll_rx(&mut rx, &mut injector);
+            assert!(matches!(exit, RdxUsbPollExit::Disconnected), "{exit:?}");
+            let injected = injector.injected();
+            assert!(injected.stalls > 0 && injected.partial > 0 && injected.garbage > 0, "{injected:?}");
+            // a stall loses its transfer, and a cut short one loses its packet
+            assert_eq!(counts.transfers, 200 - injected.stalls);
+            assert_eq!(counts.packets, counts.transfers - injected.partial);
+            assert_eq!(injector.into_inner().halts_cleared as u64, injected.stalls);
+            (counts.transfers, counts.packets, injected)
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn frame_bits_lengths() {
+        use rdxusb_protocol::{MESSAGE_ARB_ID_EXT, MESSAGE_ARB_ID_RTR, MESSAGE_FLAG_FDF};
+        assert_eq!(frame_bits(0x123, 8, 0), 111);
+        assert_eq!(frame_bits(0x123, 0, 0), 47);
+        assert_eq!(frame_bits(MESSAGE_ARB_ID_EXT | 0x123, 8, 0), 131);
+        assert_eq!(frame_bits(MESSAGE_ARB_ID_EXT | MESSAGE_ARB_ID_RTR | 0x123, 8, 0), 67);
+        assert_eq!(frame_bits(0x123, 16, MESSAGE_FLAG_FDF), 39 + 17 + 128);
+        assert_eq!(frame_bits(0x123, 64, MESSAGE_FLAG_FDF), 39 + 21 + 512);
+        assert_eq!(frame_bits(MESSAGE_ARB_ID_EXT | 0x123, 64, MESSAGE_FLAG_FDF), 39 + 19 + 21 + 512);
+    }
+
+    #[test]
+    fn bus_meter_rates() {
+        let mut meter = BusMeter::new(2);
+        let t = meter_clock(&meter);
+        for i in 0..9 {
+            meter.record(t(150 + i * 100), 0, true, 111);
+            meter.record(t(150 + i * 100), 1, false, 131);
+        }
+        meter.record(t(950), 1, false, 131);
+        // out of range channels are ignored
+        meter.record(t(950), 2, true, 111);
+
+        let read = meter.read(t(1000), Duration::from_secs(1));
+        assert_eq!(read.len(), 2);
+        // the window covers the last 9 whole buckets plus the one just started
+        assert_close(read[0].rx_frames_per_sec, 10.0);
+        assert_close(read[0].tx_frames_per_sec, 0.0);
+        assert_close(read[0].bits_per_sec, 1110.0);
+        assert_close(read[1].tx_frames_per_sec, 100.0 / 9.0);
+        assert_close(read[1].bits_per_sec, 13100.0 / 9.0);
+        assert_close(read[0].bus_load(1000), 1.11);
+        assert_eq!(read[0].bus_load(0), 0.0);
+
+        // a short window only sees recent buckets
+        let read = meter.read(t(1000), Duration::from_millis(200));
+        assert_close(read[0].rx_frames_per_sec, 10.0);
+        assert_close(read[1].tx_frames_per_sec, 20.0);
+    }
+
+    #[test]
+    fn bus_meter_window_edges() {
+        let mut meter = BusMeter::new(1);
+        let t = meter_clock(&meter);
+        meter.record(t(100), 0, true, 100);
+        meter.record(t(400), 0, true, 100);
+
+        // a meter younger than the window divides by its age
+        assert_close(meter.read(t(500), Duration::from_secs(10))[0].rx_frames_per_sec, 4.0);
+
+        // buckets older than the window are left out, and ones from a full lap ago are cleared
+        assert_close(meter.read(t(10_350), Duration::from_secs(10))[0].rx_frames_per_sec, 1.0 / 9.95);
+        meter.record(t(20_000), 0, true, 100);
+        let read = meter.read(t(20_050), Duration::from_secs(10));
+        assert_close(read[0].rx_frames_per_sec, 1.0 / 9.95);
+        assert_close(read[0].bits_per_sec, 100.0 / 9.95);
+    }
+
+    #[test]
+    fn bus_meter_talkers() {
+        let mut meter = BusMeter::new(1);
+        let t = meter_clock(&meter);
+        for i in 0..10 {
+            meter.record_talker(t(i * 10), 0, 0x100);
+            meter.record_talker(t(i * 10), 0, rdxusb_protocol::MESSAGE_ARB_ID_EXT | 0x100);
+            if i % 2 == 0 {
+                // flags other than the extended one don't split a talker
+                meter.record_talker(t(i * 10), 0, rdxusb_protocol::MESSAGE_ARB_ID_DEVICE | 0x200);
+            }
+        }
+        meter.record_talker(t(90), 1, 0x300);
+
+        let top = meter.top_talkers(t(100), 0, 2);
+        assert_eq!(top.len(), 2);
+        assert!(top.iter().all(|talker| talker.arb_id & 0x1fff_ffff == 0x100));
+        let all = meter.top_talkers(t(100), 0, 10);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2].arb_id, 0x200);
+        assert!(all[2].frames_per_sec < all[0].frames_per_sec);
+        assert!(meter.top_talkers(t(100), 1, 10).is_empty());
+
+        // rates decay once a talker goes quiet
+        let later = meter.top_talkers(t(5_000), 0, 1)[0].frames_per_sec;
+        assert!(later < all[0].frames_per_sec / 50.0, "{later}");
+
+        // the slowest talker makes room for a new one
+        let mut meter = BusMeter::new(1);
+        let t = meter_clock(&meter);
+        for id in 0..MAX_TALKERS as u32 {
+            meter.record_talker(t(0), 0, id);
+            meter.record_talker(t(0), 0, id);
+        }
+        meter.record_talker(t(0), 0, 0);
+        meter.record_talker(t(1), 0, 0x1_0000);
+        let all = meter.top_talkers(t(1), 0, usize::MAX);
+        assert_eq!(all.len(), MAX_TALKERS);
+        assert_eq!(all[0].arb_id, 0);
+        assert!(all.iter().any(|talker| talker.arb_id == 0x1_0000));
     }
 }
@@ -1,10 +1,81 @@
 pub mod host;
+/// Packet filter expressions, e.g. `id==0x123 || (id&0x7f0)==0x240 && !rtr`.
+pub mod filter;
+/// Reading, writing, and converting packet captures.
+pub mod capture;
+/// Known Redux Robotics USB vendor/product IDs.
+pub mod vendor;
+/// Test traffic generation with configurable ids, payloads, and send rates.
+pub mod generator;
+/// Fault injection for testing hosts' recovery from misbehaving devices.
+pub mod fault;
 /// Integrated tokio-driven event loop that handles hotplug and polling logic automatically.
 /// This is the backend used for the C API.
 #[cfg(feature = "event-loop")]
 pub mod event_loop;
+/// Owned device handles for Rust applications, built on the event loop.
+#[cfg(feature = "event-loop")]
+pub mod managed;
+/// Synchronous wrappers around the full speed host, for applications that don't use async.
+#[cfg(feature = "blocking")]
+pub mod blocking;
+/// Live packet streaming to WebSocket clients such as browser dashboards.
+#[cfg(feature = "websocket")]
+pub mod websocket;
+/// Live packet mirroring to UDP targets, for network-based CAN tools.
+#[cfg(feature = "udp-mirror")]
+pub mod udp_mirror;
+/// Gatewaying packets to and from an MQTT broker.
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+/// Sharing one event loop between processes over a Unix socket.
+#[cfg(all(feature = "daemon", unix))]
+pub mod daemon;
 /// An abstracted C API used for everything else.
 #[cfg(feature = "c-api")]
 pub mod c_api;
 
-pub use rdxusb_protocol::{RdxUsbPacket, MESSAGE_ARB_ID_DEVICE, MESSAGE_ARB_ID_EXT, MESSAGE_ARB_ID_RTR};
\ No newline at end of file
+pub use rdxusb_protocol::{RdxUsbPacket, MESSAGE_ARB_ID_DEVICE, MESSAGE_ARB_ID_EXT, MESSAGE_ARB_ID_RTR};
+
+/// Spawns a task on `rt`, named for tokio-console when built with the `tokio-console` feature and `tokio_unstable`.
+#[cfg(any(feature = "event-loop", feature = "blocking"))]
+pub(crate) fn spawn_named<F>(rt: &tokio::runtime::Handle, name: &str, future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(all(feature = "tokio-console", tokio_unstable))]
+    return tokio::task::Builder::new().name(name).spawn_on(future, rt).expect("rdxusb: could not spawn task");
+    #[cfg(not(all(feature = "tokio-console", tokio_unstable)))]
+    {
+        let _ = name;
+        rt.spawn(future)
+    }
+}
+
+/// Escapes `s` for use inside a JSON string literal.
+#[cfg(feature = "event-loop")]
+pub(crate) fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats a packet as the JSON object sent to WebSocket and MQTT clients.
+#[cfg(any(feature = "websocket", feature = "mqtt"))]
+pub(crate) fn packet_json(packet: &RdxUsbPacket) -> String {
+    let mut data = String::with_capacity(packet.dlc as usize * 2);
+    crate::capture::hex_encode(packet.payload(), &mut data);
+    let (timestamp_ns, flags) = (packet.timestamp_ns, packet.flags);
+    format!(
+        "{{\"timestamp_ns\":{timestamp_ns},\"channel\":{},\"id\":{},\"ext\":{},\"rtr\":{},\"device\":{},\"flags\":{flags},\"data\":\"{data}\"}}",
+        packet.channel, packet.id(), packet.extended(), packet.rtr(), packet.device(),
+    )
+}
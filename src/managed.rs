@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use futures_util::{stream, Stream};
+use rdxusb_protocol::RdxUsbPacket;
+
+use crate::event_loop::{self, DeviceDescription, DeviceState, EventLoopError, HandleStats};
+use crate::host::{RdxUsbTimeoutProfile, RdxUsbTimeouts};
+
+/// A device handle opened through the event loop, which reconnects on its own and is closed on drop.
+///
+/// This wraps the same handle-based API the C API uses, so the event loop's config (see [`event_loop::init`]) applies.
+pub struct ManagedDevice {
+    handle_id: i32,
+}
+
+impl ManagedDevice {
+    /// Opens a handle to the device matching vid/pid and, if given, serial number.
+    ///
+    /// The device doesn't need to be attached yet; use [`Self::wait_connected`] to wait for it.
+    /// **capacity** is the per-channel rx queue size.
+    pub fn open(vid: u16, pid: u16, serial_number: Option<&str>, capacity: usize) -> Result<Self, EventLoopError> {
+        let handle_id = event_loop::open_device(vid, pid, serial_number.map(str::to_string), false, capacity)?;
+        Ok(Self { handle_id })
+    }
+
+    /// Opens the first attached known Redux device.
+    pub fn open_first_redux_device(capacity: usize) -> Result<Self, EventLoopError> {
+        Ok(Self { handle_id: event_loop::open_first_redux_device(false, capacity)? })
+    }
+
+    /// The underlying event loop handle id.
+    pub fn handle_id(&self) -> i32 {
+        self.handle_id
+    }
+
+    pub fn state(&self) -> Result<DeviceState, EventLoopError> {
+        event_loop::device_state(self.handle_id)
+    }
+
+    /// Everything known about the device, see [`event_loop::describe_device`].
+    pub fn describe(&self) -> Result<DeviceDescription, EventLoopError> {
+        event_loop::describe_device(self.handle_id)
+    }
+
+    /// Applies a named set of timeouts to this device, see [`event_loop::set_timeouts`].
+    pub fn set_timeout_profile(&self, profile: RdxUsbTimeoutProfile) -> Result<(), EventLoopError> {
+        event_loop::set_timeout_profile(self.handle_id, profile)
+    }
+
+    /// Overrides individual timeouts of this device, see [`event_loop::set_timeouts`].
+    pub fn set_timeouts(&self, timeouts: RdxUsbTimeouts) -> Result<(), EventLoopError> {
+        event_loop::set_timeouts(self.handle_id, timeouts)
+    }
+
+    /// Bounds how many received packets per second this device's poller processes, see [`event_loop::set_max_rx_rate`].
+    pub fn set_max_rx_rate(&self, packets_per_sec: u32) -> Result<(), EventLoopError> {
+        event_loop::set_max_rx_rate(self.handle_id, packets_per_sec)
+    }
+
+    /// Watches this device's traffic, sampled every `interval`, see [`event_loop::watch_stats`].
+    pub fn watch_stats(&self, interval: Duration) -> Result<tokio::sync::watch::Receiver<HandleStats>, EventLoopError> {
+        event_loop::watch_stats(self.handle_id, interval)
+    }
+
+    /// Sets the order this device is reopened in when several devices attach at once, see
+    /// [`event_loop::set_reconnect_priority`].
+    pub fn set_reconnect_priority(&self, priority: u8) -> Result<(), EventLoopError> {
+        event_loop::set_reconnect_priority(self.handle_id, priority)
+    }
+
+    /// Starts recording this device's received packets into a native capture file, see [`event_loop::start_capture`].
+    pub fn start_capture(&self, path: impl AsRef<std::path::Path>) -> Result<(), EventLoopError> {
+        event_loop::start_capture(self.handle_id, path)
+    }
+
+    /// Finishes this device's capture.
+    pub fn stop_capture(&self) -> Result<(), EventLoopError> {
+        event_loop::stop_capture(self.handle_id)
+    }
+
+    /// Adds an annotation to this device's capture, see [`event_loop::annotate_capture`].
+    pub fn annotate(&self, text: &str) -> Result<(), EventLoopError> {
+        event_loop::annotate_capture(self.handle_id, text)
+    }
+
+    /// Resolves once the device is attached and connected, or fails with [`EventLoopError::Timeout`].
+    ///
+    /// Can be awaited from any executor, e.g. to wait for a sensor before enabling whatever depends on it.
+    pub async fn wait_connected(&self, timeout: Duration) -> Result<(), EventLoopError> {
+        event_loop::wait_connected(self.handle_id, timeout).await
+    }
+
+    /// Blocking version of [`Self::wait_connected`] for synchronous code.
+    pub fn wait_connected_blocking(&self, timeout: Duration) -> Result<(), EventLoopError> {
+        event_loop::wait_connected_blocking(self.handle_id, timeout)
+    }
+
+    /// Reads queued packets from `channel`, returning how many were read.
+    ///
+    /// With exclusive readers turned on, the calling thread becomes the channel's only reader, see
+    /// [`event_loop::read_packets`].
+    pub fn read_packets(&self, channel: u8, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        event_loop::read_packets(self.handle_id, channel, packets)
+    }
+
+    /// Pings the device, returning the round trip time; see [`event_loop::ping`].
+    pub fn ping(&self, timeout: Duration) -> Result<Duration, EventLoopError> {
+        event_loop::ping(self.handle_id, timeout)
+    }
+
+    /// Clears a halt (stall) condition on one of the device's bulk endpoints, see [`event_loop::clear_halt`].
+    pub fn clear_halt(&self, endpoint: u8) -> Result<(), EventLoopError> {
+        event_loop::clear_halt(self.handle_id, endpoint)
+    }
+
+    /// Restricts each channel to a single reader thread, see [`event_loop::set_exclusive_readers`].
+    pub fn set_exclusive_readers(&self, exclusive: bool) -> Result<(), EventLoopError> {
+        event_loop::set_exclusive_readers(self.handle_id, exclusive)
+    }
+
+    /// Lets another thread take over reading `channel`.
+    pub fn release_reader(&self, channel: u8) -> Result<(), EventLoopError> {
+        event_loop::release_reader(self.handle_id, Some(channel))
+    }
+
+    /// Reads queued error frames from any channel, returning how many were read.
+    pub fn read_error_frames(&self, packets: &mut [RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        event_loop::read_error_frames(self.handle_id, packets)
+    }
+
+    /// Queues packets for sending, returning how many were queued.
+    pub fn write_packets(&self, packets: &[RdxUsbPacket]) -> Result<usize, EventLoopError> {
+        event_loop::write_packets(self.handle_id, packets)
+    }
+
+    /// Sends a device-addressed command until the device echoes it back, see [`event_loop::write_reliable`].
+    pub fn write_reliable(&self, packet: &RdxUsbPacket, retries: u32, timeout: Duration) -> Result<RdxUsbPacket, EventLoopError> {
+        event_loop::write_reliable(self.handle_id, packet, retries, timeout)
+    }
+}
+
+impl Drop for ManagedDevice {
+    fn drop(&mut self) {
+        event_loop::close_device(self.handle_id).ok();
+    }
+}
+
+/// A group of devices an application needs all of, such as every sensor on a robot.
+pub struct DeviceSet {
+    devices: Vec<ManagedDevice>,
+}
+
+impl DeviceSet {
+    /// Opens a handle for each serial number of a vid/pid; see [`ManagedDevice::open`].
+    pub fn open(vid: u16, pid: u16, serial_numbers: &[&str], capacity: usize) -> Result<Self, EventLoopError> {
+        let devices = serial_numbers.iter()
+            .map(|serial_number| ManagedDevice::open(vid, pid, Some(serial_number), capacity))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { devices })
+    }
+
+    /// Groups already opened devices.
+    pub fn from_devices(devices: Vec<ManagedDevice>) -> Self {
+        Self { devices }
+    }
+
+    pub fn devices(&self) -> &[ManagedDevice] {
+        &self.devices
+    }
+
+    /// Devices that aren't connected right now.
+    pub fn missing(&self) -> Vec<&ManagedDevice> {
+        self.devices.iter().filter(|d| d.state().ok() != Some(DeviceState::Connected)).collect()
+    }
+
+    /// Is every device connected right now?
+    pub fn all_connected(&self) -> bool {
+        self.missing().is_empty()
+    }
+
+    /// Resolves once every device is connected at the same time, or fails with [`EventLoopError::Timeout`].
+    pub async fn wait_all_connected(&self, timeout: Duration) -> Result<(), EventLoopError> {
+        event_loop::wait_all_connected(&self.handle_ids(), timeout).await
+    }
+
+    /// Blocking version of [`Self::wait_all_connected`] for synchronous code.
+    pub fn wait_all_connected_blocking(&self, timeout: Duration) -> Result<(), EventLoopError> {
+        event_loop::wait_all_connected_blocking(&self.handle_ids(), timeout)
+    }
+
+    /// Stream of `(handle id, state)` for every state transition of any device in the set.
+    ///
+    /// Each device's current state is yielded first. The stream ends once every device is closed.
+    pub fn events(&self) -> Result<impl Stream<Item = (i32, DeviceState)>, EventLoopError> {
+        let streams = self.devices.iter().map(|device| {
+            let handle_id = device.handle_id;
+            let state = event_loop::subscribe_device_state(handle_id)?;
+            Ok(Box::pin(stream::unfold((state, true), move |(mut state, first)| async move {
+                if !first {
+                    state.changed().await.ok()?;
+                }
+                let current = *state.borrow_and_update();
+                Some(((handle_id, current), (state, false)))
+            })))
+        }).collect::<Result<Vec<_>, EventLoopError>>()?;
+        Ok(stream::select_all(streams))
+    }
+
+    fn handle_ids(&self) -> Vec<i32> {
+        self.devices.iter().map(ManagedDevice::handle_id).collect()
+    }
+}
@@ -0,0 +1,331 @@
+//! Gateways packets to and from an MQTT broker, so test rigs built around MQTT can talk to devices directly.
+//!
+//! Published packets go to `rdxusb/<serial>/<channel>/<id>`, with the id in hex like candump logs: three digits for
+//! standard ids, eight for extended ones, e.g. `rdxusb/0042/0/12a` or `rdxusb/0042/1/0204c401`.
+//! Payloads are either the JSON object also sent to WebSocket clients or the raw 80 byte [`RdxUsbPacket`],
+//! see [`MqttPayloadFormat`].
+//!
+//! Messages published to `rdxusb/<serial>/<channel>/tx` are sent on that channel. The payload is either a raw
+//! [`RdxUsbPacket`] (whose channel is replaced by the topic's) or a `cansend` style frame like `123#DEADBEEF`.
+//!
+//! Only the parts of MQTT 3.1.1 needed for this are implemented: a clean session without credentials or TLS,
+//! and QoS 0 in both directions.
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rdxusb_protocol::RdxUsbPacket;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+
+use crate::filter::Filter;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+/// SUBSCRIBE has its reserved flag bits set to 0b0010.
+const SUBSCRIBE: u8 = 0x82;
+const PINGREQ: u8 = 0xc0;
+const DISCONNECT: u8 = 0xe0;
+const PROTOCOL_LEVEL: u8 = 4;
+const CLEAN_SESSION: u8 = 0x02;
+
+/// Message payloads an [`MqttGateway`] publishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MqttPayloadFormat {
+    /// A JSON object like
+    /// `{"timestamp_ns":1234,"channel":0,"id":291,"ext":false,"rtr":false,"device":true,"flags":0,"data":"0102"}`. (default)
+    #[default]
+    Json,
+    /// The 80 byte little-endian [`RdxUsbPacket`] layout.
+    Raw,
+}
+
+/// Publishes packets to an MQTT broker and sends the ones published to the command topics, see the module docs.
+///
+/// Messages are queued without waiting and dropped if the queue is full or the broker is unreachable, so a slow
+/// broker never holds up the caller. Lost connections are retried every second.
+/// Dropping the gateway disconnects from the broker.
+pub struct MqttGateway {
+    /// `rdxusb/<serial>/`
+    topic_prefix: String,
+    format: MqttPayloadFormat,
+    filter: Option<Filter>,
+    tx: mpsc::Sender<(String, Vec<u8>)>,
+    connected: Arc<AtomicBool>,
+    broker: SocketAddr,
+    /// messages that couldn't be queued
+    dropped: u64,
+}
+
+impl MqttGateway {
+    /// Connects to the broker and subscribes to the command topics for `serial`. Must be called from within a tokio runtime.
+    ///
+    /// Commands are written to the event loop device `handle_id`, or ignored if `None`.
+    /// Up to `capacity` messages are queued for the broker.
+    pub async fn connect(broker: impl ToSocketAddrs, serial: &str, handle_id: Option<i32>, format: MqttPayloadFormat, capacity: usize) -> io::Result<Self> {
+        let broker = tokio::net::lookup_host(broker).await?.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "broker address did not resolve"))?;
+        let topic_prefix = format!("rdxusb/{serial}/");
+        let client_id = format!("rdxusb-{serial}");
+        let stream = open_session(broker, &client_id, &topic_prefix).await?;
+        log::debug!(target: "rdxusb", "mqtt: connected to {broker} as {client_id}");
+
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let connected = Arc::new(AtomicBool::new(true));
+        let gateway = Gateway { broker, client_id, topic_prefix: topic_prefix.clone(), handle_id, connected: connected.clone() };
+        crate::spawn_named(&tokio::runtime::Handle::current(), &format!("mqtt:{broker}"), gateway.run(stream, rx));
+        Ok(Self { topic_prefix, format, filter: None, tx, connected, broker, dropped: 0 })
+    }
+
+    /// Only publishes packets matching `filter`, or every packet if `None`.
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+    }
+
+    pub fn broker(&self) -> SocketAddr {
+        self.broker
+    }
+
+    /// Whether the broker connection is currently up.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages dropped because the queue was full or the broker unreachable.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Publishes a packet to its topic, if it matches the filter.
+    pub fn publish(&mut self, packet: &RdxUsbPacket) {
+        if self.filter.as_ref().is_some_and(|f| !f.matches(packet)) { return; }
+        if !self.is_connected() {
+            self.dropped += 1;
+            return;
+        }
+        let topic = if packet.extended() {
+            format!("{}{}/{:08x}", self.topic_prefix, packet.channel, packet.id())
+        } else {
+            format!("{}{}/{:03x}", self.topic_prefix, packet.channel, packet.id())
+        };
+        let payload = match self.format {
+            MqttPayloadFormat::Json => crate::packet_json(packet).into_bytes(),
+            MqttPayloadFormat::Raw => bytemuck::bytes_of(packet).to_vec(),
+        };
+        if self.tx.try_send((topic, payload)).is_err() {
+            self.dropped += 1;
+        }
+    }
+}
+
+/// The connection task's state.
+struct Gateway {
+    broker: SocketAddr,
+    client_id: String,
+    topic_prefix: String,
+    handle_id: Option<i32>,
+    connected: Arc<AtomicBool>,
+}
+
+impl Gateway {
+    async fn run(self, mut stream: TcpStream, mut rx: mpsc::Receiver<(String, Vec<u8>)>) {
+        loop {
+            match self.serve(&mut stream, &mut rx).await {
+                Ok(()) => return,
+                Err(e) => log::debug!(target: "rdxusb", "mqtt: connection to {} lost: {e}", self.broker),
+            }
+            self.connected.store(false, Ordering::Relaxed);
+            stream = loop {
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                // don't flood the broker with stale messages once it's back
+                loop {
+                    match rx.try_recv() {
+                        Ok(_) => continue,
+                        Err(mpsc::error::TryRecvError::Empty) => break,
+                        Err(mpsc::error::TryRecvError::Disconnected) => return,
+                    }
+                }
+                match open_session(self.broker, &self.client_id, &self.topic_prefix).await {
+                    Ok(stream) => break stream,
+                    Err(e) => log::trace!(target: "rdxusb", "mqtt: reconnecting to {} failed: {e}", self.broker),
+                }
+            };
+            log::debug!(target: "rdxusb", "mqtt: reconnected to {}", self.broker);
+            self.connected.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Forwards queued messages and incoming commands until the connection fails, or returns `Ok` once the
+    /// gateway was dropped.
+    async fn serve(&self, stream: &mut TcpStream, rx: &mut mpsc::Receiver<(String, Vec<u8>)>) -> io::Result<()> {
+        let (mut reader, mut writer) = stream.split();
+        let mut inbox = Vec::with_capacity(1024);
+        let mut outbox = Vec::with_capacity(1024);
+        let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + KEEP_ALIVE / 2, KEEP_ALIVE / 2);
+        loop {
+            tokio::select! {
+                msg = rx.recv() => {
+                    let Some((topic, payload)) = msg else {
+                        writer.write_all(&[DISCONNECT, 0]).await?;
+                        return Ok(());
+                    };
+                    outbox.clear();
+                    encode_or_skip(&topic, &payload, &mut outbox);
+                    // batch up whatever else is queued into the same write
+                    while let Ok((topic, payload)) = rx.try_recv() {
+                        encode_or_skip(&topic, &payload, &mut outbox);
+                    }
+                    writer.write_all(&outbox).await?;
+                }
+                // read_buf is cancel safe, unlike reading whole MQTT packets
+                n = reader.read_buf(&mut inbox) => {
+                    if n? == 0 { return Err(io::ErrorKind::UnexpectedEof.into()); }
+                    while let Some((header, body)) = take_packet(&mut inbox)? {
+                        if header & 0xf0 == PUBLISH {
+                            self.handle_command(header, &body).await;
+                        }
+                    }
+                }
+                _ = ping.tick() => {
+                    writer.write_all(&[PINGREQ, 0]).await?;
+                }
+            }
+        }
+    }
+
+    async fn handle_command(&self, header: u8, body: &[u8]) {
+        let Some((topic, payload)) = split_publish(header, body) else { return; };
+        let Some(channel) = topic.strip_prefix(&self.topic_prefix)
+            .and_then(|t| t.strip_suffix("/tx"))
+            .and_then(|c| c.parse::<u8>().ok()) else { return; };
+        let Some(handle_id) = self.handle_id else { return; };
+
+        let packet = if payload.len() == RdxUsbPacket::SIZE {
+            Ok(bytemuck::pod_read_unaligned::<RdxUsbPacket>(payload))
+        } else {
+            std::str::from_utf8(payload).map_err(|_| "not utf-8")
+                .and_then(|frame| crate::capture::candump::parse_frame(frame.trim()))
+        };
+        match packet {
+            Ok(mut packet) => {
+                packet.channel = channel;
+                // write_packets takes the event loop lock and may wait for room in the device's queue
+                match tokio::task::spawn_blocking(move || crate::event_loop::write_packets(handle_id, &[packet])).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => log::debug!(target: "rdxusb", "mqtt: command on {topic} not sent: {e:?}"),
+                    Err(e) => log::debug!(target: "rdxusb", "mqtt: command on {topic} not sent: {e}"),
+                }
+            }
+            Err(e) => log::debug!(target: "rdxusb", "mqtt: bad command on {topic}: {e}"),
+        }
+    }
+}
+
+/// Connects, waits for the broker to accept the session, and subscribes to the command topics.
+async fn open_session(broker: SocketAddr, client_id: &str, topic_prefix: &str) -> io::Result<TcpStream> {
+    let mut stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(broker)).await??;
+    stream.set_nodelay(true)?;
+
+    let mut out = Vec::new();
+    let mut body = Vec::new();
+    put_str(&mut body, "MQTT")?;
+    body.extend_from_slice(&[PROTOCOL_LEVEL, CLEAN_SESSION]);
+    body.extend_from_slice(&(KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    put_str(&mut body, client_id)?;
+    put_header(&mut out, CONNECT, body.len());
+    out.extend_from_slice(&body);
+    stream.write_all(&out).await?;
+
+    let mut inbox = Vec::new();
+    let (header, body) = tokio::time::timeout(CONNECT_TIMEOUT, async {
+        loop {
+            if let Some(packet) = take_packet(&mut inbox)? { return Ok::<_, io::Error>(packet); }
+            if stream.read_buf(&mut inbox).await? == 0 { return Err(io::ErrorKind::UnexpectedEof.into()); }
+        }
+    }).await??;
+    if header != CONNACK || body.len() != 2 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected CONNACK"));
+    }
+    if body[1] != 0 {
+        return Err(io::Error::new(io::ErrorKind::ConnectionRefused, format!("broker refused connection (code {})", body[1])));
+    }
+
+    out.clear();
+    let mut body = 1u16.to_be_bytes().to_vec();
+    put_str(&mut body, &format!("{topic_prefix}+/tx"))?;
+    body.push(0);
+    put_header(&mut out, SUBSCRIBE, body.len());
+    out.extend_from_slice(&body);
+    stream.write_all(&out).await?;
+    Ok(stream)
+}
+
+/// Writes a length-prefixed UTF-8 string, which MQTT limits to 65535 bytes.
+fn put_str(out: &mut Vec<u8>, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "string longer than 65535 bytes"))?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+/// Writes the fixed header: the packet type and flags, then the remaining length in 7 bit groups.
+fn put_header(out: &mut Vec<u8>, header: u8, mut len: usize) {
+    out.push(header);
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        out.push(if len > 0 { byte | 0x80 } else { byte });
+        if len == 0 { break; }
+    }
+}
+
+fn encode_publish(topic: &str, payload: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    let start = out.len();
+    put_header(out, PUBLISH, 2 + topic.len() + payload.len());
+    put_str(out, topic).inspect_err(|_| out.truncate(start))?;
+    out.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Appends a publish to `out`, or drops it if it can't be encoded.
+fn encode_or_skip(topic: &str, payload: &[u8], out: &mut Vec<u8>) {
+    if let Err(e) = encode_publish(topic, payload, out) {
+        log::debug!(target: "rdxusb", "mqtt: not publishing to {topic}: {e}");
+    }
+}
+
+/// Removes the first complete packet from `buf`, if there is one, returning its fixed header byte and body.
+fn take_packet(buf: &mut Vec<u8>) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut len = 0usize;
+    for i in 1..5 {
+        let Some(&byte) = buf.get(i) else { return Ok(None); };
+        len |= ((byte & 0x7f) as usize) << (7 * (i - 1));
+        if byte & 0x80 == 0 {
+            let start = i + 1;
+            if buf.len() < start + len { return Ok(None); }
+            let header = buf[0];
+            let body = buf[start..start + len].to_vec();
+            buf.drain(..start + len);
+            return Ok(Some((header, body)));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "bad remaining length"))
+}
+
+/// Splits a PUBLISH body into its topic and payload.
+fn split_publish(header: u8, body: &[u8]) -> Option<(&str, &[u8])> {
+    let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = std::str::from_utf8(body.get(2..2 + topic_len)?).ok()?;
+    // QoS 1 and 2 messages carry a packet id, though they shouldn't arrive on a QoS 0 subscription
+    let qos = (header >> 1) & 0x3;
+    let payload_start = 2 + topic_len + if qos > 0 { 2 } else { 0 };
+    Some((topic, body.get(payload_start..)?))
+}
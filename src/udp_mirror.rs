@@ -0,0 +1,196 @@
+//! Packets are sent as UDP datagrams to a unicast, broadcast, or multicast address, in one of two layouts.
+//!
+//! [`UdpMirrorFormat::Raw`] datagrams start with an 8 byte header:
+//!
+//! | offset | size | field |
+//! |---|---|---|
+//! | 0 | 4 | magic, `RDXM` |
+//! | 4 | 1 | layout version, currently 1 |
+//! | 5 | 1 | number of packets that follow |
+//! | 6 | 2 | sequence number, little-endian, incremented per datagram |
+//!
+//! followed by the packets, each the 80 byte little-endian [`RdxUsbPacket`] layout.
+//!
+//! [`UdpMirrorFormat::Cannelloni`] datagrams use the cannelloni v2 data format, so cannelloni and tools built on it
+//! can receive them directly. Like candump logs, it has no room for timestamps, the
+//! [`MESSAGE_ARB_ID_DEVICE`] bit, or the other packet flags, and all channels share one stream.
+//!
+//! [`MESSAGE_ARB_ID_DEVICE`]: rdxusb_protocol::MESSAGE_ARB_ID_DEVICE
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+use rdxusb_protocol::RdxUsbPacket;
+
+use crate::filter::Filter;
+
+/// Largest datagram sent, which fits an Ethernet MTU without fragmenting.
+const MAX_DATAGRAM: usize = 1472;
+
+const RAW_MAGIC: &[u8; 4] = b"RDXM";
+const RAW_VERSION: u8 = 1;
+
+const CANNELLONI_VERSION: u8 = 2;
+const CANNELLONI_OP_DATA: u8 = 0;
+const CANNELLONI_HEADER_SIZE: usize = 5;
+/// Marks a CAN FD frame in the length byte, which is then followed by the FD flags.
+const CANNELLONI_FD_FRAME: u8 = 0x80;
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+
+/// Datagram layouts a [`UdpMirror`] can send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UdpMirrorFormat {
+    /// Full [`RdxUsbPacket`]s behind a small header, see the module docs. (default)
+    #[default]
+    Raw,
+    /// The cannelloni v2 data format.
+    Cannelloni,
+}
+
+/// Mirrors published packets to a UDP target.
+///
+/// Datagrams are sent without waiting, and ones the socket can't take right away are dropped,
+/// so a slow or missing receiver never holds up the caller.
+pub struct UdpMirror {
+    socket: UdpSocket,
+    target: SocketAddr,
+    format: UdpMirrorFormat,
+    filter: Option<Filter>,
+    seq: u16,
+    datagram: Vec<u8>,
+    /// datagrams the socket refused
+    dropped: u64,
+}
+
+impl UdpMirror {
+    /// Creates a mirror sending to `target` from an ephemeral port.
+    ///
+    /// Multicast targets are sent with a TTL of 1, so they stay on the local network; see [`Self::set_multicast_ttl`].
+    pub fn new(target: SocketAddr, format: UdpMirrorFormat) -> io::Result<Self> {
+        let bind: SocketAddr = match target.ip() {
+            IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+        match target.ip() {
+            IpAddr::V4(ip) if ip.is_multicast() => socket.set_multicast_ttl_v4(1)?,
+            IpAddr::V4(ip) if ip.is_broadcast() => socket.set_broadcast(true)?,
+            _ => (),
+        }
+        Ok(Self { socket, target, format, filter: None, seq: 0, datagram: Vec::with_capacity(MAX_DATAGRAM), dropped: 0 })
+    }
+
+    /// How many routers multicast datagrams may cross.
+    pub fn set_multicast_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.socket.set_multicast_ttl_v4(ttl)
+    }
+
+    /// Only mirrors packets matching `filter`, or every packet if `None`.
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+    }
+
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    /// Number of datagrams dropped because the socket couldn't send them.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Sends one packet in its own datagram.
+    pub fn publish(&mut self, packet: &RdxUsbPacket) {
+        self.publish_all(std::slice::from_ref(packet));
+    }
+
+    /// Sends packets, packing as many into each datagram as fit.
+    pub fn publish_all(&mut self, packets: &[RdxUsbPacket]) {
+        let mut count = 0usize;
+        for packet in packets {
+            if self.filter.as_ref().is_some_and(|f| !f.matches(packet)) { continue; }
+            let size = self.encoded_size(packet);
+            if count > 0 && (self.datagram.len() + size > MAX_DATAGRAM || count == self.max_count()) {
+                self.send(count);
+                count = 0;
+            }
+            if count == 0 {
+                self.start_datagram();
+            }
+            self.encode(packet);
+            count += 1;
+        }
+        if count > 0 {
+            self.send(count);
+        }
+    }
+
+    fn max_count(&self) -> usize {
+        match self.format {
+            UdpMirrorFormat::Raw => u8::MAX as usize,
+            UdpMirrorFormat::Cannelloni => u16::MAX as usize,
+        }
+    }
+
+    fn encoded_size(&self, packet: &RdxUsbPacket) -> usize {
+        match self.format {
+            UdpMirrorFormat::Raw => RdxUsbPacket::SIZE,
+            UdpMirrorFormat::Cannelloni => 4 + 1 + packet.fd() as usize + if packet.rtr() { 0 } else { packet.payload().len() },
+        }
+    }
+
+    fn start_datagram(&mut self) {
+        self.datagram.clear();
+        match self.format {
+            UdpMirrorFormat::Raw => {
+                self.datagram.extend_from_slice(RAW_MAGIC);
+                self.datagram.extend_from_slice(&[RAW_VERSION, 0]);
+                self.datagram.extend_from_slice(&self.seq.to_le_bytes());
+            }
+            UdpMirrorFormat::Cannelloni => {
+                self.datagram.extend_from_slice(&[CANNELLONI_VERSION, CANNELLONI_OP_DATA, self.seq as u8, 0, 0]);
+            }
+        }
+    }
+
+    fn encode(&mut self, packet: &RdxUsbPacket) {
+        match self.format {
+            UdpMirrorFormat::Raw => self.datagram.extend_from_slice(bytemuck::bytes_of(packet)),
+            UdpMirrorFormat::Cannelloni => {
+                let mut can_id = packet.id();
+                if packet.extended() { can_id |= CAN_EFF_FLAG; }
+                if packet.rtr() { can_id |= CAN_RTR_FLAG; }
+                if packet.error_frame() { can_id |= CAN_ERR_FLAG; }
+                self.datagram.extend_from_slice(&can_id.to_be_bytes());
+                let payload = packet.payload();
+                if packet.fd() {
+                    let fd_flags = packet.brs() as u8 | (packet.esi() as u8) << 1;
+                    self.datagram.extend_from_slice(&[payload.len() as u8 | CANNELLONI_FD_FRAME, fd_flags]);
+                } else {
+                    self.datagram.push(payload.len() as u8);
+                }
+                // remote frames carry a length but no data
+                if !packet.rtr() {
+                    self.datagram.extend_from_slice(payload);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, count: usize) {
+        match self.format {
+            UdpMirrorFormat::Raw => self.datagram[5] = count as u8,
+            UdpMirrorFormat::Cannelloni => self.datagram[3..CANNELLONI_HEADER_SIZE].copy_from_slice(&(count as u16).to_be_bytes()),
+        }
+        if let Err(e) = self.socket.send_to(&self.datagram, self.target) {
+            if e.kind() != io::ErrorKind::WouldBlock {
+                log::debug!(target: "rdxusb", "udp mirror: send to {} failed: {e}", self.target);
+            }
+            self.dropped += 1;
+        }
+        self.seq = self.seq.wrapping_add(1);
+    }
+}
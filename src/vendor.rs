@@ -0,0 +1,46 @@
+use nusb::DeviceInfo;
+
+/// USB vendor ID used by Redux Robotics devices.
+pub const REDUX_VID: u16 = 0x16d0;
+
+/// USB product ID of the Canandgyro.
+pub const CANANDGYRO_PID: u16 = 0x1278;
+/// USB product ID of the Canandcolor.
+pub const CANANDCOLOR_PID: u16 = 0x1279;
+
+/// A known RdxUsb-compatible product.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdxUsbProduct {
+    /// USB vendor ID
+    pub vid: u16,
+    /// USB product ID
+    pub pid: u16,
+    /// Human-readable product name
+    pub name: &'static str,
+}
+
+/// Every RdxUsb-compatible product this version of the library knows about.
+pub const KNOWN_PRODUCTS: &[RdxUsbProduct] = &[
+    RdxUsbProduct { vid: REDUX_VID, pid: CANANDGYRO_PID, name: "Canandgyro" },
+    RdxUsbProduct { vid: REDUX_VID, pid: CANANDCOLOR_PID, name: "Canandcolor" },
+];
+
+/// Looks up a known product by vid/pid.
+pub fn lookup(vid: u16, pid: u16) -> Option<&'static RdxUsbProduct> {
+    KNOWN_PRODUCTS.iter().find(|p| p.vid == vid && p.pid == pid)
+}
+
+/// Is the vid/pid pair a known Redux device?
+pub fn is_redux_device(vid: u16, pid: u16) -> bool {
+    lookup(vid, pid).is_some()
+}
+
+/// Lists all currently attached known Redux devices.
+pub fn list_redux_devices() -> Result<impl Iterator<Item = DeviceInfo>, nusb::Error> {
+    Ok(nusb::list_devices()?.filter(|d| is_redux_device(d.vendor_id(), d.product_id())))
+}
+
+/// Returns the first attached known Redux device, if any.
+pub fn first_redux_device() -> Option<DeviceInfo> {
+    list_redux_devices().ok()?.next()
+}
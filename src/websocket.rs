@@ -0,0 +1,180 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use futures_util::{SinkExt, StreamExt};
+use rdxusb_protocol::RdxUsbPacket;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::filter::Filter;
+
+/// Serves published packets to WebSocket clients, e.g. browser dashboards.
+///
+/// Clients connecting to `/binary` receive each packet as a binary message holding the raw [`RdxUsbPacket`];
+/// any other path receives JSON text messages like
+/// `{"timestamp_ns":1234,"channel":0,"id":291,"ext":false,"rtr":false,"device":true,"flags":0,"data":"0102"}`.
+///
+/// A client may send a text message containing a [`Filter`] expression to only receive matching packets,
+/// or an empty message to receive everything again. Invalid expressions are answered with `{"error":"..."}`.
+///
+/// Filters are checked when a packet is published, so only the clients it's for are woken up.
+/// Clients that fall more than the buffer capacity behind skip the packets they missed.
+/// Messages from clients are limited to [`MAX_CLIENT_MESSAGE_SIZE`] bytes; larger ones close the connection.
+pub struct WsServer {
+    subscribers: Subscribers,
+    local_addr: SocketAddr,
+    accept_task: JoinHandle<()>,
+}
+
+/// Largest message or frame a client may send. Clients only send filter expressions, which are far smaller.
+pub const MAX_CLIENT_MESSAGE_SIZE: usize = 4096;
+
+/// A connected client's filter and packet queue.
+struct Subscriber {
+    id: u64,
+    filter: Option<Filter>,
+    tx: mpsc::Sender<RdxUsbPacket>,
+    /// packets dropped because the queue was full
+    skipped: AtomicU64,
+}
+
+/// Publishing only takes the read lock, so filters are evaluated without blocking other publishers.
+type Subscribers = Arc<RwLock<Vec<Subscriber>>>;
+
+impl WsServer {
+    /// Binds the server and starts accepting clients. Must be called from within a tokio runtime.
+    pub async fn bind(addr: impl ToSocketAddrs, capacity: usize) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let subscribers = Subscribers::default();
+        let accept_subscribers = subscribers.clone();
+        let capacity = capacity.max(1);
+        let accept_task = crate::spawn_named(&tokio::runtime::Handle::current(), &format!("websocket:{local_addr}"), async move {
+            let mut next_id = 0;
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        log::debug!(target: "rdxusb", "websocket: client {peer} connected");
+                        let (tx, rx) = mpsc::channel(capacity);
+                        accept_subscribers.write().unwrap().push(Subscriber { id: next_id, filter: None, tx, skipped: AtomicU64::new(0) });
+                        crate::spawn_named(&tokio::runtime::Handle::current(), &format!("websocket-client:{peer}"), serve_client(stream, peer, next_id, accept_subscribers.clone(), rx));
+                        next_id += 1;
+                    }
+                    Err(e) => {
+                        log::error!(target: "rdxusb", "websocket: accept failed: {e}");
+                        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    }
+                }
+            }
+        });
+        Ok(Self { subscribers, local_addr, accept_task })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Sends a packet to every connected client whose filter matches it.
+    pub fn publish(&self, packet: &RdxUsbPacket) {
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            if subscriber.filter.as_ref().is_some_and(|f| !f.matches(packet)) { continue; }
+            if let Err(mpsc::error::TrySendError::Full(_)) = subscriber.tx.try_send(*packet) {
+                subscriber.skipped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Number of currently connected clients.
+    pub fn client_count(&self) -> usize {
+        self.subscribers.read().unwrap().len()
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        // client tasks exit on their own once their senders are gone
+        self.accept_task.abort();
+        self.subscribers.write().unwrap().clear();
+    }
+}
+
+async fn serve_client(stream: TcpStream, peer: SocketAddr, id: u64, subscribers: Subscribers, rx: mpsc::Receiver<RdxUsbPacket>) {
+    serve_connection(stream, peer, id, &subscribers, rx).await;
+    let mut subscribers = subscribers.write().unwrap();
+    if let Some(i) = subscribers.iter().position(|s| s.id == id) {
+        let skipped = subscribers.swap_remove(i).skipped.into_inner();
+        if skipped > 0 {
+            log::debug!(target: "rdxusb", "websocket: client {peer} skipped {skipped} packets");
+        }
+    }
+    log::debug!(target: "rdxusb", "websocket: client {peer} disconnected");
+}
+
+fn set_filter(subscribers: &Subscribers, id: u64, filter: Option<Filter>) {
+    if let Some(subscriber) = subscribers.write().unwrap().iter_mut().find(|s| s.id == id) {
+        subscriber.filter = filter;
+    }
+}
+
+async fn serve_connection(stream: TcpStream, peer: SocketAddr, id: u64, subscribers: &Subscribers, mut rx: mpsc::Receiver<RdxUsbPacket>) {
+    let mut binary = false;
+    // the handshake callback signature is dictated by tungstenite
+    #[allow(clippy::result_large_err)]
+    let callback = |req: &Request, resp: Response| -> Result<Response, ErrorResponse> {
+        binary = req.uri().path() == "/binary";
+        Ok(resp)
+    };
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_CLIENT_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_CLIENT_MESSAGE_SIZE),
+        ..Default::default()
+    };
+    let ws = match tokio_tungstenite::accept_hdr_async_with_config(stream, callback, Some(config)).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            log::debug!(target: "rdxusb", "websocket: handshake with {peer} failed: {e}");
+            return;
+        }
+    };
+    let (mut sink, mut source) = ws.split();
+
+    loop {
+        tokio::select! {
+            msg = source.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let text = text.trim();
+                    if text.is_empty() {
+                        set_filter(subscribers, id, None);
+                    } else {
+                        match Filter::parse(text) {
+                            Ok(f) => set_filter(subscribers, id, Some(f)),
+                            Err(e) => {
+                                let reply = format!("{{\"error\":\"{}\"}}", crate::json_escape(&e.to_string()));
+                                if sink.send(Message::Text(reply)).await.is_err() { break; }
+                            }
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => (),
+            },
+            packet = rx.recv() => match packet {
+                Some(packet) => {
+                    let msg = if binary {
+                        Message::Binary(bytemuck::bytes_of(&packet).to_vec())
+                    } else {
+                        Message::Text(crate::packet_json(&packet))
+                    };
+                    if sink.send(msg).await.is_err() { break; }
+                }
+                None => break,
+            },
+        }
+    }
+}
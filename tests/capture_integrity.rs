@@ -0,0 +1,90 @@
+//! Hash chained captures detect edits.
+
+use rdxusb::capture::integrity::{verify, CaptureIntegrity};
+use rdxusb::capture::native::{HEADER_SIZE, INDEX_INTERVAL};
+use rdxusb::capture::{Annotation, CaptureError, CaptureWriter, PacketWrite};
+use rdxusb::RdxUsbPacket;
+
+const KEY: &[u8] = b"match 42 recorder";
+/// Size of an uncompressed packet record, including its record header.
+const PACKET_RECORD: usize = 84;
+
+/// A finished capture of `packets` packets and an annotation. Chain links follow every [`INDEX_INTERVAL`] packets.
+fn chained_capture(integrity: CaptureIntegrity, packets: u64) -> Vec<u8> {
+    let mut writer = CaptureWriter::with_integrity(Vec::new(), None, integrity).unwrap();
+    for i in 0..packets {
+        let packet = RdxUsbPacket { timestamp_ns: i * 1000, arb_id: 0x200 + i as u32 % 8, dlc: 8, channel: 0, flags: 0, data: [i as u8; 64] };
+        writer.write_packet(&packet).unwrap();
+    }
+    writer.write_annotation(&Annotation { timestamp_ns: 5000, text: "auton started".into() }).unwrap();
+    writer.finish_into_inner().unwrap()
+}
+
+#[test]
+fn intact_capture_verifies() {
+    let capture = chained_capture(CaptureIntegrity::Chained, INDEX_INTERVAL + 10);
+    let report = verify(capture.as_slice(), None).unwrap();
+    assert!(report.sealed);
+    assert!(!report.signed);
+    assert_eq!(report.links, 1);
+
+    let capture = chained_capture(CaptureIntegrity::Signed(KEY.to_vec()), 10);
+    let report = verify(capture.as_slice(), Some(KEY)).unwrap();
+    assert!(report.sealed && report.signed);
+}
+
+#[test]
+fn every_one_byte_edit_fails_verification() {
+    for integrity in [CaptureIntegrity::Chained, CaptureIntegrity::Signed(KEY.to_vec())] {
+        let key = matches!(integrity, CaptureIntegrity::Signed(_)).then_some(KEY);
+        let capture = chained_capture(integrity, 10);
+        // everything up to the seal is covered; the time index after it isn't
+        let verified = verify(capture.as_slice(), key).unwrap().verified_bytes as usize;
+        for offset in 0..verified {
+            let mut edited = capture.clone();
+            edited[offset] ^= 0x01;
+            match verify(edited.as_slice(), key) {
+                Err(_) => {}
+                // a garbled record length can hide the seal, leaving nothing verified
+                Ok(report) => assert!(!report.sealed && report.verified_bytes == 0, "edit at {offset} went unnoticed: {report:?}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn edits_are_caught_at_the_next_link() {
+    let capture = chained_capture(CaptureIntegrity::Chained, INDEX_INTERVAL + 10);
+    let link = HEADER_SIZE + INDEX_INTERVAL as usize * PACKET_RECORD;
+    // a payload byte of the first packet, and a byte of the link itself
+    for offset in [HEADER_SIZE + 20, link + 10] {
+        let mut edited = capture.clone();
+        edited[offset] ^= 0xff;
+        assert!(matches!(verify(edited.as_slice(), None), Err(CaptureError::Tampered { offset }) if offset == link as u64));
+    }
+    // past the link, the seal catches it
+    let mut edited = capture.clone();
+    edited[link + 36 + 20] ^= 0xff;
+    assert!(matches!(verify(edited.as_slice(), None), Err(CaptureError::Tampered { offset }) if offset > link as u64));
+}
+
+#[test]
+fn signature_needs_the_key() {
+    let capture = chained_capture(CaptureIntegrity::Signed(KEY.to_vec()), 10);
+    assert!(matches!(verify(capture.as_slice(), Some(b"another key")), Err(CaptureError::BadSignature)));
+
+    // an unsigned capture can't pass as signed, even with a valid chain
+    let capture = chained_capture(CaptureIntegrity::Chained, 10);
+    assert!(matches!(verify(capture.as_slice(), Some(KEY)), Err(CaptureError::BadSignature)));
+}
+
+#[test]
+fn truncated_capture_verifies_up_to_its_last_link() {
+    let capture = chained_capture(CaptureIntegrity::Chained, INDEX_INTERVAL + 10);
+    let link_end = HEADER_SIZE + INDEX_INTERVAL as usize * PACKET_RECORD + 36;
+    // cut at a record boundary after the link, as a crash between writes would
+    let report = verify(&capture[..link_end + 3 * PACKET_RECORD], None).unwrap();
+    assert!(!report.sealed);
+    assert_eq!(report.links, 1);
+    assert_eq!(report.verified_bytes, link_end as u64);
+}
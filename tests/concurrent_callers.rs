@@ -0,0 +1,157 @@
+//! Concurrent C callers reading and writing one handle.
+//!
+//! No device is attached, so reads that get past the reader claim fail with RDXUSB_ERR_DEVICE_NOT_CONNECTED;
+//! what matters here is which threads get RDXUSB_ERR_CHANNEL_CLAIMED instead.
+
+use std::{ffi::{c_void, CString}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Barrier}, thread, time::Duration};
+
+use rdxusb::{c_api::*, event_loop::EventLoopError, RdxUsbPacket};
+
+const THREADS: usize = 8;
+const ROUNDS: usize = 500;
+const EMPTY_PACKET: RdxUsbPacket = RdxUsbPacket { timestamp_ns: 0, arb_id: 0, dlc: 0, channel: 0, flags: 0, data: [0; 64] };
+
+/// Opens a handle to a device that isn't attached, unique to each test.
+fn open(serial_number: &str) -> i32 {
+    let serial_number = CString::new(serial_number).unwrap();
+    let handle = rdxusb_open_device(0xffff, 0xfffe, serial_number.as_ptr(), false, 64);
+    assert!(handle >= 0, "open failed: {handle}");
+    handle
+}
+
+fn read(handle: i32, channel: u8) -> i32 {
+    let mut packets = [EMPTY_PACKET; 4];
+    let mut packets_read = 0u64;
+    rdxusb_read_packets(handle, channel, packets.as_mut_ptr(), packets.len() as u64, &mut packets_read)
+}
+
+fn read_any(handle: i32) -> i32 {
+    let mut packets = [EMPTY_PACKET; 4];
+    let mut packets_read = 0u64;
+    rdxusb_read_packets_any(handle, packets.as_mut_ptr(), packets.len() as u64, &mut packets_read)
+}
+
+fn write(handle: i32) -> i32 {
+    let packets = [EMPTY_PACKET; 4];
+    let mut packets_written = 0u64;
+    rdxusb_write_packets(handle, packets.as_ptr(), packets.len() as u64, &mut packets_written)
+}
+
+/// Has `THREADS` threads read `channel(thread)` and write at the same time, returning how many reads
+/// each thread had refused with RDXUSB_ERR_CHANNEL_CLAIMED.
+fn hammer(handle: i32, channel: fn(usize) -> u8) -> Vec<usize> {
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let threads: Vec<_> = (0..THREADS).map(|i| {
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            barrier.wait();
+            let mut claimed = 0;
+            for _ in 0..ROUNDS {
+                match read(handle, channel(i)) {
+                    EventLoopError::ERR_CHANNEL_CLAIMED => claimed += 1,
+                    ret => assert_eq!(ret, EventLoopError::ERR_DEVICE_NOT_CONNECTED),
+                }
+                assert_eq!(write(handle), EventLoopError::ERR_DEVICE_NOT_CONNECTED, "writes are never claimed");
+            }
+            claimed
+        })
+    }).collect();
+    threads.into_iter().map(|t| t.join().unwrap()).collect()
+}
+
+#[test]
+fn shared_readers_by_default() {
+    let handle = open("concurrent-shared");
+    assert!(hammer(handle, |_| 0).iter().all(|&claimed| claimed == 0));
+    assert_eq!(rdxusb_close_device(handle), 0);
+}
+
+#[test]
+fn exclusive_readers_admit_one_thread_per_channel() {
+    let handle = open("concurrent-exclusive");
+    assert_eq!(rdxusb_set_exclusive_readers(handle, true), 0);
+
+    let claimed = hammer(handle, |_| 0);
+    assert_eq!(claimed.iter().filter(|&&claimed| claimed == 0).count(), 1, "one reader owns the channel: {claimed:?}");
+    assert_eq!(claimed.iter().filter(|&&claimed| claimed == ROUNDS).count(), THREADS - 1, "every other read is refused: {claimed:?}");
+
+    // the owner exited, so nobody else can read channel 0 until its claim is released
+    assert_eq!(read(handle, 0), EventLoopError::ERR_CHANNEL_CLAIMED);
+    assert_eq!(rdxusb_release_reader(handle, 0), 0);
+    assert_eq!(read(handle, 0), EventLoopError::ERR_DEVICE_NOT_CONNECTED);
+    assert_eq!(rdxusb_close_device(handle), 0);
+}
+
+#[test]
+fn exclusive_readers_of_separate_channels_coexist() {
+    let handle = open("concurrent-channels");
+    assert_eq!(rdxusb_set_exclusive_readers(handle, true), 0);
+    assert!(hammer(handle, |i| i as u8).iter().all(|&claimed| claimed == 0));
+    assert_eq!(rdxusb_close_device(handle), 0);
+}
+
+#[test]
+fn read_any_excludes_channel_readers() {
+    let handle = open("concurrent-any");
+    assert_eq!(rdxusb_set_exclusive_readers(handle, true), 0);
+    assert_eq!(thread::spawn(move || read(handle, 1)).join().unwrap(), EventLoopError::ERR_DEVICE_NOT_CONNECTED);
+    assert_eq!(read_any(handle), EventLoopError::ERR_CHANNEL_CLAIMED);
+
+    assert_eq!(rdxusb_release_reader(handle, 1), 0);
+    assert_eq!(read_any(handle), EventLoopError::ERR_DEVICE_NOT_CONNECTED);
+    assert_eq!(thread::spawn(move || read(handle, 1)).join().unwrap(), EventLoopError::ERR_CHANNEL_CLAIMED);
+
+    // turning enforcement off drops the claims
+    assert_eq!(rdxusb_set_exclusive_readers(handle, false), 0);
+    assert_eq!(thread::spawn(move || read(handle, 1)).join().unwrap(), EventLoopError::ERR_DEVICE_NOT_CONNECTED);
+    assert_eq!(rdxusb_close_device(handle), 0);
+}
+
+/// Tracks the calls of [`slow_stats`], passed to it as user data.
+#[derive(Default)]
+struct StatsCalls {
+    running: AtomicBool,
+    calls: AtomicUsize,
+}
+
+extern "C" fn slow_stats(_handle_id: i32, _stats: *const RdxUsbStatsSnapshot, user_data: *mut c_void) {
+    let calls = unsafe { &*(user_data as *const StatsCalls) };
+    calls.running.store(true, Ordering::SeqCst);
+    calls.calls.fetch_add(1, Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(20));
+    calls.running.store(false, Ordering::SeqCst);
+}
+
+/// Starts [`slow_stats`] on `handle`, returning once a call is in progress.
+fn start_slow_stats(handle: i32, calls: &StatsCalls) {
+    let user_data = calls as *const StatsCalls as *mut c_void;
+    assert_eq!(rdxusb_set_stats_callback(handle, 1, Some(slow_stats), user_data), 0);
+    while !calls.running.load(Ordering::SeqCst) { thread::yield_now(); }
+}
+
+/// Checks that [`slow_stats`] isn't running and isn't called again.
+fn assert_stats_stopped(calls: &StatsCalls) {
+    assert!(!calls.running.load(Ordering::SeqCst), "the call in progress was waited out");
+    let made = calls.calls.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(calls.calls.load(Ordering::SeqCst), made, "no calls after removal");
+}
+
+#[test]
+fn removing_stats_callback_waits_for_call_in_progress() {
+    let handle = open("concurrent-stats-remove");
+    let calls = StatsCalls::default();
+    start_slow_stats(handle, &calls);
+    assert_eq!(rdxusb_set_stats_callback(handle, 1, None, std::ptr::null_mut()), 0);
+    assert_stats_stopped(&calls);
+    assert_eq!(rdxusb_close_device(handle), 0);
+}
+
+#[test]
+fn closing_handle_waits_for_stats_call_in_progress() {
+    let handle = open("concurrent-stats-close");
+    let calls = StatsCalls::default();
+    start_slow_stats(handle, &calls);
+    assert_eq!(rdxusb_close_device(handle), 0);
+    assert_stats_stopped(&calls);
+}
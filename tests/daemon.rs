@@ -0,0 +1,43 @@
+//! Clients of a daemon serving this process's event loop.
+//!
+//! No device is attached, so reads and writes on a client's own handle fail with RDXUSB_ERR_DEVICE_NOT_CONNECTED;
+//! what matters here is which calls get RDXUSB_ERR_DEVICE_NOT_OPENED instead.
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+
+use rdxusb::{daemon::{DaemonClient, DaemonServer, SOCKET_MODE}, event_loop::EventLoopError, RdxUsbPacket};
+
+const EMPTY_PACKET: RdxUsbPacket = RdxUsbPacket { timestamp_ns: 0, arb_id: 0, dlc: 0, channel: 0, flags: 0, data: [0; 64] };
+
+fn serve(name: &str) -> DaemonServer {
+    let path = std::env::temp_dir().join(format!("rdxusb-test-{name}-{}.sock", std::process::id()));
+    DaemonServer::bind(path).unwrap()
+}
+
+#[test]
+fn socket_is_created_with_socket_mode() {
+    let server = serve("mode");
+    let mode = std::fs::metadata(server.path()).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, SOCKET_MODE);
+}
+
+#[test]
+fn clients_only_use_their_own_handles() {
+    let server = serve("owner");
+    let owner = DaemonClient::connect(server.path()).unwrap();
+    let other = DaemonClient::connect(server.path()).unwrap();
+    let handle = owner.open_device(0xffff, 0xfffd, Some("daemon-owner"), false, u64::MAX as usize).unwrap();
+
+    let mut packets = [EMPTY_PACKET; 4];
+    assert_eq!(other.read_packets(handle, 0, &mut packets), Err(EventLoopError::DeviceNotOpened));
+    assert_eq!(other.read_packets_any(handle, &mut packets), Err(EventLoopError::DeviceNotOpened));
+    assert_eq!(other.write_packets(handle, &packets), Err(EventLoopError::DeviceNotOpened));
+    assert_eq!(other.close_device(handle), Err(EventLoopError::DeviceNotOpened));
+
+    // the handle is still open for its owner
+    assert_eq!(owner.read_packets(handle, 0, &mut packets), Err(EventLoopError::DeviceNotConnected));
+    assert_eq!(owner.write_packets(handle, &packets), Err(EventLoopError::DeviceNotConnected));
+    assert_eq!(owner.close_device(handle), Ok(()));
+    assert_eq!(owner.read_packets(handle, 0, &mut packets), Err(EventLoopError::DeviceNotOpened));
+}